@@ -0,0 +1,4 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+use criterion::{criterion_group, criterion_main};
+criterion_group!(benches, blocksr::bench::all);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+fn main() {
+    //C fixtures are only needed to verify the ABI in tests; see tests/fixtures/abi.c
+    #[cfg(feature = "abi-fixtures")]
+    {
+        println!("cargo:rerun-if-changed=tests/fixtures/abi.c");
+        cc::Build::new().file("tests/fixtures/abi.c").compile("blocksr_fixtures");
+    }
+    //Rust can't catch ObjC exceptions, so blocksr::exception calls into this shim
+    #[cfg(feature = "objc-exceptions")]
+    {
+        println!("cargo:rerun-if-changed=src/exception.m");
+        let mut build = cc::Build::new();
+        build.file("src/exception.m").flag("-fobjc-exceptions");
+        if std::env::var_os("CARGO_FEATURE_GNUSTEP").is_some() {
+            build.flag("-fobjc-runtime=gnustep-2.0");
+        }
+        build.compile("blocksr_exception");
+    }
+    //ObjC has no block-based KVO API, so blocksr::kvo observes with this shim
+    #[cfg(feature = "kvo")]
+    {
+        println!("cargo:rerun-if-changed=src/kvo.m");
+        let mut build = cc::Build::new();
+        build.file("src/kvo.m");
+        if std::env::var_os("CARGO_FEATURE_GNUSTEP").is_some() {
+            build.flag("-fobjc-runtime=gnustep-2.0");
+            println!("cargo:rustc-link-lib=gnustep-base");
+        }
+        else {
+            println!("cargo:rustc-link-lib=framework=Foundation");
+        }
+        build.compile("blocksr_kvo");
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Argument adapters, which convert arguments into owned values before the closure runs.
+
+Completion handler arguments such as `NSData` or `NSError` are only valid during the invocation.  Capturing the raw
+pointer for later is a common use-after-free.  In the macros, an argument may be declared `name: Raw as Owned`.  The block
+still receives `Raw` from objc, but the closure receives `Owned`, converted inside the thunk by [CopyArgument].
+
+```
+use blocksr::{once_escaping, CopyArgument};
+//stands in for an NSData
+#[repr(C)] struct Bytes { ptr: *const u8, len: usize }
+struct OwnedBytes(Vec<u8>);
+impl CopyArgument<*const Bytes> for OwnedBytes {
+    unsafe fn copy_argument(raw: *const Bytes) -> Self {
+        OwnedBytes(std::slice::from_raw_parts((*raw).ptr, (*raw).len).to_vec())
+    }
+}
+once_escaping!(DataHandler (data: *const Bytes as OwnedBytes) -> ());
+let block = unsafe{ DataHandler::new(|data: OwnedBytes| {
+    //data is owned, so it may outlive the invocation
+    std::thread::spawn(move || assert_eq!(data.0, vec![1,2,3]));
+})};
+let storage = [1u8,2,3];
+let bytes = Bytes{ptr: storage.as_ptr(), len: 3};
+unsafe{ block.as_block().invoke((&bytes as *const Bytes,)) };
+```
+*/
+
+/**
+Converts a block argument of type `Raw`, valid only during the invocation, into an owned value.
+
+Implement this to copy bytes, retain an object, and so on.
+*/
+pub trait CopyArgument<Raw>: Sized {
+    /**
+    Converts `raw`.
+
+    # Safety
+    This is called inside the thunk with the argument objc passed, which is valid for the duration of the call.
+    */
+    unsafe fn copy_argument(raw: Raw) -> Self;
+}
+
+/**
+Converts a block argument of type `Raw` into a borrow, valid only during the invocation, for `once_noescape` blocks.
+
+The closure bound is generic over the borrow's lifetime, so a closure can't keep it.  Where the argument points to a
+pointer and a length, such as a struct describing a buffer, the adapter makes the slice.
+
+```
+use blocksr::{once_noescape, BorrowArgument};
+//stands in for a buffer description passed by pointer
+#[repr(C)] struct Buffer { ptr: *const u8, len: usize }
+struct Bytes<'a>(&'a [u8]);
+impl<'a> BorrowArgument<'a, *const Buffer> for Bytes<'a> {
+    unsafe fn borrow_argument(raw: *const Buffer) -> Self {
+        Bytes(std::slice::from_raw_parts((*raw).ptr, (*raw).len))
+    }
+}
+once_noescape!(ReadBlock (buffer: *const Buffer as Bytes) -> usize);
+let storage = [1u8,2,3];
+let buffer = Buffer{ptr: storage.as_ptr(), len: 3};
+let r = unsafe{ ReadBlock::scoped(|bytes: Bytes| bytes.0.len(), |block| block.as_block().invoke((&buffer as *const Buffer,))) };
+assert_eq!(r, 3);
+```
+*/
+pub trait BorrowArgument<'a, Raw>: Sized {
+    /**
+    Converts `raw`.
+
+    # Safety
+    This is called inside the thunk with the argument objc passed, which is valid for the duration of the call.  The
+    result must not be used after the call.
+    */
+    unsafe fn borrow_argument(raw: Raw) -> Self;
+}
+
+impl<'a> BorrowArgument<'a, *const core::ffi::c_char> for &'a core::ffi::CStr {
+    unsafe fn borrow_argument(raw: *const core::ffi::c_char) -> Self {
+        unsafe{ core::ffi::CStr::from_ptr(raw) }
+    }
+}
+
+///The type the closure receives for an argument declared `A` or `A as O`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __adapted {
+    ($A:ty) => {$A};
+    ($A:ty as $O:ty) => {$O};
+}
+
+///Converts an argument declared `a: A` or `a: A as O`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __adapt {
+    ($a:ident : $A:ty) => {$a};
+    ($a:ident : $A:ty as $O:ty) => {unsafe{ <$O as blocksr::CopyArgument<$A>>::copy_argument($a) }};
+}
+
+///Converts an argument declared `a: A` or `a: A as B` in a noescape block.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __borrow {
+    ($a:ident : $A:ty) => {$a};
+    ($a:ident : $A:ty as $B:ty) => {unsafe{ <$B as blocksr::BorrowArgument<'_, $A>>::borrow_argument($a) }};
+}
+
+#[test] fn adapt_many() {
+    use std::ffi::{c_char, CStr};
+    struct Owned(String);
+    impl CopyArgument<*const c_char> for Owned {
+        unsafe fn copy_argument(raw: *const c_char) -> Self {
+            Owned(unsafe{ CStr::from_ptr(raw) }.to_str().unwrap().to_owned())
+        }
+    }
+    crate::many_escaping_reentrant!(MyBlock (environment: &u8, count: u8, name: *const c_char as Owned) -> usize);
+    let block = unsafe{ MyBlock::new(2, |environment, count, name: Owned| {
+        (*environment + count) as usize * name.0.len()
+    })};
+    let r = unsafe{ block.as_block().invoke((1, c"abcd".as_ptr())) };
+    assert_eq!(r, 12);
+}
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Gathering a `many` block's return values.
+
+Enumeration APIs call a block once per item, and bindings usually want the results together.  A `many` block created
+with `new_aggregating` folds each invocation's return value into an accumulator, and returns an [Aggregate] handle to
+it alongside the block.  The fold runs after the closure, under a lock, so it should be cheap: pushing onto a [Vec],
+or a running total.
+
+```
+use blocksr::many_escaping_reentrant;
+many_escaping_reentrant!(ItemBlock (environment: &u32, item: u32) -> u32);
+let (block, aggregate) = unsafe{ ItemBlock::new_aggregating(10, Vec::new(), |all: &mut Vec<u32>, r: &u32| all.push(*r), |scale, item| scale * item) };
+unsafe{ block.as_block().invoke((1,)) };
+unsafe{ block.as_block().invoke((2,)) };
+assert_eq!(aggregate.with(|all| all.clone()), vec![10, 20]);
+```
+
+The handle reads the accumulator at any time.  Once the block is disposed, [Aggregate::try_into_inner] takes it.
+*/
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/**
+The accumulator of a block created with `new_aggregating`.
+*/
+#[derive(Debug)]
+pub struct Aggregate<T>(Arc<Mutex<T>>);
+
+impl<T> Aggregate<T> {
+    #[doc(hidden)]
+    pub fn __new(init: T) -> Self {
+        Aggregate(Arc::new(Mutex::new(init)))
+    }
+    ///The accumulator shared with the block.
+    #[doc(hidden)]
+    pub fn __shared(&self) -> Arc<Mutex<T>> {
+        self.0.clone()
+    }
+    fn lock(&self) -> MutexGuard<'_, T> {
+        //a fold that panicked leaves the accumulator as it was before that value
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    ///Calls `f` with the accumulator, while the block may still be invoked.
+    pub fn with<U, F: FnOnce(&T) -> U>(&self, f: F) -> U {
+        f(&self.lock())
+    }
+    ///Takes the values accumulated so far, leaving `T::default()` for later invocations.
+    pub fn take(&self) -> T where T: Default {
+        core::mem::take(&mut *self.lock())
+    }
+    ///Returns true if the block was disposed, so no more values will be accumulated.
+    pub fn is_disposed(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+    /**
+    Returns the accumulator, if the block was disposed.
+
+    Like [Arc::try_unwrap], this returns `Err(self)` if the block still exists.
+    */
+    pub fn try_into_inner(self) -> Result<T, Self> {
+        Arc::try_unwrap(self.0).map(|mutex| mutex.into_inner().unwrap_or_else(|e| e.into_inner())).map_err(Aggregate)
+    }
+}
+
+///Folds `r` into the accumulator shared with an [Aggregate].
+#[doc(hidden)]
+pub fn aggregate<T, R, F: FnOnce(&mut T, &R)>(shared: &Mutex<T>, fold: F, r: &R) {
+    fold(&mut shared.lock().unwrap_or_else(|e| e.into_inner()), r)
+}
+
+#[test] fn aggregating() {
+    crate::many_escaping_nonreentrant!(SumBlock (environment: &mut u32, item: u32) -> u32);
+    let (mut block, aggregate) = unsafe{ SumBlock::new_aggregating(0, 0u32, |total, r| *total += r, |count, item| { *count += 1; item * *count }) };
+    assert_eq!(unsafe{ block.as_block().invoke((5,)) }, 5);
+    assert_eq!(unsafe{ block.as_block().invoke((5,)) }, 10);
+    assert_eq!(aggregate.with(|total| *total), 15);
+    assert!(!aggregate.is_disposed());
+    let aggregate = aggregate.try_into_inner().unwrap_err();
+    unsafe{ (crate::many::BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert!(aggregate.is_disposed());
+    assert_eq!(aggregate.try_into_inner().unwrap(), 15);
+}
@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Pluggable allocation for block payloads.
+
+Every escaping block allocates: the closure of a once block, and the closure and environment of a many block.  Apps
+creating thousands of short-lived blocks per second (audio taps, per-packet handlers) can route these allocations
+through a [PayloadAllocator], such as the built-in `SlabAllocator` (with `std`), which recycles freed payloads instead
+of returning them to malloc.
+
+```
+use blocksr::arena::{set_payload_allocator, SlabAllocator};
+static SLAB: SlabAllocator = SlabAllocator::new(1024);
+//before any block is created
+set_payload_allocator(&SLAB).unwrap();
+```
+
+The allocator can only be set before the first payload is allocated.  Payloads are freed by dispose (or by invoking
+a once block), which may happen on any thread, so allocators must be [Sync].
+
+If an allocation fails, `new` calls the allocation error handler, which aborts by default, as `Box::new` does.  Each
+block type also has `try_new`, which returns an [AllocError] instead, for daemons that must survive memory pressure.
+*/
+
+use core::alloc::Layout;
+use core::ffi::c_void;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use alloc::boxed::Box;
+
+/**
+Allocates block payloads.
+
+# Safety
+Implementations must behave like [alloc::alloc::GlobalAlloc]: `allocate` returns memory that fits `layout` (or null on
+failure), and `deallocate` receives only pointers returned by `allocate` with the same layout.
+*/
+pub unsafe trait PayloadAllocator: Sync {
+    ///Allocates memory for `layout`.  Returns null on failure.
+    ///
+    /// # Safety
+    /// `layout` has a nonzero size.
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8;
+    ///Frees memory.
+    ///
+    /// # Safety
+    /// `ptr` was returned by [allocate](PayloadAllocator::allocate) with the same `layout`, and is not freed again.
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+}
+
+///The error from [set_payload_allocator] if it is too late to change allocators.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct AllocatorInUse;
+
+impl core::fmt::Display for AllocatorInUse {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "a payload allocator was already set, or a payload was already allocated")
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for AllocatorInUse {}
+
+//No allocator was set and nothing was allocated yet.
+const UNSET: usize = 0;
+//A payload was allocated with the global allocator, so that's the allocator from now on.
+const GLOBAL: usize = 1;
+//Otherwise the state is a pointer to a leaked `&'static dyn PayloadAllocator`, which is aligned, so it's neither of the above.
+static ALLOCATOR: AtomicUsize = AtomicUsize::new(UNSET);
+
+/**
+Routes all payload allocations through `allocator`.
+
+Every payload must be freed by the allocator that allocated it, so this fails with [AllocatorInUse] if an allocator
+was already set, or if any block payload was already allocated.  Call it early, e.g. at the top of `main`.
+*/
+pub fn set_payload_allocator(allocator: &'static dyn PayloadAllocator) -> Result<(), AllocatorInUse> {
+    let leaked: &'static &'static dyn PayloadAllocator = Box::leak(Box::new(allocator));
+    let state = leaked as *const &'static dyn PayloadAllocator as usize;
+    ALLOCATOR.compare_exchange(UNSET, state, Ordering::AcqRel, Ordering::Acquire).map(|_| ()).map_err(|_| {
+        //nobody else saw it
+        drop(unsafe{ Box::from_raw(state as *mut &'static dyn PayloadAllocator) });
+        AllocatorInUse
+    })
+}
+
+//The allocator in use.  None means the global allocator.  Locks in the global allocator if none was set yet.
+fn allocator() -> Option<&'static dyn PayloadAllocator> {
+    let state = match ALLOCATOR.compare_exchange(UNSET, GLOBAL, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => GLOBAL,
+        Err(state) => state,
+    };
+    if state == GLOBAL {
+        None
+    }
+    else {
+        Some(unsafe{ *(state as *const &'static dyn PayloadAllocator) })
+    }
+}
+
+/**
+The error from a `try_new` constructor when a block's payload can't be allocated.
+*/
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct AllocError {
+    layout: Layout,
+}
+
+impl AllocError {
+    ///The layout of the payload that couldn't be allocated.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
+impl core::fmt::Display for AllocError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "failed to allocate a block payload of {} bytes", self.layout.size())
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for AllocError {}
+
+/**
+Moves `value` into a new payload allocation.
+
+Zero-sized values don't allocate, so they need no dispose.
+*/
+#[doc(hidden)]
+#[track_caller]
+pub fn alloc_payload<T>(value: T) -> *mut c_void {
+    unwrap_alloc(try_alloc_payload(value))
+}
+
+/**
+Moves `value` into a new payload allocation, or drops it and returns an error if the allocator fails.
+
+In debug builds with `std`, records the caller as where the block was created; see [crate::provenance].
+*/
+#[doc(hidden)]
+#[track_caller]
+pub fn try_alloc_payload<T>(value: T) -> Result<*mut c_void, AllocError> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        core::mem::forget(value);
+        return Ok(NonNull::<T>::dangling().as_ptr() as *mut c_void);
+    }
+    let ptr = match allocator() {
+        None => unsafe{ alloc::alloc::alloc(layout) },
+        Some(allocator) => unsafe{ allocator.allocate(layout) },
+    } as *mut T;
+    if ptr.is_null() {
+        return Err(AllocError{layout});
+    }
+    unsafe{ ptr.write(value) };
+    #[cfg(all(debug_assertions, feature = "std"))]
+    crate::provenance::record(ptr as *mut c_void, core::panic::Location::caller());
+    Ok(ptr as *mut c_void)
+}
+
+///Unwraps the result of a fallible allocation, calling the allocation error handler on failure, as `Box::new` does.
+#[doc(hidden)]
+pub fn unwrap_alloc<T>(result: Result<T, AllocError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => alloc::alloc::handle_alloc_error(error.layout),
+    }
+}
+
+/**
+Allocates a box, and has `init` initialize it in place, so a large value isn't built on the stack and moved.
+
+# Panics
+If `init` returns a reference to anything but the slot it was given.  Returning the slot's reference is how `init`
+shows it was initialized.  If `init` panics, the allocation is freed.
+*/
+#[doc(hidden)]
+pub fn box_in_place<T, I>(init: I) -> Box<T> where I: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> &'a mut T {
+    let mut uninit = new_uninit_box::<T>();
+    let slot: *mut MaybeUninit<T> = &mut *uninit;
+    let initialized: *mut T = init(&mut uninit);
+    assert_eq!(initialized as *mut MaybeUninit<T>, slot, "an in-place initializer must return the slot it was given");
+    //Safety: init returned the slot as a &mut T, so it is initialized
+    unsafe{ Box::from_raw(Box::into_raw(uninit) as *mut T) }
+}
+
+//Box::new_uninit, which our MSRV doesn't have.  Allocating directly avoids moving an uninitialized T onto the heap.
+fn new_uninit_box<T>() -> Box<MaybeUninit<T>> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        return Box::new(MaybeUninit::uninit());
+    }
+    let ptr = unsafe{ alloc::alloc::alloc(layout) } as *mut MaybeUninit<T>;
+    if ptr.is_null() {
+        alloc::alloc::handle_alloc_error(layout);
+    }
+    unsafe{ Box::from_raw(ptr) }
+}
+
+/**
+Moves the value out of a payload allocation and frees it.
+
+# Safety
+`ptr` must have been returned by [alloc_payload] for a `T`, and not taken already.
+*/
+#[doc(hidden)]
+pub unsafe fn take_payload<T>(ptr: *mut c_void) -> T {
+    let value = unsafe{ (ptr as *mut T).read() };
+    let layout = Layout::new::<T>();
+    if layout.size() != 0 {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        crate::provenance::remove(ptr);
+        //kept, so that the address isn't reused by another payload while a stale block may still refer to it
+        #[cfg(feature = "poison-disposed")]
+        {
+            DISPOSED.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(Default::default).insert(ptr as usize);
+            return value;
+        }
+        #[allow(unreachable_code)]
+        match allocator() {
+            None => unsafe{ alloc::alloc::dealloc(ptr as *mut u8, layout) },
+            Some(allocator) => unsafe{ allocator.deallocate(ptr as *mut u8, layout) },
+        }
+    }
+    value
+}
+
+//Payload allocations that were taken, with the `poison-disposed` feature, which never frees them
+#[cfg(feature = "poison-disposed")]
+static DISPOSED: std::sync::Mutex<Option<std::collections::HashSet<usize>>> = std::sync::Mutex::new(None);
+
+///Whether `ptr` is a payload that was already taken, and so disposed.  Zero-sized payloads aren't tracked.
+#[cfg(feature = "poison-disposed")]
+pub(crate) fn is_disposed(ptr: *mut c_void) -> bool {
+    DISPOSED.lock().unwrap_or_else(|e| e.into_inner()).as_ref().is_some_and(|disposed| disposed.contains(&(ptr as usize)))
+}
+
+/**
+Recycles small allocations in per-size free lists.
+
+Sizes up to 512 bytes are rounded up to a power of two, and each size keeps up to `capacity` freed allocations for
+reuse.  Larger or over-aligned payloads go to the global allocator.
+*/
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SlabAllocator {
+    classes: [std::sync::Mutex<FreeList>; CLASSES],
+    capacity: usize,
+}
+
+#[cfg(feature = "std")]
+const CLASSES: usize = 6;
+#[cfg(feature = "std")]
+const SMALLEST: usize = 16;
+#[cfg(feature = "std")]
+const SLAB_ALIGN: usize = 16;
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct FreeList(alloc::vec::Vec<*mut u8>);
+//Safety: the pointers are unused allocations
+#[cfg(feature = "std")]
+unsafe impl Send for FreeList {}
+
+#[cfg(feature = "std")]
+impl SlabAllocator {
+    ///Creates a slab that keeps up to `capacity` free allocations per size.
+    pub const fn new(capacity: usize) -> Self {
+        use std::sync::Mutex;
+        SlabAllocator {
+            classes: [
+                Mutex::new(FreeList(alloc::vec::Vec::new())),
+                Mutex::new(FreeList(alloc::vec::Vec::new())),
+                Mutex::new(FreeList(alloc::vec::Vec::new())),
+                Mutex::new(FreeList(alloc::vec::Vec::new())),
+                Mutex::new(FreeList(alloc::vec::Vec::new())),
+                Mutex::new(FreeList(alloc::vec::Vec::new())),
+            ],
+            capacity,
+        }
+    }
+    //the size class for `layout`, if it has one
+    fn class(layout: Layout) -> Option<usize> {
+        if layout.align() > SLAB_ALIGN {
+            return None;
+        }
+        let size = layout.size().max(SMALLEST).next_power_of_two();
+        let class = (size / SMALLEST).trailing_zeros() as usize;
+        if class < CLASSES { Some(class) } else { None }
+    }
+    fn class_layout(class: usize) -> Layout {
+        Layout::from_size_align(SMALLEST << class, SLAB_ALIGN).unwrap()
+    }
+    ///The number of free allocations that would be reused for `layout`.
+    pub fn free_count(&self, layout: Layout) -> usize {
+        Self::class(layout).map(|class| self.classes[class].lock().unwrap().0.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe impl PayloadAllocator for SlabAllocator {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        match Self::class(layout) {
+            None => unsafe{ alloc::alloc::alloc(layout) },
+            Some(class) => {
+                let reused = self.classes[class].lock().unwrap().0.pop();
+                reused.unwrap_or_else(|| unsafe{ alloc::alloc::alloc(Self::class_layout(class)) })
+            }
+        }
+    }
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        match Self::class(layout) {
+            None => unsafe{ alloc::alloc::dealloc(ptr, layout) },
+            Some(class) => {
+                let mut free = self.classes[class].lock().unwrap();
+                if free.0.len() < self.capacity {
+                    free.0.push(ptr);
+                }
+                else {
+                    drop(free);
+                    unsafe{ alloc::alloc::dealloc(ptr, Self::class_layout(class)) }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test] fn slab_reuses() {
+    let slab = SlabAllocator::new(1);
+    let layout = Layout::new::<[u64; 3]>();
+    let first = unsafe{ slab.allocate(layout) };
+    let second = unsafe{ slab.allocate(layout) };
+    unsafe{ slab.deallocate(first, layout) };
+    //over capacity, so freed
+    unsafe{ slab.deallocate(second, layout) };
+    assert_eq!(slab.free_count(layout), 1);
+    //rounded up to the same size
+    assert_eq!(unsafe{ slab.allocate(Layout::new::<[u64; 4]>()) }, first);
+    assert_eq!(slab.free_count(layout), 0);
+    unsafe{ slab.deallocate(first, layout) };
+    assert_eq!(SlabAllocator::class(Layout::from_size_align(1024, 8).unwrap()), None);
+}
+
+#[test] fn zero_sized() {
+    let ptr = alloc_payload(());
+    unsafe{ take_payload::<()>(ptr) };
+}
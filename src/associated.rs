@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Storing blocks on ObjC objects, with the `associated-objects` feature.
+
+A block that should live as long as some object, such as a handler for its notifications, can be attached to the
+object with `objc_setAssociatedObject`.  The runtime then releases the block when the object is deallocated, or when
+another block is attached with the same key.
+
+```no_run
+use blocksr::{many_escaping_reentrant, HeapBlock, AssociationKey, attach_block, attached_block};
+many_escaping_reentrant!(Handler (environment: &u8, arg: u8) -> u8);
+static HANDLER: AssociationKey = AssociationKey::new();
+# let object: *const core::ffi::c_void = core::ptr::null();
+let stack = unsafe{ Handler::new(2, |environment, arg| environment * arg) };
+unsafe{ attach_block(object, &HANDLER, HeapBlock::copy(&stack)) };
+//later...
+let handler: Option<HeapBlock<Handler>> = unsafe{ attached_block(object, &HANDLER) };
+```
+*/
+
+use core::ffi::c_void;
+use core::ptr::NonNull;
+use crate::HeapBlock;
+
+//objc_AssociationPolicy.  Blocks are associated with copy semantics, like a `copy` property.
+const OBJC_ASSOCIATION_COPY: usize = 0x303;
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn objc_setAssociatedObject(object: *const c_void, key: *const c_void, value: *const c_void, policy: usize);
+    fn objc_getAssociatedObject(object: *const c_void, key: *const c_void) -> *const c_void;
+}
+
+/**
+A key for attaching a block to an object.  Declare one `static` per use.
+
+The runtime identifies keys by address, so each static is a distinct key.
+*/
+#[derive(Debug)]
+pub struct AssociationKey(#[allow(dead_code)] u8);
+
+impl AssociationKey {
+    ///Creates a key.  Store it in a `static`, so its address is stable.
+    pub const fn new() -> Self {
+        AssociationKey(0)
+    }
+    fn as_ptr(&'static self) -> *const c_void {
+        self as *const Self as *const c_void
+    }
+}
+
+impl Default for AssociationKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+Attaches `block` to `object` under `key`, replacing any block attached with the same key.
+
+The object keeps its own reference, which it releases when it is deallocated, or the block is replaced or detached.
+
+# Safety
+You must verify that `object` is a valid ObjC object, and that `T` is the block type other code reads with `key`.
+*/
+pub unsafe fn attach_block<T>(object: *const c_void, key: &'static AssociationKey, block: HeapBlock<T>) {
+    //the association takes its own reference; ours is released when `block` drops
+    unsafe{ objc_setAssociatedObject(object, key.as_ptr(), block.as_ptr() as *const c_void, OBJC_ASSOCIATION_COPY) };
+}
+
+/**
+Returns a new reference to the block attached to `object` under `key`, if any.
+
+# Safety
+You must verify that `object` is a valid ObjC object, and that the block attached under `key` is a `T`.
+*/
+pub unsafe fn attached_block<T>(object: *const c_void, key: &'static AssociationKey) -> Option<HeapBlock<T>> {
+    let block = NonNull::new(unsafe{ objc_getAssociatedObject(object, key.as_ptr()) } as *mut T)?;
+    Some(unsafe{ HeapBlock::copy(block.as_ref()) })
+}
+
+/**
+Detaches the block attached to `object` under `key`, if any.  The object releases its reference.
+
+# Safety
+You must verify that `object` is a valid ObjC object.
+*/
+pub unsafe fn detach_block(object: *const c_void, key: &'static AssociationKey) {
+    unsafe{ objc_setAssociatedObject(object, key.as_ptr(), core::ptr::null(), OBJC_ASSOCIATION_COPY) };
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn round_trip() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    extern "C" {
+        fn objc_getClass(name: *const core::ffi::c_char) -> *const c_void;
+        fn class_createInstance(class: *const c_void, extra: usize) -> *const c_void;
+        fn objc_release(object: *const c_void);
+    }
+    static KEY: AssociationKey = AssociationKey::new();
+    crate::many_escaping_reentrant!(MyBlock (environment: &u8, arg: u8) -> u8);
+    let object = unsafe{ class_createInstance(objc_getClass(c"NSObject".as_ptr()), 0) };
+    let disposed = Arc::new(AtomicBool::new(false));
+    let on_dispose = disposed.clone();
+    let stack = unsafe{ MyBlock::new_with_dispose(2, |environment, arg| environment * arg, move || on_dispose.store(true, Ordering::SeqCst)) };
+    unsafe{ attach_block(object, &KEY, HeapBlock::copy(&stack)) };
+    let attached: HeapBlock<MyBlock> = unsafe{ attached_block(object, &KEY) }.unwrap();
+    assert_eq!(unsafe{ attached.as_block().invoke((3,)) }, 6);
+    drop(attached);
+    assert!(!disposed.load(Ordering::SeqCst));
+    unsafe{ detach_block(object, &KEY) };
+    assert!(disposed.load(Ordering::SeqCst));
+    assert!(unsafe{ attached_block::<MyBlock>(object, &KEY) }.is_none());
+    unsafe{ objc_release(object) };
+}
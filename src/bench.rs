@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Benchmarks, with the `bench` feature.
+
+These measure block creation, heap copies, invocation and disposal, and continuation round-trips with
+[criterion].  They are public so that you can reproduce our numbers, or compare them against another blocks crate,
+from your own bench target:
+
+```ignore
+use criterion::{criterion_group, criterion_main};
+criterion_group!(benches, blocksr::bench::all);
+criterion_main!(benches);
+```
+
+The crate's own are run with `cargo bench --features bench`.
+
+Blocks are invoked through [Block::invoke](crate::Block::invoke), and copied and disposed by calling the helpers in
+their descriptors the way the runtime would (heap copies through [Harness](crate::testing::Harness)), so apart from the isa no ObjC is involved.  Where the blocks runtime
+is available, `heap_copy/runtime` also measures the real `Block_copy` and `Block_release`.
+*/
+
+use std::hint::black_box;
+use criterion::Criterion;
+use crate::{Block, BlockFlags};
+use crate::testing::Harness;
+use crate::continuation::{continuation, ContinuationPool};
+use crate::executors::block_on;
+
+crate::once_escaping!(OnceBlock (arg: u64) -> u64);
+crate::many_escaping_reentrant!(ManyBlock (environment: &u64, arg: u64) -> u64);
+crate::many_escaping_reentrant!(LargeBlock (environment: &[u64; 512], arg: u64) -> u64);
+crate::many_escaping_reentrant!(CopiedBlock (environment: &u64, arg: u64) -> u64; clone_on_copy);
+
+///Disposes the literal the way `Block_release` does, without a runtime or a heap copy.
+unsafe fn simulated_dispose<T>(block: T) {
+    let header = unsafe{ &*(&block as *const T as *const Block<(),()>) };
+    if header.flags().contains(BlockFlags::HAS_COPY_DISPOSE) {
+        //the dispose helper follows reserved, size and the copy helper
+        let dispose_helper: extern "C" fn(*const T) = unsafe{ *(header.descriptor() as *const extern "C" fn(*const T)).add(3) };
+        dispose_helper(&block);
+    }
+    core::mem::forget(block);
+}
+
+///Creating blocks, and creating and disposing them.
+pub fn creation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("creation");
+    group.bench_function("once", |b| b.iter(|| {
+        let block = unsafe{ OnceBlock::new(|arg| arg + 1) };
+        //invoking frees the closure
+        unsafe{ block.as_block().invoke((black_box(1),)) }
+    }));
+    group.bench_function("many_dispose", |b| b.iter(|| {
+        let block = unsafe{ ManyBlock::new(black_box(2u64), |environment, arg| environment + arg) };
+        unsafe{ simulated_dispose(block) };
+    }));
+    group.bench_function("many_trivial", |b| b.iter(|| {
+        let block = unsafe{ crate::VoidManyBlock::new((), |_| {}) };
+        unsafe{ simulated_dispose(black_box(block)) };
+    }));
+    group.finish();
+}
+
+///Copying a block to the heap and releasing the copy.
+pub fn heap_copy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("heap_copy");
+    //each copy owns a clone, so disposing it doesn't free the original's payload
+    let block = unsafe{ CopiedBlock::new_clone_on_copy(2u64, |environment, arg| environment + arg) };
+    group.bench_function("simulated", |b| b.iter(|| {
+        let copy = unsafe{ Harness::copy(&block) };
+        drop(black_box(copy));
+    }));
+    #[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+    if crate::runtime_available() {
+        group.bench_function("runtime", |b| b.iter(|| {
+            let heap = unsafe{ crate::HeapBlock::copy(&block) };
+            black_box(heap.as_ptr());
+        }));
+    }
+    group.finish();
+}
+
+///Invoking blocks.
+pub fn invocation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("invocation");
+    let block = unsafe{ ManyBlock::new(2u64, |environment, arg| environment + arg) };
+    group.bench_function("many", |b| b.iter(|| {
+        unsafe{ block.as_block().invoke((black_box(1),)) }
+    }));
+    let generic: &Block<(u64,),u64> = block.as_block();
+    group.bench_function("closure_baseline", |b| {
+        let closure = |arg: u64| arg + 2;
+        b.iter(|| closure(black_box(1)))
+    });
+    group.bench_function("generic", |b| b.iter(|| {
+        unsafe{ black_box(generic).invoke((black_box(1),)) }
+    }));
+    group.finish();
+    unsafe{ simulated_dispose(block) };
+}
+
+///Large environments, stored inline or with `new_indirect`.
+pub fn environments(c: &mut Criterion) {
+    let mut group = c.benchmark_group("environment");
+    let blocks = [
+        ("inline", unsafe{ LargeBlock::new([1; 512], |environment, arg| environment[(arg % 512) as usize] + arg) }),
+        ("indirect", unsafe{ LargeBlock::new_indirect([1; 512], |environment, arg| environment[(arg % 512) as usize] + arg) }),
+    ];
+    for (name, block) in &blocks {
+        group.bench_function(format!("{}_invoke", name), |b| b.iter(|| {
+            unsafe{ block.as_block().invoke((black_box(1),)) }
+        }));
+    }
+    group.bench_function("inline_create_dispose", |b| b.iter(|| {
+        let block = unsafe{ LargeBlock::new(black_box([1; 512]), |environment, arg| environment[0] + arg) };
+        unsafe{ simulated_dispose(block) };
+    }));
+    group.bench_function("indirect_create_dispose", |b| b.iter(|| {
+        let block = unsafe{ LargeBlock::new_indirect(black_box([1; 512]), |environment, arg| environment[0] + arg) };
+        unsafe{ simulated_dispose(block) };
+    }));
+    group.finish();
+    for (_, block) in blocks {
+        unsafe{ simulated_dispose(block) };
+    }
+}
+
+///Completing and polling continuations.
+pub fn continuations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("continuation");
+    group.bench_function("complete_poll", |b| b.iter(|| {
+        let (continuation, completer) = continuation();
+        completer.complete(black_box(1u64));
+        block_on(continuation)
+    }));
+    let pool = ContinuationPool::new(16);
+    group.bench_function("pooled_complete_poll", |b| b.iter(|| {
+        let (continuation, completer) = pool.acquire();
+        completer.complete(black_box(1u64));
+        block_on(continuation)
+    }));
+    group.bench_function("block_complete_poll", |b| b.iter(|| {
+        let (continuation, completer) = continuation();
+        let block = unsafe{ OnceBlock::new(move |arg| { completer.complete(arg); arg }) };
+        unsafe{ block.as_block().invoke((black_box(1),)) };
+        block_on(continuation)
+    }));
+    group.finish();
+}
+
+///Runs every benchmark.
+pub fn all(c: &mut Criterion) {
+    creation(c);
+    heap_copy(c);
+    invocation(c);
+    environments(c);
+    continuations(c);
+}
@@ -0,0 +1,531 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Generic block type, parameterized by argument tuple and return type.
+
+The macros in this crate declare a distinct newtype per block signature.  Sometimes that's too much ceremony,
+for example when you receive a block from ObjC and just want to call it.  [Block] describes any block
+with a given signature, regardless of who made it.
+
+```
+use blocksr::{once_escaping, Block};
+once_escaping!(MyBlock (a: u8, b: f64) -> f64);
+let f = unsafe{ MyBlock::new(|a,b| {
+    a as f64 + b
+})};
+let block: &Block<(u8,f64),f64> = f.as_block();
+let r = unsafe{ block.invoke((1,2.5)) };
+assert_eq!(r, 3.5);
+```
+
+Arities up to 12 arguments are supported.
+*/
+
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ffi::{c_char, c_int, CStr};
+
+/**
+A block with arguments `Args` (a tuple) and return type `R`.
+
+This type is only ever used behind a pointer or reference; it describes the header common to every block literal
+(ABI.2010.3.16).  Whatever follows the header (captures, etc.) is opaque.
+*/
+#[repr(C)]
+#[derive(Debug)]
+pub struct Block<Args,R> {
+    pub(crate) isa: *const c_void,
+    pub(crate) flags: c_int,
+    pub(crate) reserved: MaybeUninit<c_int>,
+    //first arg to this fn ptr is &block_literal_1
+    pub(crate) invoke: *const c_void,
+    pub(crate) descriptor: *const c_void,
+    //Block is not constructible in Rust and not Send/Sync by default
+    _marker: PhantomData<*const (Args,R)>,
+}
+
+impl<Args,R> Block<Args,R> {
+    ///Invokes the block with the arguments specified.
+    ///
+    /// # Safety
+    /// You must verify that
+    /// * `Args` and `R` match the block's actual signature
+    /// * Invoking the block is consistent with its kind; for example a once block may be invoked at most once.
+    pub unsafe fn invoke(&self, args: Args) -> R where Args: BlockArguments<R> {
+        args.invoke_block(self)
+    }
+    ///The raw invoke pointer, suitable for transmuting to a function pointer of the right type.
+    ///
+    /// The first argument to this function is a pointer to the block itself.
+    pub fn invoke_ptr(&self) -> *const c_void {
+        self.invoke
+    }
+    ///Decodes the block's flags.
+    pub fn flags(&self) -> BlockFlags {
+        BlockFlags::from_bits(self.flags)
+    }
+    #[allow(dead_code)] //used by optional features
+    pub(crate) fn descriptor(&self) -> *const c_void {
+        self.descriptor
+    }
+    //the pointer-sized word at `index` in the descriptor
+    fn descriptor_word(&self, index: usize) -> *const c_void {
+        unsafe{ *(self.descriptor as *const *const c_void).add(index) }
+    }
+    ///The size of the block literal, as recorded in its descriptor.
+    pub fn literal_size(&self) -> usize {
+        //the second word; the first is reserved
+        self.descriptor_word(1) as usize
+    }
+    /**
+    The ObjC type encoding of the block's signature, if the descriptor has one.
+
+    Blocks compiled by clang usually have one, e.g. `v16@?0@8` for a block taking an object.
+    */
+    pub fn signature(&self) -> Option<&CStr> {
+        let flags = self.flags();
+        if !flags.contains(BlockFlags::HAS_SIGNATURE) {
+            return None;
+        }
+        /*
+        struct Block_descriptor_1 {
+            unsigned long int reserved;
+            unsigned long int size;
+            void (*copy_helper)(void *dst, void *src);     // IFF (1<<25)
+            void (*dispose_helper)(void *src);             // IFF (1<<25)
+            const char *signature;                         // IFF (1<<30)
+        }
+         */
+        let index = if flags.contains(BlockFlags::HAS_COPY_DISPOSE) { 4 } else { 2 };
+        let signature = self.descriptor_word(index) as *const c_char;
+        if signature.is_null() {
+            None
+        }
+        else {
+            Some(unsafe{ CStr::from_ptr(signature) })
+        }
+    }
+}
+
+/**
+The flags word of a block literal.
+
+```
+use blocksr::{many_escaping_reentrant, BlockFlags};
+many_escaping_reentrant!(MyBlock (environment: &(), arg: u8) -> ());
+assert!(MyBlock::FLAGS.contains(BlockFlags::HAS_COPY_DISPOSE));
+assert!(!MyBlock::FLAGS.contains(BlockFlags::IS_GLOBAL));
+```
+
+The [Debug] representation lists the flags by name, which is handy for logging blocks received from ObjC.
+*/
+#[derive(Clone,Copy,PartialEq,Eq,Hash)]
+pub struct BlockFlags(c_int);
+
+impl BlockFlags {
+    ///The descriptor contains copy and dispose helpers.
+    pub const HAS_COPY_DISPOSE: BlockFlags = BlockFlags(crate::once::BLOCK_HAS_COPY_DISPOSE);
+    ///The block does not escape the call it was passed to.
+    pub const IS_NOESCAPE: BlockFlags = BlockFlags(crate::once::BLOCK_IS_NOESCAPE);
+    ///The block is a global (static) block, which is never copied or disposed.
+    pub const IS_GLOBAL: BlockFlags = BlockFlags(crate::once::BLOCK_IS_GLOBAL);
+    ///The block returns its value indirectly, through a pointer in the first argument slot.
+    pub const HAS_STRET: BlockFlags = BlockFlags(crate::once::BLOCK_HAS_STRET);
+    ///The descriptor contains an ObjC type encoding of the block's signature.
+    pub const HAS_SIGNATURE: BlockFlags = BlockFlags(crate::once::BLOCK_HAS_SIGNATURE);
+    ///The descriptor's layout field uses the extended layout encoding.
+    pub const HAS_EXTENDED_LAYOUT: BlockFlags = BlockFlags(crate::once::BLOCK_HAS_EXTENDED_LAYOUT);
+
+    const NAMED: [(BlockFlags, &'static str); 6] = [
+        (BlockFlags::HAS_COPY_DISPOSE, "HAS_COPY_DISPOSE"),
+        (BlockFlags::IS_NOESCAPE, "IS_NOESCAPE"),
+        (BlockFlags::IS_GLOBAL, "IS_GLOBAL"),
+        (BlockFlags::HAS_STRET, "HAS_STRET"),
+        (BlockFlags::HAS_SIGNATURE, "HAS_SIGNATURE"),
+        (BlockFlags::HAS_EXTENDED_LAYOUT, "HAS_EXTENDED_LAYOUT"),
+    ];
+
+    ///Wraps a raw flags word.  Bits without a name, such as the runtime's refcount, are kept.
+    pub const fn from_bits(bits: c_int) -> Self {
+        BlockFlags(bits)
+    }
+    ///The raw flags word.
+    pub const fn bits(self) -> c_int {
+        self.0
+    }
+    ///Returns true if all of the flags in `other` are set.
+    pub const fn contains(self, other: BlockFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for BlockFlags {
+    type Output = BlockFlags;
+    fn bitor(self, rhs: BlockFlags) -> BlockFlags {
+        BlockFlags(self.0 | rhs.0)
+    }
+}
+
+impl core::fmt::Debug for BlockFlags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "BlockFlags(")?;
+        let mut rest = self.0;
+        let mut first = true;
+        for (flag, name) in BlockFlags::NAMED {
+            if self.contains(flag) {
+                if !first { write!(f, " | ")?; }
+                write!(f, "{}", name)?;
+                rest &= !flag.0;
+                first = false;
+            }
+        }
+        if rest != 0 || first {
+            if !first { write!(f, " | ")?; }
+            write!(f, "{:#x}", rest)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/**
+Formats a block literal for [Debug], decoding its header.
+
+`closure` says where the closure lives: `"boxed"` or `"inline"`.
+*/
+#[doc(hidden)]
+pub fn debug_literal(f: &mut core::fmt::Formatter<'_>, name: &str, header: &Block<(),()>, closure: &str) -> core::fmt::Result {
+    let mut debug = f.debug_struct(name);
+    debug.field("flags", &header.flags());
+    //noescape literals fix up their descriptor pointer once they are in place
+    if !header.descriptor.is_null() {
+        debug.field("size", &header.literal_size());
+        if let Some(signature) = header.signature() {
+            debug.field("signature", &signature);
+        }
+    }
+    debug.field("closure", &format_args!("{}", closure));
+    debug.finish()
+}
+
+/**
+Implements [Debug] for a generated block type.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __debug_block {
+    ($blockname:ident $(<$g:ident>)?, $closure:literal) => {
+        impl$(<$g>)? core::fmt::Debug for $blockname$(<$g>)? {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                blocksr::hidden::debug_literal(f, stringify!($blockname), unsafe{ &*(self as *const Self as *const blocksr::Block<(),()>) }, $closure)
+            }
+        }
+    };
+}
+
+/**
+Implements `RefEncode` for a generated block type, with the `interop-objc2` feature; otherwise, does nothing.
+*/
+#[cfg(not(feature = "interop-objc2"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __objc2_encode {
+    ($($t:tt)*) => {};
+}
+
+/**
+Declares the descriptors of a generated block type, `once` or `many`, with its signature.  Used inside the type's
+`impl` block, after [__introspection].
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __descriptor {
+    (once) => {
+        #[doc(hidden)]
+        const __DESCRIPTOR: &'static blocksr::hidden::BlockDescriptorOnce = &blocksr::hidden::BlockDescriptorOnce::new(core::mem::size_of::<blocksr::hidden::BlockLiteralOnceEscape>()).with_signature(Self::__SIGNATURE);
+    };
+    (many) => {
+        #[doc(hidden)]
+        const __DESCRIPTOR: &'static blocksr::hidden::ManyDescriptors = &blocksr::hidden::ManyDescriptors::new(Self::__SIGNATURE);
+    };
+}
+
+/**
+Declares the introspection items of a generated block type.  Used inside the type's `impl` block.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __introspection {
+    ($flags:expr) => {
+        ///The flags this type's literals are created with.
+        ///
+        /// This is an upper bound for `many` blocks: a literal whose closure and environment are zero-sized, and
+        /// need no drop, is created without [HAS_COPY_DISPOSE](blocksr::BlockFlags::HAS_COPY_DISPOSE), since it has
+        /// nothing to copy or dispose.  [flags](Self::flags) reports what a literal actually has.
+        ///
+        /// Types declared with `isa = global` have [IS_GLOBAL](blocksr::BlockFlags::IS_GLOBAL), and never
+        /// [HAS_COPY_DISPOSE](blocksr::BlockFlags::HAS_COPY_DISPOSE).  Types declared with `once_noescape` have
+        /// [IS_NOESCAPE](blocksr::BlockFlags::IS_NOESCAPE), as clang sets it on blocks passed to `noescape` parameters.
+        #[allow(dead_code)]
+        pub const FLAGS: blocksr::BlockFlags = blocksr::BlockFlags::from_bits({
+            #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _;
+            blocksr::hidden::isa_flags($flags | blocksr::hidden::descriptor_flags(Self::__SIGNATURE), Self::__GLOBAL)
+        });
+        ///This type's [Signature](blocksr::Signature), if it has one, for its descriptors.
+        #[doc(hidden)]
+        const __SIGNATURE: Option<&'static core::ffi::CStr> = {
+            #[allow(unused_imports)] use blocksr::hidden::NoSignature as _;
+            blocksr::hidden::SignatureProbe::<Self>::SIGNATURE
+        };
+        ///The size of this type's literal.
+        #[allow(dead_code)]
+        pub const fn literal_size() -> usize {
+            core::mem::size_of::<Self>()
+        }
+        ///Decodes this block's flags, which may differ from [Self::FLAGS] after the runtime has copied it.
+        #[allow(dead_code)]
+        pub fn flags(&self) -> blocksr::BlockFlags {
+            unsafe{ &*(self as *const Self as *const blocksr::Block<(),()>) }.flags()
+        }
+        ///The ObjC type encoding of this block's signature, if its descriptor has one.
+        #[allow(dead_code)]
+        pub fn signature(&self) -> Option<&core::ffi::CStr> {
+            unsafe{ &*(self as *const Self as *const blocksr::Block<(),()>) }.signature()
+        }
+    };
+}
+
+/**
+Argument tuples that can be passed to a [Block].
+
+This trait is implemented for tuples of 0 to 12 elements.
+*/
+pub trait BlockArguments<R>: Sized {
+    ///Calls the block's invoke pointer with the receiver's elements as arguments.
+    ///
+    /// # Safety
+    /// See [Block::invoke].
+    unsafe fn invoke_block(self, block: &Block<Self,R>) -> R;
+}
+
+macro_rules! block_arguments {
+    ($($a:ident : $A:ident),*) => {
+        impl<$($A,)* R> BlockArguments<R> for ($($A,)*) {
+            unsafe fn invoke_block(self, block: &Block<Self,R>) -> R {
+                let ($($a,)*) = self;
+                let invoke: extern "C" fn(*const Block<Self,R> $(,$A)*) -> R = core::mem::transmute(block.invoke);
+                invoke(block $(,$a)*)
+            }
+        }
+    }
+}
+block_arguments!();
+block_arguments!(a: A);
+block_arguments!(a: A, b: B);
+block_arguments!(a: A, b: B, c: C);
+block_arguments!(a: A, b: B, c: C, d: D);
+block_arguments!(a: A, b: B, c: C, d: D, e: E);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K);
+block_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K, l: L);
+
+/*
+ABI tests per arity.
+
+Arguments cycle through i8, f64, u64, f32, i32, u16 so that each arity mixes integer and floating-point registers,
+and higher arities spill onto the stack.  The argument in position n (1-based) has value n.  Each block returns the sum
+of its arguments as f64.
+ */
+#[cfg(test)]
+macro_rules! arity_test {
+    ($test:ident, $fixture:ident, $($a:ident : $A:ty = $v:expr),*) => {
+        #[test] fn $test() {
+            crate::once_escaping!(ArityBlock ($($a: $A),*) -> f64);
+            #[allow(clippy::unnecessary_cast)]
+            let expected: f64 = 0.0 $(+ $v as f64)*;
+            let make = || unsafe{ ArityBlock::new(|$($a),*| {
+                $(assert_eq!($a, $v);)*
+                0.0 $(+ $a as f64)*
+            })};
+            let block = make();
+            let r = unsafe{ block.as_block().invoke(($($v,)*)) };
+            assert_eq!(r, expected);
+
+            #[cfg(feature = "abi-fixtures")]
+            {
+                extern "C" {
+                    fn $fixture(block: *const Block<($($A,)*),f64>) -> f64;
+                }
+                let block = make();
+                let r = unsafe{ $fixture(block.as_block()) };
+                assert_eq!(r, expected);
+            }
+        }
+    }
+}
+#[cfg(test)] arity_test!(arity_0, blocksr_fixture_arity_0, );
+#[cfg(test)] arity_test!(arity_1, blocksr_fixture_arity_1, a: i8 = 1);
+#[cfg(test)] arity_test!(arity_2, blocksr_fixture_arity_2, a: i8 = 1, b: f64 = 2.0);
+#[cfg(test)] arity_test!(arity_3, blocksr_fixture_arity_3, a: i8 = 1, b: f64 = 2.0, c: u64 = 3);
+#[cfg(test)] arity_test!(arity_4, blocksr_fixture_arity_4, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0);
+#[cfg(test)] arity_test!(arity_5, blocksr_fixture_arity_5, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5);
+#[cfg(test)] arity_test!(arity_6, blocksr_fixture_arity_6, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6);
+#[cfg(test)] arity_test!(arity_7, blocksr_fixture_arity_7, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6, g: i8 = 7);
+#[cfg(test)] arity_test!(arity_8, blocksr_fixture_arity_8, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6, g: i8 = 7, h: f64 = 8.0);
+#[cfg(test)] arity_test!(arity_9, blocksr_fixture_arity_9, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6, g: i8 = 7, h: f64 = 8.0, i: u64 = 9);
+#[cfg(test)] arity_test!(arity_10, blocksr_fixture_arity_10, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6, g: i8 = 7, h: f64 = 8.0, i: u64 = 9, j: f32 = 10.0);
+#[cfg(test)] arity_test!(arity_11, blocksr_fixture_arity_11, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6, g: i8 = 7, h: f64 = 8.0, i: u64 = 9, j: f32 = 10.0, k: i32 = 11);
+#[cfg(test)] arity_test!(arity_12, blocksr_fixture_arity_12, a: i8 = 1, b: f64 = 2.0, c: u64 = 3, d: f32 = 4.0, e: i32 = 5, f: u16 = 6, g: i8 = 7, h: f64 = 8.0, i: u64 = 9, j: f32 = 10.0, k: i32 = 11, l: u16 = 12);
+
+/*
+Struct-by-value tests, shaped like CGRect/CGSize.
+ */
+#[cfg(test)]
+#[repr(C)]
+#[derive(Debug,Clone,Copy,PartialEq)]
+struct Size {
+    width: f64,
+    height: f64,
+}
+#[cfg(test)]
+#[repr(C)]
+#[derive(Debug,Clone,Copy,PartialEq)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[test] fn struct_return_stret() {
+    crate::once_escaping!(RectBlock (rect: Rect, size: Size) -> Rect);
+    let make = || unsafe{ RectBlock::new(|rect, size| {
+        Rect{x: rect.x, y: rect.y, width: rect.width * size.width, height: rect.height * size.height}
+    })};
+    let expected = Rect{x: 1.0, y: 2.0, width: 15.0, height: 24.0};
+    let block = make();
+    //clang only sets the flag when the return pointer takes an argument register
+    let stret = block.0.flags & crate::hidden::BLOCK_HAS_STRET != 0;
+    assert_eq!(stret, cfg!(target_arch = "x86_64"));
+    let r = unsafe{ block.as_block().invoke((Rect{x: 1.0, y: 2.0, width: 3.0, height: 4.0}, Size{width: 5.0, height: 6.0})) };
+    assert_eq!(r, expected);
+
+    #[cfg(feature = "abi-fixtures")]
+    {
+        extern "C" {
+            fn blocksr_fixture_rect(block: *const Block<(Rect,Size),Rect>) -> Rect;
+        }
+        let block = make();
+        let r = unsafe{ blocksr_fixture_rect(block.as_block()) };
+        assert_eq!(r, expected);
+    }
+}
+
+#[test] fn struct_return_registers() {
+    crate::once_escaping!(SizeBlock (size: Size) -> Size);
+    let make = || unsafe{ SizeBlock::new(|size| {
+        Size{width: size.height, height: size.width}
+    })};
+    let expected = Size{width: 6.0, height: 5.0};
+    let block = make();
+    assert_eq!(block.0.flags & crate::hidden::BLOCK_HAS_STRET, 0);
+    let r = unsafe{ block.as_block().invoke((Size{width: 5.0, height: 6.0},)) };
+    assert_eq!(r, expected);
+
+    #[cfg(feature = "abi-fixtures")]
+    {
+        extern "C" {
+            fn blocksr_fixture_size(block: *const Block<(Size,),Size>) -> Size;
+        }
+        let block = make();
+        let r = unsafe{ blocksr_fixture_size(block.as_block()) };
+        assert_eq!(r, expected);
+    }
+}
+
+/*
+Small returns, which 32-bit targets treat differently: an 8-byte struct like NSRange goes in memory on armv7 and i386
+Linux, but in registers on i386 Darwin, while a `u64` is always in registers.
+ */
+#[cfg(test)]
+#[repr(C)]
+#[derive(Debug,Clone,Copy,PartialEq)]
+struct Range {
+    location: u32,
+    length: u32,
+}
+
+#[test] fn struct_return_small() {
+    crate::once_escaping!(RangeBlock (range: Range) -> Range);
+    let make = || unsafe{ RangeBlock::new(|range| {
+        Range{location: range.length, length: range.location}
+    })};
+    let expected = Range{location: 6, length: 5};
+    let block = make();
+    let stret = block.0.flags & crate::hidden::BLOCK_HAS_STRET != 0;
+    //on i386 Linux, stret_flag can't tell the range from a u64, and assumes the u64
+    if !cfg!(all(target_arch = "x86", not(any(target_vendor = "apple", windows)))) {
+        assert_eq!(stret, cfg!(all(target_arch = "arm", not(target_os = "watchos"))));
+    }
+    let r = unsafe{ block.as_block().invoke((Range{location: 5, length: 6},)) };
+    assert_eq!(r, expected);
+
+    #[cfg(feature = "abi-fixtures")]
+    {
+        extern "C" {
+            fn blocksr_fixture_range(block: *const Block<(Range,),Range>) -> Range;
+        }
+        let block = make();
+        let r = unsafe{ blocksr_fixture_range(block.as_block()) };
+        assert_eq!(r, expected);
+    }
+}
+
+#[test] fn scalar_return_wide() {
+    crate::once_escaping!(WideBlock (location: u32, length: u32) -> u64);
+    let make = || unsafe{ WideBlock::new(|location, length| {
+        (location as u64) << 32 | length as u64
+    })};
+    let expected = 5 << 32 | 6;
+    let block = make();
+    assert_eq!(block.0.flags & crate::hidden::BLOCK_HAS_STRET, 0);
+    assert_eq!(crate::hidden::stret_flag::<f64>(), 0);
+    let r = unsafe{ block.as_block().invoke((5, 6)) };
+    assert_eq!(r, expected);
+
+    #[cfg(feature = "abi-fixtures")]
+    {
+        extern "C" {
+            fn blocksr_fixture_wide(block: *const Block<(u32,u32),u64>) -> u64;
+        }
+        let block = make();
+        let r = unsafe{ blocksr_fixture_wide(block.as_block()) };
+        assert_eq!(r, expected);
+    }
+}
+
+#[test] fn introspection() {
+    crate::once_escaping!(SizeBlock (size: Size) -> Size);
+    let block = unsafe{ SizeBlock::new(|size| size) };
+    assert_eq!(block.flags(), SizeBlock::FLAGS);
+    assert_eq!(block.as_block().literal_size(), SizeBlock::literal_size());
+    assert!(!SizeBlock::FLAGS.contains(BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(block.signature(), None);
+    assert_eq!(format!("{:?}", BlockFlags::HAS_COPY_DISPOSE | BlockFlags::from_bits(2)), "BlockFlags(HAS_COPY_DISPOSE | 0x2)");
+    assert_eq!(format!("{:?}", BlockFlags::from_bits(0)), "BlockFlags(0x0)");
+}
+
+#[test] fn debug() {
+    crate::many_escaping_reentrant!(LogBlock (environment: &u8, size: Size) -> Size);
+    let block = unsafe{ LogBlock::new(0, |_, size| size) };
+    let expected = format!("LogBlock {{ flags: {:?}, size: {}, closure: boxed }}", LogBlock::FLAGS, LogBlock::literal_size());
+    assert_eq!(format!("{:?}", block), expected);
+    //FLAGS is an upper bound: a zero-sized payload has no helpers
+    crate::many_escaping_reentrant!(TrivialBlock (environment: &(), size: Size) -> Size);
+    let trivial = unsafe{ TrivialBlock::new((), |_, size| size) };
+    assert_eq!(trivial.flags(), BlockFlags::from_bits(TrivialBlock::FLAGS.bits() & !BlockFlags::HAS_COPY_DISPOSE.bits()));
+    let expected = format!("TrivialBlock {{ flags: {:?}, size: {}, closure: boxed }}", trivial.flags(), TrivialBlock::literal_size());
+    assert_eq!(format!("{:?}", trivial), expected);
+}
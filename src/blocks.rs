@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! The block layer: literals, the macros that declare them, and the ABI.
+
+This is everything a binding needs to pass blocks to ObjC and receive them back.  It requires only `core` and
+`alloc`, so it is available with `default-features = false`, and depends on nothing but the blocks runtime.
+
+```
+use blocksr::blocks::{once_escaping, Block};
+once_escaping!(MyBlock (arg: u8) -> u8);
+let f = unsafe{ MyBlock::new(|arg| arg * 2) };
+let _block: &Block<(u8,),u8> = f.as_block();
+```
+
+The same items are also at the crate root, where they have always been.  Continuations and the other async and
+dispatch helpers are in `blocksr::rt`, with the `rt` feature.
+*/
+
+pub use crate::{once_escaping, once_escaping_tolerant, once_noescape, many_escaping_nonreentrant, many_escaping_reentrant};
+pub use crate::{Block, BlockArguments, BlockFlags, HeapBlock};
+pub use crate::{StackBlockCell, QueueConfined, CancellationToken};
+pub use crate::{CopyArgument, BorrowArgument, BlockParam, ThrowingReturn, OutParameter, IntoOutError};
+pub use crate::{ProxyBlock, ProxyArguments, BlockBuilder, BuilderArguments, BuiltBlock, Captures, RetainedObject};
+pub use crate::{Trampoline, OnceTrampoline, VoidBlock, VoidManyBlock, QosClass};
+pub use crate::{runtime_available, global_block_isa, set_payload_allocator};
+pub use crate::{raw, arena, objc_types, encoding, Signature};
+#[cfg(feature = "std")]
+pub use crate::{static_block, StaticBlock, named_block, Aggregate};
+#[cfg(feature = "fn_traits")]
+pub use crate::CallableBlock;
+#[cfg(feature = "c_variadic")]
+pub use crate::VaArgs;
@@ -0,0 +1,310 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Blocks with explicit capture lists.
+
+In C, whether a block retains what it captures is decided by the compiler, from each variable's type and qualifiers.
+A [BlockBuilder] makes the same decisions explicit: each capture is added as strong, weak, or a plain Rust value before
+the body is supplied, so retain cycles can be audited at the call site.
+
+```no_run
+use blocksr::BlockBuilder;
+# let view: *const core::ffi::c_void = core::ptr::null();
+# let delegate: *const core::ffi::c_void = core::ptr::null();
+let block = unsafe {
+    BlockBuilder::new()
+        //retained until the block is disposed
+        .capture_strong(delegate)
+        //doesn't keep the view alive, which would be a cycle
+        .capture_weak(view)
+        .capture_value(String::from("tapped"))
+        .build(|captures, (count,): (u32,)| {
+            if let Some(view) = captures.weak(1) {
+                //message view.as_ptr()...
+            }
+            let label: &String = captures.value(2);
+            count + label.len() as u32
+        })
+};
+//pass block.as_block() somewhere...
+```
+
+Like the `many` blocks, every heap copy shares the captures.  Strong captures are retained when they are added and released
+when the block is disposed; weak captures are registered with the ObjC runtime and unregistered then.
+*/
+
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use crate::{Block, BlockArguments};
+use crate::many::{BlockLiteralManyEscape, BLOCK_DESCRIPTOR_MANY};
+use crate::once::{BLOCK_HAS_COPY_DISPOSE, stret_flag};
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn objc_retain(object: *const c_void) -> *const c_void;
+    fn objc_release(object: *const c_void);
+    fn objc_initWeak(location: *mut *const c_void, object: *const c_void) -> *const c_void;
+    fn objc_loadWeakRetained(location: *mut *const c_void) -> *const c_void;
+    fn objc_destroyWeak(location: *mut *const c_void);
+}
+
+enum Capture {
+    Strong(*const c_void),
+    //the runtime tracks the slot's address, so it is boxed
+    Weak(Box<UnsafeCell<*const c_void>>),
+    Value(Box<dyn Any + Send + Sync>),
+}
+
+impl core::fmt::Debug for Capture {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Capture::Strong(object) => write!(f, "strong {:p}", *object),
+            Capture::Weak(_) => write!(f, "weak"),
+            Capture::Value(_) => write!(f, "value"),
+        }
+    }
+}
+
+/**
+The captures of a block made with [BlockBuilder], by index in the order they were added.
+
+# Panics
+The accessors panic if the capture at `index` is of another kind, or doesn't exist.
+*/
+#[derive(Debug)]
+pub struct Captures(Vec<Capture>);
+
+//Safety: objects are retained, or tracked weakly by the runtime, and may be released on any thread.  Values are Send.
+unsafe impl Send for Captures {}
+//Safety: accessors only read the pointers, hand out shared references to values, which are Sync, or use the
+//runtime's thread-safe weak functions
+unsafe impl Sync for Captures {}
+
+impl Captures {
+    ///The object captured strongly at `index`.
+    pub fn strong(&self, index: usize) -> *const c_void {
+        match &self.0[index] {
+            Capture::Strong(object) => *object,
+            other => panic!("capture {} is {:?}, not strong", index, other),
+        }
+    }
+    ///The object captured weakly at `index`, retained for the caller, or `None` if it was deallocated.
+    pub fn weak(&self, index: usize) -> Option<RetainedObject> {
+        match &self.0[index] {
+            Capture::Weak(slot) => {
+                let object = unsafe{ objc_loadWeakRetained(slot.get()) };
+                if object.is_null() { None } else { Some(RetainedObject(object)) }
+            }
+            other => panic!("capture {} is {:?}, not weak", index, other),
+        }
+    }
+    ///The value captured at `index`.
+    pub fn value<T: 'static>(&self, index: usize) -> &T {
+        match &self.0[index] {
+            Capture::Value(value) => value.downcast_ref().unwrap_or_else(|| panic!("capture {} is not a {}", index, core::any::type_name::<T>())),
+            other => panic!("capture {} is {:?}, not a value", index, other),
+        }
+    }
+    ///The number of captures.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    ///Returns true if nothing was captured.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Drop for Captures {
+    fn drop(&mut self) {
+        for capture in &self.0 {
+            match capture {
+                Capture::Strong(object) => unsafe{ objc_release(*object) },
+                Capture::Weak(slot) => unsafe{ objc_destroyWeak(slot.get()) },
+                Capture::Value(_) => {}
+            }
+        }
+    }
+}
+
+/**
+An object loaded from a weak capture, which is released when this is dropped.
+*/
+#[derive(Debug)]
+pub struct RetainedObject(*const c_void);
+
+impl RetainedObject {
+    ///The object.  It stays alive at least as long as `self`.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0
+    }
+}
+
+impl Drop for RetainedObject {
+    fn drop(&mut self) {
+        unsafe{ objc_release(self.0) }
+    }
+}
+
+/**
+Builds a block from an explicit capture list and a body.
+
+Captures are added with [capture_strong](Self::capture_strong), [capture_weak](Self::capture_weak) and
+[capture_value](Self::capture_value), and read in the body from [Captures] by the index they were added at.
+*/
+#[derive(Debug,Default)]
+pub struct BlockBuilder {
+    captures: Vec<Capture>,
+}
+
+impl BlockBuilder {
+    ///Creates a builder with no captures.
+    pub fn new() -> Self {
+        BlockBuilder{captures: Vec::new()}
+    }
+    /**
+    Captures `object` strongly, like a `__strong` variable: it is retained now and released when the block is disposed.
+
+    # Safety
+    `object` must be a valid ObjC object, or nil.
+    */
+    pub unsafe fn capture_strong(mut self, object: *const c_void) -> Self {
+        self.captures.push(Capture::Strong(unsafe{ objc_retain(object) }));
+        self
+    }
+    /**
+    Captures `object` weakly, like a `__weak` variable: the block doesn't keep it alive, and loading it yields `None`
+    once it is deallocated.
+
+    # Safety
+    `object` must be a valid ObjC object that supports weak references, or nil.
+    */
+    pub unsafe fn capture_weak(mut self, object: *const c_void) -> Self {
+        let slot = Box::new(UnsafeCell::new(core::ptr::null()));
+        unsafe{ objc_initWeak(slot.get(), object) };
+        self.captures.push(Capture::Weak(slot));
+        self
+    }
+    ///Captures a Rust value, which is dropped when the block is disposed.
+    ///
+    /// The block may be invoked from several threads at once, each reading the value, so it must be `Sync`.
+    pub fn capture_value<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.captures.push(Capture::Value(Box::new(value)));
+        self
+    }
+    /**
+    Creates the block.  `body` receives the captures and the arguments as a tuple.
+
+    Like a `many_escaping_reentrant` block, the result may be invoked from several threads at once, so `body` must be
+    `Sync`.
+
+    # Safety
+    You must verify that
+    * `Args` and `R` match the signature objc expects, and are FFI-safe
+    * The block is disposed, by the runtime, only after its last invocation
+    */
+    pub unsafe fn build<Args,R,F>(self, body: F) -> BuiltBlock<Args,R> where Args: BuilderArguments<R> + 'static, R: 'static, F: Fn(&Captures, Args) -> R + Send + Sync + 'static {
+        extern "C" fn dispose_thunk<Args,R>(block: *mut BlockLiteralManyEscape) {
+            let payload_ptr = unsafe{ crate::many::live_payload(block) } as *mut BuilderPayload<Args,R>;
+            let _boxed_payload = unsafe{ Box::from_raw(payload_ptr) };
+            //drop
+        }
+        let payload = Box::new(BuilderPayload{captures: Captures(self.captures), body: Box::new(body)});
+        let literal = BlockLiteralManyEscape {
+            isa: crate::runtime::stack_block_isa(),
+            flags: stret_flag::<R>() | BLOCK_HAS_COPY_DISPOSE,
+            reserved: 0,
+            invoke: Args::builder_thunk(),
+            descriptor: core::ptr::addr_of_mut!(BLOCK_DESCRIPTOR_MANY) as *mut c_void,
+            //note: this leak will be cleaned up by dispose
+            payload: Box::into_raw(payload) as *mut c_void,
+            dispose: dispose_thunk::<Args,R>,
+            take_environment: None,
+            clone_payload: None,
+        };
+        BuiltBlock(literal, PhantomData)
+    }
+}
+
+type Body<Args,R> = Box<dyn Fn(&Captures, Args) -> R + Send + Sync>;
+
+struct BuilderPayload<Args,R> {
+    captures: Captures,
+    body: Body<Args,R>,
+}
+
+/**
+Argument tuples for blocks made with [BlockBuilder].
+
+This trait is implemented for tuples of 0 to 12 elements.
+*/
+pub trait BuilderArguments<R>: BlockArguments<R> {
+    #[doc(hidden)]
+    fn builder_thunk() -> *const c_void;
+}
+
+macro_rules! builder_arguments {
+    ($($a:ident : $A:ident),*) => {
+        impl<$($A,)* R> BuilderArguments<R> for ($($A,)*) {
+            fn builder_thunk() -> *const c_void {
+                //This thunk is safe to call from C
+                extern "C" fn invoke_thunk<$($A,)* R>(block: *mut BlockLiteralManyEscape $(,$a: $A)*) -> R {
+                    let payload: &BuilderPayload<($($A,)*),R> = unsafe{ &*(crate::many::live_payload(block) as *const BuilderPayload<($($A,)*),R>) };
+                    (payload.body)(&payload.captures, ($($a,)*))
+                }
+                invoke_thunk::<$($A,)* R> as *const c_void
+            }
+        }
+    }
+}
+builder_arguments!();
+builder_arguments!(a: A);
+builder_arguments!(a: A, b: B);
+builder_arguments!(a: A, b: B, c: C);
+builder_arguments!(a: A, b: B, c: C, d: D);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K);
+builder_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K, l: L);
+
+/**
+A block made by [BlockBuilder].
+
+The captures are dropped when the block is disposed, with assistance from the ObjC runtime.
+*/
+#[repr(transparent)]
+#[derive(Debug)]
+pub struct BuiltBlock<Args,R>(BlockLiteralManyEscape, PhantomData<*const (Args,R)>);
+
+impl<Args,R> BuiltBlock<Args,R> {
+    ///Views this block as a generic [Block].
+    pub fn as_block(&self) -> &Block<Args,R> {
+        unsafe{ &*(self as *const Self as *const Block<Args,R>) }
+    }
+    ///The capture list, for auditing.
+    pub fn captures(&self) -> &Captures {
+        unsafe{ &(*(self.0.payload as *const BuilderPayload<Args,R>)).captures }
+    }
+}
+
+#[test] fn values() {
+    let block = unsafe{ BlockBuilder::new()
+        .capture_value(2u32)
+        .capture_value(String::from("abc"))
+        .build(|captures, (arg,): (u32,)| {
+            arg * captures.value::<u32>(0) + captures.value::<String>(1).len() as u32
+        })
+    };
+    assert_eq!(block.captures().len(), 2);
+    assert_eq!(unsafe{ block.as_block().invoke((5,)) }, 13);
+    let copy = unsafe{ crate::testing::Harness::copy(&block) };
+    assert_eq!(unsafe{ copy.invoke::<(u32,),u32>((1,)) }, 5);
+    //the captures are dropped when the copy is disposed
+    drop(copy);
+}
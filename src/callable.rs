@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Calling blocks like closures, with the nightly `fn_traits` feature.
+
+A block received from objc is a [Block], which is invoked with an unsafe method taking an argument tuple.
+[Block::as_fn] vouches for it once, returning a [CallableBlock] that implements [Fn], so it can be called with
+ordinary call syntax and passed to generic Rust code expecting `impl Fn(A) -> R`.
+
+```ignore
+use blocksr::Block;
+fn sum(f: impl Fn(u8) -> u8) -> u8 {
+    (0..3).map(f).sum()
+}
+fn forward(block: &Block<(u8,),u8>) -> u8 {
+    let f = unsafe{ block.as_fn() };
+    f(1) + sum(f)
+}
+```
+*/
+
+use core::marker::Tuple;
+use crate::{Block, BlockArguments};
+
+/**
+A [Block] that may be called like a closure.  Created by [Block::as_fn].
+*/
+#[derive(Debug)]
+pub struct CallableBlock<'a, Args, R>(&'a Block<Args,R>);
+
+impl<Args, R> Clone for CallableBlock<'_, Args, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<Args, R> Copy for CallableBlock<'_, Args, R> {}
+
+impl<Args, R> Block<Args, R> {
+    /**
+    Returns a view of the block that implements [Fn], [FnMut] and [FnOnce].
+
+    # Safety
+    Calls are not unsafe, so you must verify up front that every call through the view is valid, as for
+    [Block::invoke]:
+    * `Args` and `R` match the block's actual signature
+    * The block may be called as many times, and as re-entrantly, as the code you pass the view to may call it.  For
+      example, a once block should only be passed to code taking [FnOnce].
+    */
+    pub unsafe fn as_fn(&self) -> CallableBlock<'_, Args, R> {
+        CallableBlock(self)
+    }
+}
+
+impl<Args: BlockArguments<R> + Tuple, R> FnOnce<Args> for CallableBlock<'_, Args, R> {
+    type Output = R;
+    extern "rust-call" fn call_once(self, args: Args) -> R {
+        unsafe{ self.0.invoke(args) }
+    }
+}
+
+impl<Args: BlockArguments<R> + Tuple, R> FnMut<Args> for CallableBlock<'_, Args, R> {
+    extern "rust-call" fn call_mut(&mut self, args: Args) -> R {
+        unsafe{ self.0.invoke(args) }
+    }
+}
+
+impl<Args: BlockArguments<R> + Tuple, R> Fn<Args> for CallableBlock<'_, Args, R> {
+    extern "rust-call" fn call(&self, args: Args) -> R {
+        unsafe{ self.0.invoke(args) }
+    }
+}
+
+#[test] fn call_like_closure() {
+    fn sum(f: impl Fn(u8) -> u8) -> u8 {
+        (0..3).map(f).sum()
+    }
+    crate::many_escaping_reentrant!(AddBlock (environment: &u8, arg: u8) -> u8);
+    let block = unsafe{ AddBlock::new(1, |environment, arg| environment + arg) };
+    let f = unsafe{ block.as_block().as_fn() };
+    assert_eq!(f(2), 3);
+    assert_eq!(sum(f), 6);
+}
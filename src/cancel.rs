@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Cooperative cancellation for long-lived blocks.
+
+A `many` block created with `new_cancellable` comes with a [CancellationToken].  Once the token is cancelled, the
+block's invocations return `Default::default()` without running the closure.  The closure also receives the token, so
+a long invocation can check it.  When the block is disposed, the token is cancelled too, so whoever holds it can tell
+the block is gone.
+
+```
+use blocksr::many_escaping_reentrant;
+many_escaping_reentrant!(TapBlock (environment: &(), frames: u32) -> u32);
+let (block, token) = unsafe{ TapBlock::new_cancellable((), |_environment, token, frames| {
+    if token.is_cancelled() { 0 } else { frames }
+})};
+assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 3);
+token.cancel();
+assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 0);
+```
+*/
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/**
+Cancels a block created with `new_cancellable`.
+
+Clones share the same state.
+*/
+#[derive(Debug,Clone,Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    ///Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    ///Cancels the token.  Cancelling more than once has no further effect.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+    ///Returns true if the token was cancelled, or its block was disposed.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/**
+Environment wrapper used by `new_cancellable`.
+
+Cancels the token when dropped, that is, when the block is disposed.
+*/
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Cancellable<E> {
+    environment: E,
+    token: CancellationToken,
+}
+impl<E> Cancellable<E> {
+    pub fn new(environment: E, token: CancellationToken) -> Self {
+        Cancellable{environment, token}
+    }
+    pub fn environment(&self) -> &E {
+        &self.environment
+    }
+    pub fn environment_mut(&mut self) -> &mut E {
+        &mut self.environment
+    }
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+    ///The environment and token, for closures that take both mutably and by reference.
+    pub fn parts_mut(&mut self) -> (&mut E, &CancellationToken) {
+        (&mut self.environment, &self.token)
+    }
+}
+impl<E> Drop for Cancellable<E> {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+/**
+The value a cancelled invocation returns.
+
+This is [Default], stated as a bound on the closure so that block types whose return type isn't [Default] can still be declared.
+*/
+#[doc(hidden)]
+pub trait DefaultReturn<R> {
+    fn default_return() -> R;
+}
+impl<C, R: Default> DefaultReturn<R> for C {
+    fn default_return() -> R {
+        R::default()
+    }
+}
+
+#[test] fn cancelled_on_dispose() {
+    crate::many_escaping_nonreentrant!(MyBlock (environment: &mut u8, arg: u8) -> u8);
+    let (mut block, token) = unsafe{ MyBlock::new_cancellable(1, |environment, _token, arg| {
+        *environment += arg;
+        *environment
+    })};
+    assert_eq!(unsafe{ block.as_block().invoke((2,)) }, 3);
+    assert!(!token.is_cancelled());
+    //simulate the runtime disposing the block
+    unsafe{ (crate::many::BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert!(token.is_cancelled());
+}
@@ -0,0 +1,329 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! The time source for features that wait, such as the `slow-completion` warnings.
+
+By default, deadlines are kept by [SystemClock], which runs one timer thread for the whole library.  [set_clock]
+replaces it with another [BlockClock]: an async runtime's own timers, so that no thread is spawned, or a
+[ManualClock], so that tests of timing behavior run instantly and deterministically.
+
+```
+use blocksr::clock::{BlockClock, ManualClock};
+use std::time::Duration;
+let clock = ManualClock::new();
+let fired = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+let set = fired.clone();
+clock.call_at(clock.now() + Duration::from_secs(60), Box::new(move || set.store(true, std::sync::atomic::Ordering::SeqCst)));
+clock.advance(Duration::from_secs(60));
+assert!(fired.load(std::sync::atomic::Ordering::SeqCst));
+```
+*/
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use crate::CancellationToken;
+
+///A callback for [BlockClock::call_at].
+pub type Callback = Box<dyn FnOnce() + Send>;
+
+/**
+A source of time, and of callbacks at a deadline.
+*/
+pub trait BlockClock: Send + Sync {
+    ///The current time.
+    fn now(&self) -> Instant;
+    /**
+    Calls `f` once [now](BlockClock::now) reaches `deadline`, on any thread.
+
+    `f` should be called promptly after the deadline, but not before it.  It is cheap, and must not block.
+    */
+    fn call_at(&self, deadline: Instant, f: Callback);
+    /**
+    Like [call_at](BlockClock::call_at), but `f` is dropped without being called if `token` is cancelled first.
+
+    The default checks `token` at the deadline.  Clocks that keep the callbacks themselves should discard cancelled
+    ones sooner, since most `slow-completion` checks are cancelled long before they are due.
+    */
+    fn call_at_cancellable(&self, deadline: Instant, f: Callback, token: CancellationToken) {
+        self.call_at(deadline, Box::new(move || {
+            if !token.is_cancelled() {
+                f()
+            }
+        }));
+    }
+}
+
+static CLOCK: RwLock<Option<Arc<dyn BlockClock>>> = RwLock::new(None);
+
+/**
+Replaces the clock used by the library.
+
+Deadlines already scheduled stay with the clock they were scheduled on.
+*/
+pub fn set_clock(clock: Arc<dyn BlockClock>) {
+    *CLOCK.write().unwrap_or_else(|e| e.into_inner()) = Some(clock);
+}
+
+///The clock used by the library: the one passed to [set_clock], or [SystemClock].
+pub fn clock() -> Arc<dyn BlockClock> {
+    if let Some(clock) = CLOCK.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return clock.clone();
+    }
+    static SYSTEM: OnceLock<Arc<dyn BlockClock>> = OnceLock::new();
+    SYSTEM.get_or_init(|| Arc::new(SystemClock)).clone()
+}
+
+struct Scheduled {
+    //earliest first, then in the order they were scheduled
+    key: Reverse<(Instant, u64)>,
+    f: Callback,
+    token: Option<CancellationToken>,
+}
+
+impl Scheduled {
+    fn is_cancelled(&self) -> bool {
+        self.token.as_ref().is_some_and(|token| token.is_cancelled())
+    }
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+impl Eq for Scheduled {}
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+//Below this many callbacks, cancelled ones are left until they are due.
+const MIN_PRUNE: usize = 64;
+
+//Callbacks by deadline.
+struct Schedule {
+    heap: BinaryHeap<Scheduled>,
+    sequence: u64,
+    //the length at which cancelled callbacks are next removed, so that pruning is amortized over the pushes
+    prune_at: usize,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule{heap: BinaryHeap::new(), sequence: 0, prune_at: MIN_PRUNE}
+    }
+}
+
+impl Schedule {
+    fn push(&mut self, deadline: Instant, f: Callback, token: Option<CancellationToken>) {
+        if self.heap.len() >= self.prune_at {
+            self.heap.retain(|s| !s.is_cancelled());
+            self.prune_at = MIN_PRUNE.max(self.heap.len() * 2);
+        }
+        self.sequence += 1;
+        self.heap.push(Scheduled{key: Reverse((deadline, self.sequence)), f, token});
+    }
+    //The earliest deadline of a callback that isn't cancelled.
+    fn next_deadline(&mut self) -> Option<Instant> {
+        while self.heap.peek()?.is_cancelled() {
+            self.heap.pop();
+        }
+        self.heap.peek().map(|s| s.key.0.0)
+    }
+    //Removes the callbacks whose deadline is at or before `now`, in order of deadline, skipping cancelled ones.
+    fn due(&mut self, now: Instant) -> Vec<Callback> {
+        let mut callbacks = Vec::new();
+        while self.heap.peek().is_some_and(|s| s.key.0.0 <= now) {
+            let scheduled = self.heap.pop().expect("peeked");
+            if !scheduled.is_cancelled() {
+                callbacks.push(scheduled.f);
+            }
+        }
+        callbacks
+    }
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+/**
+The real time, with callbacks run by one timer thread, which is started the first time one is scheduled.
+*/
+#[derive(Debug,Clone,Copy,Default)]
+pub struct SystemClock;
+
+#[derive(Default)]
+struct Timer {
+    scheduled: Mutex<Schedule>,
+    changed: Condvar,
+}
+
+impl Timer {
+    fn run(&self) -> ! {
+        let mut scheduled = self.scheduled.lock().unwrap();
+        loop {
+            let callbacks = scheduled.due(Instant::now());
+            if !callbacks.is_empty() {
+                drop(scheduled);
+                for f in callbacks {
+                    f();
+                }
+                scheduled = self.scheduled.lock().unwrap();
+                continue;
+            }
+            scheduled = match scheduled.next_deadline() {
+                None => self.changed.wait(scheduled).unwrap(),
+                Some(next) => {
+                    let timeout = next.saturating_duration_since(Instant::now());
+                    self.changed.wait_timeout(scheduled, timeout).unwrap().0
+                }
+            };
+        }
+    }
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    static STARTED: std::sync::Once = std::sync::Once::new();
+    let timer = TIMER.get_or_init(Timer::default);
+    STARTED.call_once(|| {
+        std::thread::Builder::new().name("blocksr timer".to_string()).spawn(move || timer.run()).expect("can't spawn timer thread");
+    });
+    timer
+}
+
+impl SystemClock {
+    fn schedule(&self, deadline: Instant, f: Callback, token: Option<CancellationToken>) {
+        let timer = timer();
+        timer.scheduled.lock().unwrap().push(deadline, f, token);
+        timer.changed.notify_one();
+    }
+}
+
+impl BlockClock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn call_at(&self, deadline: Instant, f: Callback) {
+        self.schedule(deadline, f, None);
+    }
+    fn call_at_cancellable(&self, deadline: Instant, f: Callback, token: CancellationToken) {
+        self.schedule(deadline, f, Some(token));
+    }
+}
+
+/**
+A clock that only moves when [advanced](ManualClock::advance), for tests.
+
+Callbacks run on the thread calling `advance`.
+*/
+pub struct ManualClock {
+    start: Instant,
+    state: Mutex<(Duration, Schedule)>,
+}
+
+impl std::fmt::Debug for ManualClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("ManualClock").field("elapsed", &state.0).field("scheduled", &state.1.len()).finish()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    ///Creates a clock that reads the current time until it is advanced.
+    pub fn new() -> Self {
+        ManualClock{start: Instant::now(), state: Mutex::new((Duration::ZERO, Schedule::default()))}
+    }
+    ///Moves the clock forward by `by`, and runs the callbacks that became due, in order of deadline.
+    pub fn advance(&self, by: Duration) {
+        let callbacks = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.0 += by;
+            let now = self.start + state.0;
+            state.1.due(now)
+        };
+        for f in callbacks {
+            f();
+        }
+    }
+}
+
+impl BlockClock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.state.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+    fn call_at(&self, deadline: Instant, f: Callback) {
+        self.schedule(deadline, f, None);
+    }
+    fn call_at_cancellable(&self, deadline: Instant, f: Callback, token: CancellationToken) {
+        self.schedule(deadline, f, Some(token));
+    }
+}
+
+impl ManualClock {
+    fn schedule(&self, deadline: Instant, f: Callback, token: Option<CancellationToken>) {
+        if deadline > self.now() {
+            self.state.lock().unwrap_or_else(|e| e.into_inner()).1.push(deadline, f, token);
+        }
+        else if !token.is_some_and(|token| token.is_cancelled()) {
+            f();
+        }
+    }
+}
+
+#[test] fn manual() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let clock = ManualClock::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for seconds in [20, 10, 30] {
+        let order = order.clone();
+        clock.call_at(clock.now() + Duration::from_secs(seconds), Box::new(move || order.lock().unwrap().push(seconds)));
+    }
+    clock.advance(Duration::from_secs(20));
+    assert_eq!(*order.lock().unwrap(), vec![10, 20]);
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(*order.lock().unwrap(), vec![10, 20, 30]);
+    let immediate = Arc::new(AtomicUsize::new(0));
+    let count = immediate.clone();
+    clock.call_at(clock.now(), Box::new(move || { count.fetch_add(1, Ordering::SeqCst); }));
+    assert_eq!(immediate.load(Ordering::SeqCst), 1);
+}
+
+#[test] fn cancelled() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let clock = ManualClock::new();
+    let called = Arc::new(AtomicUsize::new(0));
+    let deadline = clock.now() + Duration::from_secs(10);
+    let kept = CancellationToken::new();
+    let count = called.clone();
+    clock.call_at_cancellable(deadline, Box::new(move || { count.fetch_add(1, Ordering::SeqCst); }), kept.clone());
+    for _ in 0..1000 {
+        let token = CancellationToken::new();
+        let count = called.clone();
+        clock.call_at_cancellable(deadline, Box::new(move || { count.fetch_add(1, Ordering::SeqCst); }), token.clone());
+        token.cancel();
+    }
+    //cancelled callbacks are discarded as more are scheduled, not held until they are due
+    assert!(clock.state.lock().unwrap().1.len() < 2 * MIN_PRUNE);
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(called.load(Ordering::SeqCst), 1);
+}
+
+#[test] fn system() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let start = Instant::now();
+    SystemClock.call_at(start + Duration::from_millis(20), Box::new(move || sender.send(Instant::now()).unwrap()));
+    assert!(receiver.recv().unwrap() >= start + Duration::from_millis(20));
+}
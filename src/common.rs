@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Pre-declared reply blocks for system prompts, with the `common` feature.
+
+Permission and authentication prompts across many frameworks reply with the same signatures: `(BOOL success,
+NSError *error)` for LocalAuthentication, EventKit, Contacts and `UNUserNotificationCenter`, or `(BOOL granted)` for
+AVFoundation.  These types declare them once, with constructors that bridge the reply to a future.
+
+```no_run
+use blocksr::common::SuccessReplyBlock;
+async fn example() {
+    let (reply, result) = unsafe{ SuccessReplyBlock::result_future() };
+    //pass reply to e.g. evaluatePolicy:localizedReason:reply:
+    # drop(reply);
+    match result.await {
+        Ok(()) => {}
+        Err(error) => { /* error.as_ptr() is the NSError, or null */ }
+    }
+}
+```
+
+A [BlockError] is the `NSError` itself.  [BlockError::code] reads its domain and code into an [ErrorCode], which maps
+the common domains, POSIX, Cocoa and URL loading, to an [io::ErrorKind], and a [BlockError] converts to an
+[io::Error] with that kind, for bindings that return idiomatic errors.
+
+```
+use blocksr::common::ErrorCode;
+use std::io::ErrorKind;
+assert_eq!(ErrorCode::new("NSURLErrorDomain", -1001).kind(), ErrorKind::TimedOut);
+assert_eq!(ErrorCode::new("NSCocoaErrorDomain", 260), ErrorCode::Cocoa(260));
+assert_eq!(ErrorCode::new("NSPOSIXErrorDomain", 2).kind(), ErrorKind::NotFound);
+```
+
+`BOOL` is a `signed char` on some targets and a `bool` on others, so it is declared as `i8`, and any nonzero value
+is `YES`.
+*/
+
+use core::ffi::{c_char, c_void, CStr};
+use std::io;
+use crate::continuation::{continuation, Continuation};
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn objc_retain(object: *const c_void) -> *const c_void;
+    fn objc_release(object: *const c_void);
+    fn sel_registerName(name: *const c_char) -> *const c_void;
+    fn objc_msgSend();
+}
+
+//Sends a message with no arguments.  Safety: `receiver` responds to `selector`, returning an `R`.
+unsafe fn send<R>(receiver: *const c_void, selector: &CStr) -> R {
+    let send: unsafe extern "C" fn(*const c_void, *const c_void) -> R = unsafe{ core::mem::transmute(objc_msgSend as unsafe extern "C" fn()) };
+    unsafe{ send(receiver, sel_registerName(selector.as_ptr())) }
+}
+
+#[allow(clippy::unused_unit)]
+mod declarations {
+    use core::ffi::c_void;
+    blocksr::once_escaping!(pub SuccessReplyBlock (success: i8, error: *const c_void) -> ());
+    blocksr::once_escaping!(pub GrantedReplyBlock (granted: i8) -> ());
+}
+pub use declarations::{SuccessReplyBlock,GrantedReplyBlock};
+
+/**
+The failure a [SuccessReplyBlock] reports: its `NSError`, retained, or null if it replied `NO` without one.
+*/
+#[derive(Debug)]
+pub struct BlockError(*const c_void);
+
+//Safety: NSError is immutable, and retain and release are thread-safe
+unsafe impl Send for BlockError {}
+unsafe impl Sync for BlockError {}
+
+impl BlockError {
+    unsafe fn retaining(error: *const c_void) -> Self {
+        if error.is_null() {
+            BlockError(error)
+        }
+        else {
+            BlockError(unsafe{ objc_retain(error) })
+        }
+    }
+    ///The `NSError`, or null.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0
+    }
+    ///The error's domain and code, or [ErrorCode::None] if there is no `NSError`.
+    pub fn code(&self) -> ErrorCode {
+        if self.0.is_null() {
+            return ErrorCode::None;
+        }
+        let code: isize = unsafe{ send(self.0, c"code") };
+        let domain: *const c_void = unsafe{ send(self.0, c"domain") };
+        let domain: *const c_char = unsafe{ send(domain, c"UTF8String") };
+        ErrorCode::new(&unsafe{ CStr::from_ptr(domain) }.to_string_lossy(), code)
+    }
+}
+
+/**
+The domain and code of an `NSError`, with the common domains broken out.
+
+[kind](ErrorCode::kind) maps them to an [io::ErrorKind].  Codes without a close match, and other domains, map to
+[io::ErrorKind::Other].
+*/
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    ///There was no `NSError`.
+    None,
+    ///`NSPOSIXErrorDomain`: an `errno` value.
+    Posix(i32),
+    ///`NSCocoaErrorDomain`, such as `NSFileReadNoSuchFileError`.
+    Cocoa(isize),
+    ///`NSURLErrorDomain`, such as `NSURLErrorTimedOut`.
+    Url(isize),
+    ///Another domain, and the code.
+    Other(String, isize),
+}
+
+impl ErrorCode {
+    ///Classifies `code` in `domain`.
+    pub fn new(domain: &str, code: isize) -> Self {
+        match domain {
+            "NSPOSIXErrorDomain" => match i32::try_from(code) {
+                Ok(code) => ErrorCode::Posix(code),
+                Err(_) => ErrorCode::Other(domain.to_string(), code),
+            },
+            "NSCocoaErrorDomain" => ErrorCode::Cocoa(code),
+            "NSURLErrorDomain" => ErrorCode::Url(code),
+            _ => ErrorCode::Other(domain.to_string(), code),
+        }
+    }
+    ///The closest [io::ErrorKind].
+    pub fn kind(&self) -> io::ErrorKind {
+        use io::ErrorKind::*;
+        match *self {
+            ErrorCode::Posix(code) => io::Error::from_raw_os_error(code).kind(),
+            ErrorCode::Cocoa(code) => match code {
+                //NSFileNoSuchFileError, NSFileReadNoSuchFileError, NSCoderValueNotFoundError
+                4 | 260 | 4865 => NotFound,
+                //NSFileReadNoPermissionError, NSFileWriteNoPermissionError, NSFileWriteVolumeReadOnlyError
+                257 | 513 | 642 => PermissionDenied,
+                //NSFileWriteFileExistsError
+                516 => AlreadyExists,
+                //NSFileReadInvalidFileNameError, NSFileWriteInvalidFileNameError
+                258 | 514 => InvalidInput,
+                //NSFileReadCorruptFileError, NSFileReadInapplicableStringEncodingError, NSPropertyListReadCorruptError, NSCoderReadCorruptError
+                259 | 261 | 3840 | 4864 => InvalidData,
+                //NSFileReadTooLargeError
+                263 => OutOfMemory,
+                //NSUserCancelledError
+                3072 => Interrupted,
+                //NSFeatureUnsupportedError
+                3328 => Unsupported,
+                _ => Other,
+            },
+            ErrorCode::Url(code) => match code {
+                //NSURLErrorCancelled
+                -999 => Interrupted,
+                //NSURLErrorBadURL
+                -1000 => InvalidInput,
+                //NSURLErrorTimedOut
+                -1001 => TimedOut,
+                //NSURLErrorUnsupportedURL
+                -1002 => Unsupported,
+                //NSURLErrorCannotFindHost, NSURLErrorDNSLookupFailed, NSURLErrorFileDoesNotExist
+                -1003 | -1006 | -1100 => NotFound,
+                //NSURLErrorCannotConnectToHost
+                -1004 => ConnectionRefused,
+                //NSURLErrorNetworkConnectionLost
+                -1005 => ConnectionReset,
+                //NSURLErrorNotConnectedToInternet
+                -1009 => NotConnected,
+                //NSURLErrorBadServerResponse, NSURLErrorCannotDecodeRawData, NSURLErrorCannotDecodeContentData, NSURLErrorCannotParseResponse
+                -1011 | -1015 | -1016 | -1017 => InvalidData,
+                //NSURLErrorUserCancelledAuthentication, NSURLErrorUserAuthenticationRequired, NSURLErrorNoPermissionsToReadFile
+                -1012 | -1013 | -1102 => PermissionDenied,
+                //NSURLErrorZeroByteResource
+                -1014 => UnexpectedEof,
+                _ => Other,
+            },
+            ErrorCode::None | ErrorCode::Other(..) => Other,
+        }
+    }
+}
+
+impl Drop for BlockError {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe{ objc_release(self.0) }
+        }
+    }
+}
+
+impl std::fmt::Display for BlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_null() {
+            write!(f, "the operation failed without an error")
+        }
+        else {
+            write!(f, "the operation failed with NSError {:p}", self.0)
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+impl From<BlockError> for io::Error {
+    ///A POSIX error becomes the OS error with its `errno`.  Otherwise, the [io::Error] has the kind of the
+    ///[ErrorCode], and wraps the [BlockError].
+    fn from(error: BlockError) -> Self {
+        match error.code() {
+            ErrorCode::Posix(code) => io::Error::from_raw_os_error(code),
+            code => io::Error::new(code.kind(), error),
+        }
+    }
+}
+
+impl SuccessReplyBlock {
+    /**
+    Creates a reply block, and a future for its reply: `Ok(())` for `YES`, or the error for `NO`.
+
+    # Safety
+    The same requirements as `new` apply.
+    */
+    #[track_caller]
+    pub unsafe fn result_future() -> (Self, Continuation<Result<(), BlockError>>) {
+        let (continuation, completer) = continuation();
+        completer.set_name(core::any::type_name::<Self>());
+        let block = unsafe{ Self::new(move |success, error| {
+            //the error may be autoreleased, so it is retained before the reply returns
+            completer.complete(if success != 0 { Ok(()) } else { Err(BlockError::retaining(error)) });
+        })};
+        (block, continuation)
+    }
+}
+
+impl GrantedReplyBlock {
+    /**
+    Creates a reply block, and a future for whether access was granted.
+
+    # Safety
+    The same requirements as `new` apply.
+    */
+    #[track_caller]
+    pub unsafe fn granted_future() -> (Self, Continuation<bool>) {
+        let (continuation, completer) = continuation();
+        completer.set_name(core::any::type_name::<Self>());
+        (unsafe{ Self::new(move |granted| { completer.complete(granted != 0); }) }, continuation)
+    }
+}
+
+#[test] fn replies() {
+    use crate::executors::block_on;
+    let (reply, result) = unsafe{ SuccessReplyBlock::result_future() };
+    unsafe{ reply.as_block().invoke((1, core::ptr::null())) };
+    assert!(block_on(result).is_ok());
+    let (reply, result) = unsafe{ SuccessReplyBlock::result_future() };
+    unsafe{ reply.as_block().invoke((0, core::ptr::null())) };
+    assert!(block_on(result).unwrap_err().as_ptr().is_null());
+    let (reply, granted) = unsafe{ GrantedReplyBlock::granted_future() };
+    unsafe{ reply.as_block().invoke((2,)) };
+    assert!(block_on(granted));
+}
+
+#[test] fn error_kinds() {
+    use io::ErrorKind;
+    assert_eq!(ErrorCode::new("NSPOSIXErrorDomain", 13), ErrorCode::Posix(13));
+    assert_eq!(ErrorCode::new("NSPOSIXErrorDomain", 13).kind(), ErrorKind::PermissionDenied);
+    assert_eq!(ErrorCode::new("NSCocoaErrorDomain", 516).kind(), ErrorKind::AlreadyExists);
+    assert_eq!(ErrorCode::new("NSURLErrorDomain", -1009).kind(), ErrorKind::NotConnected);
+    assert_eq!(ErrorCode::new("NSOSStatusErrorDomain", -50), ErrorCode::Other("NSOSStatusErrorDomain".to_string(), -50));
+    assert_eq!(ErrorCode::new("NSOSStatusErrorDomain", -50).kind(), ErrorKind::Other);
+    let error = io::Error::from(unsafe{ BlockError::retaining(core::ptr::null()) });
+    assert_eq!(error.kind(), ErrorKind::Other);
+    assert!(error.into_inner().unwrap().is::<BlockError>());
+}
@@ -0,0 +1,2105 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Bridges completion handlers to `async fn`s.
+
+This is similar to `withCheckedContinuation` in Swift.  Create a pair with [continuation], move the [Completer] into
+a completion handler, and await the [Continuation].
+
+```
+use blocksr::continuation::continuation;
+async fn example() -> u8 {
+    let (continuation, completer) = continuation();
+    //ordinarily, the completer is moved into a block and completed by objc
+    std::thread::spawn(move || completer.complete(3));
+    continuation.await
+}
+```
+
+This does not depend on any particular async runtime.  For synchronous code, [Continuation::wait] blocks until the
+continuation resolves, [Continuation::try_take] takes a value that is already there, and [channel_continuation] and
+[Completer::from_sender] complete to a channel instead.
+Futures of your own, such as retry wrappers, can poll the shared state directly, with [SharedCompletion].  For
+object-safe async traits, [Continuation::boxed] erases the continuation's type, into a [BoxContinuation].
+
+On Apple platforms, UI bindings can run work on the main thread with `on_main`, which runs a closure synchronously
+if this is the main thread, or otherwise sends it to the main queue and returns a continuation for its result.
+
+For app shutdown, completers may be [tagged](Completer::tagged), and [cancel_all] resolves the pending continuations
+with a tag with an error, so tasks awaiting handlers that will never be called don't hang.
+
+With the `slow-completion` feature, a warning is logged with the `log` crate when a completer goes longer than
+`set_slow_completion_threshold` without completing, which is useful for finding APIs that are pathologically slow to
+call their completion handler.
+
+# Drop order
+
+Bindings that clean up resources in a completion value, like closing a file handle or releasing an object, can rely
+on when it is dropped:
+
+* A value passed to [Completer::complete] is moved to whoever awaits the [Continuation], and dropped by them.
+* If the continuation is dropped after completing, but before it was polled, the value is dropped then, on the thread
+  dropping the continuation.
+* If the continuation was dropped first, `complete` returns `false`, and the value is dropped before it returns, on the
+  completing thread.  [MappedCompleter] doesn't run its conversion at all; [Completer::has_consumer] lets other
+  bindings skip expensive work too.
+
+`blocksr::testing::DropLog`, with the `testing` feature, records drops to check these orders in a binding's tests.
+*/
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+#[cfg(feature = "slow-completion")]
+pub use crate::slow_completion::set_slow_completion_threshold;
+
+#[derive(Debug)]
+enum Shared<R> {
+    Pending(Option<Waker>),
+    Completed(R),
+    //the value was returned from poll
+    Taken,
+    //the completer was dropped without completing
+    Abandoned,
+    //the continuation was dropped before taking a value
+    Dropped,
+}
+
+impl<R> Shared<R> {
+    //Takes a completed value, or stores the waker of a pending poll.  `None` if the completer was abandoned.
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        let taken = self.take();
+        if let Shared::Pending(waker) = self {
+            //polling again from the same task, as `select` does, needn't clone the waker
+            if !waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+                *waker = Some(cx.waker().clone());
+            }
+        }
+        taken
+    }
+    //Takes a completed value, leaving a pending state as it is.  Completing before the first poll is the fast path:
+    //the value is ready without ever storing a waker.
+    fn take(&mut self) -> Poll<Option<R>> {
+        match std::mem::replace(self, Shared::Taken) {
+            Shared::Pending(waker) => {
+                *self = Shared::Pending(waker);
+                Poll::Pending
+            }
+            Shared::Completed(value) => Poll::Ready(Some(value)),
+            Shared::Abandoned => Poll::Ready(None),
+            Shared::Taken => panic!("Continuation polled after completion"),
+            Shared::Dropped => unreachable!("the continuation is alive"),
+        }
+    }
+    //Replaces a pending state with `state`, returning the waker to wake.  Otherwise, nobody will take it, or
+    //cancel_all resolved it first, so `state` is handed back, to drop outside the lock.
+    fn resolve(&mut self, state: Shared<R>) -> Result<Option<Waker>, Shared<R>> {
+        match self {
+            Shared::Pending(waker) => {
+                let waker = waker.take();
+                *self = state;
+                Ok(waker)
+            }
+            _ => Err(state),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Slot<R> {
+    //incremented each time a pool recycles the slot
+    generation: u64,
+    state: Shared<R>,
+}
+
+type SharedSlot<R> = Arc<Mutex<Slot<R>>>;
+
+//returns a slot to the pool it came from
+struct Recycler<R> {
+    pool: Arc<PoolInner>,
+    recycle: fn(&PoolInner, SharedSlot<R>),
+}
+
+impl<R> Clone for Recycler<R> {
+    fn clone(&self) -> Self {
+        Recycler{pool: self.pool.clone(), recycle: self.recycle}
+    }
+}
+
+impl<R> std::fmt::Debug for Recycler<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recycler").finish()
+    }
+}
+
+//Releases one half's reference.  If it was the last, the pool gets the slot back.
+fn release<R>(mut shared: SharedSlot<R>, recycler: &Option<Recycler<R>>) {
+    if let Some(recycler) = recycler {
+        if let Some(slot) = Arc::get_mut(&mut shared) {
+            let slot = slot.get_mut().unwrap_or_else(|e| e.into_inner());
+            slot.generation = slot.generation.wrapping_add(1);
+            slot.state = Shared::Pending(None);
+            (recycler.recycle)(&recycler.pool, shared);
+        }
+    }
+}
+
+/**
+The awaitable half of a continuation.
+
+# Panics
+Polling panics if the [Completer] was dropped without completing.  This usually means the completion handler
+was never called.
+*/
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Continuation<R> {
+    shared: Option<SharedSlot<R>>,
+    generation: u64,
+    recycler: Option<Recycler<R>>,
+}
+
+/**
+The completing half of a continuation.
+
+This is typically moved into a completion handler.
+*/
+#[derive(Debug)]
+pub struct Completer<R> {
+    shared: Option<SharedSlot<R>>,
+    generation: u64,
+    recycler: Option<Recycler<R>>,
+    //completes a channel or function instead of a slot
+    sink: Option<Sink<R>>,
+    //the entry in the registry for cancel_all, removed when the completer drops
+    registration: Option<Registration>,
+    #[cfg(feature = "slow-completion")]
+    watch: crate::slow_completion::Watch,
+}
+
+//returns whether the value was delivered
+struct Sink<R>(Box<dyn FnOnce(R) -> bool + Send>);
+
+impl<R> std::fmt::Debug for Sink<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sink").finish()
+    }
+}
+
+fn pair<R>(shared: SharedSlot<R>, generation: u64, recycler: Option<Recycler<R>>) -> (Continuation<R>, Completer<R>) {
+    (Continuation{shared: Some(shared.clone()), generation, recycler: recycler.clone()}, Completer{shared: Some(shared), generation, recycler, sink: None, registration: None, #[cfg(feature = "slow-completion")] watch: crate::slow_completion::Watch::new(std::any::type_name::<Completer<R>>())})
+}
+
+/**
+Creates a linked [Continuation] and [Completer].
+
+The completer usually runs on whatever thread objc calls the handler on, so `R` must be [Send].  To complete with a
+value that can't leave its thread, use [local_continuation].
+
+```compile_fail
+let (continuation, completer) = blocksr::continuation::continuation::<std::rc::Rc<u8>>();
+```
+*/
+pub fn continuation<R: Send>() -> (Continuation<R>, Completer<R>) {
+    pair(Arc::new(Mutex::new(Slot{generation: 0, state: Shared::Pending(None)})), 0, None)
+}
+
+impl<R> Completer<R> {
+    //Returns whether the state was delivered to a consumer.
+    fn finish(&mut self, state: Shared<R>) -> bool {
+        if let Some(sink) = self.sink.take() {
+            #[cfg(feature = "slow-completion")]
+            self.watch.finish();
+            //abandoning drops the sink, which for a channel disconnects it
+            if let Shared::Completed(value) = state {
+                return (sink.0)(value);
+            }
+            false
+        }
+        else if let Some(shared) = self.shared.take() {
+            #[cfg(feature = "slow-completion")]
+            self.watch.finish();
+            let resolved = {
+                let mut slot = shared.lock().unwrap();
+                assert_eq!(slot.generation, self.generation, "Completer used after its slot was recycled");
+                slot.state.resolve(state)
+            };
+            let delivered = match resolved {
+                Ok(waker) => {
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                    true
+                }
+                Err(state) => {
+                    drop(state);
+                    false
+                }
+            };
+            release(shared, &self.recycler);
+            delivered
+        }
+        else {
+            false
+        }
+    }
+    /**
+    Completes the continuation with `value`, waking the task that awaits it.
+
+    Returns whether a consumer still existed.  If the [Continuation] was dropped, `value` is dropped before this
+    returns, on this thread.  See [the module docs](self#drop-order).
+    */
+    pub fn complete(mut self, value: R) -> bool {
+        self.finish(Shared::Completed(value))
+    }
+    /**
+    Completes the continuation with a completion handler's arguments, as a tuple.
+
+    As with `from_completer`, a `Completer<A>` takes `(a,)`, and a `Completer<(A, B)>` takes `(a, b)`.  This lets a
+    handler, or code generic over one, pass its arguments along without packing them by hand.
+
+    ```
+    use blocksr::continuation::continuation;
+    let (continuation, completer) = continuation::<u8>();
+    completer.complete_args((3,));
+    # let _ = continuation;
+    ```
+    */
+    pub fn complete_args<Args>(self, args: Args) -> bool where R: CompleteFrom<Args, ()> {
+        self.complete(R::complete_from(args))
+    }
+    /**
+    Whether the [Continuation] still exists and is waiting, so that a value passed to [complete](Completer::complete)
+    could be taken.
+
+    This is for skipping expensive work to produce a value nobody wants.  The continuation may still be dropped
+    before `complete` is called, so check what `complete` returns where that matters.  A completer made with
+    [from_fn](Completer::from_fn) always has a consumer.
+    */
+    pub fn has_consumer(&self) -> bool {
+        match &self.shared {
+            //after cancel_all, the continuation holds its error instead
+            Some(shared) => matches!(shared.lock().unwrap().state, Shared::Pending(_)),
+            None => self.sink.is_some(),
+        }
+    }
+    /**
+    Whether the [Continuation] was dropped, or resolved by [cancel_all], so nobody will take a value.
+
+    This is `!has_consumer()`.  A closure objc calls can check it to skip preparing a result, or to cancel related
+    work, once the task awaiting it is gone.
+    */
+    pub fn is_abandoned(&self) -> bool {
+        !self.has_consumer()
+    }
+    ///Names what completes this completer, typically the block type it was moved into, for diagnostics such as the
+    ///`slow-completion` warnings.  `from_completer` and `future` name it after their block.
+    pub fn set_name(&self, name: &'static str) {
+        #[cfg(feature = "slow-completion")]
+        self.watch.set_name(name);
+        #[cfg(not(feature = "slow-completion"))]
+        let _ = name;
+    }
+}
+
+impl<R> Drop for Completer<R> {
+    fn drop(&mut self) {
+        self.finish(Shared::Abandoned);
+    }
+}
+
+impl<R> Drop for Continuation<R> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            let old = {
+                let mut slot = shared.lock().unwrap_or_else(|e| e.into_inner());
+                std::mem::replace(&mut slot.state, Shared::Dropped)
+            };
+            //a value that was never taken is dropped here, outside the lock
+            drop(old);
+            release(shared, &self.recycler);
+        }
+    }
+}
+
+impl<R> Future for Continuation<R> {
+    type Output = R;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut slot = self.shared.as_ref().unwrap().lock().unwrap();
+        assert_eq!(slot.generation, self.generation, "Continuation used after its slot was recycled");
+        slot.state.poll(cx).map(|value| value.expect("Completer was dropped without completing the continuation"))
+    }
+}
+
+/**
+The state a [Continuation] shares with its [Completer], for futures that manage it themselves.
+
+A retrying or instrumented future could await a [Continuation] inside its own `poll`, but then each attempt nests
+another future.  Instead, it can hold a `SharedCompletion`, [poll](SharedCompletion::poll) it directly, and hand out
+[completers](SharedCompletion::completer) to blocks, or [complete](SharedCompletion::complete) it itself.  Clones
+share the state.
+
+```
+use blocksr::continuation::SharedCompletion;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+//counts how often it is polled
+struct Counted {
+    shared: SharedCompletion<u8>,
+    polls: usize,
+}
+impl Future for Counted {
+    type Output = Option<u8>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u8>> {
+        self.polls += 1;
+        self.shared.poll(cx)
+    }
+}
+let shared = SharedCompletion::new();
+let counted = Counted{shared: shared.clone(), polls: 0};
+//ordinarily, the completer is moved into a block and completed by objc
+shared.completer().complete(3);
+# let _ = counted;
+```
+*/
+#[derive(Debug)]
+pub struct SharedCompletion<R> {
+    consumer: Arc<Consumer<R>>,
+}
+
+//The clones' share of the state, which is dropped with the last clone, like a Continuation.
+#[derive(Debug)]
+struct Consumer<R>(SharedSlot<R>);
+
+impl<R> Drop for Consumer<R> {
+    fn drop(&mut self) {
+        let old = {
+            let mut slot = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::replace(&mut slot.state, Shared::Dropped)
+        };
+        //a value that was never taken is dropped here, outside the lock
+        drop(old);
+    }
+}
+
+impl<R> Clone for SharedCompletion<R> {
+    fn clone(&self) -> Self {
+        SharedCompletion{consumer: self.consumer.clone()}
+    }
+}
+
+impl<R: Send> Default for SharedCompletion<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Send> SharedCompletion<R> {
+    ///Creates a pending completion.
+    pub fn new() -> Self {
+        SharedCompletion{consumer: Arc::new(Consumer(Arc::new(Mutex::new(Slot{generation: 0, state: Shared::Pending(None)}))))}
+    }
+}
+
+impl<R> SharedCompletion<R> {
+    /**
+    Takes the value, if it was completed, or arranges for `cx` to be woken when it is.
+
+    Returns `Ready(None)` if a [completer](SharedCompletion::completer) was dropped without completing, so the value
+    will never arrive.  Only the most recent waker is woken, so poll it from one task.
+
+    # Panics
+    If the value was already taken.
+    */
+    pub fn poll(&self, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        self.consumer.0.lock().unwrap().state.poll(cx)
+    }
+    /**
+    Completes with `value`, waking the task polling it.
+
+    Returns `false`, and drops `value`, if it was already completed or abandoned.
+    */
+    pub fn complete(&self, value: R) -> bool {
+        let resolved = self.consumer.0.lock().unwrap().state.resolve(Shared::Completed(value));
+        match resolved {
+            Ok(waker) => {
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+                true
+            }
+            Err(value) => {
+                drop(value);
+                false
+            }
+        }
+    }
+    /**
+    Creates a [Completer] for this state, to move into a block.
+
+    As with a [Continuation]'s completer, dropping it without completing abandons the state, unless something else
+    completed it first.  Once every clone of the `SharedCompletion` is dropped, it has no
+    [consumer](Completer::has_consumer).
+    */
+    pub fn completer(&self) -> Completer<R> {
+        Completer{shared: Some(self.consumer.0.clone()), generation: 0, recycler: None, sink: None, registration: None, #[cfg(feature = "slow-completion")] watch: crate::slow_completion::Watch::new(std::any::type_name::<Completer<R>>())}
+    }
+}
+
+impl<R: Send + 'static> Completer<R> {
+    /**
+    Creates a completer that calls `f` with the value, instead of completing a [Continuation].
+
+    This bridges completion handlers to code built on something other than futures, like a `crossbeam` channel.
+    Dropping the completer without completing drops `f` without calling it.
+    */
+    pub fn from_fn<F: FnOnce(R) + Send + 'static>(f: F) -> Self {
+        Self::from_sink(move |value| {
+            f(value);
+            true
+        })
+    }
+    fn from_sink<F: FnOnce(R) -> bool + Send + 'static>(f: F) -> Self {
+        Completer{shared: None, generation: 0, recycler: None, sink: Some(Sink(Box::new(f))), registration: None, #[cfg(feature = "slow-completion")] watch: crate::slow_completion::Watch::new(std::any::type_name::<Completer<R>>())}
+    }
+    /**
+    Creates a completer that sends the value on `sender`.
+
+    Dropping the completer without completing drops `sender`, so a receiver waiting on it sees the channel disconnect
+    once no other senders remain.  The value is discarded if the receiver is gone, and `complete` returns `false`.
+    */
+    pub fn from_sender(sender: std::sync::mpsc::Sender<R>) -> Self {
+        Self::from_sink(move |value| sender.send(value).is_ok())
+    }
+}
+
+/**
+Creates a [Completer] and the [Receiver](std::sync::mpsc::Receiver) it sends to, for synchronous code.
+
+```
+let (receiver, completer) = blocksr::continuation::channel_continuation();
+//ordinarily, the completer is moved into a block and completed by objc
+std::thread::spawn(move || completer.complete(3));
+assert_eq!(receiver.recv(), Ok(3));
+```
+
+If the completer is dropped without completing, `recv` returns an error.
+*/
+pub fn channel_continuation<R: Send + 'static>() -> (std::sync::mpsc::Receiver<R>, Completer<R>) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    (receiver, Completer::from_sender(sender))
+}
+
+impl<R> Continuation<R> {
+    /**
+    Takes the value, if the continuation has already been completed, without awaiting.
+
+    Many APIs call their completion handler synchronously when they have a cached result, so bindings can check for
+    it and skip suspending.  If this returns `None`, the continuation may still be awaited, or checked again.  Polling
+    once with a no-op waker, as with `FutureExt::now_or_never` in the `futures` crate, or in a `select`, is also
+    `Ready` when the value is already there.
+
+    ```
+    use blocksr::continuation::continuation;
+    let (mut continuation, completer) = continuation::<u8>();
+    assert_eq!(continuation.try_take(), None);
+    completer.complete(3);
+    assert_eq!(continuation.try_take(), Some(3));
+    ```
+
+    # Panics
+    If the [Completer] was dropped without completing, or the value was already taken, as when polled.
+    */
+    pub fn try_take(&mut self) -> Option<R> {
+        let mut slot = self.shared.as_ref().unwrap().lock().unwrap();
+        assert_eq!(slot.generation, self.generation, "Continuation used after its slot was recycled");
+        match slot.state.take() {
+            Poll::Ready(value) => Some(value.expect("Completer was dropped without completing the continuation")),
+            Poll::Pending => None,
+        }
+    }
+    /**
+    Blocks the current thread until the continuation resolves, for synchronous code.
+
+    Don't call this on a thread the completion handler needs, like the main thread for a handler objc calls on the
+    main queue, or it will deadlock.
+
+    On Apple platforms, the thread waits on a `dispatch_semaphore_t`, like Apple's own synchronous wrappers of
+    async APIs, rather than parking.  A semaphore has no owner, so the system can't raise the priority of whichever
+    thread will complete the continuation: to avoid priority inversion when a UI thread waits, the handler's queue
+    should have a QoS at least as high as the waiting thread's.
+
+    # Panics
+    If the [Completer] was dropped without completing, as when polled.
+    */
+    pub fn wait(mut self) -> R {
+        if let Some(value) = self.try_take() {
+            return value;
+        }
+        let parker = Arc::new(Parker::new());
+        let waker = Waker::from(parker.clone());
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(value) = Pin::new(&mut self).poll(&mut cx) {
+                return value;
+            }
+            parker.park();
+        }
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+extern "C" {
+    fn dispatch_semaphore_create(value: isize) -> *mut std::ffi::c_void;
+    fn dispatch_semaphore_wait(semaphore: *mut std::ffi::c_void, timeout: u64) -> isize;
+    fn dispatch_semaphore_signal(semaphore: *mut std::ffi::c_void) -> isize;
+    fn dispatch_release(object: *mut std::ffi::c_void);
+}
+
+//Blocks the thread calling `wait` until woken.  A wake before the park makes the park return immediately, and
+//spurious wakes are fine, since `wait` polls again.
+#[cfg(target_vendor = "apple")]
+struct Parker(*mut std::ffi::c_void);
+#[cfg(not(target_vendor = "apple"))]
+struct Parker(std::thread::Thread);
+
+//Safety: dispatch semaphores may be signaled and released from any thread
+#[cfg(target_vendor = "apple")]
+unsafe impl Send for Parker {}
+#[cfg(target_vendor = "apple")]
+unsafe impl Sync for Parker {}
+
+impl Parker {
+    #[cfg(target_vendor = "apple")]
+    fn new() -> Self {
+        let semaphore = unsafe{ dispatch_semaphore_create(0) };
+        assert!(!semaphore.is_null(), "dispatch_semaphore_create failed");
+        Parker(semaphore)
+    }
+    #[cfg(not(target_vendor = "apple"))]
+    fn new() -> Self {
+        Parker(std::thread::current())
+    }
+    #[cfg(target_vendor = "apple")]
+    fn park(&self) {
+        //DISPATCH_TIME_FOREVER
+        unsafe{ dispatch_semaphore_wait(self.0, !0) };
+    }
+    #[cfg(not(target_vendor = "apple"))]
+    fn park(&self) {
+        std::thread::park();
+    }
+}
+
+impl std::task::Wake for Parker {
+    #[cfg(target_vendor = "apple")]
+    fn wake(self: Arc<Self>) {
+        unsafe{ dispatch_semaphore_signal(self.0) };
+    }
+    #[cfg(not(target_vendor = "apple"))]
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+impl Drop for Parker {
+    fn drop(&mut self) {
+        unsafe{ dispatch_release(self.0) };
+    }
+}
+
+impl<R> Completer<Box<R>> {
+    /**
+    Completes the continuation with a value that `init` writes directly into its heap allocation.
+
+    Completing with a large value, like a buffer copied out of `NSData`, moves it into the continuation and out again
+    when it is polled.  With a `Completer<Box<R>>`, the value is written once: `init` initializes the allocation in place
+    and returns it, and only the box's pointer moves after that.
+
+    ```
+    use blocksr::continuation::continuation;
+    let (continuation, completer) = continuation::<Box<[u8; 65536]>>();
+    let bytes = [7u8; 16];
+    completer.complete_in_place(|slot| {
+        let buffer = slot.as_mut_ptr() as *mut u8;
+        unsafe {
+            core::ptr::write_bytes(buffer, 0, 65536);
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, bytes.len());
+            slot.assume_init_mut()
+        }
+    });
+    # let _ = continuation;
+    ```
+
+    # Panics
+    If `init` returns a reference to anything but `slot`.  Returning `slot`'s reference is how `init` shows it was
+    initialized.
+    */
+    pub fn complete_in_place<F>(self, init: F) where F: for<'a> FnOnce(&'a mut std::mem::MaybeUninit<R>) -> &'a mut R {
+        self.complete(crate::arena::box_in_place(init));
+    }
+}
+
+impl<R> Completer<R> {
+    /**
+    Adapts this completer to take a `T`, which `f` converts into the `R` it completes with.
+
+    This lets a binding share one conversion, e.g. from a raw pointer to an owned Rust type, between the
+    completion handlers of several APIs.  Nothing is boxed.  If the [Continuation] was already dropped, `f` isn't
+    called.
+
+    ```
+    use blocksr::continuation::continuation;
+    let (continuation, completer) = continuation::<String>();
+    let completer = completer.contramap(|code: i32| format!("status {}", code));
+    completer.complete(200);
+    # let _ = continuation;
+    ```
+    */
+    pub fn contramap<T, F: FnOnce(T) -> R>(self, f: F) -> MappedCompleter<R, F> {
+        MappedCompleter{completer: self, f}
+    }
+}
+
+impl<R> Completer<Result<R, Panicked>> {
+    /**
+    Completes the continuation with what `f` returns, or with `Err(Panicked)` if it panics.
+
+    A completion handler that panics partway through computing its value would otherwise drop the completer while
+    unwinding, and the task awaiting it would panic with a generic message, if the process survived unwinding into
+    objc at all.  This catches the panic instead, so the task fails with the original payload.
+
+    ```
+    use blocksr::continuation::{continuation, Panicked};
+    let (continuation, completer) = continuation::<Result<u8, Panicked>>();
+    completer.complete_catching(|| panic!("bad reply"));
+    let error = continuation.wait().unwrap_err();
+    assert_eq!(error.message(), Some("bad reply"));
+    ```
+    */
+    pub fn complete_catching<F: FnOnce() -> R>(self, f: F) -> bool {
+        let value = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(Panicked);
+        self.complete(value)
+    }
+}
+
+impl<R: Send + 'static> Completer<R> {
+    /**
+    Returns a task, a [BoxContinuation] with no output, that awaits `future`, then completes the continuation with
+    its output.
+
+    A block that starts async Rust work can't await it, so the work is handed to an executor.  Pass the task to the
+    executor's spawn function, such as `tokio::spawn`.  If the task is dropped without running to completion, the
+    completer is dropped too, abandoning the continuation.
+
+    ```ignore
+    let (continuation, completer) = blocksr::continuation::continuation();
+    tokio::spawn(completer.complete_async(async { fetch().await }));
+    let value = continuation.await;
+    ```
+    */
+    pub fn complete_async<F: Future<Output = R> + Send + 'static>(self, future: F) -> BoxContinuation<()> {
+        Box::pin(async move {
+            self.complete(future.await);
+        })
+    }
+}
+
+//pthread_main_np is Apple's; glibc, and gnustep's libdispatch, lack it
+#[cfg(target_vendor = "apple")]
+extern "C" {
+    static _dispatch_main_q: std::ffi::c_void;
+    fn dispatch_async(queue: *const std::ffi::c_void, block: *const std::ffi::c_void);
+    fn pthread_main_np() -> std::ffi::c_int;
+}
+
+/**
+The result of `on_main`: the closure's return value, or a continuation for it.
+
+Awaiting it, with [IntoFuture](std::future::IntoFuture), resolves immediately in the synchronous case.
+*/
+#[derive(Debug)]
+#[must_use = "the closure may not have run yet"]
+pub enum OnMain<R> {
+    ///The closure ran synchronously, since this is the main thread.
+    Ran(R),
+    ///The closure was sent to the main queue, and will complete the continuation when it runs there.
+    Dispatched(Continuation<R>),
+}
+
+impl<R: Send> std::future::IntoFuture for OnMain<R> {
+    type Output = R;
+    type IntoFuture = Continuation<R>;
+    fn into_future(self) -> Continuation<R> {
+        match self {
+            OnMain::Ran(value) => {
+                let (continuation, completer) = continuation();
+                completer.complete(value);
+                continuation
+            }
+            OnMain::Dispatched(continuation) => continuation,
+        }
+    }
+}
+
+/**
+Runs `f` on the main thread: synchronously, if this is the main thread, or else as a block sent to the main queue
+with `dispatch_async`.
+
+UI bindings often can't know which thread a callback arrives on, but must touch views on the main thread.  Calling `f`
+directly when possible keeps the work in order with the caller's, and avoids a round trip through the queue.
+
+```no_run
+# #[cfg(target_vendor = "apple")]
+async fn example() {
+    use blocksr::continuation::{on_main, OnMain};
+    match on_main(|| { /* update a view */ 3 }) {
+        OnMain::Ran(value) => assert_eq!(value, 3),
+        OnMain::Dispatched(continuation) => assert_eq!(continuation.await, 3),
+    }
+    //or, when it doesn't matter which
+    let value = on_main(|| 3).await;
+}
+```
+
+The main thread is checked with `pthread_main_np`, so work already on the main thread runs synchronously even when it
+isn't on the main queue, as in a `dispatch_sync` to the main queue from a background thread.
+
+Apple platforms only.
+*/
+#[cfg(target_vendor = "apple")]
+pub fn on_main<R: Send + 'static, F: FnOnce() -> R + Send + 'static>(f: F) -> OnMain<R> {
+    if unsafe{ pthread_main_np() } != 0 {
+        return OnMain::Ran(f());
+    }
+    let (continuation, completer) = continuation();
+    completer.set_name("blocksr::continuation::on_main");
+    let block = unsafe{ crate::VoidBlock::new(move || { completer.complete(f()); }) };
+    //the queue copies the block, and invokes the copy once
+    unsafe{ dispatch_async(&_dispatch_main_q, &block as *const _ as *const std::ffi::c_void) };
+    OnMain::Dispatched(continuation)
+}
+
+impl<T: ?Sized> Completer<Box<T>> {
+    /**
+    Boxes `value` as a `T`, such as `dyn Error + Send + Sync`, and completes the continuation with it.
+
+    Completing with a trait object shares one `Completer<Box<dyn Trait>>` between every concrete type, so a large
+    binding doesn't instantiate the machinery for each of them.  See [BoxInto].
+
+    ```
+    use blocksr::continuation::continuation;
+    let (continuation, completer) = continuation::<Box<dyn std::error::Error + Send + Sync>>();
+    completer.complete_boxed(std::fmt::Error);
+    assert!(continuation.wait().is::<std::fmt::Error>());
+    ```
+    */
+    pub fn complete_boxed<V: BoxInto<T>>(self, value: V) -> bool {
+        self.complete(value.box_into())
+    }
+}
+
+impl<T: ?Sized> LocalCompleter<Box<T>> {
+    ///Boxes `value` as a `T`, and completes the continuation with it, as for [Completer::complete_boxed].  This
+    ///includes trait objects that aren't [Send], like `dyn Any`.
+    pub fn complete_boxed<V: BoxInto<T>>(self, value: V) -> bool {
+        self.complete(value.box_into())
+    }
+}
+
+/**
+Boxes a value as the trait object `T`, for [Completer::complete_boxed].
+
+Implemented for the [Any] and [Error](std::error::Error) trait objects, with and without `Send` and `Sync`.  Unsizing a
+`Box` to an arbitrary trait object can't be expressed generically on stable Rust, so for your own traits, implement
+this with `Box::new(self)`.
+*/
+pub trait BoxInto<T: ?Sized> {
+    ///Boxes the value as a `T`.
+    fn box_into(self) -> Box<T>;
+}
+
+macro_rules! box_into {
+    ($($bound:path),* => $object:ty) => {
+        impl<V: $($bound +)* 'static> BoxInto<$object> for V {
+            fn box_into(self) -> Box<$object> {
+                Box::new(self)
+            }
+        }
+    }
+}
+box_into!(Any => dyn Any);
+box_into!(Any, Send => dyn Any + Send);
+box_into!(Any, Send, Sync => dyn Any + Send + Sync);
+box_into!(std::error::Error => dyn std::error::Error);
+box_into!(std::error::Error, Send => dyn std::error::Error + Send);
+box_into!(std::error::Error, Send, Sync => dyn std::error::Error + Send + Sync);
+
+/**
+The error for a completion handler that panicked, from [Completer::complete_catching].
+
+This holds the payload `panic!` was called with.  [Panicked::resume] continues the panic in the awaiting task.
+*/
+pub struct Panicked(pub Box<dyn Any + Send>);
+
+impl Panicked {
+    ///The panic message, if the payload is a string, as it is for `panic!` with a message.
+    pub fn message(&self) -> Option<&str> {
+        if let Some(message) = self.0.downcast_ref::<&'static str>() {
+            Some(message)
+        }
+        else {
+            self.0.downcast_ref::<String>().map(|message| message.as_str())
+        }
+    }
+    ///Continues unwinding with the original payload, with [std::panic::resume_unwind].
+    pub fn resume(self) -> ! {
+        std::panic::resume_unwind(self.0)
+    }
+}
+
+impl std::fmt::Debug for Panicked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Panicked").field(&self.message()).finish()
+    }
+}
+
+impl std::fmt::Display for Panicked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "the completion handler panicked: {}", message),
+            None => write!(f, "the completion handler panicked"),
+        }
+    }
+}
+impl std::error::Error for Panicked {}
+
+/**
+A [Completer] that converts its value first.  Created by [Completer::contramap].
+
+Like the completer it wraps, dropping it without completing abandons the continuation.
+*/
+#[derive(Debug)]
+pub struct MappedCompleter<R, F> {
+    completer: Completer<R>,
+    f: F,
+}
+
+impl<R, F> MappedCompleter<R, F> {
+    ///Converts `value`, and completes the continuation with the result.  Returns whether a consumer still existed.
+    ///
+    /// If the [Continuation] was dropped, `value` is dropped without being converted.
+    pub fn complete<T>(self, value: T) -> bool where F: FnOnce(T) -> R {
+        let MappedCompleter{completer, f} = self;
+        if !completer.has_consumer() {
+            return false;
+        }
+        completer.complete(f(value))
+    }
+}
+
+impl<R> Continuation<R> {
+    /**
+    Converts the result with `f` once it is available.
+
+    Unlike mapping with an `async` block, the result is a named type, so it can be stored or returned without boxing.
+
+    ```
+    use blocksr::continuation::continuation;
+    async fn example() -> usize {
+        let (continuation, completer) = continuation::<String>();
+        std::thread::spawn(move || completer.complete("four".to_string()));
+        continuation.map(|s| s.len()).await
+    }
+    ```
+    */
+    pub fn map<T, F: FnOnce(R) -> T>(self, f: F) -> MappedContinuation<R, F> {
+        MappedContinuation{continuation: self, f: Some(f)}
+    }
+}
+
+/**
+A continuation, or a future adapted from one, with its type erased.  See [Continuation::boxed].
+*/
+pub type BoxContinuation<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/**
+A [LocalContinuation] with its type erased.  See [LocalContinuation::boxed_local].
+*/
+pub type LocalBoxContinuation<T> = Pin<Box<dyn Future<Output = T>>>;
+
+impl<R: Send + 'static> Continuation<R> {
+    /**
+    Boxes and pins the continuation, erasing its type.
+
+    Object-safe async traits can't return `impl Future`, so their methods return a boxed future instead, like
+    `async_trait` does.  A [Continuation] also converts [into](Into::into) a [BoxContinuation].
+
+    ```
+    use blocksr::continuation::{continuation, BoxContinuation};
+    trait Prompt {
+        fn ask(&self) -> BoxContinuation<bool>;
+    }
+    struct Granting;
+    impl Prompt for Granting {
+        fn ask(&self) -> BoxContinuation<bool> {
+            let (continuation, completer) = continuation();
+            //ordinarily, the completer is moved into a reply block
+            std::thread::spawn(move || completer.complete(true));
+            continuation.boxed()
+        }
+    }
+    let prompts: Vec<Box<dyn Prompt>> = vec![Box::new(Granting)];
+    let _granted = prompts[0].ask();
+    ```
+    */
+    pub fn boxed(self) -> BoxContinuation<R> {
+        Box::pin(self)
+    }
+}
+
+impl<R: Send + 'static> From<Continuation<R>> for BoxContinuation<R> {
+    fn from(continuation: Continuation<R>) -> Self {
+        continuation.boxed()
+    }
+}
+
+/**
+A [Continuation] whose result is converted.  Created by [Continuation::map].
+*/
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct MappedContinuation<R, F> {
+    continuation: Continuation<R>,
+    f: Option<F>,
+}
+
+//f is never pinned; it's only moved out
+impl<R, F> Unpin for MappedContinuation<R, F> {}
+
+impl<R, T, F: FnOnce(R) -> T> Future for MappedContinuation<R, F> {
+    type Output = T;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match Pin::new(&mut self.continuation).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(value) => {
+                let f = self.f.take().expect("MappedContinuation polled after completion");
+                Poll::Ready(f(value))
+            }
+        }
+    }
+}
+
+impl<R: Send + 'static, T, F: FnOnce(R) -> T + Send + 'static> MappedContinuation<R, F> {
+    ///Boxes and pins the continuation, erasing its type.  See [Continuation::boxed].
+    pub fn boxed(self) -> BoxContinuation<T> {
+        Box::pin(self)
+    }
+}
+
+impl<R: Send + 'static, T, F: FnOnce(R) -> T + Send + 'static> From<MappedContinuation<R, F>> for BoxContinuation<T> {
+    fn from(continuation: MappedContinuation<R, F>) -> Self {
+        continuation.boxed()
+    }
+}
+
+/**
+The awaitable half of a [local_continuation].
+*/
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct LocalContinuation<R> {
+    shared: Rc<RefCell<Shared<R>>>,
+}
+
+/**
+The completing half of a [local_continuation].
+
+Unlike [Completer], this is neither [Send] nor [Sync], so it can only be completed on the thread that created it.
+*/
+#[derive(Debug)]
+pub struct LocalCompleter<R> {
+    shared: Option<Rc<RefCell<Shared<R>>>>,
+}
+
+/**
+Creates a linked [LocalContinuation] and [LocalCompleter] for values that aren't [Send].
+
+This is for completion handlers that objc guarantees to call on the current thread, usually the main thread.  Since
+neither half can leave the thread, the continuation must be awaited on a same-thread executor, such as
+`blocksr::executors::block_on` or a Tokio `LocalSet`.
+
+```
+use std::rc::Rc;
+let (continuation, completer) = blocksr::continuation::local_continuation();
+completer.complete(Rc::new(3));
+# let _ = continuation;
+```
+
+Moving either half to another thread is a compile error:
+
+```compile_fail
+let (continuation, completer) = blocksr::continuation::local_continuation::<u8>();
+std::thread::spawn(move || completer.complete(3));
+```
+*/
+pub fn local_continuation<R>() -> (LocalContinuation<R>, LocalCompleter<R>) {
+    let shared = Rc::new(RefCell::new(Shared::Pending(None)));
+    (LocalContinuation{shared: shared.clone()}, LocalCompleter{shared: Some(shared)})
+}
+
+impl<R> LocalCompleter<R> {
+    fn finish(&mut self, state: Shared<R>) -> bool {
+        if let Some(shared) = self.shared.take() {
+            let old = {
+                let mut slot = shared.borrow_mut();
+                if let Shared::Dropped = *slot { state } else { std::mem::replace(&mut *slot, state) }
+            };
+            match old {
+                Shared::Pending(waker) => {
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                    true
+                }
+                old => {
+                    drop(old);
+                    false
+                }
+            }
+        }
+        else {
+            false
+        }
+    }
+    ///Whether the [LocalContinuation] still exists and is waiting, so that a value passed to
+    ///[complete](LocalCompleter::complete) could be taken.  See [Completer::has_consumer].
+    pub fn has_consumer(&self) -> bool {
+        match &self.shared {
+            Some(shared) => matches!(*shared.borrow(), Shared::Pending(_)),
+            None => false,
+        }
+    }
+    ///Whether the [LocalContinuation] was dropped, so nobody will take a value.  See [Completer::is_abandoned].
+    pub fn is_abandoned(&self) -> bool {
+        !self.has_consumer()
+    }
+    ///Completes the continuation with `value`, waking the task that awaits it.  As with [Completer::complete], returns
+    ///whether a consumer still existed, and if not, drops `value` before returning.
+    pub fn complete(mut self, value: R) -> bool {
+        self.finish(Shared::Completed(value))
+    }
+}
+
+impl<R> Drop for LocalContinuation<R> {
+    fn drop(&mut self) {
+        let old = std::mem::replace(&mut *self.shared.borrow_mut(), Shared::Dropped);
+        drop(old);
+    }
+}
+
+impl<R> Drop for LocalCompleter<R> {
+    fn drop(&mut self) {
+        self.finish(Shared::Abandoned);
+    }
+}
+
+impl<R> Future for LocalContinuation<R> {
+    type Output = R;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut state = self.shared.borrow_mut();
+        match std::mem::replace(&mut *state, Shared::Taken) {
+            Shared::Pending(_) => {
+                *state = Shared::Pending(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            Shared::Completed(value) => Poll::Ready(value),
+            Shared::Taken => panic!("LocalContinuation polled after completion"),
+            Shared::Abandoned => panic!("LocalCompleter was dropped without completing the continuation"),
+            Shared::Dropped => unreachable!("the continuation is alive"),
+        }
+    }
+}
+
+impl<R: 'static> LocalContinuation<R> {
+    ///Boxes and pins the continuation, erasing its type, for object-safe traits whose futures aren't [Send].  See
+    ///[Continuation::boxed].
+    pub fn boxed_local(self) -> LocalBoxContinuation<R> {
+        Box::pin(self)
+    }
+}
+
+impl<R: 'static> From<LocalContinuation<R>> for LocalBoxContinuation<R> {
+    fn from(continuation: LocalContinuation<R>) -> Self {
+        continuation.boxed_local()
+    }
+}
+
+/**
+Recycles continuation allocations, for bindings that create continuations at a high rate (e.g. per frame).
+
+When both halves of a pooled continuation are dropped, its allocation returns to the pool, and the next
+[acquire](ContinuationPool::acquire) of the same type reuses it.  Each reuse bumps a generation counter, which the halves
+check, so a stale half can never observe a later continuation.
+
+```
+use blocksr::continuation::ContinuationPool;
+let pool = ContinuationPool::new(4);
+for frame in 0..120 {
+    let (continuation, completer) = pool.acquire::<u64>();
+    completer.complete(frame);
+    # let _ = continuation;
+}
+```
+*/
+#[derive(Debug,Clone)]
+pub struct ContinuationPool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    free: Mutex<HashMap<TypeId, Vec<Arc<dyn Any + Send + Sync>>>>,
+    capacity: usize,
+}
+
+impl ContinuationPool {
+    ///Creates a pool that keeps up to `capacity` free allocations per result type.
+    pub fn new(capacity: usize) -> Self {
+        ContinuationPool{inner: Arc::new(PoolInner{free: Mutex::new(HashMap::new()), capacity})}
+    }
+    ///Creates a linked [Continuation] and [Completer], reusing a free allocation if one is available.
+    pub fn acquire<R: Send + 'static>(&self) -> (Continuation<R>, Completer<R>) {
+        fn recycle<R: Send + 'static>(pool: &PoolInner, shared: SharedSlot<R>) {
+            let mut free = pool.free.lock().unwrap();
+            let list = free.entry(TypeId::of::<R>()).or_default();
+            if list.len() < pool.capacity {
+                list.push(shared);
+            }
+        }
+        let reused = self.inner.free.lock().unwrap().get_mut(&TypeId::of::<R>()).and_then(|list| list.pop());
+        let shared = match reused {
+            Some(any) => any.downcast::<Mutex<Slot<R>>>().expect("pool slot has the wrong type"),
+            None => Arc::new(Mutex::new(Slot{generation: 0, state: Shared::Pending(None)})),
+        };
+        let generation = shared.lock().unwrap().generation;
+        pair(shared, generation, Some(Recycler{pool: self.inner.clone(), recycle: recycle::<R>}))
+    }
+    ///The number of free allocations for `R`.
+    pub fn free_count<R: 'static>(&self) -> usize {
+        self.inner.free.lock().unwrap().get(&TypeId::of::<R>()).map(|list| list.len()).unwrap_or(0)
+    }
+}
+
+impl<R> Continuation<R> {
+    /**
+    Makes the continuation abortable from elsewhere, for example when the user hits cancel.
+
+    Aborting resolves the [Abortable] with [Aborted] and runs its `on_abort` hooks, which typically cancel the underlying
+    objc task.
+    */
+    pub fn abortable(self) -> (Abortable<R>, AbortHandle) {
+        let abort = Arc::new(Mutex::new(AbortState{aborted: false, waker: None, on_abort: Vec::new()}));
+        (Abortable{continuation: self, abort: abort.clone()}, AbortHandle{abort})
+    }
+}
+
+struct AbortState {
+    aborted: bool,
+    waker: Option<Waker>,
+    on_abort: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+impl std::fmt::Debug for AbortState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AbortState").field("aborted", &self.aborted).field("on_abort", &self.on_abort.len()).finish()
+    }
+}
+
+///The error for an [Abortable] that was aborted.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Aborted;
+
+impl std::fmt::Display for Aborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the operation was aborted")
+    }
+}
+impl std::error::Error for Aborted {}
+
+/**
+A [Continuation] that may be aborted with an [AbortHandle].
+
+If the continuation has not resolved when it is aborted, this resolves to `Err(Aborted)`.
+*/
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Abortable<R> {
+    continuation: Continuation<R>,
+    abort: Arc<Mutex<AbortState>>,
+}
+
+impl<R> Abortable<R> {
+    /**
+    Registers `f` to run when the operation is aborted.
+
+    If it was already aborted, `f` runs immediately.
+    */
+    pub fn on_abort<F: FnOnce() + Send + 'static>(&self, f: F) {
+        let mut state = self.abort.lock().unwrap();
+        if state.aborted {
+            drop(state);
+            f();
+        }
+        else {
+            state.on_abort.push(Box::new(f));
+        }
+    }
+}
+
+impl<R> Future for Abortable<R> {
+    type Output = Result<R, Aborted>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            let mut state = self.abort.lock().unwrap();
+            if state.aborted {
+                return Poll::Ready(Err(Aborted));
+            }
+            state.waker = Some(cx.waker().clone());
+        }
+        Pin::new(&mut self.continuation).poll(cx).map(Ok)
+    }
+}
+
+impl<R: Send + 'static> Abortable<R> {
+    ///Boxes and pins the continuation, erasing its type.  See [Continuation::boxed].
+    pub fn boxed(self) -> BoxContinuation<Result<R, Aborted>> {
+        Box::pin(self)
+    }
+}
+
+impl<R: Send + 'static> From<Abortable<R>> for BoxContinuation<Result<R, Aborted>> {
+    fn from(continuation: Abortable<R>) -> Self {
+        continuation.boxed()
+    }
+}
+
+///Aborts an [Abortable] from another task or thread.
+#[derive(Debug,Clone)]
+pub struct AbortHandle {
+    abort: Arc<Mutex<AbortState>>,
+}
+
+impl AbortHandle {
+    ///Aborts the operation, running its `on_abort` hooks.  Aborting more than once has no further effect.
+    pub fn abort(&self) {
+        let (waker, hooks) = {
+            let mut state = self.abort.lock().unwrap();
+            if state.aborted {
+                return;
+            }
+            state.aborted = true;
+            (state.waker.take(), std::mem::take(&mut state.on_abort))
+        };
+        //run hooks without the lock, so they may call back into us
+        for hook in hooks {
+            hook();
+        }
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+//resolves a continuation with the error, if it has the right type and is pending
+type Cancel = Arc<dyn Fn(&dyn Any) -> bool + Send + Sync>;
+
+struct Entry {
+    id: u64,
+    cancel: Cancel,
+}
+
+static REGISTRY: Mutex<Option<HashMap<&'static str, Vec<Entry>>>> = Mutex::new(None);
+
+#[derive(Debug)]
+struct Registration {
+    tag: &'static str,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entries) = registry.as_mut().and_then(|registry| registry.get_mut(self.tag)) {
+            entries.retain(|entry| entry.id != self.id);
+            if entries.is_empty() {
+                registry.as_mut().unwrap().remove(self.tag);
+            }
+        }
+    }
+}
+
+impl<T: Send + 'static, E: Clone + Send + 'static> Completer<Result<T, E>> {
+    /**
+    Tags the continuation, so that [cancel_all] with the same tag resolves it with an error.  Call it when the
+    continuation is created, before the completer is moved into a block.
+
+    For a completer made with [from_fn](Completer::from_fn) or [from_sender](Completer::from_sender), there is no
+    continuation to resolve, and this does nothing.  Tagging again replaces the tag.
+    */
+    pub fn tagged(mut self, tag: &'static str) -> Self {
+        static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let Some(shared) = &self.shared else { return self };
+        let weak = Arc::downgrade(shared);
+        let generation = self.generation;
+        let cancel = Arc::new(move |error: &dyn Any| {
+            let (Some(error), Some(shared)) = (error.downcast_ref::<E>(), weak.upgrade()) else { return false };
+            let waker = {
+                let mut slot = shared.lock().unwrap_or_else(|e| e.into_inner());
+                if slot.generation != generation || !matches!(slot.state, Shared::Pending(_)) {
+                    return false;
+                }
+                match std::mem::replace(&mut slot.state, Shared::Completed(Err(error.clone()))) {
+                    Shared::Pending(waker) => waker,
+                    _ => unreachable!(),
+                }
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            true
+        });
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        //drop the old registration first, outside the registry's lock
+        self.registration = None;
+        REGISTRY.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(Default::default).entry(tag).or_default().push(Entry{id, cancel});
+        self.registration = Some(Registration{tag, id});
+        self
+    }
+}
+
+/**
+Resolves every pending continuation tagged `tag`, and whose error type is `E`, with `Err(error)`.  Returns how many
+were resolved.
+
+This is for shutdown: a task awaiting a completion handler that will never be called, because the app is terminating,
+would otherwise hang.  When the handler is called after all, its value is dropped, and `complete` returns `false`.
+
+```
+use blocksr::continuation::{cancel_all, continuation};
+let (continuation, completer) = continuation::<Result<u8, &str>>();
+let completer = completer.tagged("network");
+assert_eq!(cancel_all("network", "shutting down"), 1);
+assert_eq!(continuation.wait(), Err("shutting down"));
+assert!(!completer.complete(Ok(3)));
+```
+*/
+pub fn cancel_all<E: Clone + Send + 'static>(tag: &str, error: E) -> usize {
+    //cancelling wakes tasks, which may drop completers, which lock the registry
+    let cancels: Vec<_> = REGISTRY.lock().unwrap_or_else(|e| e.into_inner()).as_ref()
+        .and_then(|registry| registry.get(tag))
+        .map(|entries| entries.iter().map(|entry| entry.cancel.clone()).collect())
+        .unwrap_or_default();
+    cancels.iter().filter(|cancel| cancel(&error)).count()
+}
+
+/**
+A fan-in for many completions, such as hundreds of block-based calls issued at once.
+
+Rather than one allocation per [Continuation], all of the [GroupCompleter]s share one allocation.  The group is a future that
+wakes once, when all (or a quorum) have completed.
+
+```
+use blocksr::continuation::CompletionGroup;
+async fn example() -> Vec<usize> {
+    let (group, completers) = CompletionGroup::new(100);
+    for completer in completers {
+        //ordinarily, each completer is moved into a block and completed by objc
+        std::thread::spawn(move || { let index = completer.index(); completer.complete(index * 2) });
+    }
+    group.await
+}
+```
+
+# Panics
+Polling panics if so many completers are dropped without completing that the group can never resolve.
+*/
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct CompletionGroup<R> {
+    shared: Arc<Mutex<GroupState<R>>>,
+}
+
+#[derive(Debug)]
+struct GroupState<R> {
+    results: Vec<Option<R>>,
+    completed: usize,
+    abandoned: usize,
+    quorum: usize,
+    waker: Option<Waker>,
+}
+
+impl<R> GroupState<R> {
+    fn is_ready(&self) -> bool {
+        self.completed >= self.quorum || self.completed + (self.results.len() - self.completed - self.abandoned) < self.quorum
+    }
+}
+
+/**
+Completes one slot of a [CompletionGroup].
+*/
+#[derive(Debug)]
+pub struct GroupCompleter<R> {
+    shared: Option<Arc<Mutex<GroupState<R>>>>,
+    index: usize,
+}
+
+impl<R: Send> CompletionGroup<R> {
+    ///Creates a group that resolves when all `count` completers have completed.
+    pub fn new(count: usize) -> (Self, Vec<GroupCompleter<R>>) {
+        Self::with_quorum(count, count)
+    }
+    /**
+    Creates a group of `count` completers that resolves when `quorum` of them have completed.
+
+    The output contains the values completed by then, in index order.  Later completions are discarded.
+    */
+    pub fn with_quorum(count: usize, quorum: usize) -> (Self, Vec<GroupCompleter<R>>) {
+        assert!(quorum <= count, "quorum {} exceeds count {}", quorum, count);
+        let shared = Arc::new(Mutex::new(GroupState {
+            results: (0..count).map(|_| None).collect(),
+            completed: 0,
+            abandoned: 0,
+            quorum,
+            waker: None,
+        }));
+        let completers = (0..count).map(|index| GroupCompleter{shared: Some(shared.clone()), index}).collect();
+        (CompletionGroup{shared}, completers)
+    }
+}
+
+impl<R> GroupCompleter<R> {
+    ///The index of this completer's slot.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    ///Completes this slot with `value`.
+    pub fn complete(mut self, value: R) {
+        let shared = self.shared.take().unwrap();
+        let mut state = shared.lock().unwrap();
+        let was_ready = state.is_ready();
+        state.results[self.index] = Some(value);
+        state.completed += 1;
+        if !was_ready && state.is_ready() {
+            if let Some(waker) = state.waker.take() {
+                drop(state);
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<R> Drop for GroupCompleter<R> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            let mut state = shared.lock().unwrap();
+            let was_ready = state.is_ready();
+            state.abandoned += 1;
+            if !was_ready && state.is_ready() {
+                if let Some(waker) = state.waker.take() {
+                    drop(state);
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+impl<R> Future for CompletionGroup<R> {
+    type Output = Vec<R>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<R>> {
+        let mut state = self.shared.lock().unwrap();
+        if !state.is_ready() {
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        assert!(state.completed >= state.quorum, "GroupCompleters were dropped without completing the group");
+        //later completions go nowhere
+        state.quorum = 0;
+        let results = std::mem::take(&mut state.results);
+        state.results = (0..results.len()).map(|_| None).collect();
+        Poll::Ready(results.into_iter().flatten().collect())
+    }
+}
+
+/**
+Converts a completion handler's arguments into the value of a [Completer].
+
+Implemented for a single argument, and for tuples of arguments.
+*/
+#[doc(hidden)]
+pub trait CompleteFrom<Args, R> {
+    fn complete_from(args: Args) -> Self;
+    ///The handler's return value, after completing.
+    fn completion_return() -> R;
+}
+
+/**
+Implemented for `(Output, Args)`, where `Output` is what a handler with `Args` completes, for `future`.
+
+The pair lets `future` infer `Output` from the block's arguments.
+*/
+#[doc(hidden)]
+pub trait CompletionOutput<R> {}
+
+impl<A> CompletionOutput<()> for (A, (A,)) {}
+
+impl<A> CompleteFrom<(A,), ()> for A {
+    fn complete_from(args: (A,)) -> Self {
+        args.0
+    }
+    fn completion_return() {}
+}
+
+macro_rules! complete_from_tuple {
+    ($($A:ident),*) => {
+        impl<$($A),*> CompleteFrom<($($A,)*), ()> for ($($A,)*) {
+            fn complete_from(args: ($($A,)*)) -> Self {
+                args
+            }
+            fn completion_return() {}
+        }
+        impl<$($A),*> CompletionOutput<()> for (($($A,)*), ($($A,)*)) {}
+    }
+}
+complete_from_tuple!();
+complete_from_tuple!(A, B);
+complete_from_tuple!(A, B, C);
+complete_from_tuple!(A, B, C, D);
+complete_from_tuple!(A, B, C, D, E);
+complete_from_tuple!(A, B, C, D, E, F);
+complete_from_tuple!(A, B, C, D, E, F, G);
+complete_from_tuple!(A, B, C, D, E, F, G, H);
+complete_from_tuple!(A, B, C, D, E, F, G, H, I);
+complete_from_tuple!(A, B, C, D, E, F, G, H, I, J);
+complete_from_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+complete_from_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/**
+Declares an `async fn` that wraps a completion-handler API.
+
+This expands to a [once_escaping](crate::once_escaping) block type for the handler, the continuation wiring, and the `async fn` itself.
+
+```
+use blocksr::bind_async;
+# use std::ffi::c_void;
+# unsafe fn objc_api(arg: u8, handler: *const c_void) { (*(handler as *const blocksr::Block<(u8,),()>)).invoke((arg * 2,)) }
+bind_async!(
+    //the async fn
+    pub async fn doubled(arg: u8) -> Result<u8, String>;
+    //the completion handler's block type and arguments
+    via Handler(result: u8);
+    //converts the handler's arguments into the output
+    complete |result| if result > 0 { Ok(result) } else { Err("zero".to_string()) };
+    //makes the call, passing `&Handler`
+    call |handler| unsafe{ objc_api(arg, handler as *const Handler as *const c_void) }
+);
+```
+
+The `call` expression runs once, synchronously.  The handler is passed by reference and is expected to be copied by
+objc.  By declaring the binding, you assert the safety requirements of [once_escaping](crate::once_escaping)'s `new`: in particular,
+that objc calls the completion handler exactly once.
+*/
+#[macro_export]
+macro_rules! bind_async {
+    (
+        $pub:vis async fn $name:ident ($($p:ident : $P:ty),*) -> $Out:ty;
+        via $handler:ident ($($a:ident : $A:ty),*);
+        complete |$($c:ident),*| $complete:expr;
+        call |$h:ident| $call:expr
+    ) => {
+        blocksr::once_escaping!($pub $handler ($($a : $A),*) -> ());
+
+        $pub async fn $name($($p : $P),*) -> $Out {
+            let (continuation, completer) = blocksr::continuation::continuation::<$Out>();
+            {
+                let handler = unsafe{ $handler::new(move |$($a),*| {
+                    let output: $Out = (|$($c),*| $complete)($($a),*);
+                    completer.complete(output);
+                })};
+                let $h = &handler;
+                $call;
+            }
+            continuation.await
+        }
+    };
+}
+
+#[cfg(test)]
+use crate::executors::block_on;
+
+#[test] fn complete_from_thread() {
+    let (continuation, completer) = continuation();
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        completer.complete(23)
+    });
+    assert_eq!(block_on(continuation), 23);
+}
+
+#[test] #[should_panic] fn abandoned() {
+    let (continuation, completer) = continuation::<u8>();
+    std::mem::drop(completer);
+    block_on(continuation);
+}
+
+#[test] fn tee_into_completer() {
+    crate::once_escaping!(ValidationHandler (value: u8) -> bool);
+    let (continuation, completer) = continuation();
+    let block = unsafe{ ValidationHandler::new_teeing(|value| value > 2, move |r: &bool| { completer.complete(*r); }) };
+    //objc discards the return value
+    let _ = unsafe{ block.as_block().invoke((3,)) };
+    assert!(block_on(continuation));
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn bind() {
+    unsafe fn objc_api(arg: u8, handler: &crate::hidden::BlockLiteralOnceEscape) {
+        let block = unsafe{ &*(handler as *const _ as *const crate::Block<(u8,),()>) };
+        unsafe{ block.invoke((arg * 2,)) }
+    }
+    crate::bind_async!(
+        async fn doubled(arg: u8) -> Result<u8, &'static str>;
+        via Handler(result: u8);
+        complete |result| if result > 0 { Ok(result) } else { Err("zero") };
+        call |handler| unsafe{ objc_api(arg, &handler.0) }
+    );
+    assert_eq!(block_on(doubled(2)), Ok(4));
+    assert_eq!(block_on(doubled(0)), Err("zero"));
+}
+
+#[test] fn abort() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    let (continuation, completer) = continuation::<u8>();
+    let (abortable, handle) = continuation.abortable();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let hook_cancelled = cancelled.clone();
+    abortable.on_abort(move || {
+        hook_cancelled.store(true, Ordering::SeqCst);
+    });
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        handle.abort();
+        //the objc task reports its cancellation afterwards
+        completer.complete(0);
+    });
+    assert_eq!(block_on(abortable), Err(Aborted));
+    assert!(cancelled.load(Ordering::SeqCst));
+}
+
+#[test] fn abortable_completes() {
+    let (continuation, completer) = continuation();
+    let (abortable, _handle) = continuation.abortable();
+    completer.complete(5);
+    assert_eq!(block_on(abortable), Ok(5));
+}
+
+#[test] fn completion_group() {
+    let (group, completers) = CompletionGroup::new(200);
+    for completer in completers {
+        std::thread::spawn(move || {
+            let index = completer.index();
+            completer.complete(index * 2)
+        });
+    }
+    let results = block_on(group);
+    assert_eq!(results, (0..200).map(|i| i * 2).collect::<Vec<_>>());
+}
+
+#[test] fn completion_group_quorum() {
+    let (group, completers) = CompletionGroup::with_quorum(3, 2);
+    let mut completers = completers.into_iter();
+    let first = completers.next().unwrap();
+    let second = completers.next().unwrap();
+    let _third = completers.next().unwrap();
+    second.complete("b");
+    first.complete("a");
+    assert_eq!(block_on(group), vec!["a", "b"]);
+}
+
+#[test] fn pool_reuses() {
+    let pool = ContinuationPool::new(2);
+    let (continuation, completer) = pool.acquire::<u8>();
+    let first = Arc::as_ptr(continuation.shared.as_ref().unwrap());
+    completer.complete(1);
+    assert_eq!(block_on(continuation), 1);
+    assert_eq!(pool.free_count::<u8>(), 1);
+    let (continuation, completer) = pool.acquire::<u8>();
+    assert_eq!(Arc::as_ptr(continuation.shared.as_ref().unwrap()), first);
+    assert_eq!(continuation.generation, 1);
+    assert_eq!(pool.free_count::<u8>(), 0);
+    std::thread::spawn(move || completer.complete(2));
+    assert_eq!(block_on(continuation), 2);
+}
+
+#[test] fn channels() {
+    let (receiver, completer) = channel_continuation();
+    std::thread::spawn(move || completer.complete(3));
+    assert_eq!(receiver.recv(), Ok(3));
+    let (receiver, completer) = channel_continuation::<u8>();
+    drop(completer);
+    assert!(receiver.recv().is_err());
+    let (continuation, completer) = continuation();
+    std::thread::spawn(move || completer.complete(4));
+    assert_eq!(continuation.wait(), 4);
+}
+
+#[test] fn local_completer() {
+    let (continuation, completer) = local_continuation();
+    let value = Rc::new(5);
+    completer.complete(value.clone());
+    assert!(Rc::ptr_eq(&block_on(continuation), &value));
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn from_completer() {
+    crate::once_escaping!(SingleHandler (result: u8) -> ());
+    crate::once_escaping!(PairHandler (result: u8, error: i32) -> ());
+    let (single, completer) = continuation::<u8>();
+    let handler = unsafe{ SingleHandler::from_completer(completer) };
+    unsafe{ handler.as_block().invoke((4,)) };
+    assert_eq!(block_on(single), 4);
+    let (pair, completer) = continuation::<(u8, i32)>();
+    let handler = unsafe{ PairHandler::from_completer(completer) };
+    unsafe{ handler.as_block().invoke((5, -1)) };
+    assert_eq!(block_on(pair), (5, -1));
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn complete_args() {
+    #[derive(Debug,PartialEq)]
+    struct Reply {
+        length: u8,
+        ok: bool,
+    }
+    impl From<(u8, bool)> for Reply {
+        fn from((length, ok): (u8, bool)) -> Self {
+            Reply{length, ok}
+        }
+    }
+    let (single, completer) = continuation::<u8>();
+    assert!(completer.complete_args((1,)));
+    assert_eq!(block_on(single), 1);
+    let (triple, completer) = continuation::<(u8, bool, i32)>();
+    completer.complete_args((2, true, -3));
+    assert_eq!(block_on(triple), (2, true, -3));
+    crate::once_escaping!(ReplyHandler (length: u8, ok: bool) -> ());
+    let (reply, completer) = continuation::<Reply>();
+    let handler = unsafe{ ReplyHandler::from_completer_into(completer) };
+    unsafe{ handler.as_block().invoke((4, false)) };
+    assert_eq!(block_on(reply), Reply{length: 4, ok: false});
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn block_future() {
+    crate::once_escaping!(SingleHandler (result: u8) -> ());
+    crate::once_escaping!(PairHandler (result: u8, error: i32) -> ());
+    let (handler, single) = unsafe{ SingleHandler::future() };
+    unsafe{ handler.as_block().invoke((4,)) };
+    assert_eq!(block_on(single), 4);
+    let (handler, pair) = unsafe{ PairHandler::future() };
+    unsafe{ handler.as_block().invoke((5, -1)) };
+    assert_eq!(block_on(pair), (5, -1));
+}
+
+#[test] fn map() {
+    let (continuation, completer) = continuation::<String>();
+    let completer = completer.contramap(|code: i32| format!("status {}", code));
+    std::thread::spawn(move || completer.complete(200));
+    assert_eq!(block_on(continuation.map(|s| s.len())), 10);
+}
+
+#[test] fn drop_order() {
+    use crate::testing::DropLog;
+    let log = DropLog::new();
+    //the consumer takes the value
+    let (pending, completer) = continuation();
+    assert!(completer.complete(log.probe("taken")));
+    let value = crate::executors::block_on(pending);
+    log.mark("polled");
+    drop(value);
+    //completed, then dropped without polling
+    let (pending, completer) = continuation();
+    assert!(completer.complete(log.probe("unpolled")));
+    log.mark("completed");
+    drop(pending);
+    //dropped, then completed
+    let (pending, completer) = continuation();
+    assert!(completer.has_consumer());
+    assert!(!completer.is_abandoned());
+    drop(pending);
+    assert!(!completer.has_consumer());
+    assert!(completer.is_abandoned());
+    assert!(!completer.complete(log.probe("unwanted")));
+    log.mark("returned");
+    //resolved by cancel_all, then completed
+    let (cancelled, completer) = continuation::<Result<crate::testing::DropProbe, &str>>();
+    let completer = completer.tagged("drop_order");
+    assert_eq!(cancel_all("drop_order", "shutdown"), 1);
+    assert!(!completer.has_consumer());
+    assert!(completer.is_abandoned());
+    assert!(!completer.complete(Ok(log.probe("cancelled"))));
+    log.mark("cancelled returned");
+    assert!(crate::executors::block_on(cancelled).is_err());
+    //nor after cancel_all
+    let (cancelled, completer) = continuation::<Result<crate::testing::DropProbe, &str>>();
+    let converting = log.clone();
+    let completer = completer.tagged("drop_order").contramap(move |event: &'static str| Ok(converting.probe(event)));
+    assert_eq!(cancel_all("drop_order", "shutdown"), 1);
+    assert!(!completer.complete("converted"));
+    drop(cancelled);
+    //the conversion doesn't run without a consumer
+    let (pending, completer) = continuation::<crate::testing::DropProbe>();
+    let converting = log.clone();
+    let completer = completer.contramap(move |event: &'static str| converting.probe(event));
+    drop(pending);
+    assert!(!completer.complete("converted"));
+    //locally
+    let (pending, completer) = local_continuation();
+    assert!(completer.has_consumer());
+    drop(pending);
+    assert!(!completer.has_consumer());
+    assert!(completer.is_abandoned());
+    assert!(!completer.complete(log.probe("local")));
+    log.mark("local returned");
+    assert_eq!(log.events(), ["polled", "taken", "completed", "unpolled", "unwanted", "returned", "cancelled", "cancelled returned", "local", "local returned"]);
+    //a channel's consumer is its receiver
+    let (receiver, completer) = channel_continuation::<u8>();
+    drop(receiver);
+    assert!(!completer.complete(1));
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn panicked() {
+    crate::once_escaping!(Handler (value: u8) -> ());
+    let (pending, completer) = continuation::<Result<u8, Panicked>>();
+    let block = unsafe{ Handler::from_completer_catching(completer, |value| {
+        assert!(value > 0, "zero reply");
+        value
+    })};
+    unsafe{ block.as_block().invoke((0,)) };
+    let error = block_on(pending).unwrap_err();
+    assert_eq!(error.message(), Some("zero reply"));
+    assert_eq!(error.to_string(), "the completion handler panicked: zero reply");
+    let (pending, completer) = continuation::<Result<u8, Panicked>>();
+    assert!(completer.complete_catching(|| 3));
+    assert_eq!(block_on(pending).unwrap(), 3);
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn from_async() {
+    crate::once_escaping!(Handler (value: u8) -> ());
+    let (pending, completer) = continuation::<u8>();
+    let (sender, receiver) = std::sync::mpsc::channel::<BoxContinuation<()>>();
+    let block = unsafe{ Handler::from_async(completer, move |task| sender.send(task).unwrap(), |value| async move { value * 2 }) };
+    unsafe{ block.as_block().invoke((3,)) };
+    //the block returned before its future ran
+    let task = receiver.try_recv().unwrap();
+    block_on(task);
+    assert_eq!(block_on(pending), 6);
+}
+
+#[test] fn shared_completion() {
+    struct Noop;
+    impl std::task::Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = Context::from_waker(&waker);
+    let shared = SharedCompletion::<u8>::new();
+    assert_eq!(shared.poll(&mut cx), Poll::Pending);
+    assert!(shared.clone().complete(1));
+    assert!(!shared.completer().complete(2));
+    assert_eq!(shared.poll(&mut cx), Poll::Ready(Some(1)));
+    let shared = SharedCompletion::<u8>::new();
+    let completer = shared.completer();
+    assert!(completer.has_consumer());
+    drop(completer);
+    assert_eq!(shared.poll(&mut cx), Poll::Ready(None));
+    //the last clone is the consumer
+    let shared = SharedCompletion::<u8>::new();
+    let completer = shared.completer();
+    let clone = shared.clone();
+    drop(shared);
+    assert!(completer.has_consumer());
+    drop(clone);
+    assert!(!completer.has_consumer());
+    assert!(!completer.complete(4));
+}
+
+#[test] fn complete_boxed() {
+    let (pending, completer) = continuation::<Box<dyn Any + Send>>();
+    assert!(completer.complete_boxed(3u8));
+    assert_eq!(block_on(pending).downcast_ref::<u8>(), Some(&3));
+    let (pending, completer) = continuation::<Result<u8, Box<dyn std::error::Error + Send>>>();
+    assert!(completer.contramap(|error: std::fmt::Error| Err(error.box_into())).complete(std::fmt::Error));
+    assert!(block_on(pending).unwrap_err().is::<std::fmt::Error>());
+    let (pending, completer) = local_continuation::<Box<dyn Any>>();
+    assert!(completer.complete_boxed(Rc::new(4u8)));
+    assert_eq!(block_on(pending).downcast_ref::<Rc<u8>>().map(|value| **value), Some(4));
+}
+
+#[test] fn cancel_all_tagged() {
+    let (cancelled, completer) = continuation::<Result<u8, &str>>();
+    let completer = completer.tagged("cancel_all_tagged");
+    let (other_error, other_completer) = continuation::<Result<u8, String>>();
+    let other_completer = other_completer.tagged("cancel_all_tagged");
+    let (completed, completed_completer) = continuation::<Result<u8, &str>>();
+    assert!(completed_completer.tagged("cancel_all_tagged").complete(Ok(1)));
+    assert_eq!(cancel_all("cancel_all_tagged", "shutdown"), 1);
+    assert!(completer.is_abandoned());
+    assert!(!other_completer.is_abandoned());
+    assert_eq!(block_on(cancelled), Err("shutdown"));
+    assert_eq!(block_on(completed), Ok(1));
+    //the handler is called after all
+    assert!(!completer.complete(Ok(2)));
+    assert!(other_completer.complete(Ok(3)));
+    assert_eq!(block_on(other_error), Ok(3));
+    assert_eq!(cancel_all("cancel_all_tagged", "shutdown"), 0);
+    assert!(REGISTRY.lock().unwrap().as_ref().unwrap().get("cancel_all_tagged").is_none());
+}
+
+#[test] fn complete_in_place() {
+    let (large, completer) = continuation::<Box<[u64; 1024]>>();
+    std::thread::spawn(move || completer.complete_in_place(|slot| {
+        for (i, word) in unsafe{ (*slot.as_mut_ptr()).iter_mut() }.enumerate() {
+            *word = i as u64;
+        }
+        unsafe{ slot.assume_init_mut() }
+    }));
+    let buffer = block_on(large);
+    assert_eq!(buffer[1023], 1023);
+    let (_continuation, completer) = continuation::<Box<()>>();
+    completer.complete_in_place(|slot| slot.write(()));
+}
+
+#[test] fn boxed() {
+    trait Prompt {
+        fn ask(&self) -> BoxContinuation<bool>;
+        fn count(&self) -> BoxContinuation<usize>;
+    }
+    struct Granting;
+    impl Prompt for Granting {
+        fn ask(&self) -> BoxContinuation<bool> {
+            let (continuation, completer) = continuation();
+            std::thread::spawn(move || completer.complete(true));
+            continuation.boxed()
+        }
+        fn count(&self) -> BoxContinuation<usize> {
+            let (continuation, completer) = continuation::<String>();
+            std::thread::spawn(move || completer.complete("four".to_string()));
+            continuation.map(|s| s.len()).into()
+        }
+    }
+    let prompt: Box<dyn Prompt> = Box::new(Granting);
+    assert!(block_on(prompt.ask()));
+    assert_eq!(block_on(prompt.count()), 4);
+    let (continuation, completer) = continuation::<u8>();
+    let (abortable, handle) = continuation.abortable();
+    let abortable: BoxContinuation<Result<u8, Aborted>> = abortable.into();
+    handle.abort();
+    assert_eq!(block_on(abortable), Err(Aborted));
+    drop(completer);
+    let (continuation, completer) = local_continuation();
+    completer.complete(std::rc::Rc::new(5));
+    let continuation: LocalBoxContinuation<std::rc::Rc<u8>> = continuation.into();
+    assert_eq!(*block_on(continuation), 5);
+}
+
+#[test] fn on_main_ran() {
+    use std::future::IntoFuture;
+    assert_eq!(block_on(OnMain::Ran(3).into_future()), 3);
+    let (continuation, completer) = continuation();
+    completer.complete(4);
+    assert_eq!(block_on(OnMain::Dispatched(continuation).into_future()), 4);
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn on_main_dispatched() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    let ran = Arc::new(AtomicBool::new(false));
+    let flag = ran.clone();
+    //the test harness runs off the main thread, and nothing drains the main queue
+    match on_main(move || flag.store(true, Ordering::SeqCst)) {
+        OnMain::Ran(()) => panic!("ran synchronously off the main thread"),
+        OnMain::Dispatched(mut continuation) => assert!(continuation.try_take().is_none()),
+    }
+    assert!(!ran.load(Ordering::SeqCst));
+}
+
+#[test] fn now_or_never() {
+    struct NoopWaker;
+    impl std::task::Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+    //polls once, like futures' now_or_never
+    fn now_or_never<F: Future + Unpin>(mut future: F) -> Option<F::Output> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        match Pin::new(&mut future).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(value) => Some(value),
+            Poll::Pending => None,
+        }
+    }
+    let (ready, completer) = continuation();
+    completer.complete(3);
+    assert_eq!(now_or_never(ready), Some(3));
+    let (pending, completer) = continuation::<u8>();
+    assert_eq!(now_or_never(pending), None);
+    assert!(!completer.complete(3));
+
+    let (mut taken, completer) = continuation();
+    assert_eq!(taken.try_take(), None);
+    completer.complete(4);
+    assert_eq!(taken.try_take(), Some(4));
+    let (waited, completer) = continuation();
+    completer.complete(5);
+    assert_eq!(waited.wait(), 5);
+
+    //a select between a pending continuation and one completed before polling takes the completed one
+    let (mut slow, slow_completer) = continuation::<&str>();
+    let (mut fast, fast_completer) = continuation::<&str>();
+    fast_completer.complete("fast");
+    let first = block_on(std::future::poll_fn(|cx| {
+        if let Poll::Ready(value) = Pin::new(&mut slow).poll(cx) {
+            return Poll::Ready(value);
+        }
+        Pin::new(&mut fast).poll(cx)
+    }));
+    assert_eq!(first, "fast");
+    //the losing side is still usable
+    slow_completer.complete("slow");
+    assert_eq!(slow.try_take(), Some("slow"));
+
+    let noop = Arc::new(NoopWaker);
+    let waker = Waker::from(noop.clone());
+    let (mut polled, completer) = continuation::<u8>();
+    for _ in 0..3 {
+        assert!(Pin::new(&mut polled).poll(&mut Context::from_waker(&waker)).is_pending());
+    }
+    //the slot holds one clone, however often it is polled
+    assert_eq!(Arc::strong_count(&noop), 3);
+    completer.complete(6);
+    assert_eq!(Arc::strong_count(&noop), 2);
+    assert_eq!(polled.try_take(), Some(6));
+}
+
+#[test] #[should_panic(expected = "without completing")] fn try_take_abandoned() {
+    let (mut abandoned, completer) = continuation::<u8>();
+    drop(completer);
+    abandoned.try_take();
+}
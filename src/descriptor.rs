@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Interned block descriptors.
+
+A descriptor with a signature depends on the block's type.  The macros know the type at compile time, so they emit
+one for each declared type with a [Signature](crate::Signature).  A signature only known at runtime, like one passed to
+[RawBlockBuilder::signature](crate::raw::RawBlockBuilder::signature), has no static to live in.
+
+Instead, [intern_descriptor] builds each distinct shape once and returns the same descriptor for every later request,
+so identical shapes share one descriptor.
+*/
+
+use core::ffi::{c_void, CStr};
+use std::sync::Mutex;
+
+/**
+Everything a descriptor records about a block.
+*/
+#[derive(Debug,Clone,Copy)]
+pub struct DescriptorShape {
+    ///The size of the block literal.
+    pub size: usize,
+    ///The copy and dispose helpers, for blocks with [BLOCK_HAS_COPY_DISPOSE](crate::raw::BLOCK_HAS_COPY_DISPOSE).
+    pub helpers: Option<(extern "C" fn(*mut c_void, *const c_void), extern "C" fn(*const c_void))>,
+    ///The signature in ObjC type encoding, for blocks with [BLOCK_HAS_SIGNATURE](crate::raw::BLOCK_HAS_SIGNATURE).
+    pub signature: Option<&'static CStr>,
+}
+
+impl DescriptorShape {
+    ///A shape of `size` bytes, without helpers or a signature.
+    pub const fn new(size: usize) -> Self {
+        DescriptorShape{size, helpers: None, signature: None}
+    }
+    //signatures are compared by content, so separately emitted copies of a string match
+    fn matches(&self, other: &DescriptorShape) -> bool {
+        let helpers = |shape: &DescriptorShape| shape.helpers.map(|(copy, dispose)| (copy as usize, dispose as usize));
+        self.size == other.size && helpers(self) == helpers(other) && self.signature == other.signature
+    }
+    //the descriptor's words, in ABI order
+    fn words(&self) -> Vec<*const c_void> {
+        let mut words = vec![core::ptr::null(), self.size as *const c_void];
+        if let Some((copy, dispose)) = self.helpers {
+            words.push(copy as *const c_void);
+            words.push(dispose as *const c_void);
+        }
+        if let Some(signature) = self.signature {
+            words.push(signature.as_ptr() as *const c_void);
+            //no extended layout
+            words.push(core::ptr::null());
+        }
+        words
+    }
+}
+
+struct Interned {
+    shape: DescriptorShape,
+    descriptor: &'static [*const c_void],
+}
+//Safety: the descriptor is immutable, and its pointers are to functions and static strings
+unsafe impl Send for Interned {}
+
+static INTERNED: Mutex<Vec<Interned>> = Mutex::new(Vec::new());
+
+/**
+Returns a descriptor for `shape`, the same one for every equal shape.
+
+The first request for a shape allocates its descriptor, which lives for the rest of the program.  Later requests are
+a lookup.
+*/
+pub fn intern_descriptor(shape: DescriptorShape) -> *const c_void {
+    let mut interned = INTERNED.lock().unwrap();
+    if let Some(existing) = interned.iter().find(|i| i.shape.matches(&shape)) {
+        return existing.descriptor.as_ptr() as *const c_void;
+    }
+    let descriptor: &'static [*const c_void] = Box::leak(shape.words().into_boxed_slice());
+    interned.push(Interned{shape, descriptor});
+    descriptor.as_ptr() as *const c_void
+}
+
+#[test] fn shares_shapes() {
+    let signature = c"v8@?0";
+    //a separate allocation with the same contents
+    let copy = std::ffi::CString::new("v8@?0").unwrap();
+    let a = intern_descriptor(DescriptorShape{signature: Some(signature), ..DescriptorShape::new(40)});
+    let b = intern_descriptor(DescriptorShape{signature: Some(Box::leak(copy.into_boxed_c_str())), ..DescriptorShape::new(40)});
+    assert_eq!(a, b);
+    let c = intern_descriptor(DescriptorShape{signature: Some(c"i12@?0i8"), ..DescriptorShape::new(40)});
+    assert_ne!(a, c);
+    assert_ne!(a, intern_descriptor(DescriptorShape::new(40)));
+    let words = unsafe{ core::slice::from_raw_parts(c as *const *const c_void, 4) };
+    assert_eq!(words[1] as usize, 40);
+    assert_eq!(unsafe{ CStr::from_ptr(words[2] as *const core::ffi::c_char) }, c"i12@?0i8");
+}
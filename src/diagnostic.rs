@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Where the library's diagnostics go.
+
+The library never writes to stdout.  Diagnostics, such as a tolerant once block invoked twice, or a thread-affine block
+used off its thread, are written to stderr by default.  With the `quiet` feature, they go only to the `log` crate, so
+an app without a logger hears nothing.
+*/
+
+///Reports a problem the library recovers from.
+pub(crate) fn warn(args: core::fmt::Arguments<'_>) {
+    #[cfg(feature = "quiet")]
+    log::warn!("{}", args);
+    #[cfg(not(feature = "quiet"))]
+    eprintln!("{}", args);
+}
+
+///Reports a problem the library can't recover from, and aborts.
+#[allow(dead_code)] //only used in debug builds, or with objc-exceptions
+pub(crate) fn abort(args: core::fmt::Arguments<'_>) -> ! {
+    #[cfg(feature = "quiet")]
+    log::error!("{}", args);
+    #[cfg(not(feature = "quiet"))]
+    eprintln!("{}", args);
+    std::process::abort()
+}
+
+/**
+Runs `f` in a child process, which re-runs the test `name` alone, and returns what it wrote to stderr before it aborted.
+
+Panics if the child didn't abort.  Diagnostics only reach stderr without the `quiet` feature.
+*/
+#[cfg(all(test, not(feature = "quiet")))]
+#[allow(dead_code)] //only used by debug-build tests
+pub(crate) fn expect_abort(name: &str, f: impl FnOnce()) -> String {
+    const CHILD: &str = "BLOCKSR_EXPECT_ABORT";
+    if std::env::var_os(CHILD).is_some() {
+        f();
+        std::process::exit(0);
+    }
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([name, "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .output().unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        //SIGABRT
+        assert_eq!(output.status.signal(), Some(6), "{} didn't abort: {:?}", name, output);
+    }
+    #[cfg(not(unix))]
+    assert!(!output.status.success(), "{} didn't abort: {:?}", name, output);
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[cfg(all(unix, feature = "quiet"))]
+#[test] fn silent() {
+    use std::io::{Read, Seek};
+    use std::os::unix::io::AsRawFd;
+    extern "C" {
+        fn dup(fd: i32) -> i32;
+        fn dup2(from: i32, to: i32) -> i32;
+    }
+    crate::many_escaping_reentrant!(MyBlock (environment: &u8) -> u8);
+    crate::once_escaping_tolerant!(TolerantBlock (arg: u8) -> u8);
+    let path = std::env::temp_dir().join(format!("blocksr-silent-{}", std::process::id()));
+    let mut captured = std::fs::File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+    let saved = unsafe{ [dup(1), dup(2)] };
+    unsafe{ dup2(captured.as_raw_fd(), 1) };
+    unsafe{ dup2(captured.as_raw_fd(), 2) };
+    let mut block = unsafe{ MyBlock::new(1, |environment| *environment) };
+    let mut copy = unsafe{ core::ptr::read(&block) };
+    unsafe{ (crate::many::BLOCK_DESCRIPTOR_MANY.copy_helper)(&mut copy.0, &mut block.0) };
+    unsafe{ (crate::many::BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    let tolerant = unsafe{ TolerantBlock::new(|arg| arg) };
+    assert_eq!(unsafe{ tolerant.as_block().invoke((1,)) }, 1);
+    assert_eq!(unsafe{ tolerant.as_block().invoke((1,)) }, 0);
+    unsafe{ dup2(saved[0], 1) };
+    unsafe{ dup2(saved[1], 2) };
+    let mut output = String::new();
+    captured.rewind().unwrap();
+    captured.read_to_string(&mut output).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(output, "");
+}
@@ -0,0 +1,374 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Dynamic invocation of foreign blocks, driven by their signature.
+
+Blocks compiled by clang embed an ObjC type encoding of their signature in the descriptor.  This module parses it,
+and calls the block through libffi with arguments whose types are only known at runtime.  This is intended for
+tooling, such as bridging generators, test harnesses and scripting layers.
+
+```ignore
+use blocksr::dyncall::{DynamicBlock, Value};
+let block = unsafe{ DynamicBlock::from_ptr(foreign_block_ptr) }.unwrap();
+assert_eq!(block.signature().encoding(), "d20@?0i8d12");
+let r = unsafe{ block.invoke(&[Value::I32(2), Value::F64(0.5)]) }.unwrap();
+```
+
+Structs, unions and arrays passed by value are not supported.
+*/
+
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter};
+use libffi::middle::{Arg, Cif, CodePtr, Ret, Type};
+use crate::Block;
+
+///The type of an argument or return value, as decoded from a type encoding.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Kind {
+    Void,
+    Bool,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+    ///Any pointer, including objects, classes, selectors, blocks and C strings.
+    Pointer,
+}
+
+impl Kind {
+    fn ffi_type(self) -> Type {
+        match self {
+            Kind::Void => Type::void(),
+            Kind::Bool | Kind::U8 => Type::u8(),
+            Kind::I8 => Type::i8(),
+            Kind::I16 => Type::i16(),
+            Kind::U16 => Type::u16(),
+            Kind::I32 => Type::i32(),
+            Kind::U32 => Type::u32(),
+            Kind::I64 => Type::i64(),
+            Kind::U64 => Type::u64(),
+            Kind::F32 => Type::f32(),
+            Kind::F64 => Type::f64(),
+            Kind::Pointer => Type::pointer(),
+        }
+    }
+}
+
+///A runtime-typed argument or return value.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Value {
+    Void,
+    Bool(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Pointer(*mut c_void),
+}
+
+impl Value {
+    ///The kind of this value.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Value::Void => Kind::Void,
+            Value::Bool(_) => Kind::Bool,
+            Value::I8(_) => Kind::I8,
+            Value::U8(_) => Kind::U8,
+            Value::I16(_) => Kind::I16,
+            Value::U16(_) => Kind::U16,
+            Value::I32(_) => Kind::I32,
+            Value::U32(_) => Kind::U32,
+            Value::I64(_) => Kind::I64,
+            Value::U64(_) => Kind::U64,
+            Value::F32(_) => Kind::F32,
+            Value::F64(_) => Kind::F64,
+            Value::Pointer(_) => Kind::Pointer,
+        }
+    }
+    fn arg(&self) -> Arg<'_> {
+        match self {
+            Value::Void => panic!("void is not an argument"),
+            Value::Bool(b) => Arg::new(b),
+            Value::I8(v) => Arg::new(v),
+            Value::U8(v) => Arg::new(v),
+            Value::I16(v) => Arg::new(v),
+            Value::U16(v) => Arg::new(v),
+            Value::I32(v) => Arg::new(v),
+            Value::U32(v) => Arg::new(v),
+            Value::I64(v) => Arg::new(v),
+            Value::U64(v) => Arg::new(v),
+            Value::F32(v) => Arg::new(v),
+            Value::F64(v) => Arg::new(v),
+            Value::Pointer(v) => Arg::new(v),
+        }
+    }
+}
+
+///Errors from dynamic invocation.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum DynCallError {
+    ///The block's descriptor does not contain a signature.
+    NoSignature,
+    ///The signature contains a type encoding we don't support, such as a struct passed by value.
+    UnsupportedEncoding(String),
+    ///The wrong number of arguments was passed.
+    ArgumentCount{expected: usize, found: usize},
+    ///The argument at `index` does not match the signature.
+    ArgumentKind{index: usize, expected: Kind, found: Kind},
+}
+
+impl Display for DynCallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DynCallError::NoSignature => write!(f, "block has no signature"),
+            DynCallError::UnsupportedEncoding(e) => write!(f, "unsupported type encoding {:?}", e),
+            DynCallError::ArgumentCount{expected, found} => write!(f, "expected {} arguments, found {}", expected, found),
+            DynCallError::ArgumentKind{index, expected, found} => write!(f, "argument {} should be {:?}, found {:?}", index, expected, found),
+        }
+    }
+}
+impl std::error::Error for DynCallError {}
+
+///A parsed block signature.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Signature {
+    encoding: String,
+    ret: Kind,
+    ///Excludes the implicit block argument
+    args: Vec<Kind>,
+}
+
+impl Signature {
+    /**
+    Parses a block type encoding, such as `v16@?0@8`.
+
+    The first argument must be the block itself (`@?`), which is implicit in [Signature::argument_kinds].  Void
+    arguments are rejected, as are encodings we don't support.
+    */
+    pub fn parse(encoding: &str) -> Result<Signature, DynCallError> {
+        let unsupported = || DynCallError::UnsupportedEncoding(encoding.to_owned());
+        let mut kinds = Vec::new();
+        let mut rest = encoding.as_bytes();
+        while !rest.is_empty() {
+            let (kind, after) = parse_type(rest).ok_or_else(unsupported)?;
+            kinds.push(kind);
+            //skip the frame offset
+            rest = after;
+            while let Some((b'0'..=b'9' | b'-', after)) = rest.split_first() {
+                rest = after;
+            }
+        }
+        if kinds.len() < 2 || kinds[1] != Kind::Pointer {
+            return Err(unsupported());
+        }
+        let ret = kinds.remove(0);
+        kinds.remove(0);
+        //only a return value may be void
+        if kinds.contains(&Kind::Void) {
+            return Err(unsupported());
+        }
+        Ok(Signature{encoding: encoding.to_owned(), ret, args: kinds})
+    }
+    ///The encoding this signature was parsed from.
+    pub fn encoding(&self) -> &str {
+        &self.encoding
+    }
+    pub fn return_kind(&self) -> Kind {
+        self.ret
+    }
+    ///Argument kinds, not including the implicit block argument.
+    pub fn argument_kinds(&self) -> &[Kind] {
+        &self.args
+    }
+}
+
+///Parses one type from the front of `encoding`, returning the remainder.
+fn parse_type(encoding: &[u8]) -> Option<(Kind, &[u8])> {
+    let (first, rest) = encoding.split_first()?;
+    let kind = match first {
+        //qualifiers: const, in, inout, out, bycopy, byref, oneway
+        b'r' | b'n' | b'N' | b'o' | b'O' | b'R' | b'V' => return parse_type(rest),
+        b'v' => Kind::Void,
+        b'B' => Kind::Bool,
+        b'c' => Kind::I8,
+        b'C' => Kind::U8,
+        b's' => Kind::I16,
+        b'S' => Kind::U16,
+        //in type encodings, long is always 32-bit
+        b'i' | b'l' => Kind::I32,
+        b'I' | b'L' => Kind::U32,
+        b'q' => Kind::I64,
+        b'Q' => Kind::U64,
+        b'f' => Kind::F32,
+        b'd' => Kind::F64,
+        b'*' | b'#' | b':' => Kind::Pointer,
+        b'@' => {
+            let mut rest = rest;
+            match rest.first() {
+                //block
+                Some(b'?') => rest = &rest[1..],
+                //class name
+                Some(b'"') => {
+                    let end = rest[1..].iter().position(|b| *b == b'"')?;
+                    rest = &rest[end + 2..];
+                }
+                _ => {}
+            }
+            return Some((Kind::Pointer, rest));
+        }
+        b'^' => {
+            //skip the pointee
+            return Some((Kind::Pointer, skip_pointee(rest)?));
+        }
+        _ => return None,
+    };
+    Some((kind, rest))
+}
+
+///Skips a pointee type, which may be an aggregate we can't pass by value.
+fn skip_pointee(encoding: &[u8]) -> Option<&[u8]> {
+    let (open, close) = match encoding.first()? {
+        b'{' => (b'{', b'}'),
+        b'(' => (b'(', b')'),
+        b'[' => (b'[', b']'),
+        b'?' => return Some(&encoding[1..]),
+        _ => return parse_type(encoding).map(|(_, rest)| rest),
+    };
+    let mut depth = 0;
+    for (i, b) in encoding.iter().enumerate() {
+        if *b == open {
+            depth += 1;
+        }
+        else if *b == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(&encoding[i + 1..]);
+            }
+        }
+    }
+    None
+}
+
+/**
+A foreign block whose signature is known at runtime.
+*/
+#[derive(Debug)]
+pub struct DynamicBlock {
+    block: *const Block<(),()>,
+    signature: Signature,
+}
+
+impl DynamicBlock {
+    /**
+    Reads the signature of the block at `ptr`.
+
+    # Safety
+    `ptr` must point to a valid block, which must remain valid while the result is in use.
+    */
+    pub unsafe fn from_ptr(ptr: *const c_void) -> Result<DynamicBlock, DynCallError> {
+        let block = ptr as *const Block<(),()>;
+        let signature = (*block).signature().ok_or(DynCallError::NoSignature)?;
+        let encoding = signature.to_str().map_err(|_| DynCallError::UnsupportedEncoding(String::from_utf8_lossy(signature.to_bytes()).into_owned()))?;
+        Ok(DynamicBlock{block, signature: Signature::parse(encoding)?})
+    }
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    /**
+    Invokes the block with runtime-typed arguments.
+
+    # Safety
+    You must verify that
+    * The block's signature is accurate
+    * Pointer arguments are valid for the block
+    * Invoking the block is consistent with its kind; for example a once block may be invoked at most once.
+    */
+    pub unsafe fn invoke(&self, args: &[Value]) -> Result<Value, DynCallError> {
+        let kinds = &self.signature.args;
+        if args.len() != kinds.len() {
+            return Err(DynCallError::ArgumentCount{expected: kinds.len(), found: args.len()});
+        }
+        for (index, (arg, kind)) in args.iter().zip(kinds).enumerate() {
+            if arg.kind() != *kind {
+                return Err(DynCallError::ArgumentKind{index, expected: *kind, found: arg.kind()});
+            }
+        }
+        let types: Vec<Type> = std::iter::once(Type::pointer()).chain(kinds.iter().map(|k| k.ffi_type())).collect();
+        let cif = Cif::new(types, self.signature.ret.ffi_type());
+        let block_arg = self.block;
+        let ffi_args: Vec<Arg> = std::iter::once(Arg::new(&block_arg)).chain(args.iter().map(|a| a.arg())).collect();
+        let code = CodePtr::from_ptr((*self.block).invoke_ptr());
+        //libffi widens small integer returns to a full register
+        let mut ret = [0u64; 2];
+        cif.call_return_into(code, &ffi_args, Ret::new(&mut ret));
+        let word = ret[0];
+        let bytes = &ret as *const [u64; 2] as *const u8;
+        Ok(match self.signature.ret {
+            Kind::Void => Value::Void,
+            Kind::Bool => Value::Bool(word as u8 != 0),
+            Kind::I8 => Value::I8(word as i8),
+            Kind::U8 => Value::U8(word as u8),
+            Kind::I16 => Value::I16(word as i16),
+            Kind::U16 => Value::U16(word as u16),
+            Kind::I32 => Value::I32(word as i32),
+            Kind::U32 => Value::U32(word as u32),
+            Kind::I64 => Value::I64(word as i64),
+            Kind::U64 => Value::U64(word),
+            Kind::F32 => Value::F32(*(bytes as *const f32)),
+            Kind::F64 => Value::F64(*(bytes as *const f64)),
+            Kind::Pointer => Value::Pointer(word as usize as *mut c_void),
+        })
+    }
+}
+
+#[test] fn parse_signatures() {
+    let s = Signature::parse("v16@?0@8").unwrap();
+    assert_eq!(s.return_kind(), Kind::Void);
+    assert_eq!(s.argument_kinds(), &[Kind::Pointer]);
+    let s = Signature::parse("B32@?0@\"NSData\"8^{CGRect={CGPoint=dd}{CGSize=dd}}16r*24").unwrap();
+    assert_eq!(s.return_kind(), Kind::Bool);
+    assert_eq!(s.argument_kinds(), &[Kind::Pointer, Kind::Pointer, Kind::Pointer]);
+    assert!(matches!(Signature::parse("v40@?0{CGSize=dd}8"), Err(DynCallError::UnsupportedEncoding(_))));
+    assert!(matches!(Signature::parse("v@?v"), Err(DynCallError::UnsupportedEncoding(_))));
+}
+
+#[test] fn invoke_dynamic() {
+    use crate::raw::{RawBlock, RawBlockBuilder};
+    #[repr(C)]
+    struct Descriptor {
+        reserved: std::os::raw::c_ulong,
+        size: std::os::raw::c_ulong,
+        signature: *const std::os::raw::c_char,
+    }
+    extern "C" fn invoke(_block: *mut RawBlock, a: i32, b: f64, c: u8) -> f64 {
+        a as f64 * b + c as f64
+    }
+    let descriptor = Descriptor {
+        reserved: 0,
+        size: std::mem::size_of::<RawBlock>() as std::os::raw::c_ulong,
+        signature: c"d21@?0i8d12C20".as_ptr(),
+    };
+    let raw = unsafe {
+        RawBlockBuilder::new()
+            .flags(crate::raw::BLOCK_HAS_SIGNATURE)
+            .descriptor(&descriptor as *const Descriptor as *const c_void)
+            .invoke(invoke as *const c_void)
+            .build()
+    };
+    let block = unsafe{ DynamicBlock::from_ptr(&raw as *const RawBlock as *const c_void) }.unwrap();
+    assert_eq!(block.signature().argument_kinds(), &[Kind::I32, Kind::F64, Kind::U8]);
+    let r = unsafe{ block.invoke(&[Value::I32(2), Value::F64(0.5), Value::U8(3)]) }.unwrap();
+    assert_eq!(r, Value::F64(4.0));
+    let e = unsafe{ block.invoke(&[Value::I32(2)]) };
+    assert_eq!(e, Err(DynCallError::ArgumentCount{expected: 3, found: 1}));
+}
@@ -0,0 +1,306 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! ObjC type encodings of block signatures.
+
+A block type declared with the macros implements [Signature] when its arguments and return type implement [Encode],
+so bindings that build an `NSMethodSignature` or `NSInvocation`, or check a method with the `verify` feature, can use
+the encoding the block's callers expect instead of writing it out by hand:
+
+```
+use blocksr::{once_escaping, Signature};
+once_escaping!(EnumerationBlock (object: *const core::ffi::c_void, index: usize, stop: *mut bool) -> ());
+# #[cfg(target_pointer_width = "64")]
+assert_eq!(EnumerationBlock::SIGNATURE, c"v@?^vQ^B");
+```
+
+The encoding is the return type, the block itself as `@?`, then the arguments, the same as the signature clang
+records for a block, but without offsets.  The block's descriptor records it too, so `signature()` on an instance, and
+the runtime, see the same encoding.  A Rust type doesn't say whether a pointer is to an ObjC object, so objects
+passed as `*const c_void` are encoded as `^v`, not `@`.  Variadic blocks, blocks that don't return, and blocks
+with reference arguments have no signature.
+*/
+
+use core::ffi::{c_void, CStr};
+
+//Longer than any signature of primitives and pointers, with the macros' 12 arguments
+const CAPACITY: usize = 256;
+
+/**
+An ObjC type encoding, built at compile time.
+*/
+#[derive(Clone,Copy)]
+pub struct Encoding {
+    //nul-terminated
+    bytes: [u8; CAPACITY],
+    len: usize,
+}
+
+impl Encoding {
+    /**
+    Creates an encoding, such as `"{CGSize=dd}"`.
+
+    # Panics
+    If `encoding` is longer than 255 bytes, or contains a nul.  In a `const`, this fails to compile.
+    */
+    pub const fn new(encoding: &str) -> Self {
+        Encoding{bytes: [0; CAPACITY], len: 0}.push_str(encoding)
+    }
+    const fn push_str(mut self, encoding: &str) -> Self {
+        let encoding = encoding.as_bytes();
+        let mut i = 0;
+        while i < encoding.len() {
+            assert!(encoding[i] != 0, "type encodings can't contain a nul");
+            assert!(self.len + 1 < CAPACITY, "type encoding is too long");
+            self.bytes[self.len] = encoding[i];
+            self.len += 1;
+            i += 1;
+        }
+        self
+    }
+    ///Appends `other`.
+    pub const fn push(mut self, other: &Encoding) -> Self {
+        let mut i = 0;
+        while i < other.len {
+            assert!(self.len + 1 < CAPACITY, "type encoding is too long");
+            self.bytes[self.len] = other.bytes[i];
+            self.len += 1;
+            i += 1;
+        }
+        self
+    }
+    ///The encoding, as bytes without the nul.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl core::fmt::Debug for Encoding {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Encoding({:?})", core::str::from_utf8(self.as_bytes()).unwrap_or("?"))
+    }
+}
+
+impl PartialEq for Encoding {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+impl Eq for Encoding {}
+
+/**
+Types with an ObjC type encoding, for block arguments and return values.
+
+# Safety
+`ENCODING` must be the encoding of a C type with the same ABI as `Self`.
+*/
+pub unsafe trait Encode {
+    ///The encoding of `Self`.
+    const ENCODING: Encoding;
+}
+
+/**
+Types that can be pointed to, with the ObjC type encoding of a pointer to them.
+
+# Safety
+`ENCODING_REF` must be the encoding of a pointer to a C type with the same layout as `Self`.
+*/
+pub unsafe trait RefEncode {
+    ///The encoding of a pointer to `Self`.
+    const ENCODING_REF: Encoding;
+}
+
+macro_rules! encode {
+    ($($t:ty => $encoding:literal),* $(,)?) => {
+        $(
+            unsafe impl Encode for $t {
+                const ENCODING: Encoding = Encoding::new($encoding);
+            }
+            unsafe impl RefEncode for $t {
+                const ENCODING_REF: Encoding = Encoding::new("^").push(&<$t as Encode>::ENCODING);
+            }
+        )*
+    };
+}
+encode!(i16 => "s", u16 => "S", i32 => "i", u32 => "I", i64 => "q", u64 => "Q", f32 => "f", f64 => "d", bool => "B");
+#[cfg(target_pointer_width = "64")]
+encode!(isize => "q", usize => "Q");
+#[cfg(not(target_pointer_width = "64"))]
+encode!(isize => "l", usize => "L");
+
+//char * is a string, whatever its sign
+unsafe impl Encode for i8 {
+    const ENCODING: Encoding = Encoding::new("c");
+}
+unsafe impl RefEncode for i8 {
+    const ENCODING_REF: Encoding = Encoding::new("*");
+}
+unsafe impl Encode for u8 {
+    const ENCODING: Encoding = Encoding::new("C");
+}
+unsafe impl RefEncode for u8 {
+    const ENCODING_REF: Encoding = Encoding::new("*");
+}
+
+unsafe impl Encode for () {
+    const ENCODING: Encoding = Encoding::new("v");
+}
+unsafe impl RefEncode for c_void {
+    const ENCODING_REF: Encoding = Encoding::new("^v");
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for *const T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+unsafe impl<T: RefEncode + ?Sized> Encode for *mut T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+unsafe impl<T: RefEncode + ?Sized> Encode for &T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+unsafe impl<T: RefEncode + ?Sized> RefEncode for *const T {
+    const ENCODING_REF: Encoding = Encoding::new("^").push(&T::ENCODING_REF);
+}
+unsafe impl<T: RefEncode + ?Sized> RefEncode for *mut T {
+    const ENCODING_REF: Encoding = Encoding::new("^").push(&T::ENCODING_REF);
+}
+unsafe impl<Args,R> RefEncode for crate::Block<Args,R> {
+    const ENCODING_REF: Encoding = Encoding::new("@?");
+}
+
+/**
+Function pointer types with an ObjC type encoding: the return type, a block as `@?`, then the arguments.
+
+This trait is implemented for `fn` pointers of 0 to 12 arguments.  It describes the signature of a block that takes
+those arguments, not the function itself.
+*/
+pub trait EncodeSignature {
+    ///The encoding of the signature.
+    const ENCODING: Encoding;
+}
+
+macro_rules! encode_signature {
+    ($($A:ident),*) => {
+        impl<R: Encode, $($A: Encode),*> EncodeSignature for fn($($A),*) -> R {
+            const ENCODING: Encoding = R::ENCODING.push(&Encoding::new("@?"))$(.push(&<$A as Encode>::ENCODING))*;
+        }
+    }
+}
+encode_signature!();
+encode_signature!(A);
+encode_signature!(A, B);
+encode_signature!(A, B, C);
+encode_signature!(A, B, C, D);
+encode_signature!(A, B, C, D, E);
+encode_signature!(A, B, C, D, E, F);
+encode_signature!(A, B, C, D, E, F, G);
+encode_signature!(A, B, C, D, E, F, G, H);
+encode_signature!(A, B, C, D, E, F, G, H, I);
+encode_signature!(A, B, C, D, E, F, G, H, I, J);
+encode_signature!(A, B, C, D, E, F, G, H, I, J, K);
+encode_signature!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/**
+The signature of a block type declared with the macros, as a `fn` pointer type.
+*/
+#[doc(hidden)]
+pub trait BlockType {
+    //a fn pointer, since arguments may have elided lifetimes
+    type Fn;
+}
+
+/**
+Declares a generated block type's [BlockType].  Used where the type is declared.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __block_type {
+    ($blockname:ident $(<$g:ident>)?; ($($A:ty),*) -> $R:ty) => {
+        impl$(<$g>)? blocksr::hidden::BlockType for $blockname$(<$g>)? {
+            type Fn = fn($($A),*) -> $R;
+        }
+    };
+}
+
+struct SignatureOf<F>(core::marker::PhantomData<F>);
+
+impl<F: EncodeSignature> SignatureOf<F> {
+    const BYTES: &'static [u8; CAPACITY] = &F::ENCODING.bytes;
+    const CSTR: &'static CStr = match CStr::from_bytes_until_nul(Self::BYTES) {
+        Ok(signature) => signature,
+        Err(_) => panic!("type encodings are nul-terminated"),
+    };
+}
+
+/**
+The ObjC type encoding of a block type's signature.  See [the module docs](self).
+*/
+pub trait Signature {
+    ///The encoding, such as `v@?Q^B` for a block taking an `NSUInteger` and a `BOOL *`.
+    const SIGNATURE: &'static CStr;
+}
+
+impl<T: BlockType> Signature for T where T::Fn: EncodeSignature {
+    const SIGNATURE: &'static CStr = SignatureOf::<T::Fn>::CSTR;
+}
+
+/**
+Finds the signature of a block type, which may not have one.
+
+The macros read `SignatureProbe::<Self>::SIGNATURE` with [NoSignature] in scope.  A type with a [Signature] gets the
+inherent `SIGNATURE`, which takes precedence over the trait's `None`, so its descriptors record the same encoding.
+*/
+#[doc(hidden)]
+pub struct SignatureProbe<T>(core::marker::PhantomData<T>);
+
+impl<T: Signature> SignatureProbe<T> {
+    pub const SIGNATURE: Option<&'static CStr> = Some(T::SIGNATURE);
+}
+
+///The signature of a block type without a [Signature].  See [SignatureProbe].
+#[doc(hidden)]
+pub trait NoSignature {
+    const SIGNATURE: Option<&'static CStr> = None;
+}
+impl<T> NoSignature for SignatureProbe<T> {}
+
+#[allow(clippy::unused_unit)]
+#[test] fn signatures() {
+    crate::once_escaping!(Enumeration (object: *const c_void, index: u32, stop: *mut bool) -> ());
+    assert_eq!(Enumeration::SIGNATURE, c"v@?^vI^B");
+    crate::many_escaping_reentrant!(Strings (environment: &(), name: *const i8, names: *const *const i8) -> f64);
+    assert_eq!(Strings::SIGNATURE, c"d@?*^*");
+    crate::many_escaping_nonreentrant!(Nested (environment: &mut (), handler: *const crate::Block<(),()>) -> i8);
+    assert_eq!(Nested::SIGNATURE, c"c@?@?");
+    crate::once_noescape!(NoEscape (flag: bool) -> u16);
+    assert_eq!(NoEscape::<()>::SIGNATURE, c"S@?B");
+    assert_eq!(<fn(u8, i64) -> f32 as EncodeSignature>::ENCODING, Encoding::new("f@?Cq"));
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn signatures_recorded() {
+    use crate::BlockFlags;
+    //blocks advertise the same encoding to the runtime
+    crate::once_escaping!(Enumeration (object: *const c_void, index: u32, stop: *mut bool) -> ());
+    let once = unsafe{ Enumeration::new(|_, _, _| ()) };
+    assert!(Enumeration::FLAGS.contains(BlockFlags::HAS_SIGNATURE));
+    assert_eq!(once.signature(), Some(Enumeration::SIGNATURE));
+    crate::many_escaping_reentrant!(Strings (environment: &Vec<u8>, name: *const i8) -> f64);
+    let many = unsafe{ Strings::new(vec![1], |_, _| 0.0) };
+    assert!(many.flags().contains(BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(many.signature(), Some(Strings::SIGNATURE));
+    crate::many_escaping_nonreentrant!(Trivial (environment: &mut (), handler: *const crate::Block<(),()>) -> i8);
+    let trivial = unsafe{ Trivial::new((), |_, _| 0) };
+    assert!(!trivial.flags().contains(BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(trivial.signature(), Some(Trivial::SIGNATURE));
+    let mut cell = core::mem::MaybeUninit::uninit();
+    let cell = unsafe{ core::pin::Pin::new_unchecked(&mut cell) };
+    crate::once_noescape!(NoEscape (flag: bool) -> u16);
+    let noescape = unsafe{ NoEscape::new(cell, |_| 0) };
+    assert_eq!(noescape.signature(), Some(NoEscape::<()>::SIGNATURE));
+    //a type without a signature records none
+    #[repr(C)]
+    struct Opaque(u8);
+    crate::once_escaping!(Unencoded (value: Opaque) -> u8);
+    let unencoded = unsafe{ Unencoded::new(|value| value.0) };
+    assert!(!Unencoded::FLAGS.contains(BlockFlags::HAS_SIGNATURE));
+    assert_eq!(unencoded.signature(), None);
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*
+Catches ObjC exceptions for blocksr::exception, compiled when the `objc-exceptions` feature is on.
+
+Rust can't catch ObjC exceptions itself, so `f` runs inside an @try here.  Deliberately free of headers, so it builds
+against either Apple's runtime or GNUstep's.
+*/
+typedef struct objc_object *id;
+id objc_retain(id object);
+
+id blocksr_try(void (*f)(void *), void *context) {
+    @try {
+        f(context);
+        return (id) 0;
+    }
+    @catch (id exception) {
+        return objc_retain(exception);
+    }
+}
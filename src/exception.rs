@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Catching ObjC exceptions, with the `objc-exceptions` feature.
+
+A closure passed to a block macro often calls back into ObjC.  If that throws an `NSException`, it unwinds through the
+block's thunk and into whatever objc code called the block, which is rarely prepared for it.  Wrap calls that may
+throw with [catch_exception], to get the exception as an error, or [abort_on_exception], to stop there:
+
+```ignore
+use blocksr::once_escaping;
+use blocksr::exception::catch_exception;
+once_escaping!(MyBlock (array: *const c_void) -> ());
+let f = unsafe{ MyBlock::new(|array| {
+    match catch_exception(|| {
+        //call into objc, e.g. an out-of-bounds objectAtIndex:
+    }) {
+        Ok(()) => {}
+        Err(exception) => {
+            //report it
+        }
+    }
+})};
+```
+
+Rust panics in the closure are not exceptions, and unwind as usual.
+*/
+
+use core::ffi::c_void;
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn objc_release(object: *const c_void);
+}
+extern "C-unwind" {
+    //see src/exception.m
+    fn blocksr_try(f: extern "C-unwind" fn(*mut c_void), context: *mut c_void) -> *const c_void;
+}
+
+/**
+An ObjC exception, typically an `NSException`, caught by [catch_exception].  It is released on drop.
+*/
+#[derive(Debug)]
+pub struct Exception(*const c_void);
+
+impl Exception {
+    ///The exception object.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0
+    }
+}
+
+impl Drop for Exception {
+    fn drop(&mut self) {
+        unsafe{ objc_release(self.0) }
+    }
+}
+
+impl std::fmt::Display for Exception {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ObjC exception {:p}", self.0)
+    }
+}
+
+impl std::error::Error for Exception {}
+
+/**
+Calls `f`, returning its result, or the ObjC exception it threw.
+*/
+pub fn catch_exception<R, F: FnOnce() -> R>(f: F) -> Result<R, Exception> {
+    extern "C-unwind" fn call<R, F: FnOnce() -> R>(context: *mut c_void) {
+        let (f, result) = unsafe{ &mut *(context as *mut (Option<F>, Option<R>)) };
+        *result = Some((f.take().unwrap())());
+    }
+    let mut context: (Option<F>, Option<R>) = (Some(f), None);
+    let exception = unsafe{ blocksr_try(call::<R,F>, &mut context as *mut _ as *mut c_void) };
+    if exception.is_null() {
+        Ok(context.1.expect("f returned"))
+    }
+    else {
+        Err(Exception(exception))
+    }
+}
+
+/**
+Calls `f`, aborting the process if it throws an ObjC exception.
+
+This is the policy to use where an exception must not reach the caller, such as in a block called by code that isn't
+exception-safe, and there's no sensible way to recover.
+*/
+pub fn abort_on_exception<R, F: FnOnce() -> R>(f: F) -> R {
+    catch_exception(f).unwrap_or_else(|exception| {
+        crate::diagnostic::abort(format_args!("{} was thrown in a block; aborting", exception))
+    })
+}
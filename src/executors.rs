@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Executors for testing continuations.
+
+[Continuation](crate::continuation::Continuation) does not depend on any particular async runtime.  This module
+runs a future to completion on a minimal built-in executor, on Tokio, or on async-std, so bindings can be tested
+against each of them.
+
+```
+use blocksr::continuation::continuation;
+use blocksr::executors::{block_on, block_on_tokio, block_on_async_std};
+for run in [block_on::<blocksr::continuation::Continuation<u8>>, block_on_tokio, block_on_async_std] {
+    let (continuation, completer) = continuation();
+    std::thread::spawn(move || completer.complete(3));
+    assert_eq!(run(continuation), 3);
+}
+```
+*/
+
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+struct ThreadWaker(std::thread::Thread);
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+///Runs `future` to completion on the current thread, parking while it is pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(value) => return value,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+///Runs `future` to completion on a new multi-threaded Tokio runtime.
+#[cfg(feature = "test-executors")]
+pub fn block_on_tokio<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread().build().unwrap().block_on(future)
+}
+
+///Runs `future` to completion on async-std.
+#[cfg(feature = "test-executors")]
+pub fn block_on_async_std<F: Future>(future: F) -> F::Output {
+    async_std::task::block_on(future)
+}
+
+#[cfg(all(test, feature = "test-executors"))]
+mod tests {
+    use std::time::Duration;
+    use crate::continuation::{continuation, Continuation};
+    use super::*;
+
+    fn executors() -> [fn(Continuation<u8>) -> u8; 3] {
+        [block_on, block_on_tokio, block_on_async_std]
+    }
+
+    #[test] fn complete_before_poll() {
+        for run in executors() {
+            let (continuation, completer) = continuation();
+            completer.complete(1);
+            assert_eq!(run(continuation), 1);
+        }
+    }
+
+    #[test] fn cross_thread_wakeup() {
+        for run in executors() {
+            let (continuation, completer) = continuation();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(10));
+                completer.complete(2)
+            });
+            assert_eq!(run(continuation), 2);
+        }
+    }
+
+    #[test] fn drop_before_complete() {
+        for _run in executors() {
+            let (continuation, completer) = continuation::<u8>();
+            std::mem::drop(continuation);
+            //completing an abandoned continuation is harmless
+            assert!(!completer.complete(3));
+        }
+    }
+
+    #[test] fn completer_dropped() {
+        for run in executors() {
+            let (continuation, completer) = continuation::<u8>();
+            std::thread::spawn(move || std::mem::drop(completer));
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(continuation)));
+            assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Blocks on the heap.
+
+A block literal created by the macros lives on the stack.  ObjC APIs that keep a block around call `Block_copy`,
+which moves it to the heap and manages it with a refcount.  [HeapBlock] is an owned reference to such a copy.
+
+```no_run
+use blocksr::{many_escaping_reentrant, HeapBlock};
+many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, arg: u8) -> ());
+let stack = unsafe{ MyBlock::new(Vec::<u8>::new(), |_environment, _arg| { }) };
+let heap = unsafe{ HeapBlock::copy(&stack) };
+//hand clones of `heap` to objc...
+let environment: Vec<u8> = unsafe{ heap.try_into_environment() }.unwrap_or_else(|_| panic!("still shared"));
+```
+*/
+
+use core::ffi::c_void;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicI32, Ordering};
+use crate::many::{BlockLiteralManyEscape, ManyLiteral};
+
+#[cfg_attr(feature = "gnustep", link(name = "objc"))]
+extern "C" {
+    fn _Block_copy(block: *const c_void) -> *mut c_void;
+    fn _Block_release(block: *const c_void);
+}
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn objc_autorelease(object: *const c_void) -> *const c_void;
+}
+
+//From Block_private.h.  The refcount is stored in the flags word, in units of 2.
+#[cfg(not(feature = "gnustep"))]
+const BLOCK_REFCOUNT_MASK: i32 = 0xfffe;
+
+/**
+An owned reference to a block that was copied to the heap.
+
+Cloning calls `Block_copy`, which increments the refcount.  Dropping calls `Block_release`.
+
+# Returning blocks to ObjC
+
+A method implemented in Rust may have to return a block, such as a factory method or a protocol method that hands
+back a handler.  Under ObjC conventions, unless the method is in the `copy`/`new` family, the caller doesn't own the
+result: it receives an autoreleased heap block, and calls `Block_copy` if it keeps it.
+
+A stack block can't be returned, since it dies with the frame.  Copy it to the heap, and hand ownership to the
+autorelease pool with [HeapBlock::autorelease].
+
+```no_run
+use blocksr::{many_escaping_reentrant, HeapBlock};
+many_escaping_reentrant!(Handler (environment: &u8, arg: u8) -> u8);
+extern "C" fn make_handler() -> *const Handler {
+    let stack = unsafe{ Handler::new(2, |environment, arg| environment * arg) };
+    unsafe{ HeapBlock::copy(&stack) }.autorelease()
+}
+```
+
+For `copy`/`new` methods, where the caller owns the result, return [HeapBlock::into_raw] instead.
+*/
+#[derive(Debug)]
+pub struct HeapBlock<T>(NonNull<T>);
+
+unsafe impl<T: Send> Send for HeapBlock<T> {}
+
+impl<T> HeapBlock<T> {
+    /**
+    Copies `block` to the heap with `Block_copy`.
+
+    # Safety
+    You must verify that `block` is a block literal, such as a type declared by the macros.
+    */
+    pub unsafe fn copy(block: &T) -> Self {
+        let ptr = unsafe{ _Block_copy(block as *const T as *const c_void) } as *mut T;
+        HeapBlock(NonNull::new(ptr).expect("Block_copy returned null"))
+    }
+    /**
+    Takes ownership of a heap block, without changing its refcount.
+
+    # Safety
+    You must verify that `ptr` is a heap block of type `T` and that you own a reference to it.
+    */
+    pub unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        HeapBlock(ptr)
+    }
+    ///Gives up ownership without changing the refcount.  Use [HeapBlock::from_raw] to recover it.
+    pub fn into_raw(self) -> NonNull<T> {
+        let ptr = self.0;
+        core::mem::forget(self);
+        ptr
+    }
+    /**
+    Gives up ownership to the current autorelease pool, and returns the block, for returning to objc.
+
+    The block stays valid until the pool drains.  This matches the convention for methods returning a block outside
+    the `copy`/`new` family; see [returning blocks to ObjC](HeapBlock#returning-blocks-to-objc).  Call it
+    with an autorelease pool in place, as objc does around the methods it calls.
+    */
+    pub fn autorelease(self) -> *const T {
+        let ptr = self.into_raw();
+        unsafe{ objc_autorelease(ptr.as_ptr() as *const c_void) as *const T }
+    }
+    ///Returns a pointer to the block, for passing into objc.
+    pub fn as_ptr(&self) -> *const T {
+        self.0.as_ptr()
+    }
+    //reads the int at `index` after the isa; the runtime updates these atomically
+    fn header_word(&self, index: usize) -> i32 {
+        let word = unsafe{ (self.0.as_ptr() as *const u8).add(core::mem::size_of::<*const c_void>() + index * core::mem::size_of::<i32>()) } as *const AtomicI32;
+        unsafe{ &*word }.load(Ordering::Acquire)
+    }
+    ///Returns true if this is the only reference to the block.
+    #[cfg(not(feature = "gnustep"))]
+    pub fn is_unique(&self) -> bool {
+        self.header_word(0) & BLOCK_REFCOUNT_MASK == 2
+    }
+    ///Returns true if this is the only reference to the block.
+    #[cfg(feature = "gnustep")]
+    pub fn is_unique(&self) -> bool {
+        //libobjc2 keeps the refcount in the reserved word, starting from 1
+        self.header_word(1) == 1
+    }
+}
+
+impl<T: ManyLiteral> HeapBlock<T> {
+    /**
+    Recovers the environment, if this is the only reference to the block.
+
+    Like [alloc::sync::Arc::try_unwrap], this returns `Err(self)` if other references exist.  On success,
+    the closure is dropped and the block is released without disposing the environment.
+
+    Blocks whose constructor wraps the environment, such as `new_indirect`, `new_with_dispose` or `new_shared`, always
+    return `Err(self)`, since their stored environment isn't an `E`.
+
+    # Safety
+    You must verify that `E` is the environment type the block was created with, by `new`.
+    */
+    pub unsafe fn try_into_environment<E>(self) -> Result<E, Self> {
+        let literal = self.0.as_ptr() as *mut BlockLiteralManyEscape;
+        let take = match unsafe{ (*literal).take_environment } {
+            Some(take) if self.is_unique() => take,
+            _ => return Err(self),
+        };
+        let mut environment = core::mem::MaybeUninit::<E>::uninit();
+        unsafe{ take(literal, environment.as_mut_ptr() as *mut c_void) };
+        unsafe {
+            (*literal).payload = core::ptr::null_mut();
+            (*literal).dispose = crate::many::dispose_taken;
+            (*literal).take_environment = None;
+        }
+        //release with the no-op dispose
+        core::mem::drop(self);
+        Ok(unsafe{ environment.assume_init() })
+    }
+}
+
+impl<T> Clone for HeapBlock<T> {
+    fn clone(&self) -> Self {
+        unsafe{ HeapBlock::copy(self.0.as_ref()) }
+    }
+}
+
+impl<T> Drop for HeapBlock<T> {
+    fn drop(&mut self) {
+        unsafe{ _Block_release(self.0.as_ptr() as *const c_void) }
+    }
+}
+
+impl<T> core::ops::Deref for HeapBlock<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe{ self.0.as_ref() }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn try_into_environment() {
+    crate::many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, arg: u8) -> u8);
+    let stack = unsafe{ MyBlock::new(vec![1,2,3], |environment, arg| environment[arg as usize]) };
+    let heap = unsafe{ HeapBlock::copy(&stack) };
+    let shared = heap.clone();
+    assert!(!heap.is_unique());
+    let heap = unsafe{ heap.try_into_environment::<Vec<u8>>() }.unwrap_err();
+    assert_eq!(unsafe{ heap.as_block().invoke((1,)) }, 2);
+    core::mem::drop(shared);
+    let environment: Vec<u8> = unsafe{ heap.try_into_environment() }.unwrap();
+    assert_eq!(environment, vec![1,2,3]);
+    //the stored environment is a Box<Vec<u8>>
+    let indirect = unsafe{ MyBlock::new_indirect(vec![4,5,6], |environment, arg| environment[arg as usize]) };
+    let heap = unsafe{ HeapBlock::copy(&indirect) };
+    assert!(heap.is_unique());
+    let heap = unsafe{ heap.try_into_environment::<Vec<u8>>() }.unwrap_err();
+    assert_eq!(unsafe{ heap.as_block().invoke((2,)) }, 6);
+}
@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Conversions to and from the [block2](https://crates.io/crates/block2) crate.
+
+Both crates describe the same ABI, so these are pointer casts with no runtime cost.
+
+```ignore
+use blocksr::once_escaping;
+once_escaping!(MyBlock (arg: u8) -> u8);
+let f = unsafe{ MyBlock::new(|arg| arg + 1) };
+let block2: &block2::Block<dyn Fn(u8) -> u8> = unsafe{ f.as_block().as_block2() };
+//and back
+let block: &blocksr::Block<(u8,),u8> = block2.into();
+```
+*/
+use block2::BlockFn;
+use crate::Block;
+
+impl<Args,R> Block<Args,R> {
+    ///Views this block as a [block2::Block].
+    ///
+    /// # Safety
+    /// [block2::Block::call] is safe and may be called any number of times, from any thread.  You must verify that
+    /// this is consistent with how the block was created; for example a once block must not be passed to code that
+    /// calls it twice.
+    pub unsafe fn as_block2<F>(&self) -> &block2::Block<F> where F: ?Sized + BlockFn<Args=Args,Output=R> {
+        &*(self as *const Self as *const block2::Block<F>)
+    }
+}
+
+impl<'a, F> From<&'a block2::Block<F>> for &'a Block<F::Args,F::Output> where F: ?Sized + BlockFn {
+    fn from(block: &'a block2::Block<F>) -> Self {
+        //Invoking a blocksr::Block is unsafe, so this is safe
+        unsafe{ &*(block as *const block2::Block<F> as *const Block<F::Args,F::Output>) }
+    }
+}
+
+impl<F> AsRef<Block<F::Args,F::Output>> for block2::Block<F> where F: ?Sized + BlockFn {
+    fn as_ref(&self) -> &Block<F::Args,F::Output> {
+        self.into()
+    }
+}
+
+#[test] fn round_trip() {
+    crate::once_escaping!(MyBlock (arg: u8) -> u8);
+    let f = unsafe{ MyBlock::new(|arg| arg + 1) };
+    let block2: &block2::Block<dyn Fn(u8) -> u8> = unsafe{ f.as_block().as_block2() };
+    let block: &Block<(u8,),u8> = block2.as_ref();
+    assert_eq!(block as *const _ as *const u8, &f as *const _ as *const u8);
+    let r = unsafe{ block.invoke((2,)) };
+    assert_eq!(r, 3);
+}
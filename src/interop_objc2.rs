@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Encodings for the [objc2](https://crates.io/crates/objc2) crate.
+
+With the `interop-objc2` feature, [Block] and every type the macros declare implement objc2's `RefEncode`, with the
+block encoding `@?`.  So references to them may be passed to `msg_send!` directly, and objc2's debug checks verify them
+against the method's encoding:
+
+```ignore
+use blocksr::once_escaping;
+use objc2::msg_send;
+once_escaping!(CompletionHandler (error: *const NSError) -> ());
+let handler = unsafe{ CompletionHandler::new(|error| { /* ... */ }) };
+let _: () = unsafe{ msg_send![session, finishTasksWithCompletionHandler: &handler] };
+```
+
+Together with the `interop-block2` conversions, this lets a codebase move between the two ecosystems a binding at a time.
+*/
+use objc2::encode::{Encoding, RefEncode};
+use crate::Block;
+
+//Safety: a block is an object whose pointer has the block encoding
+unsafe impl<Args,R> RefEncode for Block<Args,R> {
+    const ENCODING_REF: Encoding = Encoding::Block;
+}
+
+/**
+Implements `RefEncode` for a generated block type, with the `interop-objc2` feature.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __objc2_encode {
+    ($blockname:ident $(<$g:ident>)?) => {
+        //Safety: the type is a block literal
+        unsafe impl$(<$g>)? blocksr::hidden::objc2::encode::RefEncode for $blockname$(<$g>)? {
+            const ENCODING_REF: blocksr::hidden::objc2::encode::Encoding = blocksr::hidden::objc2::encode::Encoding::Block;
+        }
+    };
+}
+
+#[test] fn encodings() {
+    use objc2::encode::Encode;
+    crate::once_escaping!(MyBlock (arg: u8) -> u8);
+    assert_eq!(<&MyBlock>::ENCODING, Encoding::Block);
+    assert_eq!(<*const Block<(u8,),u8>>::ENCODING, Encoding::Block);
+    assert_eq!(Encoding::Block.to_string(), "@?");
+}
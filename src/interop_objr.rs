@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Typed ObjC arguments with [objr](https://crates.io/crates/objr) types, with the `objr` feature.
+
+A reference to an objr instance has the same ABI as the object pointer objc passes, so a block argument may be
+declared as one.  The closure then receives the typed reference, which it can't keep past the invocation:
+
+```ignore
+use blocksr::once_escaping;
+use objr::bindings::*;
+once_escaping!(DataHandler (data: Option<&NSData>, error: Option<&NSError>) -> ());
+let f = unsafe{ DataHandler::new(|data, error| {
+    autoreleasepool(|pool| {
+        //call methods on data and error with pool
+    })
+})};
+```
+
+Declare arguments that objc may pass as `nil` as `Option<&T>`; a plain `&T` must never be null.
+
+To keep an argument past the invocation, declare it `name: *const T as StrongCell<T>` (or `as Option<StrongCell<T>>`
+for nullable ones), and the thunk retains it for the closure.  See [CopyArgument].
+*/
+use objr::bindings::{ObjcInstance, StrongCell};
+use crate::CopyArgument;
+
+///Retains the argument.
+///
+/// # Panics
+/// If objc passed `nil`.  Use `Option<StrongCell<T>>` for nullable arguments.
+impl<T: ObjcInstance> CopyArgument<*const T> for StrongCell<T> {
+    unsafe fn copy_argument(raw: *const T) -> Self {
+        let reference = unsafe{ raw.as_ref() }.expect("nil passed for a non-null StrongCell argument");
+        StrongCell::retaining(reference)
+    }
+}
+
+///Retains the argument, if it isn't `nil`.
+impl<T: ObjcInstance> CopyArgument<*const T> for Option<StrongCell<T>> {
+    unsafe fn copy_argument(raw: *const T) -> Self {
+        unsafe{ raw.as_ref() }.map(StrongCell::retaining)
+    }
+}
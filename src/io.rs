@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Block-based IO as [AsyncRead] and [AsyncWrite], with the `futures-io` feature.
+
+Some APIs deliver data in chunks to a handler block that runs several times, like `dispatch_io_read`, or take a
+completion handler per write, like `dispatch_io_write`.  This module adapts them to the standard async IO traits, so
+they work with the wider async ecosystem.
+
+# Reading
+
+Create a pair with [chunk_reader], move the [ChunkSender] into the handler block, and read from the [ChunkReader].
+
+```
+use blocksr::io::chunk_reader;
+let (reader, sender) = chunk_reader();
+//ordinarily, the sender is moved into a handler block that objc calls with each chunk
+std::thread::spawn(move || {
+    sender.send(b"hello ");
+    sender.send(b"world");
+    sender.finish(Ok(()));
+});
+//reader can be passed to anything that takes an AsyncRead
+# let _ = reader;
+```
+
+# Writing
+
+[BlockWriter] calls a function that starts each write, and completes it with a [Completer].
+*/
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use futures_io::{AsyncRead, AsyncWrite};
+use crate::continuation::{continuation, Completer, Continuation};
+
+#[derive(Debug)]
+struct ReadState {
+    buffer: VecDeque<u8>,
+    //the end of the stream, once the sender finishes
+    end: Option<io::Result<()>>,
+    waker: Option<Waker>,
+}
+
+/**
+The reading half of a [chunk_reader].  Implements [AsyncRead].
+
+Reads return buffered chunks, then `Ok(0)` once the sender finishes successfully.  An error the sender finishes with
+is returned after the buffered chunks.
+*/
+#[derive(Debug)]
+pub struct ChunkReader {
+    shared: Arc<Mutex<ReadState>>,
+}
+
+/**
+The sending half of a [chunk_reader], which is typically moved into a handler block.
+
+Dropping it without calling [finish](ChunkSender::finish) ends the stream with [io::ErrorKind::UnexpectedEof].
+*/
+#[derive(Debug)]
+pub struct ChunkSender {
+    shared: Option<Arc<Mutex<ReadState>>>,
+}
+
+///Creates a linked [ChunkReader] and [ChunkSender].
+pub fn chunk_reader() -> (ChunkReader, ChunkSender) {
+    let shared = Arc::new(Mutex::new(ReadState{buffer: VecDeque::new(), end: None, waker: None}));
+    (ChunkReader{shared: shared.clone()}, ChunkSender{shared: Some(shared)})
+}
+
+impl ChunkSender {
+    fn update(&self, f: impl FnOnce(&mut ReadState)) {
+        let waker = {
+            let mut state = self.shared.as_ref().unwrap().lock().unwrap();
+            f(&mut state);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+    ///Delivers a chunk, waking the task that reads it.
+    ///
+    /// The chunk is copied, so it can borrow from data that only lives as long as the handler call, like a `dispatch_data_t`.
+    pub fn send(&self, chunk: &[u8]) {
+        self.update(|state| state.buffer.extend(chunk));
+    }
+    ///Ends the stream with `result`, typically when the handler is called with `done`.
+    pub fn finish(mut self, result: io::Result<()>) {
+        self.update(|state| state.end = Some(result));
+        self.shared = None;
+    }
+}
+
+impl Drop for ChunkSender {
+    fn drop(&mut self) {
+        if self.shared.is_some() {
+            self.update(|state| state.end = Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "ChunkSender was dropped without finishing the stream"))));
+        }
+    }
+}
+
+impl AsyncRead for ChunkReader {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut state = self.shared.lock().unwrap();
+        if !state.buffer.is_empty() || buf.is_empty() {
+            let len = buf.len().min(state.buffer.len());
+            for (to, from) in buf.iter_mut().zip(state.buffer.drain(..len)) {
+                *to = from;
+            }
+            return Poll::Ready(Ok(len));
+        }
+        match state.end.take() {
+            Some(Ok(())) => {
+                //stays at the end
+                state.end = Some(Ok(()));
+                Poll::Ready(Ok(0))
+            }
+            Some(Err(error)) => {
+                state.end = Some(Ok(()));
+                Poll::Ready(Err(error))
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/**
+Adapts a block-based write API to [AsyncWrite].
+
+`start` is called for each write, with the bytes to write and a [Completer] for the number written.  It typically
+passes them to an API like `dispatch_io_write`, whose completion handler completes the completer.  One write is in
+flight at a time.
+
+Like other [AsyncWrite]s that write in the background, once a write starts, it is no longer tied to the buffer it was
+started with.  A `poll_write` that returned [Poll::Pending] should be retried with the same buffer, as the trait
+requires, and returns the result of the write in flight.
+
+```
+use blocksr::io::BlockWriter;
+let writer = BlockWriter::new(|bytes: Vec<u8>, completer: blocksr::continuation::Completer<std::io::Result<usize>>| {
+    //ordinarily, started with a completion handler that objc calls
+    std::thread::spawn(move || completer.complete(Ok(bytes.len())));
+});
+//writer can be passed to anything that takes an AsyncWrite
+# let _ = writer;
+```
+*/
+#[derive(Debug)]
+pub struct BlockWriter<F> {
+    start: F,
+    in_flight: Option<Continuation<io::Result<usize>>>,
+}
+
+impl<F> BlockWriter<F> where F: FnMut(Vec<u8>, Completer<io::Result<usize>>) {
+    ///Creates a writer that starts each write with `start`.
+    pub fn new(start: F) -> Self {
+        BlockWriter{start, in_flight: None}
+    }
+}
+
+impl<F> BlockWriter<F> {
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<usize>>> {
+        match &mut self.in_flight {
+            None => Poll::Ready(None),
+            Some(continuation) => match Pin::new(continuation).poll(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(result) => {
+                    self.in_flight = None;
+                    Poll::Ready(Some(result))
+                }
+            }
+        }
+    }
+}
+
+//the closure is never pinned
+impl<F> Unpin for BlockWriter<F> {}
+
+impl<F> AsyncWrite for BlockWriter<F> where F: FnMut(Vec<u8>, Completer<io::Result<usize>>) {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.in_flight.is_none() {
+            let (continuation, completer) = continuation();
+            (self.start)(buf.to_vec(), completer);
+            self.in_flight = Some(continuation);
+        }
+        self.poll_in_flight(cx).map(|result| result.expect("a write is in flight"))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_in_flight(cx).map(|result| result.map(|r| r.map(|_| ())).unwrap_or(Ok(())))
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)] fn read_to_end(mut reader: ChunkReader) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buf = [0u8; 4];
+    loop {
+        let n = crate::executors::block_on(std::future::poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)))?;
+        if n == 0 {
+            return Ok(out);
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+}
+
+#[test] fn chunks() {
+    let (reader, sender) = chunk_reader();
+    std::thread::spawn(move || {
+        sender.send(b"hello ");
+        sender.send(b"world");
+        sender.finish(Ok(()));
+    });
+    assert_eq!(read_to_end(reader).unwrap(), b"hello world");
+    let (reader, sender) = chunk_reader();
+    sender.send(b"abc");
+    drop(sender);
+    assert_eq!(read_to_end(reader).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test] fn writes() {
+    let mut written = Vec::new();
+    let mut writer = BlockWriter::new(|bytes: Vec<u8>, completer: Completer<io::Result<usize>>| {
+        written.push(bytes.clone());
+        completer.complete(Ok(bytes.len().min(2)));
+    });
+    let n = crate::executors::block_on(std::future::poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, b"abc"))).unwrap();
+    assert_eq!(n, 2);
+    crate::executors::block_on(std::future::poll_fn(|cx| Pin::new(&mut writer).poll_close(cx))).unwrap();
+    drop(writer);
+    assert_eq!(written, vec![b"abc".to_vec()]);
+}
@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*
+The observer for blocksr::kvo, compiled when the `kvo` feature is on.
+
+ObjC has no block-based KVO API (NSKeyValueObservation is Swift-only), so an instance of this class observes on behalf
+of a handler block.  Compiled without ARC.
+*/
+#import <Foundation/Foundation.h>
+
+@interface BlocksrKVOObserver : NSObject {
+@public
+    id object;
+    NSString *keyPath;
+    void (^handler)(NSDictionary *change);
+}
+@end
+
+@implementation BlocksrKVOObserver
+- (void)observeValueForKeyPath:(NSString *)path ofObject:(id)observed change:(NSDictionary *)change context:(void *)context {
+    if (context == (void *) self) {
+        handler(change);
+    }
+    else {
+        [super observeValueForKeyPath:path ofObject:observed change:change context:context];
+    }
+}
+@end
+
+void *blocksr_kvo_observe(id object, const char *keyPath, NSUInteger options, void (^handler)(NSDictionary *change)) {
+    BlocksrKVOObserver *observer = [BlocksrKVOObserver new];
+    observer->object = [object retain];
+    observer->keyPath = [[NSString alloc] initWithUTF8String:keyPath];
+    observer->handler = [handler copy];
+    [object addObserver:observer forKeyPath:observer->keyPath options:options context:(void *) observer];
+    return observer;
+}
+
+void blocksr_kvo_invalidate(void *token) {
+    BlocksrKVOObserver *observer = token;
+    [observer->object removeObserver:observer forKeyPath:observer->keyPath context:(void *) observer];
+    [observer->object release];
+    [observer->keyPath release];
+    [observer->handler release];
+    [observer release];
+}
+
+NSUInteger blocksr_kvo_change_kind(NSDictionary *change) {
+    return [[change objectForKey:NSKeyValueChangeKindKey] unsignedIntegerValue];
+}
+
+id blocksr_kvo_change_new(NSDictionary *change) {
+    id value = [change objectForKey:NSKeyValueChangeNewKey];
+    return value == [NSNull null] ? nil : value;
+}
+
+id blocksr_kvo_change_old(NSDictionary *change) {
+    id value = [change objectForKey:NSKeyValueChangeOldKey];
+    return value == [NSNull null] ? nil : value;
+}
+
+BOOL blocksr_kvo_change_is_prior(NSDictionary *change) {
+    return [[change objectForKey:NSKeyValueChangeNotificationIsPriorKey] boolValue];
+}
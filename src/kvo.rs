@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Key-value observing with a closure, with the `kvo` feature.
+
+[observe] registers a handler for changes to a key path, and returns an [Observation] that removes the observer when
+dropped, like `NSKeyValueObservation` in Swift.
+
+```ignore
+use blocksr::kvo::{observe, Options};
+let observation = unsafe{ observe(player, c"rate", Options::NEW | Options::INITIAL, |change| {
+    //message change.new_value()...
+})};
+//changes are observed until observation is dropped
+```
+
+[observe_stream] delivers the changes as a [Stream] instead.
+
+The handler is a many-escaping block, and may be called on whatever thread changes the property, possibly from several at
+once.
+*/
+
+use core::ffi::{c_void, CStr};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use futures_core::Stream;
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn objc_retain(object: *const c_void) -> *const c_void;
+    fn objc_release(object: *const c_void);
+}
+//see src/kvo.m
+extern "C" {
+    fn blocksr_kvo_observe(object: *const c_void, key_path: *const core::ffi::c_char, options: usize, handler: *const c_void) -> *mut c_void;
+    fn blocksr_kvo_invalidate(token: *mut c_void);
+    fn blocksr_kvo_change_kind(change: *const c_void) -> usize;
+    fn blocksr_kvo_change_new(change: *const c_void) -> *const c_void;
+    fn blocksr_kvo_change_old(change: *const c_void) -> *const c_void;
+    fn blocksr_kvo_change_is_prior(change: *const c_void) -> i8;
+}
+
+type Handler = Box<dyn Fn(Change) + Send + Sync>;
+
+#[allow(clippy::unused_unit)]
+mod declarations {
+    use core::ffi::c_void;
+    blocksr::many_escaping_reentrant!(pub ChangeHandler (environment: &super::Handler, change: *const c_void) -> ());
+}
+use declarations::ChangeHandler;
+
+/**
+`NSKeyValueObservingOptions`.  Combine with `|`.
+*/
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub struct Options(usize);
+
+impl Options {
+    ///Include the new value in each change.
+    pub const NEW: Options = Options(0x01);
+    ///Include the old value in each change.
+    pub const OLD: Options = Options(0x02);
+    ///Deliver a change with the current value immediately, before [observe] returns.
+    pub const INITIAL: Options = Options(0x04);
+    ///Deliver a change before each change, as well as after.  See [Change::is_prior].
+    pub const PRIOR: Options = Options(0x08);
+}
+
+impl core::ops::BitOr for Options {
+    type Output = Options;
+    fn bitor(self, rhs: Options) -> Options {
+        Options(self.0 | rhs.0)
+    }
+}
+
+/**
+A change to an observed key path: the `NSDictionary` KVO reports, retained.
+*/
+#[derive(Debug)]
+pub struct Change(*const c_void);
+
+//Safety: change dictionaries are immutable, and retain and release are thread-safe
+unsafe impl Send for Change {}
+unsafe impl Sync for Change {}
+
+impl Change {
+    unsafe fn retaining(dictionary: *const c_void) -> Self {
+        Change(unsafe{ objc_retain(dictionary) })
+    }
+    ///The change dictionary.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0
+    }
+    ///The raw `NSKeyValueChange`: 1 for setting, 2-4 for insertion, removal and replacement in a collection.
+    pub fn kind(&self) -> usize {
+        unsafe{ blocksr_kvo_change_kind(self.0) }
+    }
+    ///The new value, if it was requested with [Options::NEW] and isn't nil.  It lives as long as `self`.
+    pub fn new_value(&self) -> Option<*const c_void> {
+        let value = unsafe{ blocksr_kvo_change_new(self.0) };
+        if value.is_null() { None } else { Some(value) }
+    }
+    ///The old value, if it was requested with [Options::OLD] and wasn't nil.  It lives as long as `self`.
+    pub fn old_value(&self) -> Option<*const c_void> {
+        let value = unsafe{ blocksr_kvo_change_old(self.0) };
+        if value.is_null() { None } else { Some(value) }
+    }
+    ///Whether this change is delivered before the value changes, with [Options::PRIOR].
+    pub fn is_prior(&self) -> bool {
+        unsafe{ blocksr_kvo_change_is_prior(self.0) != 0 }
+    }
+}
+
+impl Drop for Change {
+    fn drop(&mut self) {
+        unsafe{ objc_release(self.0) }
+    }
+}
+
+/**
+A registered observer.  Dropping it, or calling [invalidate](Observation::invalidate), removes the observer and
+releases the handler block.
+
+As with KVO generally, a change on another thread may still be delivered while the observer is being removed.
+*/
+#[derive(Debug)]
+#[must_use = "the observer is removed when the Observation is dropped"]
+pub struct Observation(*mut c_void);
+
+//Safety: removing an observer is thread-safe
+unsafe impl Send for Observation {}
+
+impl Observation {
+    ///Removes the observer.
+    pub fn invalidate(self) {
+        //drop
+    }
+}
+
+impl Drop for Observation {
+    fn drop(&mut self) {
+        unsafe{ blocksr_kvo_invalidate(self.0) }
+    }
+}
+
+/**
+Calls `handler` with each change to `key_path` of `object`, until the returned [Observation] is dropped.
+
+`object` is retained until then.
+
+# Safety
+`object` must be a valid ObjC object, and `key_path` a key path it is KVO-compliant for.
+*/
+pub unsafe fn observe<F>(object: *const c_void, key_path: &CStr, options: Options, handler: F) -> Observation where F: Fn(Change) + Send + Sync + 'static {
+    let environment: Handler = Box::new(handler);
+    //the shim copies the block, and the copy owns the environment
+    let block = unsafe{ ChangeHandler::new(environment, |environment, change| {
+        environment(Change::retaining(change))
+    })};
+    Observation(unsafe{ blocksr_kvo_observe(object, key_path.as_ptr(), options.0, &block as *const ChangeHandler as *const c_void) })
+}
+
+#[derive(Debug)]
+struct StreamState {
+    changes: VecDeque<Change>,
+    waker: Option<Waker>,
+}
+
+/**
+The changes to an observed key path, as a [Stream].  Created by [observe_stream].
+
+Changes are buffered until they are polled.  The stream doesn't end; drop it to stop observing.
+*/
+#[derive(Debug)]
+pub struct ChangeStream {
+    shared: Arc<Mutex<StreamState>>,
+    _observation: Observation,
+}
+
+/**
+Observes `key_path` of `object`, delivering each change to the returned [ChangeStream].
+
+# Safety
+The same requirements as [observe] apply.
+*/
+pub unsafe fn observe_stream(object: *const c_void, key_path: &CStr, options: Options) -> ChangeStream {
+    let shared = Arc::new(Mutex::new(StreamState{changes: VecDeque::new(), waker: None}));
+    let sender = shared.clone();
+    let observation = unsafe{ observe(object, key_path, options, move |change| {
+        let waker = {
+            let mut state = sender.lock().unwrap();
+            state.changes.push_back(change);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    })};
+    ChangeStream{shared, _observation: observation}
+}
+
+impl Stream for ChangeStream {
+    type Item = Change;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Change>> {
+        let mut state = self.shared.lock().unwrap();
+        match state.changes.pop_front() {
+            Some(change) => Poll::Ready(Some(change)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Compile-time checks that our literals match the blocks ABI (ABI.2010.3.16).
+
+```text
+struct Block_literal_1 {
+    void *isa;
+    int flags;
+    int reserved;
+    void (*invoke)(void *, ...);
+    struct Block_descriptor_1 {
+        unsigned long int reserved;
+        unsigned long int size;
+        void (*copy_helper)(void *dst, void *src);     // IFF (1<<25)
+        void (*dispose_helper)(void *src);             // IFF (1<<25)
+        const char *signature;                         // IFF (1<<30)
+        const char *layout;                            // IFF (1<<30), interpreted per (1<<31)
+    } *descriptor;
+    // imported variables
+};
+```
+
+If a field is reordered or changes type, the build fails here instead of corrupting memory at runtime.
+*/
+
+use core::ffi::{c_int, c_ulong, c_void};
+use core::mem::{align_of, offset_of, size_of};
+use crate::Block;
+use crate::many::{BlockDescriptorMany, BlockLiteralManyEscape};
+use crate::once::{BlockDescriptorNoCopy, BlockDescriptorOnce, BlockLiteralNoEscape, BlockLiteralOnceEscape};
+use crate::raw::RawBlock;
+
+const POINTER: usize = size_of::<*const c_void>();
+const INT: usize = size_of::<c_int>();
+const ULONG: usize = size_of::<c_ulong>();
+
+const ISA: usize = 0;
+const FLAGS: usize = POINTER;
+const RESERVED: usize = FLAGS + INT;
+const INVOKE: usize = RESERVED + INT;
+const DESCRIPTOR: usize = INVOKE + POINTER;
+///Offset of the first imported variable
+const CAPTURES: usize = DESCRIPTOR + POINTER;
+
+const fn round_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+///Checks the fields shared by every literal
+macro_rules! assert_header {
+    ($T:ty) => {
+        const _: () = {
+            assert!(offset_of!($T, isa) == ISA);
+            assert!(offset_of!($T, flags) == FLAGS);
+            assert!(offset_of!($T, reserved) == RESERVED);
+            assert!(offset_of!($T, invoke) == INVOKE);
+            assert!(offset_of!($T, descriptor) == DESCRIPTOR);
+            assert!(align_of::<$T>() == align_of::<*const c_void>());
+        };
+    }
+}
+
+assert_header!(Block<(), ()>);
+assert_header!(BlockLiteralOnceEscape);
+assert_header!(BlockLiteralManyEscape);
+assert_header!(BlockLiteralNoEscape<u8>);
+assert_header!(BlockLiteralNoEscape<u8, BlockDescriptorNoCopy>);
+assert_header!(RawBlock);
+
+const _: () = {
+    assert!(size_of::<Block<(), ()>>() == CAPTURES);
+
+    assert!(offset_of!(BlockLiteralOnceEscape, closure) == CAPTURES);
+    assert!(size_of::<BlockLiteralOnceEscape>() == CAPTURES + POINTER);
+
+    assert!(offset_of!(BlockLiteralManyEscape, payload) == CAPTURES);
+    assert!(offset_of!(BlockLiteralManyEscape, dispose) == CAPTURES + POINTER);
+    assert!(offset_of!(BlockLiteralManyEscape, take_environment) == CAPTURES + 2 * POINTER);
+    assert!(offset_of!(BlockLiteralManyEscape, clone_payload) == CAPTURES + 3 * POINTER);
+    assert!(size_of::<BlockLiteralManyEscape>() == CAPTURES + 4 * POINTER);
+
+    assert!(offset_of!(BlockLiteralNoEscape<u8>, inline_descriptor) == CAPTURES);
+    assert!(offset_of!(BlockLiteralNoEscape<u8>, closure_inline) == CAPTURES + size_of::<BlockDescriptorOnce>());
+
+    assert!(offset_of!(RawBlock, context) == CAPTURES);
+    assert!(size_of::<RawBlock>() == CAPTURES + POINTER);
+
+    assert!(offset_of!(BlockDescriptorOnce, reserved) == 0);
+    assert!(offset_of!(BlockDescriptorOnce, size) == ULONG);
+
+    assert!(offset_of!(BlockDescriptorMany, reserved) == 0);
+    assert!(offset_of!(BlockDescriptorMany, size) == ULONG);
+    assert!(offset_of!(BlockDescriptorMany, copy_helper) == round_up(2 * ULONG, POINTER));
+    assert!(offset_of!(BlockDescriptorMany, dispose_helper) == round_up(2 * ULONG, POINTER) + POINTER);
+
+    assert!(offset_of!(BlockDescriptorNoCopy, copy_helper) == offset_of!(BlockDescriptorMany, copy_helper));
+    assert!(offset_of!(BlockDescriptorNoCopy, dispose_helper) == offset_of!(BlockDescriptorMany, dispose_helper));
+    assert!(offset_of!(BlockDescriptorNoCopy, signature) == offset_of!(BlockDescriptorMany, signature));
+
+    assert!(offset_of!(BlockDescriptorOnce, signature) == round_up(2 * ULONG, POINTER));
+    assert!(offset_of!(BlockDescriptorOnce, layout) == round_up(2 * ULONG, POINTER) + POINTER);
+    assert!(size_of::<BlockDescriptorOnce>() == round_up(2 * ULONG, POINTER) + 2 * POINTER);
+
+    assert!(offset_of!(BlockDescriptorMany, signature) == round_up(2 * ULONG, POINTER) + 2 * POINTER);
+    assert!(offset_of!(BlockDescriptorMany, layout) == round_up(2 * ULONG, POINTER) + 3 * POINTER);
+    assert!(size_of::<BlockDescriptorMany>() == round_up(2 * ULONG, POINTER) + 4 * POINTER);
+};
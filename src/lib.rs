@@ -12,12 +12,63 @@ for Apple platform features that mimic code from first-party compilers.  Distinc
    * In Rust, blocks may be [FnOnce] (implemented), [Fn], or [FnMut] (planned), unlocking the full Rust typesystem
    * In C/ObjC, blocks may escape (implemented) or not escape (planned), unlocking various optimizations used by real C/ObjC compilers
    * C/ObjC is a giant ball of unsafe code, and most direct use of this crate is also unsafe.  Bindings authors are encouraged to wrap
-  safe API based on their local knowledge.
+     safe API based on their local knowledge.
    * Ergonomic macros for quickly binding new platform APIs
  * The `continuation` feature (off by default) bridges block-based completion handlers to Rust `async fn`s.
      * This is similar to (and informed by) Apple's own Swift bridge for async methods, with broad compatability across
        real-world Apple APIs.
-     * This Rust version is self-contained, 200 lines, does not depend on Tokio and is tested against other async runtimes.
+     * This Rust version does not depend on Tokio or any other async runtime, and is tested against several.
+     * Tagged continuations can be resolved together with `cancel_all`, for app shutdown.
+     * On Apple platforms, `on_main` runs a closure on the main thread, synchronously if it is already there, or via
+       the main queue with a continuation for its result.
+     * The `slow-completion` feature logs a warning when a completion handler takes too long to be called.  Its time
+       source can be replaced, with `blocksr::clock`, by a runtime's timers or a manual clock for tests.
+     * The `common` feature pre-declares the `(BOOL success, NSError *error)` and `(BOOL granted)` reply blocks of
+       system prompts, with futures for their replies, in `blocksr::common`.  Their `NSError`s map to
+       `std::io::Error`s, by domain and code.
+     * The `futures-io` feature adapts block-based IO, such as `dispatch_io`, to `AsyncRead` and `AsyncWrite`.
+     * Handlers called once per item feed a stream with `blocksr::stream`.  With the `futures-sink` feature, it is a
+       `Stream`, fed by a `Sink`.
+     * The `test-executors` feature exposes the executors it is tested with, in `blocksr::executors`.
+* With the nightly `fn_traits` feature, received blocks can be called like closures, and passed to code expecting
+  `impl Fn`, with `Block::as_fn`.
+* The `kvo` feature observes key paths with a closure or a `Stream`, in `blocksr::kvo`.
+* The `verify` feature checks that an ObjC method takes the block type a binding passes it, against the method's
+  type encoding, in `blocksr::verify`.
+* The `associated-objects` feature attaches heap blocks to ObjC objects, so they live as long as the object, with
+  `attach_block`.
+* The `objc-exceptions` feature catches ObjC exceptions thrown by calls a block body makes, so they don't unwind into
+  the caller, in `blocksr::exception`.
+* The `gnustep` feature uses the blocks runtime in GNUstep's libobjc2, for Linux and Windows.
+* The `weak-runtime` feature looks up the blocks runtime when the first block is created, instead of at load time,
+  and [runtime_available] reports whether it was found.
+* The `poison-disposed` feature is a debugging aid: invoking a block after ObjC disposed it aborts with a
+  message, instead of corrupting the heap.  Like `NSZombieEnabled`, it never frees payloads, so it is for debugging
+  builds only.
+* [VoidBlock]s can be tagged with a [QosClass], so GCD runs them at the QoS of the work that created them.
+* `blocksr::objc_types` has ObjC's target-dependent typedefs, like `BOOL` and `CGFloat`, so block declarations are
+  portable across Apple platforms without a `cfg` for each.
+* Block types have their ObjC type encoding as `SIGNATURE`, for `NSInvocation` and `blocksr::verify`, in
+  `blocksr::encoding`.  Their descriptors record it, as clang's do.
+* [Trampoline] wraps a closure or block as a C function pointer and context, for APIs like `dispatch_async_f`.
+* [BlockBuilder] declares a block's captures explicitly, as strong, weak or plain values, like a C capture list.
+* `many` blocks created with `new_aggregating` gather their return values across invocations, in an `Aggregate`.
+* `static_block!` declares a global block in a `static`, created on first use, for callbacks like a no-op completion
+  that are passed over and over.  `named_block` keeps a registry of them by name, with the `std` feature.
+* [StackBlockCell] reuses one pinned slot for the noescape blocks a loop creates, such as around `dispatch_sync`.
+* Block payloads can be allocated from a slab with [set_payload_allocator], for apps that create blocks at a high rate.
+* In debug builds, `blocksr::provenance` records where each block was created, for diagnostics and finding leaks.
+* The `testing` feature provides `blocksr::testing::Harness`, which copies, invokes and releases blocks the way the
+  runtime would, for unit-testing bindings, `invoke_in_place`, which does the same for noescape blocks, and `DropLog`,
+  which checks the order values are dropped in.  On macOS, `soak` runs a block through the real runtime thousands of
+  times, checking for leaks.
+* The `bench` feature exposes our criterion benchmarks in `blocksr::bench`, to reproduce numbers or catch regressions.
+* The library never writes to stdout.  Diagnostics go to stderr, or with the `quiet` feature, only to the `log` crate,
+  for GUI apps and daemons.
+* `no_std` + `alloc` support, by disabling the default `std` feature.
+* The crate is in two layers.  `blocksr::blocks` has the block literals, macros and ABI, with no dependencies but
+  the blocks runtime, and `blocksr::rt`, with the `rt` feature, has continuations, streams and dispatch helpers.  Their
+  items are also at the paths they have always had.
 * Free for noncommercial or "small commercial" use
 
 # Examples
@@ -35,17 +86,175 @@ let f = unsafe{ MyBlock::new(|_arg| {
 
 
 */
+#![cfg_attr(feature = "c_variadic", feature(c_variadic))]
+#![cfg_attr(feature = "fn_traits", feature(unboxed_closures, fn_traits, tuple_trait))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 extern crate self as blocksr;
-extern crate core;
+extern crate alloc;
+
+pub mod blocks;
+#[cfg(feature = "continuation")]
+pub mod rt;
 
 mod once;
+pub use once::StackBlockCell;
 
 mod many;
+pub use many::QueueConfined;
+
+#[cfg(feature = "std")]
+mod diagnostic;
+
+mod runtime;
+pub use runtime::{runtime_available,global_block_isa};
+
+mod block;
+mod layout;
+pub use block::{Block,BlockArguments,BlockFlags};
+
+pub mod raw;
+
+#[cfg(feature = "std")]
+mod descriptor;
+
+#[cfg(feature = "fn_traits")]
+mod callable;
+#[cfg(feature = "fn_traits")]
+pub use callable::CallableBlock;
+
+mod adapter;
+pub use adapter::{CopyArgument, BorrowArgument};
+
+mod cancel;
+pub use cancel::CancellationToken;
+
+#[cfg(feature = "std")]
+mod aggregate;
+#[cfg(feature = "std")]
+pub use aggregate::Aggregate;
+
+mod throws;
+pub use throws::{ThrowingReturn,OutParameter,IntoOutError};
+
+mod param;
+pub use param::BlockParam;
+
+mod proxy;
+pub use proxy::{ProxyBlock,ProxyArguments};
+
+mod builder;
+pub use builder::{BlockBuilder,BuilderArguments,BuiltBlock,Captures,RetainedObject};
+
+pub mod arena;
+pub use arena::set_payload_allocator;
+
+#[cfg(feature = "std")]
+pub mod provenance;
+
+mod heap;
+pub use heap::HeapBlock;
+
+#[cfg(feature = "std")]
+mod statics;
+#[cfg(feature = "std")]
+pub use statics::{StaticBlock, named_block};
+
+#[cfg(feature = "associated-objects")]
+mod associated;
+#[cfg(feature = "associated-objects")]
+pub use associated::{AssociationKey, attach_block, attached_block, detach_block};
+
+mod trampoline;
+pub use trampoline::{Trampoline,OnceTrampoline};
+
+pub mod objc_types;
+
+pub mod encoding;
+pub use encoding::Signature;
+
+mod void;
+pub use void::{VoidBlock,VoidManyBlock,QosClass};
+
+#[cfg(feature = "continuation")]
+pub mod continuation;
+
+#[cfg(feature = "continuation")]
+pub mod stream;
+
+#[cfg(feature = "futures-io")]
+pub mod io;
+
+#[cfg(feature = "common")]
+pub mod common;
+
+#[cfg(feature = "std")]
+pub mod clock;
+
+#[cfg(feature = "slow-completion")]
+mod slow_completion;
+
+#[cfg(any(all(test, feature = "continuation"), feature = "test-executors", feature = "bench"))]
+pub mod executors;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
+#[cfg(feature = "interop-block2")]
+mod interop_block2;
+
+#[cfg(feature = "interop-objc2")]
+mod interop_objc2;
+
+#[cfg(feature = "objr")]
+mod interop_objr;
+
+#[cfg(feature = "dyncall")]
+pub mod dyncall;
+
+#[cfg(feature = "objc-exceptions")]
+pub mod exception;
+
+#[cfg(feature = "kvo")]
+pub mod kvo;
+
+#[cfg(feature = "verify")]
+pub mod verify;
+
+#[cfg(feature = "c_variadic")]
+mod variadic;
+#[cfg(feature = "c_variadic")]
+pub use variadic::VaArgs;
 
 #[doc(hidden)]
 pub mod hidden {
-    pub use super::once::{BlockLiteralOnceEscape, BlockDescriptorOnce, _NSConcreteStackBlock, BLOCK_DESCRIPTOR_ONCE, BLOCK_HAS_STRET, BLOCK_HAS_COPY_DISPOSE, BLOCK_IS_GLOBAL, BLOCK_IS_NOESCAPE, BlockLiteralNoEscape};
-    pub use super::many::{BlockDescriptorMany,BlockLiteralManyEscape,Payload,BLOCK_DESCRIPTOR_MANY};
+    pub use alloc::boxed::Box;
+    #[cfg(feature = "std")]
+    pub use std::sync::{Arc, Mutex, RwLock};
+    pub use super::block::debug_literal;
+    pub use super::runtime::{stack_block_isa,DefaultIsa,isa_flags};
+    pub use super::encoding::{BlockType, SignatureProbe, NoSignature};
+    pub use super::arena::{alloc_payload, try_alloc_payload, unwrap_alloc, take_payload, box_in_place};
+    pub use super::throws::report_result;
+    pub use super::cancel::{Cancellable, DefaultReturn};
+    #[cfg(feature = "std")]
+    pub use super::aggregate::aggregate;
+    #[cfg(feature = "continuation")]
+    pub use super::continuation::{CompleteFrom,CompletionOutput};
+    pub use super::once::{BlockLiteralOnceEscape, BlockDescriptorOnce, BLOCK_DESCRIPTOR_ONCE, BLOCK_HAS_STRET, BLOCK_HAS_COPY_DISPOSE, BLOCK_IS_GLOBAL, BLOCK_IS_NOESCAPE, BlockLiteralNoEscape, BlockDescriptorNoCopy, InlineDescriptor, copied_nocopy, stret_flag, Never, take_once_closure, descriptor_flags, once_descriptor};
+    pub use super::many::{BlockDescriptorMany,ManyDescriptors,BlockLiteralManyEscape,Payload,BLOCK_DESCRIPTOR_MANY,BLOCK_DESCRIPTOR_MANY_TRIVIAL,many_descriptor,WithDispose,KeepAlive,ThreadAffine,ManyLiteral,ReentrantLiteral,take_environment_thunk,opaque_environment,clone_payload_thunk,live_payload,enter_nonreentrant,NonreentrantGuard,Nonreentrant};
+    #[cfg(target_vendor = "apple")]
+    pub use super::many::DropOnMain;
+    #[cfg(feature = "std")]
+    pub use super::many::Serialized;
+    #[cfg(feature = "interop-objc2")]
+    pub use objc2;
+    #[cfg(feature = "c_variadic")]
+    pub mod variadic {
+        pub use crate::variadic::{once_thunk_0,once_thunk_1,once_thunk_2,once_thunk_3,once_thunk_4,many_thunk_0,many_thunk_1,many_thunk_2,many_thunk_3,many_thunk_4};
+    }
 }
 
 
@@ -2,9 +2,125 @@
 /*! Blocks that may be run more than once. */
 
 
-use std::os::raw::{c_int, c_ulong};
-use std::ffi::c_void;
-use std::mem::MaybeUninit;
+use core::ffi::{c_int, c_ulong};
+use core::ffi::c_void;
+use core::mem::MaybeUninit;
+#[cfg(target_vendor = "apple")]
+use alloc::boxed::Box;
+
+/**
+The closure of a nonreentrant block, as stored in its payload, with a flag set while it is being invoked.
+
+Debug builds with `std` have the flag, so they can report a re-entrant or concurrent invocation, which would alias the
+closure and environment.
+*/
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Nonreentrant<C> {
+    closure: C,
+    #[cfg(all(debug_assertions, feature = "std"))]
+    in_flight: core::sync::atomic::AtomicBool,
+}
+
+impl<C> Nonreentrant<C> {
+    pub fn new(closure: C) -> Self {
+        Nonreentrant{closure, #[cfg(all(debug_assertions, feature = "std"))] in_flight: core::sync::atomic::AtomicBool::new(false)}
+    }
+    pub fn closure_mut(&mut self) -> &mut C {
+        &mut self.closure
+    }
+}
+
+/**
+Marks the payload of a nonreentrant block as being invoked, until the guard is dropped.
+
+Invocations may run on a different thread each time, and the closure and environment are mutated without a lock.  The
+guard is fenced like one: entering acquires, and dropping releases, so each invocation sees the writes of the last,
+provided whatever hands the block from one thread to the next (such as a dispatch queue) orders the invocations with
+any atomic operation, even a relaxed one.
+
+In debug builds with `std`, aborts if it is already being invoked.
+
+# Safety
+`payload` must point to a live `Payload<Nonreentrant<C>,E>`.  It is checked before anything borrows the payload.
+*/
+#[doc(hidden)]
+#[inline(always)]
+pub unsafe fn enter_nonreentrant<C,E>(payload: *mut c_void, blockname: &'static str) -> NonreentrantGuard {
+    core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+    #[cfg(all(debug_assertions, feature = "std"))]
+    {
+        let in_flight = unsafe{ &*core::ptr::addr_of!((*(payload as *const Payload<Nonreentrant<C>,E>)).closure.in_flight) };
+        if in_flight.swap(true, core::sync::atomic::Ordering::Relaxed) {
+            crate::diagnostic::abort(format_args!("blocksr: {}{} was invoked while it was already executing.  Blocks declared with many_escaping_nonreentrant must not be called re-entrantly or concurrently; use many_escaping_reentrant.", blockname, crate::provenance::CreatedAt(crate::provenance::created_at(payload))));
+        }
+        NonreentrantGuard(in_flight)
+    }
+    #[cfg(not(all(debug_assertions, feature = "std")))]
+    {
+        let _ = (payload, blockname);
+        NonreentrantGuard(())
+    }
+}
+
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct NonreentrantGuard(#[cfg(all(debug_assertions, feature = "std"))] *const core::sync::atomic::AtomicBool, #[cfg(not(all(debug_assertions, feature = "std")))] ());
+
+impl Drop for NonreentrantGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        //the payload outlives the invocation
+        #[cfg(all(debug_assertions, feature = "std"))]
+        unsafe{ &*self.0 }.store(false, core::sync::atomic::Ordering::Relaxed);
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+    }
+}
+
+/**
+The environment of a block created with `new_serialized`: the closure and its environment, behind a mutex, so
+concurrent invocations take turns.
+
+An invocation from inside the closure, on the same thread, would deadlock, so it panics instead.
+*/
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Serialized<T> {
+    value: std::sync::Mutex<T>,
+    //the thread holding the lock, as the address of its THREAD_TOKEN, or 0
+    owner: core::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "std")]
+std::thread_local!(static THREAD_TOKEN: u8 = const { 0 });
+
+#[cfg(feature = "std")]
+impl<T> Serialized<T> {
+    pub fn new(value: T) -> Self {
+        Serialized{value: std::sync::Mutex::new(value), owner: core::sync::atomic::AtomicUsize::new(0)}
+    }
+    ///Runs `f` with the lock held.  A panic elsewhere doesn't poison it.
+    pub fn with<R>(&self, blockname: &'static str, f: impl FnOnce(&mut T) -> R) -> R {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        //only the lock holder stores its own token, so a thread sees its token only while it holds the lock
+        let token = THREAD_TOKEN.with(|token| token as *const u8 as usize);
+        if self.owner.load(Ordering::Relaxed) == token {
+            panic!("{} was invoked re-entrantly, from inside its own closure.  Blocks created with new_serialized take turns, so this would deadlock.", blockname);
+        }
+        let mut value = self.value.lock().unwrap_or_else(|e| e.into_inner());
+        struct Release<'a>(&'a AtomicUsize);
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                self.0.store(0, Ordering::Relaxed);
+            }
+        }
+        self.owner.store(token, Ordering::Relaxed);
+        //dropped before the lock is released
+        let _release = Release(&self.owner);
+        f(&mut value)
+    }
+}
 
 #[repr(C)]
 #[derive(Debug)]
@@ -18,21 +134,128 @@ pub struct BlockDescriptorMany {
      */
     pub copy_helper: extern "C" fn(dst: *mut blocksr::hidden::BlockLiteralManyEscape, src: *mut blocksr::hidden::BlockLiteralManyEscape),
     pub dispose_helper: extern "C" fn(src: *mut blocksr::hidden::BlockLiteralManyEscape),
+    pub signature: *const core::ffi::c_char,
+    pub layout: *const core::ffi::c_char,
 }
+impl BlockDescriptorMany {
+    ///The descriptor for a literal with our helpers, with `signature` if there is one.
+    pub const fn new(signature: Option<&'static core::ffi::CStr>) -> Self {
+        BlockDescriptorMany {
+            reserved: MaybeUninit::uninit(),
+            size: core::mem::size_of::<BlockLiteralManyEscape>() as core::ffi::c_ulong,
+            copy_helper,
+            dispose_helper,
+            signature: match signature {
+                Some(signature) => signature.as_ptr(),
+                None => core::ptr::null(),
+            },
+            //no ObjC objects are captured; see BLOCK_HAS_EXTENDED_LAYOUT
+            layout: core::ptr::null(),
+        }
+    }
+}
+#[doc(hidden)]
+pub static mut BLOCK_DESCRIPTOR_MANY: BlockDescriptorMany = BlockDescriptorMany::new(None);
+
+/**
+A descriptor without copy and dispose helpers, for payloads that need neither.
+*/
+#[doc(hidden)]
+pub static mut BLOCK_DESCRIPTOR_MANY_TRIVIAL: blocksr::hidden::BlockDescriptorOnce = blocksr::hidden::BlockDescriptorOnce::new(core::mem::size_of::<BlockLiteralManyEscape>());
+
+/**
+The descriptors of a `many` block type with a signature, with helpers and without.  Types without a signature share
+[BLOCK_DESCRIPTOR_MANY] and [BLOCK_DESCRIPTOR_MANY_TRIVIAL] instead.
+*/
+#[derive(Debug)]
+#[doc(hidden)]
+pub struct ManyDescriptors {
+    pub helpers: BlockDescriptorMany,
+    pub trivial: blocksr::hidden::BlockDescriptorOnce,
+}
+impl ManyDescriptors {
+    ///The descriptors for a type with `signature`, if there is one.
+    pub const fn new(signature: Option<&'static core::ffi::CStr>) -> Self {
+        ManyDescriptors {
+            helpers: BlockDescriptorMany::new(signature),
+            trivial: blocksr::hidden::BlockDescriptorOnce::new(core::mem::size_of::<BlockLiteralManyEscape>()).with_signature(signature),
+        }
+    }
+    ///The descriptors for a block without a signature.
+    pub const UNSIGNED: ManyDescriptors = ManyDescriptors::new(None);
+}
+
+/**
+The flags and descriptor for a literal whose payload is a `Payload<C,E>`, from its type's `descriptors`.
+
+If the payload is zero-sized and nothing in it needs dropping, storing it doesn't allocate and disposing it does
+nothing, so the helpers are skipped along with [BLOCK_HAS_COPY_DISPOSE](crate::hidden::BLOCK_HAS_COPY_DISPOSE).
+Any other payload still has an allocation to free.  Global blocks, whose `flags` lack the bit already, skip the
+helpers regardless, since the runtime never calls them.
+*/
 #[doc(hidden)]
-pub static mut BLOCK_DESCRIPTOR_MANY: BlockDescriptorMany = BlockDescriptorMany {
-    reserved: MaybeUninit::uninit(),
-    size: std::mem::size_of::<BlockLiteralManyEscape>() as u64,
-    copy_helper: copy_helper,
-    dispose_helper: dispose_helper,
-};
+//taking the address of a static mut is only safe since Rust 1.82
+#[allow(unused_unsafe)]
+pub fn many_descriptor<C,E>(flags: c_int, descriptors: &'static ManyDescriptors) -> (c_int, *mut c_void) {
+    let signed = !descriptors.helpers.signature.is_null();
+    if flags & blocksr::hidden::BLOCK_HAS_COPY_DISPOSE == 0 || (core::mem::size_of::<Payload<C,E>>() == 0 && !core::mem::needs_drop::<Payload<C,E>>()) {
+        let descriptor = if signed { &descriptors.trivial as *const _ as *mut c_void } else { unsafe{ core::ptr::addr_of_mut!(BLOCK_DESCRIPTOR_MANY_TRIVIAL) as *mut c_void } };
+        (flags & !blocksr::hidden::BLOCK_HAS_COPY_DISPOSE, descriptor)
+    }
+    else {
+        let descriptor = if signed { &descriptors.helpers as *const _ as *mut c_void } else { unsafe{ core::ptr::addr_of_mut!(BLOCK_DESCRIPTOR_MANY) as *mut c_void } };
+        (flags, descriptor)
+    }
+}
 
 extern "C" fn dispose_helper(src: *mut blocksr::hidden::BlockLiteralManyEscape) {
-    println!("dispose_helper");
+    //Like dropping the last Arc: the runtime released each reference with an atomic decrement, and this acquires
+    //them, so every invocation happens before the payload is dropped.
+    core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+    #[cfg(feature = "poison-disposed")]
+    unsafe{ live_payload(src) };
     unsafe{((*src).dispose)(src)}
+    //for a literal that outlives its disposal, such as one on the stack.  The runtime frees a heap literal after this
+    //returns, so a stale heap block is caught by its payload, which the arena never frees.
+    #[cfg(feature = "poison-disposed")]
+    unsafe{ (*src).payload = poisoned() };
+}
+extern "C" fn copy_helper(dst: *mut blocksr::hidden::BlockLiteralManyEscape, src: *mut blocksr::hidden::BlockLiteralManyEscape) {
+    #[cfg(feature = "poison-disposed")]
+    unsafe{ live_payload(src) };
+    //the runtime has already copied the literal, so by default the copy shares the payload
+    if let Some(clone_payload) = unsafe{ (*src).clone_payload } {
+        unsafe{ (*dst).payload = clone_payload(src) };
+    }
+}
+
+//The payload of a disposed block.  Its address can't be an allocation, or the dangling pointer of a zero-sized payload.
+#[cfg(feature = "poison-disposed")]
+static POISON: u8 = 0;
+
+#[cfg(feature = "poison-disposed")]
+fn poisoned() -> *mut c_void {
+    core::ptr::addr_of!(POISON) as *mut c_void
 }
-extern "C" fn copy_helper(_dst: *mut blocksr::hidden::BlockLiteralManyEscape, _src: *mut blocksr::hidden::BlockLiteralManyEscape) {
-    println!("copy_helper");
+
+/**
+The payload of `block`.
+
+With the `poison-disposed` feature, aborts if the block was already disposed: if its payload was poisoned, or was
+taken, in which case the arena kept the allocation rather than freeing it.
+
+# Safety
+`block` must be a valid literal.
+*/
+#[doc(hidden)]
+#[inline(always)]
+pub unsafe fn live_payload(block: *mut BlockLiteralManyEscape) -> *mut c_void {
+    let payload = unsafe{ (*block).payload };
+    #[cfg(feature = "poison-disposed")]
+    if payload == poisoned() || crate::arena::is_disposed(payload) {
+        crate::diagnostic::abort(format_args!("blocksr: block {:p} was used after it was disposed.  ObjC probably invoked, copied or released it after releasing its last reference.", block));
+    }
+    payload
 }
 
 #[repr(C)]
@@ -43,6 +266,236 @@ pub struct Payload<C,E> {
     pub environment: E,
 }
 
+/**
+Environment wrapper used by `new_with_dispose`.
+
+Runs `on_dispose` after the wrapped environment is dropped.
+*/
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct WithDispose<E,D: FnOnce()> {
+    environment: core::mem::ManuallyDrop<E>,
+    on_dispose: Option<D>,
+}
+impl<E,D: FnOnce()> WithDispose<E,D> {
+    pub fn new(environment: E, on_dispose: D) -> Self {
+        WithDispose{environment: core::mem::ManuallyDrop::new(environment), on_dispose: Some(on_dispose)}
+    }
+    pub fn environment(&self) -> &E {
+        &self.environment
+    }
+    pub fn environment_mut(&mut self) -> &mut E {
+        &mut self.environment
+    }
+}
+//Safety: `on_dispose` is only used by `drop`, through `&mut self`
+unsafe impl<E: Sync,D: FnOnce()> Sync for WithDispose<E,D> {}
+impl<E,D: FnOnce()> Drop for WithDispose<E,D> {
+    fn drop(&mut self) {
+        unsafe{ core::mem::ManuallyDrop::drop(&mut self.environment) };
+        (self.on_dispose.take().unwrap())()
+    }
+}
+
+/**
+Environment wrapper used by `new_keeping_alive`.
+
+Fields drop in declaration order, so `keep_alive` outlives the environment.
+*/
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct KeepAlive<E,K> {
+    environment: E,
+    _keep_alive: K,
+}
+//Safety: `keep_alive` is only ever dropped
+unsafe impl<E: Sync,K> Sync for KeepAlive<E,K> {}
+impl<E,K> KeepAlive<E,K> {
+    pub fn new(environment: E, keep_alive: K) -> Self {
+        KeepAlive{environment, _keep_alive: keep_alive}
+    }
+    pub fn environment(&self) -> &E {
+        &self.environment
+    }
+    pub fn environment_mut(&mut self) -> &mut E {
+        &mut self.environment
+    }
+}
+
+//pthread_main_np is Apple's; glibc, and gnustep's libdispatch, lack it
+#[cfg(target_vendor = "apple")]
+extern "C" {
+    static _dispatch_main_q: c_void;
+    fn dispatch_async_f(queue: *const c_void, context: *mut c_void, work: extern "C" fn(*mut c_void));
+    fn pthread_main_np() -> c_int;
+}
+
+/**
+Environment wrapper used by `new_drop_on_main`.
+
+When dropped off the main thread, the wrapped environment is sent to the main queue with `dispatch_async_f` and dropped there.
+*/
+#[cfg(target_vendor = "apple")]
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct DropOnMain<E> {
+    environment: core::mem::ManuallyDrop<E>,
+}
+#[cfg(target_vendor = "apple")]
+impl<E> DropOnMain<E> {
+    pub fn new(environment: E) -> Self {
+        DropOnMain{environment: core::mem::ManuallyDrop::new(environment)}
+    }
+    pub fn environment(&self) -> &E {
+        &self.environment
+    }
+    pub fn environment_mut(&mut self) -> &mut E {
+        &mut self.environment
+    }
+}
+#[cfg(target_vendor = "apple")]
+impl<E> Drop for DropOnMain<E> {
+    fn drop(&mut self) {
+        extern "C" fn drop_thunk<E>(context: *mut c_void) {
+            let _boxed_environment = unsafe{ Box::from_raw(context as *mut E) };
+            //drop
+        }
+        let environment = unsafe{ core::mem::ManuallyDrop::take(&mut self.environment) };
+        if unsafe{ pthread_main_np() } != 0 {
+            core::mem::drop(environment);
+        }
+        else {
+            let context = Box::into_raw(Box::new(environment)) as *mut c_void;
+            unsafe{ dispatch_async_f(&_dispatch_main_q, context, drop_thunk::<E>) }
+        }
+    }
+}
+
+/**
+Environment wrapper used by `new_thread_affine`.
+
+In debug builds with `std`, records the creating thread and aborts if the contents are accessed or dropped from another thread.
+*/
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct ThreadAffine<T> {
+    #[cfg(all(debug_assertions, feature = "std"))]
+    thread: std::thread::ThreadId,
+    value: T,
+}
+//Safety: the creator of the block promises to use it only from the creating thread, which is checked in debug builds.
+unsafe impl<T> Send for ThreadAffine<T> {}
+unsafe impl<T> Sync for ThreadAffine<T> {}
+impl<T> ThreadAffine<T> {
+    pub fn new(value: T) -> Self {
+        ThreadAffine {
+            #[cfg(all(debug_assertions, feature = "std"))]
+            thread: std::thread::current().id(),
+            value
+        }
+    }
+    #[inline] fn check(&self, operation: &str) {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        if std::thread::current().id() != self.thread {
+            crate::diagnostic::abort(format_args!("blocksr: thread-affine block was {} on {:?}, but it was created on {:?}", operation, std::thread::current().id(), self.thread));
+        }
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        let _ = operation;
+    }
+    pub fn get(&self) -> &T {
+        self.check("invoked");
+        &self.value
+    }
+    pub fn get_mut(&mut self) -> &mut T {
+        self.check("invoked");
+        &mut self.value
+    }
+}
+impl<T> Drop for ThreadAffine<T> {
+    fn drop(&mut self) {
+        self.check("disposed");
+    }
+}
+
+#[cfg(all(debug_assertions, feature = "std"))]
+extern "C" {
+    fn dispatch_queue_set_specific(queue: *const c_void, key: *const c_void, context: *mut c_void, destructor: Option<extern "C" fn(*mut c_void)>);
+    fn dispatch_get_specific(key: *const c_void) -> *mut c_void;
+}
+
+//marks queues that values are confined to, with the queue itself as the value
+#[cfg(all(debug_assertions, feature = "std"))]
+static QUEUE_KEY: u8 = 0;
+
+/**
+A value that is only used on one serial dispatch queue.
+
+GCD code often guarantees that a block only runs on a particular serial queue.  That is weaker than `Send` with no
+re-entrancy, since the queue may run on any thread, but it still rules out concurrent access.  Wrapping a value that isn't
+`Send` or `Sync` in this type asserts the guarantee, and the many macros' `new_queue_confined` uses it to accept such
+closures and environments.
+
+In debug builds with the `std` feature, each access checks with `dispatch_get_specific` that it is on the queue, or a
+queue targeting it, and aborts with a message if not.  Release and `no_std` builds don't check.
+*/
+#[derive(Debug)]
+pub struct QueueConfined<T> {
+    #[cfg(all(debug_assertions, feature = "std"))]
+    queue: *const c_void,
+    value: T,
+}
+//Safety: the creator promises that the value is only used on one serial queue, which is checked in debug builds.
+unsafe impl<T> Send for QueueConfined<T> {}
+unsafe impl<T> Sync for QueueConfined<T> {}
+
+impl<T> QueueConfined<T> {
+    /**
+    Confines `value` to `queue`, a `dispatch_queue_t`.
+
+    # Safety
+    You must verify that `queue` is a serial queue, and that the value is only accessed and dropped on it.
+    */
+    pub unsafe fn new(queue: *const c_void, value: T) -> Self {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        {
+            let key = core::ptr::addr_of!(QUEUE_KEY) as *const c_void;
+            unsafe{ dispatch_queue_set_specific(queue, key, queue as *mut c_void, None) };
+            QueueConfined{queue, value}
+        }
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        {
+            let _ = queue;
+            QueueConfined{value}
+        }
+    }
+    #[inline] fn check(&self, operation: &str) {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        {
+            let current = unsafe{ dispatch_get_specific(core::ptr::addr_of!(QUEUE_KEY) as *const c_void) } as *const c_void;
+            if current != self.queue {
+                crate::diagnostic::abort(format_args!("blocksr: queue-confined value was {} off its queue {:p} (on {:?})", operation, self.queue, std::thread::current().id()));
+            }
+        }
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        let _ = operation;
+    }
+    ///The value.  Aborts in debug builds if not called on the queue.
+    pub fn get(&self) -> &T {
+        self.check("accessed");
+        &self.value
+    }
+    ///The value.  Aborts in debug builds if not called on the queue.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.check("accessed");
+        &mut self.value
+    }
+}
+impl<T> Drop for QueueConfined<T> {
+    fn drop(&mut self) {
+        self.check("dropped");
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 #[doc(hidden)]
@@ -62,11 +515,230 @@ pub struct BlockLiteralManyEscape {
     We could forego the static by allocating descriptors dynamically but then we run into the issue
     that each unique closure type may be of different size, etc.
 
-    This is a boxed pointer to some Payload type.
+    This is a pointer to some Payload type, from alloc_payload.
     */
     pub payload: *mut c_void,
 
     pub dispose: extern "C" fn(*mut BlockLiteralManyEscape),
+    //moves the environment into the second argument and drops the closure.  None if there is no environment.
+    pub take_environment: Option<unsafe extern "C" fn(*mut BlockLiteralManyEscape, *mut c_void)>,
+    //for clone-on-copy blocks, returns a clone of the payload for a new heap copy.  None to share the payload.
+    pub clone_payload: Option<unsafe extern "C" fn(*mut BlockLiteralManyEscape) -> *mut c_void>,
+}
+
+#[doc(hidden)]
+pub unsafe extern "C" fn clone_payload_thunk<G: Clone,H: Clone>(block: *mut BlockLiteralManyEscape) -> *mut c_void {
+    let original = unsafe{ live_payload(block) };
+    let payload: &Payload<G,H> = unsafe{ &*(original as *const Payload<G,H>) };
+    let cloned = Payload{closure: payload.closure.clone(), environment: payload.environment.clone()};
+    let copy = crate::arena::alloc_payload(cloned);
+    //the clone was created where the original was
+    #[cfg(all(debug_assertions, feature = "std"))]
+    if let Some(location) = crate::provenance::created_at(original) {
+        crate::provenance::record(copy, location);
+    }
+    copy
+}
+
+#[doc(hidden)]
+pub unsafe extern "C" fn take_environment_thunk<G,H>(block: *mut BlockLiteralManyEscape, out: *mut c_void) {
+    let Payload{closure, environment}: Payload<G,H> = unsafe{ crate::arena::take_payload(live_payload(block)) };
+    core::mem::drop(closure);
+    unsafe{ core::ptr::write(out as *mut H, environment) };
+}
+
+//dispose for a block whose environment was taken
+pub(crate) extern "C" fn dispose_taken(_block: *mut BlockLiteralManyEscape) {}
+
+/**
+Clears a block's `take_environment`, for constructors that store the caller's environment inside a wrapper, such as a
+`Box` or a `WithDispose`.  `HeapBlock::try_into_environment` then returns the block, rather than moving the wrapper
+out as if it were the environment, and skipping what the wrapper does on dispose.
+*/
+#[doc(hidden)]
+pub fn opaque_environment<T: ManyLiteral>(mut block: T) -> T {
+    //Safety: ManyLiteral types are a BlockLiteralManyEscape
+    unsafe{ (*(&mut block as *mut T as *mut BlockLiteralManyEscape)).take_environment = None };
+    block
+}
+
+///Implemented by block types whose literal is a [BlockLiteralManyEscape].
+#[doc(hidden)]
+pub unsafe trait ManyLiteral {}
+
+///Implemented by `many_escaping_reentrant` block types, which may be invoked from any thread, concurrently.
+#[doc(hidden)]
+pub unsafe trait ReentrantLiteral: ManyLiteral {}
+
+/**
+Declares `new_aggregating` on a `many` block type, with the `std` feature.
+*/
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __aggregating {
+    (nonreentrant ($($a:ident : $A:ty),*) -> $R:ty) => {
+        ///Creates a new escaping block, which folds each return value into an accumulator, starting from `init`.
+        ///
+        /// Returns an [blocksr::Aggregate] for the accumulator alongside the block.  `fold` runs after each
+        /// invocation, under a lock.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_aggregating<C,E,T,G>(environment: E, init: T, fold: G, f: C) -> (Self, blocksr::Aggregate<T>) where C: FnMut(&mut E, $($A),*) -> $R + Send + 'static, G: FnMut(&mut T, &$R) + Send + 'static, T: Send + 'static {
+            let mut f = f;
+            let mut fold = fold;
+            let aggregate = blocksr::Aggregate::__new(init);
+            let shared = aggregate.__shared();
+            let block = Self::new(environment, move |environment: &mut E, $($a),*| {
+                let r = f(environment, $($a),*);
+                blocksr::hidden::aggregate(&shared, &mut fold, &r);
+                r
+            });
+            (block, aggregate)
+        }
+    };
+    (reentrant ($($a:ident : $A:ty),*) -> $R:ty) => {
+        ///Creates a new escaping block, which folds each return value into an accumulator, starting from `init`.
+        ///
+        /// Returns an [blocksr::Aggregate] for the accumulator alongside the block.  `fold` runs after each
+        /// invocation, under a lock, so concurrent invocations fold one at a time.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_aggregating<C,E,T,G>(environment: E, init: T, fold: G, f: C) -> (Self, blocksr::Aggregate<T>) where C: Fn(&E, $($A),*) -> $R + Send + Sync + 'static, G: Fn(&mut T, &$R) + Send + Sync + 'static, T: Send + 'static, E: Sync {
+            let aggregate = blocksr::Aggregate::__new(init);
+            let shared = aggregate.__shared();
+            let block = Self::new(environment, move |environment: &E, $($a),*| {
+                let r = f(environment, $($a),*);
+                blocksr::hidden::aggregate(&shared, &fold, &r);
+                r
+            });
+            (block, aggregate)
+        }
+    };
+}
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __aggregating {
+    ($($t:tt)*) => {};
+}
+
+/**
+Declares `new_shared` and `new_shared_rw` on a `many` block type, and `new_serialized` on a reentrant one, with the
+`std` feature.
+*/
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __shared {
+    (nonreentrant ($($a:ident : $A:ty),*) -> $R:ty) => {
+        ///Creates a new escaping block, whose environment is shared with the caller behind a mutex.
+        ///
+        /// Returns the `Arc` alongside the block.  Each invocation locks the mutex, so code outside the block, such as
+        /// an async task awaiting its results, can read or change the environment between invocations.  The block
+        /// releases its reference when it is disposed, and the environment is dropped when the last reference is.
+        /// If the mutex was poisoned by a panic elsewhere, invocations still lock it.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_shared<C,E>(environment: E, f: C) -> (Self, blocksr::hidden::Arc<blocksr::hidden::Mutex<E>>) where C: FnMut(&mut E, $($A),*) -> $R + Send + 'static, E: Send + 'static {
+            let mut f = f;
+            let shared = blocksr::hidden::Arc::new(blocksr::hidden::Mutex::new(environment));
+            let block = blocksr::hidden::opaque_environment(Self::new(shared.clone(), move |shared: &mut blocksr::hidden::Arc<blocksr::hidden::Mutex<E>>, $($a),*| {
+                let mut environment = shared.lock().unwrap_or_else(|e| e.into_inner());
+                f(&mut environment, $($a),*)
+            }));
+            (block, shared)
+        }
+        ///Creates a new escaping block, whose environment is shared with the caller behind a reader-writer lock.
+        ///
+        /// Like `new_shared`, but each invocation takes the write lock, so other code may read the environment
+        /// concurrently with itself.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_shared_rw<C,E>(environment: E, f: C) -> (Self, blocksr::hidden::Arc<blocksr::hidden::RwLock<E>>) where C: FnMut(&mut E, $($A),*) -> $R + Send + 'static, E: Send + Sync + 'static {
+            let mut f = f;
+            let shared = blocksr::hidden::Arc::new(blocksr::hidden::RwLock::new(environment));
+            let block = blocksr::hidden::opaque_environment(Self::new(shared.clone(), move |shared: &mut blocksr::hidden::Arc<blocksr::hidden::RwLock<E>>, $($a),*| {
+                let mut environment = shared.write().unwrap_or_else(|e| e.into_inner());
+                f(&mut environment, $($a),*)
+            }));
+            (block, shared)
+        }
+    };
+    (reentrant ($($a:ident : $A:ty),*) -> $R:ty) => {
+        ///Creates a new escaping block, whose environment is shared with the caller behind a mutex.
+        ///
+        /// Returns the `Arc` alongside the block.  Each invocation locks the mutex, so concurrent invocations take
+        /// turns, and may mutate the environment.  Code outside the block, such as an async task awaiting its
+        /// results, can read or change it between invocations.  The block releases its reference when it is
+        /// disposed, and the environment is dropped when the last reference is.  If the mutex was poisoned by a panic
+        /// elsewhere, invocations still lock it.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_shared<C,E>(environment: E, f: C) -> (Self, blocksr::hidden::Arc<blocksr::hidden::Mutex<E>>) where C: Fn(&mut E, $($A),*) -> $R + Send + Sync + 'static, E: Send + 'static {
+            let shared = blocksr::hidden::Arc::new(blocksr::hidden::Mutex::new(environment));
+            let block = blocksr::hidden::opaque_environment(Self::new(shared.clone(), move |shared: &blocksr::hidden::Arc<blocksr::hidden::Mutex<E>>, $($a),*| {
+                let mut environment = shared.lock().unwrap_or_else(|e| e.into_inner());
+                f(&mut environment, $($a),*)
+            }));
+            (block, shared)
+        }
+        ///Creates a new escaping block, whose environment is shared with the caller behind a reader-writer lock.
+        ///
+        /// Like `new_shared`, but each invocation takes the read lock, so concurrent invocations run at once, and
+        /// receive `&E` as with `new`.  Code outside the block takes the write lock to change the environment.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_shared_rw<C,E>(environment: E, f: C) -> (Self, blocksr::hidden::Arc<blocksr::hidden::RwLock<E>>) where C: Fn(&E, $($A),*) -> $R + Send + Sync + 'static, E: Send + Sync + 'static {
+            let shared = blocksr::hidden::Arc::new(blocksr::hidden::RwLock::new(environment));
+            let block = blocksr::hidden::opaque_environment(Self::new(shared.clone(), move |shared: &blocksr::hidden::Arc<blocksr::hidden::RwLock<E>>, $($a),*| {
+                let environment = shared.read().unwrap_or_else(|e| e.into_inner());
+                f(&environment, $($a),*)
+            }));
+            (block, shared)
+        }
+        ///Creates a new escaping block from an `FnMut` closure, whose invocations take turns.
+        ///
+        /// For APIs that may call a block concurrently, from threads the caller doesn't control, this locks a mutex
+        /// around the closure and environment for each invocation, so the closure may mutate them without
+        /// synchronization of its own, at the cost of the lock.  Unlike `many_escaping_nonreentrant`, this is sound
+        /// however the block is called.  The one exception is the closure invoking its own block, which would
+        /// deadlock, so it panics instead.  If an invocation panicked, later ones still run.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn new_serialized<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $($A),*) -> $R + Send + 'static, E: Send + 'static {
+            blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::Serialized::new((f, environment)), move |serialized: &blocksr::hidden::Serialized<(C, E)>, $($a),*| {
+                serialized.with(core::any::type_name::<Self>(), |(f, environment)| f(environment, $($a),*))
+            }))
+        }
+    };
+}
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __shared {
+    ($($t:tt)*) => {};
 }
 
 /**
@@ -89,7 +761,12 @@ You must verify that
  * Arguments and return types are correct and in the expected order
      * Arguments and return types are FFI-safe (compiler usually warns)
  * Function will not be called in a re-entrant manner.  I believe this is required for FnMut, although I have not proven it.
-   For re-entrant blocks, see [crate::many_escaping_reentrant].
+   For re-entrant blocks, see [crate::many_escaping_reentrant], whose `new_serialized` takes an `FnMut` closure and
+   serializes invocations itself.  In debug builds with `std`, a re-entrant or concurrent
+   call aborts with a message naming the block.
+
+Arguments that are only valid during the invocation may be declared `name: Raw as Owned`, where `Owned`
+implements [blocksr::CopyArgument].  The closure then receives an owned value.
 
 The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
 Typically, you want to declare the pointer type `Arguable` in objr to pass it into objc, e.g.
@@ -122,22 +799,139 @@ let f = unsafe{ MyBlock::new(23, |environment| {
 
 The environment is dropped when the block is dropped, with assistance from the ObjC runtime.  This will occur
 sometime after the last execution.
+If there is nothing to drop or free, because the closure and environment are zero-sized and have no drop glue
+(e.g. `()` and a closure that captures nothing), the block is created without copy and dispose helpers at all.
+
+To run cleanup at that moment (e.g. to unregister something, or signal a channel), use `new_with_dispose`.  Its
+`on_dispose` callback runs after the closure and environment have been dropped.
+
+Since the block may be released on any queue, so may the environment be dropped there.  Environments that must
+deallocate on the main thread (e.g. ones holding views) should use `new_drop_on_main`, on Apple platforms.
+
+To keep an objc receiver alive until the block is disposed, pass it to `new_keeping_alive`.
+
+The environment is stored inline, next to the closure.  To box a large one separately, so it doesn't crowd the
+closure's captures, use `new_indirect`.  To build it in that box, rather than moving it there, use `new_in_place`.
+Large buffers belong in the environment rather than the closure's captures, since a closure can't be built in place.
+
+To share the environment with code outside the block, such as the async task that created it, `new_shared` wraps it in
+an `Arc<Mutex<E>>`, locked for each invocation, and returns a clone alongside the block, with the `std` feature.
+`new_shared_rw` uses an `RwLock` instead.
+
+For cooperative cancellation, `new_cancellable` returns a [blocksr::CancellationToken] alongside the block.  Once it is
+cancelled, invocations return `Default::default()` without running the closure.
+
+To gather results across invocations, as enumeration APIs need, `new_aggregating` folds each return value into an
+accumulator, and returns a `blocksr::Aggregate` for it alongside the block, with the `std` feature.
+
+Blocks following the ObjC error convention may be declared with a trailing `; throws error: *mut *mut NSError`,
+and created with `new_throwing` from a closure that returns a [Result].  See [blocksr::ThrowingReturn].
+
+By default, every heap copy of a block shares one environment.  To give each copy its own clone instead, as C does
+for captures, append `; clone_on_copy` to the declaration and create the block with `new_clone_on_copy`.
+
+As with [crate::once_escaping], a declaration prefixed with `isa = global;` or `isa = MY_ISA;` creates blocks of that
+class instead of stack blocks.  Since global blocks are never disposed, their environment is leaked.
+
+The closure must be `Send`.  For closures and environments that are tied to one thread, `new_thread_affine` checks
+in debug builds that the block is only invoked and disposed on the creating thread.  For ones confined to a serial dispatch queue,
+`new_queue_confined` checks that it is only invoked and disposed on the queue.
+
+# Memory ordering
+
+Successive invocations may run on different threads.  Each one acquires the writes of the one before, and releases its
+own, like taking and releasing a lock, so the closure and environment need no synchronization of their own.  Disposal
+acquires every invocation, like dropping the last `Arc`, so the environment's destructor sees them
+all.
 
  */
 #[macro_export]
 macro_rules! many_escaping_nonreentrant(
 
     (
-        $pub:vis $blockname: ident (environment: &mut $environment:ty $(,$a:ident : $A:ty)*) -> $R:ty
+        isa = $isa:tt; $pub:vis $blockname: ident $($rest:tt)*
+    ) => {
+        blocksr::many_escaping_nonreentrant!($pub $blockname $($rest)*);
+        impl $blockname {
+            #[doc(hidden)]
+            #[allow(unused_parens)] //isa = (expression)
+            fn __isa() -> *const core::ffi::c_void {
+                blocksr::__isa!($isa)
+            }
+            #[doc(hidden)]
+            const __GLOBAL: bool = blocksr::__isa_global!($isa);
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &mut $environment:ty $(,$a:ident : $A:ty $(as $O:ty)?)*) -> $R:ty; throws $e:ident : $E:ty
+    ) => {
+        blocksr::many_escaping_nonreentrant!($pub $blockname (environment: &mut $environment $(,$a : $A $(as $O)?)*, $e : $E) -> $R);
+        impl $blockname {
+            ///Creates a new escaping block from a closure returning a [Result].
+            ///
+            /// `Ok` is converted to the return value for success.  An error is written through the trailing out-parameter,
+            /// if it isn't null, and the return value for failure is returned.  See [blocksr::ThrowingReturn].
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_throwing<C,E,T,X,V>(environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> Result<T,X> + Send + 'static, $R: blocksr::ThrowingReturn<T>, $E: blocksr::OutParameter<V>, X: blocksr::IntoOutError<V> {
+                let mut f = f;
+                Self::new(environment, move |environment: &mut E, $($a,)* $e| {
+                    unsafe{ blocksr::hidden::report_result(f(environment, $($a),*), $e) }
+                })
+            }
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &mut $environment:ty $(,$a:ident : $A:ty $(as $O:ty)?)*) -> $R:ty; clone_on_copy
+    ) => {
+        blocksr::many_escaping_nonreentrant!($pub $blockname (environment: &mut $environment $(,$a : $A $(as $O)?)*) -> $R);
+        impl $blockname {
+            ///Creates a new escaping block, where each heap copy gets its own clone of the closure and environment.
+            ///
+            /// This matches C's per-copy capture semantics.  The block value itself keeps the original, which is dropped
+            /// with it.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_clone_on_copy<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Clone + 'static, E: Clone + 'static {
+                let mut block = Self::new(environment, f);
+                block.0.clone_payload = Some(blocksr::hidden::clone_payload_thunk::<C,E>);
+                block
+            }
+        }
+        impl Drop for $blockname {
+            fn drop(&mut self) {
+                //heap copies own their clones; the original is ours
+                if self.0.clone_payload.is_some() {
+                    (self.0.dispose)(&mut self.0)
+                }
+            }
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &mut $environment:ty $(,$a:ident : $A:ty $(as $O:ty)?)*) -> $R:ty
     ) => {
 
 
         //must be ffi-safe
         #[repr(transparent)]
-        #[derive(Debug)]
         #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
         $pub struct $blockname(blocksr::hidden::BlockLiteralManyEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
+        blocksr::__block_type!($blockname; ($($A),*) -> $R);
+        unsafe impl blocksr::hidden::ManyLiteral for $blockname {}
         impl $blockname {
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>() | blocksr::hidden::BLOCK_HAS_COPY_DISPOSE);
+            blocksr::__descriptor!(many);
 
             ///Creates a new escaping block.
             ///
@@ -146,45 +940,217 @@ macro_rules! many_escaping_nonreentrant(
             /// * Arguments and return types are correct and in the expected order
             ///     * Arguments and return types are FFI-safe (compiler usually warns)
             /// *  Function will not be called in a re-entrant manner.  I believe this is required for FnMut, although I have not proven it.
+            ///
             /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
-            pub unsafe fn new<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $($A),*) -> $R + Send + 'static {
+            #[track_caller]
+            pub unsafe fn new<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static {
+                blocksr::hidden::unwrap_alloc(Self::try_new(environment, f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, the environment and `f` are dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<C,E>(environment: E, f: C) -> Result<Self, blocksr::arena::AllocError> where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static {
                 //This thunk is safe to call from C
-                extern "C" fn invoke_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape, $($a : $A),*) -> $R where G: FnMut(&mut H, $($A),*) -> $R + Send {
-                    let payload_ptr = unsafe{(*block).payload} as *mut _ as *mut blocksr::hidden::Payload<G,H>;
-                    let mut boxed_payload: Box<blocksr::hidden::Payload<G,H>> = unsafe {Box::from_raw(payload_ptr)};
-                    let closure: &mut G = &mut boxed_payload.closure;
-                    let environment: &mut H = &mut boxed_payload.environment;
-                    let r = closure(environment, $($a),*);
-                    std::mem::forget(boxed_payload);
-                    r
+                extern "C" fn invoke_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape, $($a : $A),*) -> $R where G: FnMut(&mut H, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send {
+                    let payload_ptr = unsafe{ blocksr::hidden::live_payload(block) };
+                    //checked before the payload is borrowed
+                    let _guard = unsafe{ blocksr::hidden::enter_nonreentrant::<G,H>(payload_ptr, stringify!($blockname)) };
+                    let payload: &mut blocksr::hidden::Payload<blocksr::hidden::Nonreentrant<G>,H> = unsafe{ &mut *(payload_ptr as *mut blocksr::hidden::Payload<blocksr::hidden::Nonreentrant<G>,H>) };
+                    let closure: &mut G = payload.closure.closure_mut();
+                    let environment: &mut H = &mut payload.environment;
+                    closure(environment, $(blocksr::__adapt!($a: $A $(as $O)?)),*)
                 }
 
                 extern "C" fn dispose_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape) {
-                    let payload_ptr = unsafe{(*block).payload} as *mut _ as *mut blocksr::hidden::Payload<G,H>;
-                    let mut boxed_payload: Box<blocksr::hidden::Payload<G,H>> = unsafe {Box::from_raw(payload_ptr)};
+                    let _payload: blocksr::hidden::Payload<G,H> = unsafe{ blocksr::hidden::take_payload(blocksr::hidden::live_payload(block)) };
                     //drop
                 }
 
                 let thunk_fn: *const core::ffi::c_void = invoke_thunk::<C,E> as *const core::ffi::c_void;
                 //make payload
                 let payload = blocksr::hidden::Payload {
-                    closure: f,
+                    closure: blocksr::hidden::Nonreentrant::new(f),
                     environment
                 };
-                //box payload
-                let boxed_load = Box::new(payload);
                 //note: this leak will be cleaned up by dispose
-                let raw_load = Box::into_raw(boxed_load) as *mut _ as *mut core::ffi::c_void;
+                let raw_load = blocksr::hidden::try_alloc_payload(payload)?;
+                let (flags, descriptor) = blocksr::hidden::many_descriptor::<blocksr::hidden::Nonreentrant<C>,E>(Self::FLAGS.bits(), Self::__DESCRIPTOR);
                 let literal = blocksr::hidden::BlockLiteralManyEscape {
-                    isa: &blocksr::hidden::_NSConcreteStackBlock,
-                    flags: blocksr::hidden::BLOCK_HAS_STRET | blocksr::hidden::BLOCK_HAS_COPY_DISPOSE,
-                    reserved: std::mem::MaybeUninit::uninit().assume_init(),
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags,
+                    reserved: 0,
                     invoke: thunk_fn ,
-                    descriptor: &mut blocksr::hidden::BLOCK_DESCRIPTOR_MANY as *mut _ as *mut core::ffi::c_void,
+                    descriptor,
                     payload: raw_load,
-                    dispose: dispose_thunk::<C,E>,
+                    dispose: dispose_thunk::<blocksr::hidden::Nonreentrant<C>,E>,
+                    take_environment: Some(blocksr::hidden::take_environment_thunk::<blocksr::hidden::Nonreentrant<C>,E>),
+                    clone_payload: None,
                 };
-                $blockname(literal)
+                Ok($blockname(literal))
+            }
+            ///Creates a new escaping block, which calls `on_dispose` when it is disposed.
+            ///
+            /// `on_dispose` runs after the closure and then the environment are dropped, which is after the final
+            /// invocation has returned.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_with_dispose<C,E,D>(environment: E, f: C, on_dispose: D) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, D: FnOnce() + Send + 'static, E: 'static {
+                let mut f = f;
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::WithDispose::new(environment, on_dispose), move |environment: &mut blocksr::hidden::WithDispose<E,D>, $($a),*| {
+                    f(environment.environment_mut(), $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, whose environment is dropped on the main thread.
+            ///
+            /// This is required for environments holding objects that must deallocate on main, such as views.
+            /// If the block is disposed on another thread, the environment's drop is sent to the main queue.
+            /// The closure itself is still dropped wherever the block is disposed.  Apple platforms only.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, `E` needn't be `Send`, so you must verify that
+            /// it may be used on every thread the block is invoked on, and then sent to the main thread to be dropped.
+            #[cfg(target_vendor = "apple")]
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_drop_on_main<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, E: 'static {
+                let mut f = f;
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::DropOnMain::new(environment), move |environment: &mut blocksr::hidden::DropOnMain<E>, $($a),*| {
+                    f(environment.environment_mut(), $($a),*)
+                }))
+            }
+            ///Creates a new escaping block for a closure and environment that are not `Send`.
+            ///
+            /// In debug builds with the `std` feature, the creating thread is recorded, and the process aborts with a message if the block
+            /// is invoked or disposed on a different thread.  Release and `no_std` builds don't check.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, you must verify that the block is only invoked and
+            /// disposed on the creating thread.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_thread_affine<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + 'static, E: 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::ThreadAffine::new((environment, f)), |affine: &mut blocksr::hidden::ThreadAffine<(E,C)>, $($a),*| {
+                    let (environment, f) = affine.get_mut();
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block for a closure and environment that are not `Send`, but are confined to the
+            ///serial dispatch queue `queue`.  See [blocksr::QueueConfined].
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, you must verify that `queue` is serial, and that
+            /// the block is only invoked and disposed on it.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_queue_confined<C,E>(queue: *const core::ffi::c_void, environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + 'static, E: 'static {
+                blocksr::hidden::opaque_environment(Self::new(unsafe{ blocksr::QueueConfined::new(queue, (environment, f)) }, |confined: &mut blocksr::QueueConfined<(E,C)>, $($a),*| {
+                    let (environment, f) = confined.get_mut();
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, which passes a reference to each return value to `tee`.
+            ///
+            /// This is useful when objc ignores the return value, but you need it, for example to send it on a channel.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_teeing<C,E,T>(environment: E, f: C, tee: T) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, T: FnMut(&$R) + Send + 'static {
+                let mut f = f;
+                let mut tee = tee;
+                Self::new(environment, move |environment: &mut E, $($a),*| {
+                    let r = f(environment, $($a),*);
+                    tee(&r);
+                    r
+                })
+            }
+            ///Creates a new escaping block, which holds `keep_alive` until it is disposed.
+            ///
+            /// Use this to keep an objc receiver (session, device, etc.) alive for the block's lifetime.  `keep_alive` is
+            /// dropped after the closure and environment.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_keeping_alive<C,E,K>(environment: E, keep_alive: K, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, K: Send + 'static, E: 'static {
+                let mut f = f;
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::KeepAlive::new(environment, keep_alive), move |environment: &mut blocksr::hidden::KeepAlive<E,K>, $($a),*| {
+                    f(environment.environment_mut(), $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, whose environment is boxed separately from the closure.
+            ///
+            /// By default, the environment is stored inline, next to the closure, in one allocation.  For a large
+            /// environment, such as a ring buffer or a cache, that puts the closure's captures on a different cache
+            /// line from the block.  This stores it behind its own pointer instead, for one more allocation and one
+            /// more indirection when the closure uses the environment.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_indirect<C,E>(environment: E, f: C) -> Self where C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, E: 'static {
+                let mut f = f;
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::Box::new(environment), move |environment: &mut blocksr::hidden::Box<E>, $($a),*| {
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, whose environment `init` initializes directly in its heap allocation.
+            ///
+            /// Passing a large environment, like a buffer of several kilobytes, to `new_indirect` builds it on the stack
+            /// and copies it into the box.  This allocates the box first, and `init` writes the environment into it and
+            /// returns it.  The block stores the box as `new_indirect` does.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            ///
+            /// # Panics
+            /// If `init` returns a reference to anything but the slot it was given.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_in_place<C,E,I>(init: I, f: C) -> Self where I: for<'a> FnOnce(&'a mut core::mem::MaybeUninit<E>) -> &'a mut E, C: FnMut(&mut E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, E: 'static {
+                let mut f = f;
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::box_in_place(init), move |environment: &mut blocksr::hidden::Box<E>, $($a),*| {
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block with a [blocksr::CancellationToken].
+            ///
+            /// After the token is cancelled, invocations return `Default::default()` without running `f`.  `f` receives
+            /// the token, to check during long invocations.  The token is also cancelled when the block is disposed.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_cancellable<C,E>(environment: E, f: C) -> (Self, blocksr::CancellationToken) where C: FnMut(&mut E, &blocksr::CancellationToken, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + blocksr::hidden::DefaultReturn<$R> + 'static, E: 'static {
+                let mut f = f;
+                let token = blocksr::CancellationToken::new();
+                let block = blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::Cancellable::new(environment, token.clone()), move |cancellable: &mut blocksr::hidden::Cancellable<E>, $($a),*| {
+                    let (environment, token) = cancellable.parts_mut();
+                    if token.is_cancelled() {
+                        return C::default_return();
+                    }
+                    f(environment, token, $($a),*)
+                }));
+                (block, token)
+            }
+            blocksr::__aggregating!(nonreentrant ($($a : blocksr::__adapted!($A $(as $O)?)),*) -> $R);
+            blocksr::__shared!(nonreentrant ($($a : blocksr::__adapted!($A $(as $O)?)),*) -> $R);
+            ///Views this block as a generic [blocksr::Block].
+            #[allow(dead_code)]
+            pub fn as_block(&self) -> &blocksr::Block<($($A,)*),$R> {
+                unsafe{ &*(self as *const Self as *const blocksr::Block<($($A,)*),$R>) }
             }
 
         }
@@ -215,6 +1181,9 @@ You must verify that
  * Arguments and return types are correct and in the expected order
      * Arguments and return types are FFI-safe (compiler usually warns)
 
+Arguments that are only valid during the invocation may be declared `name: Raw as Owned`, where `Owned`
+implements [blocksr::CopyArgument].  The closure then receives an owned value.
+
 The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
 Typically, you want to declare the pointer type `Arguable` in objr to pass it into objc, e.g.
 
@@ -246,22 +1215,224 @@ let f = unsafe{ MyBlock::new(23, |environment| {
 
 The environment is dropped when the block is dropped, with assistance from the ObjC runtime.  This will occur
 sometime after the last execution.
+If there is nothing to drop or free, because the closure and environment are zero-sized and have no drop glue
+(e.g. `()` and a closure that captures nothing), the block is created without copy and dispose helpers at all.
+
+To run cleanup at that moment (e.g. to unregister something, or signal a channel), use `new_with_dispose`.  Its
+`on_dispose` callback runs after the closure and environment have been dropped.
+
+Since the block may be released on any queue, so may the environment be dropped there.  Environments that must
+deallocate on the main thread (e.g. ones holding views) should use `new_drop_on_main`, on Apple platforms.
+
+To keep an objc receiver alive until the block is disposed, pass it to `new_keeping_alive`.
+
+The environment is stored inline, next to the closure.  To box a large one separately, so it doesn't crowd the
+closure's captures, use `new_indirect`.  To build it in that box, rather than moving it there, use `new_in_place`.
+Large buffers belong in the environment rather than the closure's captures, since a closure can't be built in place.
+
+To share the environment with code outside the block, such as the async task that created it, `new_shared` wraps it in
+an `Arc<Mutex<E>>`, locked for each invocation, and returns a clone alongside the block, with the `std` feature.
+`new_shared_rw` uses an `RwLock` instead.
+
+For cooperative cancellation, `new_cancellable` returns a [blocksr::CancellationToken] alongside the block.  Once it is
+cancelled, invocations return `Default::default()` without running the closure.
+
+To gather results across invocations, as enumeration APIs need, `new_aggregating` folds each return value into an
+accumulator, and returns a `blocksr::Aggregate` for it alongside the block, with the `std` feature.
+
+Blocks following the ObjC error convention may be declared with a trailing `; throws error: *mut *mut NSError`,
+and created with `new_throwing` from a closure that returns a [Result].  See [blocksr::ThrowingReturn].
+
+By default, every heap copy of a block shares one environment.  To give each copy its own clone instead, as C does
+for captures, append `; clone_on_copy` to the declaration and create the block with `new_clone_on_copy`.
+
+Where an API may call the block concurrently, but the closure needs to mutate its state, `new_serialized` takes an
+`FnMut` closure and makes invocations take turns on a mutex, with the `std` feature:
+
+```
+use blocksr::many_escaping_reentrant;
+many_escaping_reentrant!(Counter (environment: &u32, increment: u32) -> u32);
+let f = unsafe{ Counter::new_serialized(0u32, |count: &mut u32, increment| {
+    *count += increment;
+    *count
+})};
+//pass f to an API that calls it from any thread...
+```
+
+The closure and environment must be `Send` and `Sync`: concurrent invocations share them, so any state they mutate
+needs its own synchronization, such as an atomic or a `Mutex`.  Disposal acquires every invocation, like dropping the
+last `Arc`, so the environment's destructor sees them all.  For closures and environments that are tied to one thread, `new_thread_affine` checks
+in debug builds that the block is only invoked and disposed on the creating thread.  For ones confined to a serial dispatch queue,
+`new_queue_confined` checks that it is only invoked and disposed on the queue.
+
+# Variadic blocks
+
+With the nightly `c_variadic` feature, blocks may end in `...`.  The closure receives the variable arguments
+as a trailing `blocksr::VaArgs`:
+
+```ignore
+use blocksr::many_escaping_reentrant;
+many_escaping_reentrant!(LogBlock (environment: &(), level: i32, format: *const core::ffi::c_char, ...) -> ());
+```
 
  */
 #[macro_export]
 macro_rules! many_escaping_reentrant(
 
     (
-        $pub:vis $blockname: ident (environment: &$environment:ty $(,$a:ident : $A:ty)*) -> $R:ty
+        isa = $isa:tt; $pub:vis $blockname: ident $($rest:tt)*
+    ) => {
+        blocksr::many_escaping_reentrant!($pub $blockname $($rest)*);
+        impl $blockname {
+            #[doc(hidden)]
+            #[allow(unused_parens)] //isa = (expression)
+            fn __isa() -> *const core::ffi::c_void {
+                blocksr::__isa!($isa)
+            }
+            #[doc(hidden)]
+            const __GLOBAL: bool = blocksr::__isa_global!($isa);
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &$environment:ty $(,$a:ident : $A:ty $(as $O:ty)?)*) -> $R:ty; throws $e:ident : $E:ty
+    ) => {
+        blocksr::many_escaping_reentrant!($pub $blockname (environment: &$environment $(,$a : $A $(as $O)?)*, $e : $E) -> $R);
+        impl $blockname {
+            ///Creates a new escaping block from a closure returning a [Result].
+            ///
+            /// `Ok` is converted to the return value for success.  An error is written through the trailing out-parameter,
+            /// if it isn't null, and the return value for failure is returned.  See [blocksr::ThrowingReturn].
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_throwing<C,E,T,X,V>(environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> Result<T,X> + Send + Sync + 'static, $R: blocksr::ThrowingReturn<T>, $E: blocksr::OutParameter<V>, X: blocksr::IntoOutError<V>, E: Sync {
+                Self::new(environment, move |environment: &E, $($a,)* $e| {
+                    unsafe{ blocksr::hidden::report_result(f(environment, $($a),*), $e) }
+                })
+            }
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &$environment:ty $(,$a:ident : $A:ty $(as $O:ty)?)*) -> $R:ty; clone_on_copy
+    ) => {
+        blocksr::many_escaping_reentrant!($pub $blockname (environment: &$environment $(,$a : $A $(as $O)?)*) -> $R);
+        impl $blockname {
+            ///Creates a new escaping block, where each heap copy gets its own clone of the closure and environment.
+            ///
+            /// This matches C's per-copy capture semantics.  The block value itself keeps the original, which is dropped
+            /// with it.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_clone_on_copy<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + Clone + 'static, E: Sync + Clone + 'static {
+                let mut block = Self::new(environment, f);
+                block.0.clone_payload = Some(blocksr::hidden::clone_payload_thunk::<C,E>);
+                block
+            }
+        }
+        impl Drop for $blockname {
+            fn drop(&mut self) {
+                //heap copies own their clones; the original is ours
+                if self.0.clone_payload.is_some() {
+                    (self.0.dispose)(&mut self.0)
+                }
+            }
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &$environment:ty $(,$a:ident : $A:ty)* , ...) -> $R:ty
+    ) => {
+
+
+        //must be ffi-safe
+        #[repr(transparent)]
+        #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
+        $pub struct $blockname(blocksr::hidden::BlockLiteralManyEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
+        unsafe impl blocksr::hidden::ManyLiteral for $blockname {}
+        unsafe impl blocksr::hidden::ReentrantLiteral for $blockname {}
+        impl $blockname {
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>() | blocksr::hidden::BLOCK_HAS_COPY_DISPOSE);
+            blocksr::__descriptor!(many);
+
+            ///Creates a new escaping, variadic block.
+            ///
+            /// # Safety
+            /// You must verify that
+            /// * Arguments and return types are correct and in the expected order
+            ///     * Arguments and return types are FFI-safe (compiler usually warns)
+            ///
+            /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
+            #[track_caller]
+            pub unsafe fn new<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $($A,)* &mut blocksr::VaArgs) -> $R + Send + Sync + 'static, E: Sync {
+                blocksr::hidden::unwrap_alloc(Self::try_new(environment, f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, the environment and `f` are dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<C,E>(environment: E, f: C) -> Result<Self, blocksr::arena::AllocError> where C: Fn(&E, $($A,)* &mut blocksr::VaArgs) -> $R + Send + Sync + 'static, E: Sync {
+                extern "C" fn dispose_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape) {
+                    let _payload: blocksr::hidden::Payload<G,H> = unsafe{ blocksr::hidden::take_payload(blocksr::hidden::live_payload(block)) };
+                    //drop
+                }
+
+                let thunk_fn: *const core::ffi::c_void = blocksr::__variadic_thunk!(many [C,E] $R; $($A),*) as *const core::ffi::c_void;
+                //make payload
+                let payload = blocksr::hidden::Payload {
+                    closure: f,
+                    environment
+                };
+                //note: this leak will be cleaned up by dispose
+                let raw_load = blocksr::hidden::try_alloc_payload(payload)?;
+                let (flags, descriptor) = blocksr::hidden::many_descriptor::<C,E>(Self::FLAGS.bits(), Self::__DESCRIPTOR);
+                let literal = blocksr::hidden::BlockLiteralManyEscape {
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags,
+                    reserved: 0,
+                    invoke: thunk_fn ,
+                    descriptor,
+                    payload: raw_load,
+                    dispose: dispose_thunk::<C,E>,
+                    take_environment: Some(blocksr::hidden::take_environment_thunk::<C,E>),
+                    clone_payload: None,
+                };
+                Ok($blockname(literal))
+            }
+
+        }
+
+    };
+
+    (
+        $pub:vis $blockname: ident (environment: &$environment:ty $(,$a:ident : $A:ty $(as $O:ty)?)*) -> $R:ty
     ) => {
 
 
         //must be ffi-safe
         #[repr(transparent)]
-        #[derive(Debug)]
         #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
         $pub struct $blockname(blocksr::hidden::BlockLiteralManyEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
+        blocksr::__block_type!($blockname; ($($A),*) -> $R);
+        unsafe impl blocksr::hidden::ManyLiteral for $blockname {}
+        unsafe impl blocksr::hidden::ReentrantLiteral for $blockname {}
         impl $blockname {
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>() | blocksr::hidden::BLOCK_HAS_COPY_DISPOSE);
+            blocksr::__descriptor!(many);
 
             ///Creates a new escaping block.
             ///
@@ -269,26 +1440,33 @@ macro_rules! many_escaping_reentrant(
             /// You must verify that
             /// * Arguments and return types are correct and in the expected order
             ///     * Arguments and return types are FFI-safe (compiler usually warns)
+            ///
             /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
-            pub unsafe fn new<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $($A),*) -> $R + Send + 'static {
+            #[track_caller]
+            pub unsafe fn new<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, E: Sync {
+                blocksr::hidden::unwrap_alloc(Self::try_new(environment, f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, the environment and `f` are dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<C,E>(environment: E, f: C) -> Result<Self, blocksr::arena::AllocError> where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, E: Sync {
                 //This thunk is safe to call from C
-                extern "C" fn invoke_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape, $($a : $A),*) -> $R where G: Fn(&H, $($A),*) -> $R + Send {
-                    let payload_ptr = unsafe{(*block).payload} as *mut _ as *mut blocksr::hidden::Payload<G,H>;
-                    unsafe {
-                       let boxed_payload: Box<blocksr::hidden::Payload<G,H>> = unsafe {Box::from_raw(payload_ptr)};
-                        //note: we are forbidden to use mutable references here, since functions overlap.
-                        let closure: &G = &boxed_payload.closure;
-                        let environment: &H = &boxed_payload.environment;
-                        let r = closure(environment, $($a),*);
-                        std::mem::forget(boxed_payload);
-                        r
-                    }
+                extern "C" fn invoke_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape, $($a : $A),*) -> $R where G: Fn(&H, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync {
+                    //note: we are forbidden to use mutable references here, since functions overlap.
+                    let payload: &blocksr::hidden::Payload<G,H> = unsafe{ &*(blocksr::hidden::live_payload(block) as *const blocksr::hidden::Payload<G,H>) };
+                    let closure: &G = &payload.closure;
+                    let environment: &H = &payload.environment;
+                    closure(environment, $(blocksr::__adapt!($a: $A $(as $O)?)),*)
 
                 }
 
                 extern "C" fn dispose_thunk<G,H>(block: *mut blocksr::hidden::BlockLiteralManyEscape) {
-                    let payload_ptr = unsafe{(*block).payload} as *mut _ as *mut blocksr::hidden::Payload<G,H>;
-                    let mut boxed_payload: Box<blocksr::hidden::Payload<G,H>> = unsafe {Box::from_raw(payload_ptr)};
+                    let _payload: blocksr::hidden::Payload<G,H> = unsafe{ blocksr::hidden::take_payload(blocksr::hidden::live_payload(block)) };
                     //drop
                 }
 
@@ -298,23 +1476,456 @@ macro_rules! many_escaping_reentrant(
                     closure: f,
                     environment
                 };
-                //box payload
-                let boxed_load = Box::new(payload);
                 //note: this leak will be cleaned up by dispose
-                let raw_load = Box::into_raw(boxed_load) as *mut _ as *mut core::ffi::c_void;
+                let raw_load = blocksr::hidden::try_alloc_payload(payload)?;
+                let (flags, descriptor) = blocksr::hidden::many_descriptor::<C,E>(Self::FLAGS.bits(), Self::__DESCRIPTOR);
                 let literal = blocksr::hidden::BlockLiteralManyEscape {
-                    isa: &blocksr::hidden::_NSConcreteStackBlock,
-                    flags: blocksr::hidden::BLOCK_HAS_STRET | blocksr::hidden::BLOCK_HAS_COPY_DISPOSE,
-                    reserved: std::mem::MaybeUninit::uninit().assume_init(),
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags,
+                    reserved: 0,
                     invoke: thunk_fn ,
-                    descriptor: &mut blocksr::hidden::BLOCK_DESCRIPTOR_MANY as *mut _ as *mut core::ffi::c_void,
+                    descriptor,
                     payload: raw_load,
                     dispose: dispose_thunk::<C,E>,
+                    take_environment: Some(blocksr::hidden::take_environment_thunk::<C,E>),
+                    clone_payload: None,
                 };
-                $blockname(literal)
+                Ok($blockname(literal))
             }
+            ///Creates a new escaping block, which calls `on_dispose` when it is disposed.
+            ///
+            /// `on_dispose` runs after the closure and then the environment are dropped, which is after the final
+            /// invocation has returned.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_with_dispose<C,E,D>(environment: E, f: C, on_dispose: D) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, D: FnOnce() + Send + 'static, E: Sync + 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::WithDispose::new(environment, on_dispose), move |environment: &blocksr::hidden::WithDispose<E,D>, $($a),*| {
+                    f(environment.environment(), $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, whose environment is dropped on the main thread.
+            ///
+            /// This is required for environments holding objects that must deallocate on main, such as views.
+            /// If the block is disposed on another thread, the environment's drop is sent to the main queue.
+            /// The closure itself is still dropped wherever the block is disposed.  Apple platforms only.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, `E` needn't be `Send`, so you must verify that
+            /// it may be used on every thread the block is invoked on, and then sent to the main thread to be dropped.
+            #[cfg(target_vendor = "apple")]
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_drop_on_main<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, E: Sync + 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::DropOnMain::new(environment), move |environment: &blocksr::hidden::DropOnMain<E>, $($a),*| {
+                    f(environment.environment(), $($a),*)
+                }))
+            }
+            ///Creates a new escaping block for a closure and environment that are not `Send`.
+            ///
+            /// In debug builds with the `std` feature, the creating thread is recorded, and the process aborts with a message if the block
+            /// is invoked or disposed on a different thread.  Release and `no_std` builds don't check.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, you must verify that the block is only invoked and
+            /// disposed on the creating thread.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_thread_affine<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + 'static, E: 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::ThreadAffine::new((environment, f)), |affine: &blocksr::hidden::ThreadAffine<(E,C)>, $($a),*| {
+                    let (environment, f) = affine.get();
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block for a closure and environment that are not `Send`, but are confined to the
+            ///serial dispatch queue `queue`.  See [blocksr::QueueConfined].
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, you must verify that `queue` is serial, and that
+            /// the block is only invoked and disposed on it.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_queue_confined<C,E>(queue: *const core::ffi::c_void, environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + 'static, E: 'static {
+                blocksr::hidden::opaque_environment(Self::new(unsafe{ blocksr::QueueConfined::new(queue, (environment, f)) }, |confined: &blocksr::QueueConfined<(E,C)>, $($a),*| {
+                    let (environment, f) = confined.get();
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, which passes a reference to each return value to `tee`.
+            ///
+            /// This is useful when objc ignores the return value, but you need it, for example to send it on a channel.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_teeing<C,E,T>(environment: E, f: C, tee: T) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, T: Fn(&$R) + Send + Sync + 'static, E: Sync {
+                Self::new(environment, move |environment: &E, $($a),*| {
+                    let r = f(environment, $($a),*);
+                    tee(&r);
+                    r
+                })
+            }
+            ///Creates a new escaping block, which holds `keep_alive` until it is disposed.
+            ///
+            /// Use this to keep an objc receiver (session, device, etc.) alive for the block's lifetime.  `keep_alive` is
+            /// dropped after the closure and environment.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_keeping_alive<C,E,K>(environment: E, keep_alive: K, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, K: Send + 'static, E: Sync + 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::KeepAlive::new(environment, keep_alive), move |environment: &blocksr::hidden::KeepAlive<E,K>, $($a),*| {
+                    f(environment.environment(), $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, whose environment is boxed separately from the closure.
+            ///
+            /// By default, the environment is stored inline, next to the closure, in one allocation.  For a large
+            /// environment, such as a ring buffer or a cache, that puts the closure's captures on a different cache
+            /// line from the block.  This stores it behind its own pointer instead, for one more allocation and one
+            /// more indirection when the closure uses the environment.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_indirect<C,E>(environment: E, f: C) -> Self where C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, E: Sync + 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::Box::new(environment), move |environment: &blocksr::hidden::Box<E>, $($a),*| {
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block, whose environment `init` initializes directly in its heap allocation.
+            ///
+            /// Passing a large environment, like a buffer of several kilobytes, to `new_indirect` builds it on the stack
+            /// and copies it into the box.  This allocates the box first, and `init` writes the environment into it and
+            /// returns it.  The block stores the box as `new_indirect` does.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            ///
+            /// # Panics
+            /// If `init` returns a reference to anything but the slot it was given.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_in_place<C,E,I>(init: I, f: C) -> Self where I: for<'a> FnOnce(&'a mut core::mem::MaybeUninit<E>) -> &'a mut E, C: Fn(&E, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + 'static, E: Sync + 'static {
+                blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::box_in_place(init), move |environment: &blocksr::hidden::Box<E>, $($a),*| {
+                    f(environment, $($a),*)
+                }))
+            }
+            ///Creates a new escaping block with a [blocksr::CancellationToken].
+            ///
+            /// After the token is cancelled, invocations return `Default::default()` without running `f`.  `f` receives
+            /// the token, to check during long invocations.  The token is also cancelled when the block is disposed.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_cancellable<C,E>(environment: E, f: C) -> (Self, blocksr::CancellationToken) where C: Fn(&E, &blocksr::CancellationToken, $(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + Sync + blocksr::hidden::DefaultReturn<$R> + 'static, E: Sync + 'static {
+                let token = blocksr::CancellationToken::new();
+                let block = blocksr::hidden::opaque_environment(Self::new(blocksr::hidden::Cancellable::new(environment, token.clone()), move |cancellable: &blocksr::hidden::Cancellable<E>, $($a),*| {
+                    if cancellable.token().is_cancelled() {
+                        return C::default_return();
+                    }
+                    f(cancellable.environment(), cancellable.token(), $($a),*)
+                }));
+                (block, token)
+            }
+            blocksr::__aggregating!(reentrant ($($a : blocksr::__adapted!($A $(as $O)?)),*) -> $R);
+            blocksr::__shared!(reentrant ($($a : blocksr::__adapted!($A $(as $O)?)),*) -> $R);
+            ///Views this block as a generic [blocksr::Block].
+            #[allow(dead_code)]
+            pub fn as_block(&self) -> &blocksr::Block<($($A,)*),$R> {
+                unsafe{ &*(self as *const Self as *const blocksr::Block<($($A,)*),$R>) }
+            }
+
+        }
+
+    }
+);
+#[test] fn dispose_ordering() {
+    use std::sync::{Arc, Mutex};
+    struct Environment(Arc<Mutex<Vec<&'static str>>>);
+    impl Drop for Environment {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push("environment");
+        }
+    }
+    many_escaping_nonreentrant!(MyBlock (environment: &mut Environment, arg: u8) -> u8);
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let dispose_log = log.clone();
+    let mut block = unsafe{ MyBlock::new_with_dispose(Environment(log.clone()), |environment, arg| {
+        environment.0.lock().unwrap().push("invoke");
+        arg
+    }, move || {
+        dispose_log.lock().unwrap().push("on_dispose");
+    })};
+    assert_eq!(unsafe{ block.as_block().invoke((1,)) }, 1);
+    //simulate the runtime disposing the block
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert_eq!(*log.lock().unwrap(), vec!["invoke", "environment", "on_dispose"]);
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn drop_on_main() {
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    struct Environment(Arc<AtomicBool>);
+    impl Drop for Environment {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+    many_escaping_reentrant!(MyBlock (environment: &Environment) -> bool);
+    let dropped = Arc::new(AtomicBool::new(false));
+    let mut block = unsafe{ MyBlock::new_drop_on_main(Environment(dropped.clone()), |_environment| true) };
+    //the test harness runs off the main thread, so the drop is deferred to the main queue
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert!(!dropped.load(Ordering::SeqCst));
+}
+
+#[test] fn indirect() {
+    many_escaping_nonreentrant!(MyBlock (environment: &mut [u64; 64], arg: usize) -> u64);
+    let mut block = unsafe{ MyBlock::new_indirect([0; 64], |environment, arg| { environment[arg] += 1; environment[arg] }) };
+    assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 1);
+    assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 2);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+}
+
+#[cfg(feature = "std")]
+#[test] fn shared() {
+    many_escaping_nonreentrant!(MyBlock (environment: &mut Vec<u8>, arg: u8) -> usize);
+    let (mut block, environment) = unsafe{ MyBlock::new_shared(Vec::new(), |environment, arg| { environment.push(arg); environment.len() }) };
+    assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 1);
+    environment.lock().unwrap().push(4);
+    assert_eq!(unsafe{ block.as_block().invoke((5,)) }, 3);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert_eq!(std::sync::Arc::strong_count(&environment), 1);
+    assert_eq!(*environment.lock().unwrap(), [3, 4, 5]);
+    many_escaping_reentrant!(Reader (environment: &u8, arg: u8) -> u8);
+    let (mut block, environment) = unsafe{ Reader::new_shared_rw(2, |environment, arg| environment * arg) };
+    assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 6);
+    *environment.write().unwrap() = 4;
+    assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 12);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert_eq!(std::sync::Arc::strong_count(&environment), 1);
+}
+
+#[cfg(feature = "std")]
+#[test] fn serialized() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    many_escaping_reentrant!(Counter (environment: &(), increment: u32) -> u32);
+    let in_closure = std::sync::Arc::new(AtomicBool::new(false));
+    let check = in_closure.clone();
+    let mut block = unsafe{ Counter::new_serialized(0u32, move |count: &mut u32, increment| {
+        assert!(!check.swap(true, Ordering::SeqCst), "invocations overlapped");
+        std::thread::yield_now();
+        *count += increment;
+        check.store(false, Ordering::SeqCst);
+        *count
+    })};
+    //as an API invoking the block from its own threads would
+    let address = &block as *const Counter as usize;
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(move || for _ in 0..100 { unsafe{ (*(address as *const Counter)).as_block().invoke((1,)) }; });
+        }
+    });
+    assert_eq!(unsafe{ block.as_block().invoke((0,)) }, 400);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+}
+
+#[cfg(feature = "std")]
+#[test] #[should_panic(expected = "re-entrantly")] fn serialized_recursion() {
+    Serialized::new(()).with("MyBlock", |_| Serialized::new(()).with("Other", |_| ()));
+    let serialized = Serialized::new(0u8);
+    serialized.with("MyBlock", |_| serialized.with("MyBlock", |_| ()));
+}
+
+#[test] fn in_place() {
+    many_escaping_reentrant!(MyBlock (environment: &[u64; 4096], arg: usize) -> u64);
+    fn init(slot: &mut MaybeUninit<[u64; 4096]>) -> &mut [u64; 4096] {
+        for (i, word) in unsafe{ (*slot.as_mut_ptr()).iter_mut() }.enumerate() {
+            *word = i as u64;
+        }
+        unsafe{ slot.assume_init_mut() }
+    }
+    let mut block = unsafe{ MyBlock::new_in_place(init, |environment, arg| environment[arg]) };
+    assert_eq!(unsafe{ block.as_block().invoke((4095,)) }, 4095);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+}
+
+#[test] fn thread_affine() {
+    use std::rc::Rc;
+    many_escaping_nonreentrant!(MyBlock (environment: &mut Rc<u8>, arg: u8) -> u8);
+    let mut block = unsafe{ MyBlock::new_thread_affine(Rc::new(2), |environment, arg| **environment + arg) };
+    assert_eq!(unsafe{ block.as_block().invoke((1,)) }, 3);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+}
 
+#[test] fn keeping_alive() {
+    use std::sync::{Arc, Mutex};
+    struct Receiver(Arc<Mutex<Vec<&'static str>>>);
+    impl Drop for Receiver {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push("receiver");
+        }
+    }
+    struct Environment(Arc<Mutex<Vec<&'static str>>>);
+    impl Drop for Environment {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push("environment");
         }
+    }
+    many_escaping_reentrant!(MyBlock (environment: &Environment, arg: u8) -> u8);
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut block = unsafe{ MyBlock::new_keeping_alive(Environment(log.clone()), Receiver(log.clone()), |_environment, arg| arg) };
+    assert_eq!(unsafe{ block.as_block().invoke((4,)) }, 4);
+    assert!(log.lock().unwrap().is_empty());
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert_eq!(*log.lock().unwrap(), vec!["environment", "receiver"]);
+}
 
+#[test] fn clone_on_copy() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    #[derive(Clone)]
+    struct Environment(Arc<AtomicUsize>);
+    impl Drop for Environment {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
     }
-);
\ No newline at end of file
+    many_escaping_reentrant!(MyBlock (environment: &Environment) -> usize; clone_on_copy);
+    let drops = Arc::new(AtomicUsize::new(0));
+    let block = unsafe{ MyBlock::new_clone_on_copy(Environment(drops.clone()), |environment| environment as *const Environment as usize) };
+    let original = unsafe{ block.as_block().invoke(()) };
+    //simulate the runtime copying the literal to the heap
+    let mut copy = unsafe{ core::ptr::read(&block.0) };
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.copy_helper)(&mut copy, &block.0 as *const _ as *mut _) };
+    let copied: &MyBlock = unsafe{ &*(&copy as *const BlockLiteralManyEscape as *const MyBlock) };
+    assert_ne!(unsafe{ copied.as_block().invoke(()) }, original);
+    unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut copy) };
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+    drop(block);
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+}
+
+#[test] fn trivial_payload() {
+    many_escaping_reentrant!(MyBlock (environment: &(), arg: u8) -> u8);
+    let trivial = unsafe{ MyBlock::new((), |_, arg| arg) };
+    assert!(!trivial.flags().contains(crate::BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(trivial.as_block().literal_size(), MyBlock::literal_size());
+    assert_eq!(unsafe{ trivial.as_block().invoke((2,)) }, 2);
+    let boxed = unsafe{ MyBlock::new(vec![1u8], |environment, arg| environment[0] + arg) };
+    assert!(boxed.flags().contains(crate::BlockFlags::HAS_COPY_DISPOSE));
+}
+
+#[cfg(all(feature = "poison-disposed", not(feature = "quiet")))]
+#[test] fn poisoned_on_dispose() {
+    many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, arg: u8) -> u8);
+    let stderr = crate::diagnostic::expect_abort("many::poisoned_on_dispose", || {
+        let block = unsafe{ MyBlock::new(vec![1u8], |environment, arg| environment[0] + arg) };
+        let mut literal = block.0;
+        let payload = literal.payload;
+        unsafe{ (BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut literal) };
+        assert_eq!(literal.payload, poisoned());
+        assert!(crate::arena::is_disposed(payload));
+        unsafe{ live_payload(&mut literal) };
+    });
+    assert!(stderr.contains("was used after it was disposed"), "{}", stderr);
+}
+
+#[cfg(all(feature = "poison-disposed", target_os = "macos", not(feature = "quiet")))]
+#[test] fn poisoned_after_release() {
+    many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, arg: u8) -> u8);
+    let stderr = crate::diagnostic::expect_abort("many::poisoned_after_release", || {
+        let block = unsafe{ MyBlock::new(vec![1u8], |environment, arg| environment[0] + arg) };
+        let heap = unsafe{ crate::HeapBlock::copy(&block) };
+        let stale = heap.as_ptr();
+        assert_eq!(unsafe{ (*stale).as_block().invoke((1,)) }, 2);
+        //the last release disposes the block, and frees the heap literal
+        drop(heap);
+        unsafe{ (*stale).as_block().invoke((1,)) };
+    });
+    assert!(stderr.contains("was used after it was disposed"), "{}", stderr);
+}
+
+#[cfg(all(test, target_os = "macos"))]
+extern "C" {
+    fn dispatch_queue_create(label: *const core::ffi::c_char, attr: *const c_void) -> *const c_void;
+    fn dispatch_sync_f(queue: *const c_void, context: *mut c_void, work: extern "C" fn(*mut c_void));
+    fn dispatch_release(object: *const c_void);
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn queue_confined() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    struct Environment(Rc<u8>, Arc<AtomicBool>);
+    impl Drop for Environment {
+        fn drop(&mut self) {
+            self.1.store(true, Ordering::SeqCst);
+        }
+    }
+    many_escaping_reentrant!(MyBlock (environment: &Environment, arg: u8) -> u8);
+    struct Context(Option<crate::HeapBlock<MyBlock>>, u8);
+    extern "C" fn run(context: *mut c_void) {
+        let context = unsafe{ &mut *(context as *mut Context) };
+        context.1 = unsafe{ (*context.0.as_ref().unwrap().as_ptr()).as_block().invoke((context.1,)) };
+        //the last release disposes it on the queue too
+        context.0 = None;
+    }
+    let queue = unsafe{ dispatch_queue_create(c"blocksr.test".as_ptr(), core::ptr::null()) };
+    let dropped = Arc::new(AtomicBool::new(false));
+    let block = unsafe{ MyBlock::new_queue_confined(queue, Environment(Rc::new(2), dropped.clone()), |environment, arg| *environment.0 + arg) };
+    //the stack literal shares its payload with the copy, which disposes it
+    let mut context = Context(Some(unsafe{ crate::HeapBlock::copy(&block) }), 1);
+    unsafe{ dispatch_sync_f(queue, &mut context as *mut Context as *mut c_void, run) };
+    assert_eq!(context.1, 3);
+    assert!(dropped.load(Ordering::SeqCst));
+    unsafe{ dispatch_release(queue) };
+}
+
+#[cfg(all(target_os = "macos", debug_assertions, feature = "std", not(feature = "quiet")))]
+#[test] fn queue_confined_off_queue() {
+    many_escaping_reentrant!(MyBlock (environment: &std::rc::Rc<u8>, arg: u8) -> u8);
+    let stderr = crate::diagnostic::expect_abort("many::queue_confined_off_queue", || {
+        let queue = unsafe{ dispatch_queue_create(c"blocksr.test".as_ptr(), core::ptr::null()) };
+        let block = unsafe{ MyBlock::new_queue_confined(queue, std::rc::Rc::new(2), |environment, arg| **environment + arg) };
+        //the test thread isn't the queue
+        unsafe{ block.as_block().invoke((1,)) };
+    });
+    assert!(stderr.contains("queue-confined value was accessed off its queue"), "{}", stderr);
+}
+
+#[cfg(all(debug_assertions, feature = "std", not(feature = "quiet")))]
+#[test] fn reentrancy_detected() {
+    use core::sync::atomic::{AtomicPtr, Ordering};
+    many_escaping_nonreentrant!(MyBlock (environment: &mut u8, arg: u8) -> u8);
+    let stderr = crate::diagnostic::expect_abort("many::reentrancy_detected", || {
+        //the block invokes itself, through the pointer ObjC would have
+        static BLOCK: AtomicPtr<MyBlock> = AtomicPtr::new(core::ptr::null_mut());
+        let mut block = unsafe{ MyBlock::new(0, |environment, arg| {
+            *environment += 1;
+            if arg > 0 {
+                (*BLOCK.load(Ordering::Relaxed)).as_block().invoke((arg - 1,))
+            }
+            else {
+                *environment
+            }
+        }) };
+        BLOCK.store(&mut block, Ordering::Relaxed);
+        //sequential invocations are fine
+        assert_eq!(unsafe{ block.as_block().invoke((0,)) }, 1);
+        assert_eq!(unsafe{ block.as_block().invoke((0,)) }, 2);
+        unsafe{ block.as_block().invoke((1,)) };
+    });
+    assert!(stderr.contains("MyBlock"), "{}", stderr);
+    assert!(stderr.contains("was invoked while it was already executing"), "{}", stderr);
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! ObjC's scalar typedefs, with the Rust type each has on the target.
+
+Some of ObjC's typedefs change with the target: `BOOL` is a `bool` on arm64 and watchOS, but a `signed char` on
+x86_64 and 32-bit iOS, and an `unsigned char` in GNUstep, and `CGFloat` is a `double` on 64-bit targets but a `float`
+on 32-bit ones, like arm64_32 watchOS.  A block declared with these aliases has the right ABI everywhere, without a
+`cfg` for each target:
+
+```
+use blocksr::once_escaping;
+use blocksr::objc_types::{Bool, CGFloat, NSUInteger};
+once_escaping!(EnumerationBlock (object: *const core::ffi::c_void, index: NSUInteger, stop: *mut Bool) -> ());
+once_escaping!(ScaleBlock (scale: CGFloat as f64, animated: Bool as bool) -> ());
+let block = unsafe{ ScaleBlock::new(|scale: f64, animated: bool| {
+    assert!(animated);
+    assert_eq!(scale, 2.0);
+})};
+unsafe{ block.as_block().invoke((2.0, blocksr::objc_types::YES)) };
+```
+
+As in the second declaration, `Bool as bool` and `CGFloat as f64` give the closure a `bool` and an `f64` on every
+target, converted by [CopyArgument].  Going the other way, such as for a `BOOL` return value, use [from_bool], or `as
+CGFloat`.
+*/
+
+use crate::CopyArgument;
+
+///`BOOL`, which is a `bool` on this target.
+#[cfg(all(target_vendor = "apple", any(target_arch = "aarch64", all(target_os = "watchos", target_arch = "arm"))))]
+pub type Bool = bool;
+///`BOOL`, which is an `unsigned char` in GNUstep.
+#[cfg(all(not(target_vendor = "apple"), feature = "gnustep"))]
+pub type Bool = u8;
+///`BOOL`, which is a `signed char` on this target.
+#[cfg(not(any(all(target_vendor = "apple", any(target_arch = "aarch64", all(target_os = "watchos", target_arch = "arm"))), all(not(target_vendor = "apple"), feature = "gnustep"))))]
+pub type Bool = i8;
+
+///`YES`.
+pub const YES: Bool = from_bool(true);
+///`NO`.
+pub const NO: Bool = from_bool(false);
+
+///`NSUInteger`, which is pointer-sized.
+pub type NSUInteger = usize;
+///`NSInteger`, which is pointer-sized.
+pub type NSInteger = isize;
+
+///`CGFloat`, which is a `double` on 64-bit targets.
+#[cfg(target_pointer_width = "64")]
+pub type CGFloat = f64;
+///`CGFloat`, which is a `float` on 32-bit targets.
+#[cfg(not(target_pointer_width = "64"))]
+pub type CGFloat = f32;
+
+/**
+Converts a `BOOL` to a `bool`.  Any value other than `NO` is `YES`, as in C.
+*/
+#[allow(clippy::bool_comparison)]
+pub const fn to_bool(value: Bool) -> bool {
+    value != NO
+}
+
+/**
+Converts a `bool` to a `BOOL`.
+*/
+#[allow(clippy::unnecessary_cast)]
+pub const fn from_bool(value: bool) -> Bool {
+    value as Bool
+}
+
+impl CopyArgument<Bool> for bool {
+    unsafe fn copy_argument(raw: Bool) -> Self {
+        to_bool(raw)
+    }
+}
+
+impl CopyArgument<CGFloat> for f64 {
+    unsafe fn copy_argument(raw: CGFloat) -> Self {
+        #[allow(clippy::useless_conversion)]
+        f64::from(raw)
+    }
+}
+
+#[test] fn conversions() {
+    assert!(to_bool(YES));
+    assert!(!to_bool(NO));
+    assert_eq!(from_bool(true), YES);
+    assert_eq!(core::mem::size_of::<Bool>(), 1);
+    assert_eq!(core::mem::size_of::<CGFloat>(), core::mem::size_of::<usize>());
+    crate::once_escaping!(FlagBlock (flag: Bool as bool, scale: CGFloat as f64) -> Bool);
+    let block = unsafe{ FlagBlock::new(|flag: bool, scale: f64| from_bool(flag && scale == 0.5)) };
+    assert_eq!(unsafe{ block.as_block().invoke((YES, 0.5)) }, YES);
+}
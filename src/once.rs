@@ -1,8 +1,8 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
-use std::os::raw::{c_int,c_ulong};
-use std::ffi::c_void;
-use std::marker::PhantomPinned;
-use std::mem::MaybeUninit;
+use core::ffi::{c_int,c_ulong};
+use core::ffi::c_void;
+use core::marker::PhantomPinned;
+use core::mem::MaybeUninit;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -16,7 +16,30 @@ pub struct BlockDescriptorOnce {
         void (*dispose_helper)(void *src);             // IFF (1<<25)
         required ABI.2010.3.16
         const char *signature;                         // IFF (1<<30)
+        const char *layout;                            // IFF (1<<30), interpreted per (1<<31)
      */
+    pub signature: *const core::ffi::c_char,
+    pub layout: *const core::ffi::c_char,
+}
+//Safety: the pointers, if any, are to static strings
+unsafe impl Sync for BlockDescriptorOnce {}
+impl BlockDescriptorOnce {
+    ///A descriptor for a literal of `size` bytes.
+    pub const fn new(size: usize) -> Self {
+        BlockDescriptorOnce {
+            reserved: 0, //seems defined as NULL
+            size: size as c_ulong,
+            signature: core::ptr::null(),
+            layout: EXTENDED_LAYOUT_NO_OBJECTS,
+        }
+    }
+    ///This descriptor, with `signature` if there is one.
+    pub const fn with_signature(mut self, signature: Option<&'static core::ffi::CStr>) -> Self {
+        if let Some(signature) = signature {
+            self.signature = signature.as_ptr();
+        }
+        self
+    }
 }
 #[repr(C)]
 #[derive(Debug)]
@@ -39,10 +62,7 @@ pub struct BlockLiteralOnceEscape {
     pub closure: *const c_void,
 }
 
-pub static mut BLOCK_DESCRIPTOR_ONCE: blocksr::hidden::BlockDescriptorOnce = BlockDescriptorOnce {
-    reserved: 0, //unsafe{std::mem::MaybeUninit::uninit().assume_init()} is unstable as const fn
-    size: std::mem::size_of::<blocksr::hidden::BlockLiteralOnceEscape>() as u64,
-};
+pub static mut BLOCK_DESCRIPTOR_ONCE: blocksr::hidden::BlockDescriptorOnce = BlockDescriptorOnce::new(core::mem::size_of::<blocksr::hidden::BlockLiteralOnceEscape>());
 
 
 
@@ -60,6 +80,28 @@ Declares a block that escapes and executes once.  this is a typical pattern for
 
 `::new()` is declared unsafe.
 
+With the nightly `c_variadic` feature, blocks may be variadic, like printf-style logging callbacks.  The closure
+receives the variable arguments as a trailing `blocksr::VaArgs`:
+
+```ignore
+    use blocksr::once_escaping;
+    once_escaping!(MyBlock (count: u32, ...) -> i32);
+    let f = unsafe{ MyBlock::new(|count, args| {
+        (0..count).map(|_| unsafe{ args.arg::<i32>() }).sum()
+    })};
+```
+
+Blocks that must not return (for example, trampolines into `dispatch_main`) may be declared `-> !`.
+The closure must then diverge as well:
+
+```
+    use blocksr::once_escaping;
+    once_escaping!(MyBlock (code: i32) -> !);
+    let f = unsafe{ MyBlock::new(|code| {
+        std::process::exit(code)
+    })};
+```
+
 # Safety
 
 You must verify that
@@ -69,6 +111,47 @@ You must verify that
      * If ObjC executes the block several times, it's UB
      * If ObjC executes the block less than once, it is not UB, but it will leak.
 
+Arguments and return types may be `#[repr(C)]` structs passed by value, such as `CGRect`.  The `BLOCK_HAS_STRET` flag
+is set the way clang would set it for the target, except that on 32-bit targets, a small struct with the layout of a
+`u64` is treated as one.
+
+Arguments that are only valid during the invocation may be declared `name: Raw as Owned`, where `Owned`
+implements [blocksr::CopyArgument].  The closure then receives an owned value.
+
+With the `continuation` feature, a handler returning `()` can be made straight from a `Completer` with
+`from_completer`.  A handler with one argument completes a `Completer` of that type; otherwise, a `Completer` of the
+argument tuple.  `from_completer_into` completes a `Completer` of any type implementing `From` the argument tuple.  `from_completer_catching`
+completes a `Completer<Result<T, Panicked>>` with what a closure returns, or with the payload if it panics.
+`from_async` starts async work, passing the future a closure returns to a spawn function, and completes a `Completer`
+with its output.  `future` makes the continuation too, returning the block and a future for its argument:
+
+```ignore
+once_escaping!(DataHandler (data: *const c_void) -> ());
+let (handler, data) = unsafe{ DataHandler::future() };
+//pass handler somewhere...
+let data = data.await;
+```
+
+Blocks following the ObjC error convention, `BOOL (^)(..., NSError **error)`, may be declared with a trailing
+`; throws error: *mut *mut NSError`.  The closure passed to `new_throwing` then returns a [Result]; see [blocksr::ThrowingReturn].
+
+Blocks are created with the stack block isa, `_NSConcreteStackBlock`, so objc copies them before they escape.  To pick
+another class, prefix the declaration with `isa = global;` or `isa = MY_ISA;`, where `MY_ISA` is a `*const c_void`
+(or a parenthesized expression for one), as exotic runtimes or frameworks that type-check blocks may need:
+
+```
+    use blocksr::once_escaping;
+    once_escaping!(isa = global; MyBlock (arg: u8) -> u8);
+    let f = unsafe{ MyBlock::new(|arg| arg) };
+    //pass f somewhere...
+```
+
+The runtime never copies or disposes a global block, so the value itself must outlive every use.  The other macros
+accept the same option.
+
+Every generated type has `FLAGS` and `literal_size()` for the literals it creates, and `flags()` and `signature()`
+to inspect an instance.  See [blocksr::BlockFlags].
+
 The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
 Typically, you want to declare the pointer type `Arguable` in objr to pass it into objc, e.g.
 
@@ -81,15 +164,55 @@ unsafe impl Arguable for &DataTaskCompletionHandler {}
 macro_rules! once_escaping(
 
     (
-        $pub:vis $blockname: ident ($($a:ident : $A:ty),*) -> $R:ty
+        isa = $isa:tt; $pub:vis $blockname: ident $($rest:tt)*
+    ) => {
+        blocksr::once_escaping!($pub $blockname $($rest)*);
+        impl $blockname {
+            #[doc(hidden)]
+            #[allow(unused_parens)] //isa = (expression)
+            fn __isa() -> *const core::ffi::c_void {
+                blocksr::__isa!($isa)
+            }
+            #[doc(hidden)]
+            const __GLOBAL: bool = blocksr::__isa_global!($isa);
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident ($($a:ident : $A:ty $(as $O:ty)?),*) -> $R:ty; throws $e:ident : $E:ty
+    ) => {
+        blocksr::once_escaping!($pub $blockname ($($a : $A $(as $O)?,)* $e : $E) -> $R);
+        impl $blockname {
+            ///Creates a new escaping block from a closure returning a [Result].
+            ///
+            /// `Ok` is converted to the return value for success.  An error is written through the trailing out-parameter,
+            /// if it isn't null, and the return value for failure is returned.  See [blocksr::ThrowingReturn].
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_throwing<F,T,X,V>(f: F) -> Self where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> Result<T,X> + Send + 'static, $R: blocksr::ThrowingReturn<T>, $E: blocksr::OutParameter<V>, X: blocksr::IntoOutError<V> {
+                Self::new(move |$($a,)* $e| {
+                    unsafe{ blocksr::hidden::report_result(f($($a),*), $e) }
+                })
+            }
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident ($($a:ident : $A:ty ,)* ...) -> $R:ty
     ) => {
         //must be ffi-safe
         #[repr(transparent)]
-        #[derive(Debug)]
         #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
         $pub struct $blockname(blocksr::hidden::BlockLiteralOnceEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
         impl $blockname {
-            ///Creates a new escaping block.
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>());
+            blocksr::__descriptor!(once);
+            ///Creates a new escaping, variadic block.
             ///
             /// # Safety
             /// You must verify that
@@ -100,25 +223,177 @@ macro_rules! once_escaping(
             ///     * If ObjC executes the block less than once, it is not UB, but it will leak.
             ///
             /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
-            pub unsafe fn new<F>(f: F) -> Self where F: FnOnce($($A),*) -> $R + Send + 'static {
+            #[track_caller]
+            pub unsafe fn new<F>(f: F) -> Self where F: FnOnce($($A,)* &mut blocksr::VaArgs) -> $R + Send + 'static {
+                blocksr::hidden::unwrap_alloc(Self::try_new(f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, `f` is dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<F>(f: F) -> Result<Self, blocksr::arena::AllocError> where F: FnOnce($($A,)* &mut blocksr::VaArgs) -> $R + Send + 'static {
+                let thunk_fn: *const core::ffi::c_void = blocksr::__variadic_thunk!(once [F] $R; $($A),*) as *const core::ffi::c_void;
+                let literal = blocksr::hidden::BlockLiteralOnceEscape {
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags: Self::FLAGS.bits(),
+                    reserved: core::mem::MaybeUninit::uninit(),
+                    invoke: thunk_fn ,
+                    descriptor: blocksr::hidden::once_descriptor(Self::__DESCRIPTOR),
+                    closure: blocksr::hidden::try_alloc_payload(f)?,
+                };
+                Ok($blockname(literal))
+            }
+            ///Hands off this block, consuming it so it can't be passed to objc again.
+            #[allow(dead_code)]
+            pub fn into_param(self) -> blocksr::BlockParam<'static, Self> {
+                unsafe{ blocksr::BlockParam::new(self) }
+            }
+        }
+    };
+
+    (
+        $pub:vis $blockname: ident ($($a:ident : $A:ty),*) -> !
+    ) => {
+        //must be ffi-safe
+        #[repr(transparent)]
+        #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
+        $pub struct $blockname(blocksr::hidden::BlockLiteralOnceEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
+        impl $blockname {
+            blocksr::__introspection!(0);
+            blocksr::__descriptor!(once);
+            ///Creates a new escaping block that does not return.
+            ///
+            /// # Safety
+            /// You must verify that
+            /// * Arguments are correct and in the expected order
+            ///     * Arguments are FFI-safe (compiler usually warns)
+            /// * Block will execute at most once:
+            ///     * If ObjC executes the block several times, it's UB
+            ///     * If ObjC executes the block less than once, it is not UB, but it will leak.
+            ///
+            /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
+            #[track_caller]
+            pub unsafe fn new<F>(f: F) -> Self where F: FnOnce($($A),*) -> blocksr::hidden::Never + Send + 'static {
+                blocksr::hidden::unwrap_alloc(Self::try_new(f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, `f` is dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<F>(f: F) -> Result<Self, blocksr::arena::AllocError> where F: FnOnce($($A),*) -> blocksr::hidden::Never + Send + 'static {
                 //This thunk is safe to call from C
-                extern "C" fn invoke_thunk<G>(block: *mut blocksr::hidden::BlockLiteralOnceEscape, $($a : $A),*) -> $R where G: FnOnce($($A),*) -> $R + Send {
-                    let typed_ptr: *mut G = unsafe{ (*block).closure as *mut G};
-                    let rust_fn = unsafe{ Box::from_raw(typed_ptr)};
+                extern "C" fn invoke_thunk<G>(block: *mut blocksr::hidden::BlockLiteralOnceEscape, $($a : $A),*) -> ! where G: FnOnce($($A),*) -> blocksr::hidden::Never + Send {
+                    let rust_fn: G = unsafe{ blocksr::hidden::take_payload((*block).closure as *mut core::ffi::c_void) };
                     rust_fn($($a),*)
-                    //drop box
                 }
-                let boxed = Box::new(f);
                 let thunk_fn: *const core::ffi::c_void = invoke_thunk::<F> as *const core::ffi::c_void;
                 let literal = blocksr::hidden::BlockLiteralOnceEscape {
-                    isa: &blocksr::hidden::_NSConcreteStackBlock,
-                    flags: blocksr::hidden::BLOCK_HAS_STRET,
-                    reserved: std::mem::MaybeUninit::uninit(),
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    //nothing is returned, so nothing is returned indirectly
+                    flags: Self::FLAGS.bits(),
+                    reserved: core::mem::MaybeUninit::uninit(),
                     invoke: thunk_fn ,
-                    descriptor: core::ptr::addr_of_mut!(blocksr::hidden::BLOCK_DESCRIPTOR_ONCE),
-                    closure: Box::into_raw(boxed) as *mut core::ffi::c_void,
+                    descriptor: blocksr::hidden::once_descriptor(Self::__DESCRIPTOR),
+                    closure: blocksr::hidden::try_alloc_payload(f)?,
                 };
-                $blockname(literal)
+                Ok($blockname(literal))
+            }
+            ///Hands off this block, consuming it so it can't be passed to objc again.
+            #[allow(dead_code)]
+            pub fn into_param(self) -> blocksr::BlockParam<'static, Self> {
+                unsafe{ blocksr::BlockParam::new(self) }
+            }
+        }
+    };
+    (
+        $pub:vis $blockname: ident ($($a:ident : $A:ty $(as $O:ty)?),*) -> $R:ty
+    ) => {
+        //must be ffi-safe
+        #[repr(transparent)]
+        #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
+        $pub struct $blockname(blocksr::hidden::BlockLiteralOnceEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
+        blocksr::__block_type!($blockname; ($($A),*) -> $R);
+        impl $blockname {
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>());
+            blocksr::__descriptor!(once);
+            ///Creates a new escaping block.
+            ///
+            /// # Safety
+            /// You must verify that
+            /// * Arguments and return types are correct and in the expected order
+            ///     * Arguments and return types are FFI-safe (compiler usually warns)
+            /// * Block will execute exactly once:
+            ///     * If ObjC executes the block several times, it's UB
+            ///     * If ObjC executes the block less than once, it is not UB, but it will leak.
+            ///
+            /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
+            #[track_caller]
+            pub unsafe fn new<F>(f: F) -> Self where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static {
+                blocksr::hidden::unwrap_alloc(Self::try_new(f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, `f` is dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<F>(f: F) -> Result<Self, blocksr::arena::AllocError> where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static {
+                //This thunk is safe to call from C
+                extern "C" fn invoke_thunk<G>(block: *mut blocksr::hidden::BlockLiteralOnceEscape, $($a : $A),*) -> $R where G: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send {
+                    let rust_fn: G = unsafe{ blocksr::hidden::take_payload((*block).closure as *mut core::ffi::c_void) };
+                    rust_fn($(blocksr::__adapt!($a: $A $(as $O)?)),*)
+                }
+                let thunk_fn: *const core::ffi::c_void = invoke_thunk::<F> as *const core::ffi::c_void;
+                let literal = blocksr::hidden::BlockLiteralOnceEscape {
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags: Self::FLAGS.bits(),
+                    reserved: core::mem::MaybeUninit::uninit(),
+                    invoke: thunk_fn ,
+                    descriptor: blocksr::hidden::once_descriptor(Self::__DESCRIPTOR),
+                    closure: blocksr::hidden::try_alloc_payload(f)?,
+                };
+                Ok($blockname(literal))
+            }
+            ///Hands off this block, consuming it so it can't be passed to objc again.
+            #[allow(dead_code)]
+            pub fn into_param(self) -> blocksr::BlockParam<'static, Self> {
+                unsafe{ blocksr::BlockParam::new(self) }
+            }
+            ///Creates a new escaping block, which passes a reference to the return value to `tee` after `f` returns.
+            ///
+            /// This is useful when objc ignores the return value, but you need it, for example to send it on a channel or to complete
+            /// a `Completer`.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn new_teeing<F,T>(f: F, tee: T) -> Self where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send + 'static, T: FnOnce(&$R) + Send + 'static {
+                Self::new(move |$($a),*| {
+                    let r = f($($a),*);
+                    tee(&r);
+                    r
+                })
+            }
+            blocksr::__from_completer!(($($a : blocksr::__adapted!($A $(as $O)?)),*) -> $R);
+            ///Views this block as a generic [blocksr::Block].
+            #[allow(dead_code)]
+            pub fn as_block(&self) -> &blocksr::Block<($($A,)*),$R> {
+                unsafe{ &*(self as *const Self as *const blocksr::Block<($($A,)*),$R>) }
             }
 
         }
@@ -126,30 +401,420 @@ macro_rules! once_escaping(
     }
 );
 
+/**
+Declares `from_completer` on a [once_escaping] type, with the `continuation` feature.
+*/
+#[cfg(feature = "continuation")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __from_completer {
+    (($($a:ident : $T:ty),*) -> $R:ty) => {
+        ///Creates a block that completes `completer` with its arguments: the argument itself if there is one, or a
+        ///tuple of them.  Available when the block returns `()`.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn from_completer<T>(completer: blocksr::continuation::Completer<T>) -> Self where T: blocksr::hidden::CompleteFrom<($($T,)*), $R> + Send + 'static {
+            blocksr::hidden::unwrap_alloc(Self::try_from_completer(completer))
+        }
+        ///Creates a block like `from_completer`, but returns an error instead of aborting if its payload can't be
+        ///allocated.  On failure, the completer is dropped, abandoning its continuation.
+        ///
+        /// The continuation itself is shared with an `Arc`, which has no fallible constructor on stable Rust.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn try_from_completer<T>(completer: blocksr::continuation::Completer<T>) -> Result<Self, blocksr::arena::AllocError> where T: blocksr::hidden::CompleteFrom<($($T,)*), $R> + Send + 'static {
+            completer.set_name(core::any::type_name::<Self>());
+            Self::try_new(move |$($a),*| {
+                completer.complete(T::complete_from(($($a,)*)));
+                T::completion_return()
+            })
+        }
+        ///Creates a block that completes `completer` with its arguments converted by [From]: `T` implements
+        ///`From<(A, B, ...)>` for the tuple of the block's arguments, which may have just one element.
+        ///
+        /// The block returns `Default::default()`, which for a block declared `-> ()` is nothing.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn from_completer_into<T>(completer: blocksr::continuation::Completer<T>) -> Self where T: From<($($T,)*)> + blocksr::hidden::DefaultReturn<$R> + Send + 'static {
+            completer.set_name(core::any::type_name::<Self>());
+            Self::new(move |$($a),*| {
+                completer.complete(T::from(($($a,)*)));
+                T::default_return()
+            })
+        }
+        ///Creates a block that completes `completer` with what `f` returns for its arguments, or with
+        ///`Err(Panicked)` if `f` panics, so the panic doesn't abort the process unwinding into objc.
+        ///
+        /// The block returns `Default::default()`, which for a block declared `-> ()` is nothing.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn from_completer_catching<T, F>(completer: blocksr::continuation::Completer<Result<T, blocksr::continuation::Panicked>>, f: F) -> Self where F: FnOnce($($T),*) -> T + Send + 'static, T: blocksr::hidden::DefaultReturn<$R> + Send + 'static {
+            completer.set_name(core::any::type_name::<Self>());
+            Self::new(move |$($a),*| {
+                completer.complete_catching(move || f($($a),*));
+                T::default_return()
+            })
+        }
+        ///Creates a block that starts async work: it calls `f` with its arguments, and passes a task, which awaits the
+        ///future `f` returns and completes `completer` with its output, to `spawn`.
+        ///
+        /// `spawn` is usually an executor's spawn function.  The block returns `Default::default()` without waiting for
+        /// the task, which for a block declared `-> ()` is nothing.  See [blocksr::continuation::Completer::complete_async].
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn from_async<T, F, Fut, S>(completer: blocksr::continuation::Completer<T>, spawn: S, f: F) -> Self where F: FnOnce($($T),*) -> Fut + Send + 'static, Fut: core::future::Future<Output = T> + Send + 'static, S: FnOnce(blocksr::continuation::BoxContinuation<()>) + Send + 'static, T: blocksr::hidden::DefaultReturn<$R> + Send + 'static {
+            completer.set_name(core::any::type_name::<Self>());
+            Self::new(move |$($a),*| {
+                spawn(completer.complete_async(f($($a),*)));
+                T::default_return()
+            })
+        }
+        ///Creates a block, and a future for the value it completes with, as for `from_completer`.  Available when the
+        ///block returns `()`.
+        ///
+        /// # Safety
+        /// The same requirements as `new` apply.
+        #[allow(dead_code)]
+        #[track_caller]
+        pub unsafe fn future<T>() -> (Self, blocksr::continuation::Continuation<T>) where (T, ($($T,)*)): blocksr::hidden::CompletionOutput<$R>, T: blocksr::hidden::CompleteFrom<($($T,)*), $R> + Send + 'static {
+            let (continuation, completer) = blocksr::continuation::continuation();
+            (unsafe{ Self::from_completer(completer) }, continuation)
+        }
+    };
+}
+#[cfg(not(feature = "continuation"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __from_completer {
+    ($($t:tt)*) => {};
+}
+
+/**
+Declares a block that escapes and executes once, but tolerates being executed again.
+
+Some APIs occasionally call a completion handler twice.  With [once_escaping], that is UB.  With this macro, the first call
+takes the closure with an atomic swap, and later calls are a no-op that return `Default::default()`.  With the `std`
+feature, the extra call is logged to stderr, or with the `quiet` feature, to the `log` crate.
+
+```
+    use blocksr::once_escaping_tolerant;
+    once_escaping_tolerant!(MyBlock (arg: u8) -> u8);
+    let f = unsafe{ MyBlock::new(|arg| {
+        arg + 1
+    })};
+    //pass f somewhere...
+```
+
+The return type must implement [Default].
+
+# Safety
+
+You must verify that
+ * Arguments and return types are correct and in the expected order
+     * Arguments and return types are FFI-safe (compiler usually warns)
+ * If ObjC executes the block less than once, it is not UB, but it will leak.
+*/
+#[macro_export]
+macro_rules! once_escaping_tolerant(
+
+    (
+        isa = $isa:tt; $pub:vis $blockname: ident $($rest:tt)*
+    ) => {
+        blocksr::once_escaping_tolerant!($pub $blockname $($rest)*);
+        impl $blockname {
+            #[doc(hidden)]
+            #[allow(unused_parens)] //isa = (expression)
+            fn __isa() -> *const core::ffi::c_void {
+                blocksr::__isa!($isa)
+            }
+            #[doc(hidden)]
+            const __GLOBAL: bool = blocksr::__isa_global!($isa);
+        }
+    };
+    (
+        $pub:vis $blockname: ident ($($a:ident : $A:ty),*) -> $R:ty
+    ) => {
+        //must be ffi-safe
+        #[repr(transparent)]
+        #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
+        $pub struct $blockname(blocksr::hidden::BlockLiteralOnceEscape);
+        blocksr::__debug_block!($blockname, "boxed");
+        blocksr::__objc2_encode!($blockname);
+        blocksr::__block_type!($blockname; ($($A),*) -> $R);
+        impl $blockname {
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>());
+            blocksr::__descriptor!(once);
+            ///Creates a new escaping block, which ignores invocations after the first.
+            ///
+            /// # Safety
+            /// You must verify that
+            /// * Arguments and return types are correct and in the expected order
+            ///     * Arguments and return types are FFI-safe (compiler usually warns)
+            /// * If ObjC executes the block less than once, it is not UB, but it will leak.
+            ///
+            /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
+            #[track_caller]
+            pub unsafe fn new<F>(f: F) -> Self where F: FnOnce($($A),*) -> $R + Send + 'static, $R: Default {
+                blocksr::hidden::unwrap_alloc(Self::try_new(f))
+            }
+            ///Creates a new block like `new`, but returns an error instead of aborting if its payload can't be allocated.
+            ///
+            /// On failure, `f` is dropped.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            #[track_caller]
+            pub unsafe fn try_new<F>(f: F) -> Result<Self, blocksr::arena::AllocError> where F: FnOnce($($A),*) -> $R + Send + 'static, $R: Default {
+                //This thunk is safe to call from C
+                extern "C" fn invoke_thunk<G>(block: *mut blocksr::hidden::BlockLiteralOnceEscape, $($a : $A),*) -> $R where G: FnOnce($($A),*) -> $R + Send {
+                    let closure = unsafe{ blocksr::hidden::take_once_closure(block) };
+                    if closure.is_null() {
+                        return Default::default();
+                    }
+                    let rust_fn: G = unsafe{ blocksr::hidden::take_payload(closure) };
+                    rust_fn($($a),*)
+                }
+                let thunk_fn: *const core::ffi::c_void = invoke_thunk::<F> as *const core::ffi::c_void;
+                let literal = blocksr::hidden::BlockLiteralOnceEscape {
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags: Self::FLAGS.bits(),
+                    reserved: core::mem::MaybeUninit::uninit(),
+                    invoke: thunk_fn ,
+                    descriptor: blocksr::hidden::once_descriptor(Self::__DESCRIPTOR),
+                    closure: blocksr::hidden::try_alloc_payload(f)?,
+                };
+                Ok($blockname(literal))
+            }
+            ///Views this block as a generic [blocksr::Block].
+            #[allow(dead_code)]
+            pub fn as_block(&self) -> &blocksr::Block<($($A,)*),$R> {
+                unsafe{ &*(self as *const Self as *const blocksr::Block<($($A,)*),$R>) }
+            }
+        }
+    }
+);
+
+/**
+Takes the closure out of a tolerant once block, leaving null behind, or in debug builds with `std`, a marker naming
+where the block was created.
+
+Returns null if it was already taken, that is, if the block is being invoked again.
+
+# Safety
+`block` must point to a valid literal.
+*/
+#[doc(hidden)]
+pub unsafe fn take_once_closure(block: *mut BlockLiteralOnceEscape) -> *mut c_void {
+    //the closure field is pointer-sized and aligned, so it may be viewed atomically
+    let closure = unsafe{ &*(core::ptr::addr_of_mut!((*block).closure) as *const core::sync::atomic::AtomicPtr<c_void>) };
+    //in debug builds, leave behind where the block was created, to report a second invocation
+    #[cfg(all(debug_assertions, feature = "std"))]
+    {
+        use core::sync::atomic::Ordering;
+        use crate::provenance::{is_taken_marker, marker_location, taken_marker, CreatedAt};
+        let mut current = closure.load(Ordering::Acquire);
+        loop {
+            if current.is_null() || is_taken_marker(current) {
+                crate::diagnostic::warn(format_args!("blocksr: once block {:p} was invoked more than once; ignoring{}", block, CreatedAt(marker_location(current))));
+                return core::ptr::null_mut();
+            }
+            match closure.compare_exchange(current, taken_marker(current), Ordering::AcqRel, Ordering::Acquire) {
+                Ok(taken) => return taken,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+    #[cfg(not(all(debug_assertions, feature = "std")))]
+    {
+        let taken = closure.swap(core::ptr::null_mut(), core::sync::atomic::Ordering::AcqRel);
+        #[cfg(feature = "std")]
+        if taken.is_null() {
+            crate::diagnostic::warn(format_args!("blocksr: once block {:p} was invoked more than once; ignoring", block));
+        }
+        taken
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 #[doc(hidden)]
-pub struct BlockLiteralNoEscape<C> {
+pub struct BlockLiteralNoEscape<C, D = BlockDescriptorOnce> {
     pub isa: *const c_void,
     pub flags: c_int,
     pub reserved: MaybeUninit<c_int>,
     //first arg to this fn ptr is &block_literal_1
     pub invoke: *const c_void,
     //in this situation, this points to the next field (struct is self-referential)
-    pub descriptor: *mut BlockDescriptorOnce,
+    pub descriptor: *mut D,
     //just put the descriptor on the stack!  mwahahaha
-    pub inline_descriptor: BlockDescriptorOnce,
+    pub inline_descriptor: D,
     //closure stored inline for this situation
     pub closure_inline: C,
     pub pinned: PhantomPinned,
 }
 
+/**
+The descriptor of a `nocopy` noescape block: a [BlockDescriptorOnce] with copy and dispose helpers, so that the
+runtime calls us when it copies the block.
+*/
+#[repr(C)]
+#[derive(Debug)]
+#[doc(hidden)]
+pub struct BlockDescriptorNoCopy {
+    pub reserved: c_ulong,
+    pub size: c_ulong,
+    pub copy_helper: extern "C" fn(dst: *mut c_void, src: *mut c_void),
+    pub dispose_helper: extern "C" fn(src: *mut c_void),
+    pub signature: *const core::ffi::c_char,
+    pub layout: *const core::ffi::c_char,
+}
+
+/**
+Descriptors that a noescape literal carries inline.
+*/
+#[doc(hidden)]
+pub trait InlineDescriptor {
+    ///Flags the descriptor implies, in addition to [descriptor_flags].
+    const FLAGS: c_int;
+    ///A descriptor for a literal of `size` bytes, of the block type `B`, with `signature` if there is one.
+    fn new<B>(size: usize, signature: Option<&'static core::ffi::CStr>) -> Self;
+}
+
+impl InlineDescriptor for BlockDescriptorOnce {
+    const FLAGS: c_int = 0;
+    fn new<B>(size: usize, signature: Option<&'static core::ffi::CStr>) -> Self {
+        BlockDescriptorOnce::new(size).with_signature(signature)
+    }
+}
+
+impl InlineDescriptor for BlockDescriptorNoCopy {
+    const FLAGS: c_int = BLOCK_HAS_COPY_DISPOSE;
+    fn new<B>(size: usize, signature: Option<&'static core::ffi::CStr>) -> Self {
+        extern "C" fn copy_helper<B>(_dst: *mut c_void, _src: *mut c_void) {
+            //extern "C", so this aborts after the panic hook prints the message
+            copied_nocopy(core::any::type_name::<B>())
+        }
+        extern "C" fn dispose_helper(_src: *mut c_void) {
+            //only copies are disposed, and there are none
+        }
+        BlockDescriptorNoCopy {
+            reserved: 0,
+            size: size as c_ulong,
+            copy_helper: copy_helper::<B>,
+            dispose_helper,
+            signature: match signature {
+                Some(signature) => signature.as_ptr(),
+                None => core::ptr::null(),
+            },
+            layout: EXTENDED_LAYOUT_NO_OBJECTS,
+        }
+    }
+}
+
+///Reports that the runtime copied a `nocopy` block.
+#[doc(hidden)]
+#[cold]
+pub fn copied_nocopy(blockname: &str) -> ! {
+    panic!("{} was copied, but it is declared nocopy.  The block may borrow from the stack frame that created it, so the copy could outlive what it borrows; pass it only to functions that don't retain it.", blockname)
+}
+
+/**
+Pinned storage for a [once_noescape](crate::once_noescape) block, reused across calls.
+
+Create one outside a loop, and fill it with a fresh closure each iteration with the block's `in_cell`.  The header and
+descriptor are written the first time only; later iterations write just the closure.
+
+```
+use blocksr::{once_noescape, StackBlockCell};
+use core::sync::atomic::{AtomicUsize, Ordering};
+once_noescape!(VisitBlock(index: usize) -> ());
+//stands in for a synchronous enumeration API
+unsafe fn visit(block: &blocksr::Block<(usize,),()>, index: usize) { block.invoke((index,)) }
+let total = AtomicUsize::new(0);
+let mut cell = core::pin::pin!(StackBlockCell::new());
+for index in 0..4 {
+    let total = &total;
+    let block = unsafe{ VisitBlock::in_cell(cell.as_mut(), move |index| { total.fetch_add(index, Ordering::Relaxed); }) };
+    unsafe{ visit(block.as_block(), index) };
+}
+assert_eq!(total.into_inner(), 6);
+```
+
+Every closure in a cell has the same type, so the borrows they capture last as long as the cell.  Borrow what they
+share outside the loop, as above, rather than `&mut` each iteration.
+
+The cell doesn't drop closures.  A closure is consumed when its block is invoked; if a block is never invoked, its
+closure leaks when the cell is refilled, as with `new`.
+*/
+#[derive(Debug)]
+pub struct StackBlockCell<B> {
+    slot: MaybeUninit<B>,
+    filled: bool,
+    _pinned: PhantomPinned,
+}
+
+impl<B> StackBlockCell<B> {
+    ///Creates an empty cell.  Pin it, e.g. with [core::pin::pin], before filling it.
+    pub const fn new() -> Self {
+        StackBlockCell{slot: MaybeUninit::uninit(), filled: false, _pinned: PhantomPinned}
+    }
+    ///The storage, and whether it holds a block from an earlier fill.  The block type's `in_cell` marks it filled.
+    #[doc(hidden)]
+    pub fn __slot(self: core::pin::Pin<&mut Self>) -> (core::pin::Pin<&mut MaybeUninit<B>>, &mut bool) {
+        //Safety: the slot is pinned along with the cell, and the flag is plain data
+        let cell = unsafe{ self.get_unchecked_mut() };
+        (unsafe{ core::pin::Pin::new_unchecked(&mut cell.slot) }, &mut cell.filled)
+    }
+}
+
+impl<B> Default for StackBlockCell<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /**
 Declares a block that doesn't escape and executes once.  this is a typical pattern for `dispatch_sync`.
 
 In this case we try to store the block on the stack.  To accomplish this, the block must be pinned.
 
-Here's a complete example:
+The simplest way is `scoped`, which owns the block's storage and lends the pinned block to a closure:
+
+```
+    use blocksr::once_noescape;
+    once_noescape!(MyBlock(arg: u8) -> u8);
+    let r = unsafe{ MyBlock::scoped(|arg| arg + 1, |block| {
+        //pass block somewhere that invokes it before returning...
+        block.as_block().invoke((2,))
+    })};
+    assert_eq!(r, 3);
+```
+
+The block can't escape the closure:
+
+```compile_fail
+    use blocksr::once_noescape;
+    once_noescape!(MyBlock(arg: u8) -> u8);
+    let escaped = unsafe{ MyBlock::scoped(|arg| arg, |block| block) };
+```
+
+To manage the storage yourself, pin it and pass it to `new`.  Here's a complete example:
 
 ```
     use core::pin::Pin;
@@ -171,7 +836,50 @@ Here's a complete example:
     //pass _f somewhere...
 ```
 
-`::new()` is declared unsafe.
+`::new()` is declared unsafe.  As with [once_escaping], blocks that must not return may be declared `-> !`.
+
+The closure passed to `new` must be `Send`.  When the callee invokes the block synchronously on the calling thread,
+as `dispatch_sync` onto the current queue or synchronous enumeration APIs do, `new_local` accepts closures that
+aren't, such as ones borrowing an `Rc` or a `RefCell`.
+
+Such APIs typically take a block returning `void`, yet the Rust caller wants the closure's result.  `call_returning`
+creates the block, hands it to the call, and returns the result afterwards:
+
+```
+use blocksr::once_noescape;
+once_noescape!(SyncBlock() -> ());
+//stands in for dispatch_sync
+unsafe fn run_sync(block: &blocksr::Block<(),()>) { block.invoke(()) }
+let answer = unsafe{ SyncBlock::call_returning(|| 6 * 7, |block| run_sync(block)) };
+assert_eq!(answer, 42);
+```
+
+To create a block each iteration of a loop, keep one [StackBlockCell] outside it and fill it with `in_cell`.
+
+Arguments may be declared `name: Raw as Borrowed`, where `Borrowed` implements [blocksr::BorrowArgument], such as
+`*const c_char as &CStr`.  The closure receives a borrow, which the compiler keeps from outliving the invocation, so
+it needs no copy:
+
+```
+use blocksr::once_noescape;
+use core::ffi::{c_char, CStr};
+once_noescape!(NameBlock(name: *const c_char as &CStr) -> usize);
+let length = unsafe{ NameBlock::scoped(|name: &CStr| name.to_bytes().len(), |block| block.as_block().invoke((c"four".as_ptr(),))) };
+assert_eq!(length, 4);
+```
+
+A block whose closure borrows from the stack must not be copied to the heap, where the copy could outlive the
+borrow.  Prefix the declaration with `nocopy;` (after `isa = ...;`, if any) to install a copy helper that aborts,
+naming the block type, if the runtime tries:
+
+```
+use blocksr::once_noescape;
+once_noescape!(nocopy; SortBlock(a: *const u8, b: *const u8) -> i32);
+let r = unsafe{ SortBlock::scoped(|_a, _b| 0, |block| block.as_block().invoke((core::ptr::null(), core::ptr::null()))) };
+assert_eq!(r, 0);
+```
+
+The literals have [blocksr::BlockFlags::IS_NOESCAPE], as clang's do for blocks passed to `noescape` parameters.
 
 # Safety
 
@@ -193,14 +901,128 @@ unsafe impl Arguable for &DataTaskCompletionHandler {}
 macro_rules! once_noescape(
 
     (
-        $pub:vis $blockname: ident ($($a:ident : $A:ty),*) -> $R:ty
+        isa = $isa:tt; nocopy; $pub:vis $blockname: ident $($rest:tt)*
+    ) => {
+        blocksr::once_noescape!(nocopy; $pub $blockname $($rest)*);
+        impl<F> $blockname<F> {
+            #[doc(hidden)]
+            #[allow(unused_parens)] //isa = (expression)
+            fn __isa() -> *const core::ffi::c_void {
+                blocksr::__isa!($isa)
+            }
+            #[doc(hidden)]
+            const __GLOBAL: bool = blocksr::__isa_global!($isa);
+        }
+    };
+
+    (
+        isa = $isa:tt; $pub:vis $blockname: ident $($rest:tt)*
+    ) => {
+        blocksr::once_noescape!($pub $blockname $($rest)*);
+        impl<F> $blockname<F> {
+            #[doc(hidden)]
+            #[allow(unused_parens)] //isa = (expression)
+            fn __isa() -> *const core::ffi::c_void {
+                blocksr::__isa!($isa)
+            }
+            #[doc(hidden)]
+            const __GLOBAL: bool = blocksr::__isa_global!($isa);
+        }
+    };
+
+    (
+        nocopy; $($rest:tt)*
+    ) => {
+        blocksr::once_noescape!(@descriptor blocksr::hidden::BlockDescriptorNoCopy; $($rest)*);
+    };
+
+    (
+        @descriptor $D:ty; $pub:vis $blockname: ident ($($a:ident : $A:ty $(as $O:ty)?),*) -> !
     ) => {
         //must be ffi-safe
         #[repr(transparent)]
-        #[derive(Debug)]
         #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
-        $pub struct $blockname<F>(blocksr::hidden::BlockLiteralNoEscape<F>);
+        $pub struct $blockname<F>(blocksr::hidden::BlockLiteralNoEscape<F, $D>);
+        blocksr::__debug_block!($blockname<F>, "inline");
+        blocksr::__objc2_encode!($blockname<F>);
         impl<F> $blockname<F> {
+            blocksr::__introspection!(blocksr::hidden::BLOCK_IS_NOESCAPE | <$D as blocksr::hidden::InlineDescriptor>::FLAGS);
+            ///Creates a new non-escaping block that does not return.
+            ///
+            /// # Safety
+            /// You must verify that
+            //  * Arguments are correct and in the expected order
+            //      * Arguments are FFI-safe (compiler usually warns)
+            //  * Block will execute at most once:
+            //      * If ObjC executes the block several times, it's UB
+            ///
+            /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
+            #[allow(dead_code)]
+            pub unsafe fn new(into: core::pin::Pin<&mut core::mem::MaybeUninit<Self>>, f: F) -> core::pin::Pin<&Self> where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> blocksr::hidden::Never + Send {
+                Self::new_local(into, f)
+            }
+            ///Creates the block in storage owned by this function, and passes it to `scope`.
+            ///
+            /// Unlike `new`, there is no storage for the caller to move, so the block can't move once C has a pointer to it.
+            /// Prefer this, unless the block must outlive a closure.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            pub unsafe fn scoped<T,S>(f: F, scope: S) -> T where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> blocksr::hidden::Never + Send, S: FnOnce(core::pin::Pin<&Self>) -> T {
+                let storage = core::pin::pin!(core::mem::MaybeUninit::uninit());
+                scope(Self::new(storage, f))
+            }
+            ///Creates a new non-escaping block that does not return, from a closure that is not `Send`.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, you must verify that the block is invoked synchronously,
+            /// on the calling thread, before the call it was passed to returns.
+            #[allow(dead_code)]
+            pub unsafe fn new_local(into: core::pin::Pin<&mut core::mem::MaybeUninit<Self>>, f: F) -> core::pin::Pin<&Self> where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> blocksr::hidden::Never {
+                use blocksr::hidden::BlockLiteralNoEscape;
+                use core::mem::MaybeUninit;
+                use core::pin::Pin;
+                //This thunk is safe to call from C
+                extern "C" fn invoke_thunk<G>(block: *mut BlockLiteralNoEscape<G, $D>, $($a : $A),*) -> ! where G: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> blocksr::hidden::Never {
+                    //see the returning variant for why this is safe
+                    let read_owned = unsafe{core::ptr::read(block)};
+                    (read_owned.closure_inline)($(blocksr::__borrow!($a: $A $(as $O)?)),*)
+                }
+                let thunk_fn: *const core::ffi::c_void = invoke_thunk::<F> as *const core::ffi::c_void;
+                let literal = BlockLiteralNoEscape {
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    //nothing is returned, so nothing is returned indirectly
+                    flags: Self::FLAGS.bits(),
+                    reserved: core::mem::MaybeUninit::uninit(),
+                    invoke: thunk_fn ,
+                    descriptor: core::ptr::null_mut(),
+                    inline_descriptor: <$D as blocksr::hidden::InlineDescriptor>::new::<Self>(core::mem::size_of::<BlockLiteralNoEscape<F, $D>>(), Self::__SIGNATURE),
+                    closure_inline: f,
+                    pinned: core::marker::PhantomPinned,
+                };
+                let magic_ptr = into.get_unchecked_mut();
+                *magic_ptr  = MaybeUninit::new($blockname(literal));
+                //fixup self-referential pointer, now that the literal is in its final place
+                let placed = magic_ptr.assume_init_mut();
+                placed.0.descriptor = &mut placed.0.inline_descriptor;
+                let raw_ptr: *const Self = magic_ptr.assume_init_ref();
+                Pin::new_unchecked(&*raw_ptr)
+            }
+        }
+    };
+    (
+        @descriptor $D:ty; $pub:vis $blockname: ident ($($a:ident : $A:ty $(as $O:ty)?),*) -> $R:ty
+    ) => {
+        //must be ffi-safe
+        #[repr(transparent)]
+        #[allow(non_camel_case_types)] //ex nw_parameters_configure_protocol_block_t
+        $pub struct $blockname<F>(blocksr::hidden::BlockLiteralNoEscape<F, $D>);
+        blocksr::__debug_block!($blockname<F>, "inline");
+        blocksr::__objc2_encode!($blockname<F>);
+        blocksr::__block_type!($blockname<F>; ($($A),*) -> $R);
+        impl<F> $blockname<F> {
+            blocksr::__introspection!(blocksr::hidden::stret_flag::<$R>() | blocksr::hidden::BLOCK_IS_NOESCAPE | <$D as blocksr::hidden::InlineDescriptor>::FLAGS);
             ///Creates a new escaping block.
             ///
             /// # Safety
@@ -211,12 +1033,37 @@ macro_rules! once_noescape(
             //      * If ObjC executes the block several times, it's UB
             ///
             /// The resulting block type is FFI-safe.  Typically, you pass a pointer to the block type (e.g., on the stack) into objc.
-            pub unsafe fn new<'a>(into: core::pin::Pin<&'a mut core::mem::MaybeUninit<Self>>, f: F) -> core::pin::Pin<&'a Self> where F: FnOnce($($A),*) -> $R + Send {
+            #[allow(dead_code)]
+            pub unsafe fn new(into: core::pin::Pin<&mut core::mem::MaybeUninit<Self>>, f: F) -> core::pin::Pin<&Self> where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send {
+                Self::new_local(into, f)
+            }
+            ///Creates the block in storage owned by this function, and passes it to `scope`.
+            ///
+            /// Unlike `new`, there is no storage for the caller to move, so the block can't move once C has a pointer to it.
+            /// Prefer this, unless the block must outlive a closure.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            pub unsafe fn scoped<T,S>(f: F, scope: S) -> T where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send, S: FnOnce(core::pin::Pin<&Self>) -> T {
+                let storage = core::pin::pin!(core::mem::MaybeUninit::uninit());
+                scope(Self::new(storage, f))
+            }
+            ///Creates a new non-escaping block from a closure that is not `Send`.
+            ///
+            /// This is for APIs that invoke the block before returning, on the calling thread, such as `dispatch_sync`
+            /// onto the current queue, or synchronous enumeration.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.  In addition, you must verify that the block is invoked synchronously,
+            /// on the calling thread, before the call it was passed to returns.
+            #[allow(dead_code)]
+            pub unsafe fn new_local(into: core::pin::Pin<&mut core::mem::MaybeUninit<Self>>, f: F) -> core::pin::Pin<&Self> where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R {
                 use blocksr::hidden::BlockLiteralNoEscape;
                 use core::mem::MaybeUninit;
                 use core::pin::Pin;
                 //This thunk is safe to call from C
-                extern "C" fn invoke_thunk<G>(block: *mut BlockLiteralNoEscape<G>, $($a : $A),*) -> $R where G: FnOnce($($A),*) -> $R + Send {
+                extern "C" fn invoke_thunk<G>(block: *mut BlockLiteralNoEscape<G, $D>, $($a : $A),*) -> $R where G: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R {
                     /*
                     This should be safe because:
                     * block is valid for reads
@@ -224,53 +1071,215 @@ macro_rules! once_noescape(
                     * nobody else is going to read block again; in particular we know that the thunk will be called once,
                     there is no dispose handler, etc
                      */
-                    let read_owned = unsafe{std::ptr::read(block)};
-                    (read_owned.closure_inline)($($a),*)
+                    let read_owned = unsafe{core::ptr::read(block)};
+                    (read_owned.closure_inline)($(blocksr::__borrow!($a: $A $(as $O)?)),*)
                     //drop read_owned
                 }
                 let thunk_fn: *const core::ffi::c_void = invoke_thunk::<F> as *const core::ffi::c_void;
-                let mut literal = BlockLiteralNoEscape {
-                    isa: &blocksr::hidden::_NSConcreteStackBlock,
-                    flags: blocksr::hidden::BLOCK_HAS_STRET,
-                    reserved: std::mem::MaybeUninit::uninit(),
+                let literal = BlockLiteralNoEscape {
+                    isa: { #[allow(unused_imports)] use blocksr::hidden::DefaultIsa as _; Self::__isa() },
+                    flags: Self::FLAGS.bits(),
+                    reserved: core::mem::MaybeUninit::uninit(),
                     invoke: thunk_fn ,
-                    descriptor: std::ptr::null_mut(),
-                    inline_descriptor: blocksr::hidden::BlockDescriptorOnce {
-                        reserved: 0, //seems defined as NULL
-                        size: std::mem::size_of::<BlockLiteralNoEscape<F>>() as u64
-                    },
+                    descriptor: core::ptr::null_mut(),
+                    inline_descriptor: <$D as blocksr::hidden::InlineDescriptor>::new::<Self>(core::mem::size_of::<BlockLiteralNoEscape<F, $D>>(), Self::__SIGNATURE),
                     closure_inline: f,
-                    pinned: std::marker::PhantomPinned,
+                    pinned: core::marker::PhantomPinned,
                 };
-                //fixup self-referential pointer
-                literal.descriptor = &mut literal.inline_descriptor;
                 //should be ok because we are initializing the object
                 let magic_ptr = into.get_unchecked_mut();
                 *magic_ptr  = MaybeUninit::new($blockname(literal));
+                //fixup self-referential pointer, now that the literal is in its final place
+                let placed = magic_ptr.assume_init_mut();
+                placed.0.descriptor = &mut placed.0.inline_descriptor;
                 //tell rust we're not worried about returning a temporary
                 let raw_ptr: *const Self = magic_ptr.assume_init_ref();
                 Pin::new_unchecked(&*raw_ptr)
             }
+            ///Creates the block in `cell`, reusing the header written by an earlier fill of the same cell.
+            ///
+            /// Refill the cell only after the call the previous block was passed to has returned.
+            ///
+            /// # Safety
+            /// The same requirements as `new` apply.
+            #[allow(dead_code)]
+            pub unsafe fn in_cell(cell: core::pin::Pin<&mut blocksr::StackBlockCell<Self>>, f: F) -> core::pin::Pin<&Self> where F: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> $R + Send {
+                let (slot, filled) = cell.__slot();
+                if *filled {
+                    //the previous closure was moved out when it was invoked, so overwrite it without dropping
+                    let placed = slot.get_unchecked_mut().assume_init_mut();
+                    core::ptr::write(&mut placed.0.closure_inline, f);
+                    let raw_ptr: *const Self = placed;
+                    core::pin::Pin::new_unchecked(&*raw_ptr)
+                }
+                else {
+                    *filled = true;
+                    Self::new_local(slot, f)
+                }
+            }
+            ///Views this block as a generic [blocksr::Block].
+            #[allow(dead_code)]
+            pub fn as_block(&self) -> &blocksr::Block<($($A,)*),$R> {
+                unsafe{ &*(self as *const Self as *const blocksr::Block<($($A,)*),$R>) }
+            }
 
         }
+        impl $blockname<()> {
+            ///Creates a block from `f` on the stack, passes it to `call`, and returns what `f` returned.
+            ///
+            /// This is for `dispatch_sync`-style APIs, which run the block before returning but discard its return value.
+            /// The block itself returns `Default::default()`, which for a block declared `-> ()` is nothing.
+            ///
+            /// # Safety
+            /// The same requirements as `new_local` apply: `call` must pass the block to a function that invokes it
+            /// synchronously, on the calling thread.
+            ///
+            /// # Panics
+            /// If the block was not invoked by the time `call` returns.
+            #[allow(dead_code)]
+            pub unsafe fn call_returning<T,G,C>(f: G, call: C) -> T where G: FnOnce($(blocksr::__adapted!($A $(as $O)?)),*) -> T + blocksr::hidden::DefaultReturn<$R>, C: FnOnce(&blocksr::Block<($($A,)*),$R>) {
+                let mut slot: Option<T> = None;
+                {
+                    let out = &mut slot;
+                    let mut block_value = core::mem::MaybeUninit::uninit();
+                    let block_value = core::pin::Pin::new_unchecked(&mut block_value);
+                    let block = $blockname::new_local(block_value, move |$($a),*| {
+                        *out = Some(f($($a),*));
+                        G::default_return()
+                    });
+                    call(block.as_block());
+                }
+                slot.expect("the block was not invoked before the call returned")
+            }
+        }
+    };
 
-    }
+    (
+        $pub:vis $blockname: ident $args:tt -> $($R:tt)+
+    ) => {
+        blocksr::once_noescape!(@descriptor blocksr::hidden::BlockDescriptorOnce; $pub $blockname $args -> $($R)+);
+    };
 );
 
-extern {
+//On Apple platforms, the blocks runtime is part of libSystem.  GNUstep's libobjc2 provides its own.
+#[cfg(not(feature = "weak-runtime"))]
+#[cfg_attr(feature = "gnustep", link(name = "objc"))]
+extern "C" {
     #[doc(hidden)]
     pub static _NSConcreteStackBlock: c_void;
+    #[doc(hidden)]
+    pub static _NSConcreteGlobalBlock: c_void;
 }
 
-#[doc(hidden)]
+///The block returns its value indirectly, through a pointer in the first argument slot.
 pub const BLOCK_HAS_STRET: c_int = 1<<29;
-#[doc(hidden)]
+///The descriptor contains copy and dispose helpers.
 pub const BLOCK_HAS_COPY_DISPOSE: c_int = 1 << 25;
-#[doc(hidden)]
+///The block does not escape the call it was passed to.
 pub const BLOCK_IS_NOESCAPE: c_int = 1<<23;
 
-#[doc(hidden)]
+///The block is a global (static) block, which is never copied or disposed.
 pub const BLOCK_IS_GLOBAL: c_int = 1<<28;
+///The descriptor contains an ObjC type encoding of the block's signature.
+pub const BLOCK_HAS_SIGNATURE: c_int = 1<<30;
+///The descriptor's layout field uses the extended layout encoding.
+pub const BLOCK_HAS_EXTENDED_LAYOUT: c_int = 1<<31;
+
+/**
+The extended layout for a block that captures no ObjC objects.
+
+Values below 0x1000 are an inline encoding, `0xXYZ`, of X strong, Y byref and Z weak captures.  Our captures are
+Rust pointers, so they are all zero.
+*/
+const EXTENDED_LAYOUT_NO_OBJECTS: *const core::ffi::c_char = core::ptr::null();
+
+/**
+Flags describing a descriptor the macros emit for a block type with `signature`.
+
+A type with a [Signature](crate::Signature) records it, so it has `BLOCK_HAS_SIGNATURE`, and with the
+`extended-layout` feature, `BLOCK_HAS_EXTENDED_LAYOUT`.  The runtime only reads the layout of a descriptor with a
+signature, so a type without one has neither.
+*/
+#[doc(hidden)]
+pub const fn descriptor_flags(signature: Option<&core::ffi::CStr>) -> c_int {
+    match signature {
+        Some(_) if cfg!(feature = "extended-layout") => BLOCK_HAS_SIGNATURE | BLOCK_HAS_EXTENDED_LAYOUT,
+        Some(_) => BLOCK_HAS_SIGNATURE,
+        None => 0,
+    }
+}
+
+/**
+The descriptor for a once literal, given its type's `__DESCRIPTOR`.
+
+Types without a signature share [BLOCK_DESCRIPTOR_ONCE].
+*/
+#[doc(hidden)]
+#[inline(always)]
+//taking the address of a static mut is only safe since Rust 1.82
+#[allow(unused_unsafe)]
+pub fn once_descriptor(descriptor: &'static BlockDescriptorOnce) -> *mut BlockDescriptorOnce {
+    if descriptor.signature.is_null() {
+        unsafe{ core::ptr::addr_of_mut!(BLOCK_DESCRIPTOR_ONCE) }
+    }
+    else {
+        descriptor as *const BlockDescriptorOnce as *mut BlockDescriptorOnce
+    }
+}
+
+#[doc(hidden)]
+pub trait FnPointerOutput {
+    type Output;
+}
+impl<T> FnPointerOutput for fn() -> T {
+    type Output = T;
+}
+///Names the `!` type on stable, so that it can appear in `FnOnce(..) -> Never` bounds.
+#[doc(hidden)]
+pub type Never = <fn() -> ! as FnPointerOutput>::Output;
+
+/**
+Whether a block returning `R` sets [BLOCK_HAS_STRET].
+
+clang sets the flag when the return value is passed indirectly through a hidden pointer that occupies an argument
+slot (e.g. `rdi` on x86_64).  On arm64 the indirect return pointer goes in `x8` instead, which does not interfere
+with arguments, so the flag is never set there.  Elsewhere it follows the target's rule for returning a struct:
+
+* x86_64: larger than 16 bytes, or on Windows, any size but 1, 2, 4 or 8.
+* i386: on Apple platforms and Windows, any size but 1, 2, 4 or 8.  Otherwise, larger than 8 bytes.
+* armv7: larger than 4 bytes, except 8-byte-aligned 8-byte values.  On watchOS (armv7k), larger than 16 bytes.
+
+We only know the size and alignment of `R`, not whether it's a struct.  Where a scalar and a struct of the same layout
+are returned differently, we assume the scalar: an 8-byte-aligned 8-byte struct on armv7, or a struct of 8 bytes or
+less on i386 Linux, isn't flagged, although clang returns both in memory.
+*/
+#[doc(hidden)]
+pub const fn stret_flag<R>() -> c_int {
+    let size = core::mem::size_of::<R>();
+    let align = core::mem::align_of::<R>();
+    let stret = if cfg!(target_arch = "aarch64") {
+        false
+    }
+    else if cfg!(all(target_arch = "x86_64", windows)) {
+        !matches!(size, 0 | 1 | 2 | 4 | 8)
+    }
+    else if cfg!(target_arch = "x86_64") {
+        size > 16
+    }
+    else if cfg!(all(target_arch = "x86", any(target_vendor = "apple", windows))) {
+        !matches!(size, 0 | 1 | 2 | 4 | 8)
+    }
+    else if cfg!(all(target_arch = "arm", target_os = "watchos")) {
+        size > 16
+    }
+    else if cfg!(target_arch = "arm") {
+        size > 4 && !(size == 8 && align == 8)
+    }
+    else {
+        size > 8
+    };
+    if stret { BLOCK_HAS_STRET } else { 0 }
+}
 
 
 #[test] fn make_escape() {
@@ -280,9 +1289,52 @@ pub const BLOCK_IS_GLOBAL: c_int = 1<<28;
     })};
 }
 
+#[test] fn try_new() {
+    once_escaping!(MyBlock (arg: u8) -> u8);
+    let block = unsafe{ MyBlock::try_new(|arg| arg + 1) }.unwrap();
+    assert_eq!(unsafe{ block.as_block().invoke((1,)) }, 2);
+    crate::many_escaping_reentrant!(MyManyBlock (environment: &u8) -> u8);
+    let many = unsafe{ MyManyBlock::try_new(3, |environment| *environment) }.unwrap();
+    assert_eq!(unsafe{ many.as_block().invoke(()) }, 3);
+}
+
+#[test] fn custom_isa() {
+    static CLASS: u8 = 0;
+    const MY_ISA: *const c_void = &CLASS as *const u8 as *const c_void;
+    once_escaping!(isa = MY_ISA; MyBlock (arg: u8) -> u8);
+    let block = unsafe{ MyBlock::new(|arg| arg) };
+    assert_eq!(block.as_block().isa, MY_ISA);
+    assert_eq!(unsafe{ block.as_block().invoke((2,)) }, 2);
+    crate::many_escaping_reentrant!(isa = (MY_ISA); MyManyBlock (environment: &u8) -> u8);
+    let many = unsafe{ MyManyBlock::new(3, |environment| *environment) };
+    assert_eq!(many.as_block().isa, MY_ISA);
+    once_escaping!(isa = stack; MyStackBlock (arg: u8) -> u8);
+    let stack = unsafe{ MyStackBlock::new(|arg| arg) };
+    assert_eq!(stack.as_block().isa, crate::runtime::stack_block_isa());
+    unsafe{ stack.as_block().invoke((1,)) };
+}
+
+#[test] fn global_isa() {
+    use crate::BlockFlags;
+    once_escaping!(isa = global; MyBlock (arg: u8) -> u8);
+    let block = unsafe{ MyBlock::new(|arg| arg) };
+    assert_eq!(block.as_block().isa, crate::global_block_isa());
+    assert!(block.flags().contains(BlockFlags::IS_GLOBAL));
+    assert_eq!(unsafe{ block.as_block().invoke((2,)) }, 2);
+    crate::many_escaping_reentrant!(isa = global; MyManyBlock (environment: &u8) -> u8);
+    assert!(MyManyBlock::FLAGS.contains(BlockFlags::IS_GLOBAL));
+    assert!(!MyManyBlock::FLAGS.contains(BlockFlags::HAS_COPY_DISPOSE));
+    //the payload isn't trivial, but the runtime never calls a global block's helpers
+    let many = unsafe{ MyManyBlock::new(3, |environment| *environment) };
+    assert_eq!(many.flags(), MyManyBlock::FLAGS);
+    //the signature follows the size, without helpers in between
+    assert_eq!(many.signature(), Some(c"C@?"));
+    assert_eq!(unsafe{ many.as_block().invoke(()) }, 3);
+}
+
 #[test] fn make_noescape() {
     use core::pin::Pin;
-    use std::mem::MaybeUninit;
+    use core::mem::MaybeUninit;
     let mut block_value = MaybeUninit::uninit();
     let block_value = unsafe{ Pin::new_unchecked(&mut block_value) };
 
@@ -292,4 +1344,196 @@ pub const BLOCK_IS_GLOBAL: c_int = 1<<28;
     })
 
     };
-}
\ No newline at end of file
+}
+
+#[test] fn make_noreturn() {
+    once_escaping!(MyBlock (code: i32) -> !);
+    let _f = unsafe{ MyBlock::new(|code| {
+        std::process::exit(code)
+    })};
+
+    use core::pin::Pin;
+    use core::mem::MaybeUninit;
+    let mut block_value = MaybeUninit::uninit();
+    let block_value = unsafe{ Pin::new_unchecked(&mut block_value) };
+    once_noescape!(MyNoEscapeBlock() -> !);
+    let _g = unsafe{ MyNoEscapeBlock::new(block_value, || {
+        panic!("diverges")
+    })};
+}
+
+#[test] fn noescape_scoped() {
+    once_noescape!(MyBlock(arg: u8) -> u8);
+    let r = unsafe{ MyBlock::scoped(|arg| arg * 2, |block| block.as_block().invoke((4,))) };
+    assert_eq!(r, 8);
+}
+
+#[test] fn noescape_local() {
+    use core::pin::Pin;
+    use core::mem::MaybeUninit;
+    use std::rc::Rc;
+    let mut block_value = MaybeUninit::uninit();
+    let block_value = unsafe{ Pin::new_unchecked(&mut block_value) };
+    once_noescape!(MyBlock(arg: u8) -> u8);
+    let value = Rc::new(3);
+    let f = unsafe{ MyBlock::new_local(block_value, |arg| arg + *value) };
+    assert_eq!(unsafe{ f.as_block().invoke((2,)) }, 5);
+}
+
+#[test] fn noescape_in_cell() {
+    use std::sync::Arc;
+    once_noescape!(MyBlock(arg: u8) -> u8);
+    let mut cell = core::pin::pin!(StackBlockCell::new());
+    let mut total = 0;
+    for i in 0..3u8 {
+        let block = unsafe{ MyBlock::in_cell(cell.as_mut(), move |arg| arg + i) };
+        total += unsafe{ block.as_block().invoke((1,)) };
+    }
+    assert_eq!(total, 6);
+    //a closure that was never invoked is not dropped when the cell is refilled
+    let value = Arc::new(());
+    let mut cell = core::pin::pin!(StackBlockCell::new());
+    let kept = value.clone();
+    let _ = unsafe{ MyBlock::in_cell(cell.as_mut(), move |arg| { let _ = &kept; arg }) };
+    assert_eq!(Arc::strong_count(&value), 2);
+}
+
+#[test] fn noescape_nocopy() {
+    once_noescape!(nocopy; MyBlock(arg: u8) -> u8);
+    assert!(MyBlock::<()>::FLAGS.contains(crate::BlockFlags::HAS_COPY_DISPOSE));
+    //as clang marks them
+    assert!(MyBlock::<()>::FLAGS.contains(crate::BlockFlags::IS_NOESCAPE));
+    let r = unsafe{ MyBlock::scoped(|arg| arg + 1, |block| {
+        assert_eq!(block.flags(), MyBlock::<()>::FLAGS);
+        block.as_block().invoke((1,))
+    })};
+    assert_eq!(r, 2);
+    once_noescape!(isa = stack; nocopy; MyStackBlock() -> !);
+    assert!(MyStackBlock::<()>::FLAGS.contains(crate::BlockFlags::HAS_COPY_DISPOSE));
+    assert!(MyStackBlock::<()>::FLAGS.contains(crate::BlockFlags::IS_NOESCAPE));
+}
+
+#[test] #[should_panic(expected = "MyBlock was copied, but it is declared nocopy")] fn nocopy_copied() {
+    copied_nocopy("MyBlock")
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn noescape_borrowed() {
+    use crate::BorrowArgument;
+    #[repr(C)] struct Bytes { ptr: *const u8, len: usize }
+    struct Slice<'a>(&'a [u8]);
+    impl<'a> BorrowArgument<'a, *const Bytes> for Slice<'a> {
+        unsafe fn borrow_argument(raw: *const Bytes) -> Self {
+            Slice(unsafe{ core::slice::from_raw_parts((*raw).ptr, (*raw).len) })
+        }
+    }
+    once_noescape!(SumBlock(count: u8, bytes: *const Bytes as Slice) -> u32);
+    let storage = [1u8,2,3];
+    let bytes = Bytes{ptr: storage.as_ptr(), len: 3};
+    let sum = unsafe{ SumBlock::scoped(|count, bytes: Slice| bytes.0.iter().map(|b| *b as u32).sum::<u32>() * count as u32, |block| block.as_block().invoke((2, &bytes as *const Bytes))) };
+    assert_eq!(sum, 12);
+    once_noescape!(UnitBlock(name: *const core::ffi::c_char as &core::ffi::CStr) -> ());
+    let length = unsafe{ UnitBlock::call_returning(|name: &core::ffi::CStr| name.to_bytes().len(), |block| block.invoke((c"abc".as_ptr(),))) };
+    assert_eq!(length, 3);
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn call_returning() {
+    once_noescape!(SyncBlock(arg: u8) -> ());
+    let text = String::from("abc");
+    let r = unsafe{ SyncBlock::call_returning(|arg| text.len() + arg as usize, |block| block.invoke((2,))) };
+    assert_eq!(r, 5);
+    let not_invoked = std::panic::catch_unwind(|| unsafe{ SyncBlock::call_returning(|_arg| 1, |_block| {}) });
+    assert!(not_invoked.is_err());
+}
+
+#[test] fn make_tolerant() {
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Arc;
+    once_escaping_tolerant!(MyBlock (arg: u8) -> u8);
+    let calls = Arc::new(AtomicU8::new(0));
+    let block_calls = calls.clone();
+    let block = unsafe{ MyBlock::new(move |arg| {
+        block_calls.fetch_add(1, Ordering::SeqCst);
+        arg + 1
+    })};
+    assert_eq!(unsafe{ block.as_block().invoke((1,)) }, 2);
+    assert_eq!(unsafe{ block.as_block().invoke((1,)) }, 0);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+/*
+Reads a block's extended layout as libclosure's `_Block_extended_layout` does: only with both BLOCK_HAS_EXTENDED_LAYOUT
+and BLOCK_HAS_SIGNATURE, from the word after the signature, with null meaning no ObjC captures.
+ */
+#[cfg(all(test, feature = "extended-layout"))]
+fn runtime_extended_layout<A,R>(block: &crate::Block<A,R>) -> Option<&core::ffi::CStr> {
+    let flags = block.flags().bits();
+    if flags & BLOCK_HAS_EXTENDED_LAYOUT == 0 || flags & BLOCK_HAS_SIGNATURE == 0 {
+        return None;
+    }
+    let index = if flags & BLOCK_HAS_COPY_DISPOSE != 0 { 5 } else { 3 };
+    let layout = unsafe{ *(block.descriptor() as *const *const core::ffi::c_char).add(index) };
+    if layout.is_null() {
+        Some(c"")
+    }
+    else {
+        Some(unsafe{ core::ffi::CStr::from_ptr(layout) })
+    }
+}
+
+#[cfg(feature = "extended-layout")]
+#[test] fn extended_layout() {
+    use crate::Signature;
+    once_escaping!(MyBlock (arg: u8) -> u8);
+    let block = unsafe{ MyBlock::new(|arg| arg) };
+    assert_eq!(block.0.flags & (BLOCK_HAS_SIGNATURE | BLOCK_HAS_EXTENDED_LAYOUT), BLOCK_HAS_SIGNATURE | BLOCK_HAS_EXTENDED_LAYOUT);
+    assert_eq!(runtime_extended_layout(block.as_block()), Some(c""));
+    assert_eq!(block.signature(), Some(MyBlock::SIGNATURE));
+
+    crate::many_escaping_reentrant!(MyManyBlock (environment: &Vec<u8>) -> u8);
+    let many = unsafe{ MyManyBlock::new(vec![1], |environment| environment[0]) };
+    assert!(many.flags().contains(crate::BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(runtime_extended_layout(many.as_block()), Some(c""));
+    crate::many_escaping_reentrant!(MyTrivialBlock (environment: &()) -> u8);
+    let trivial = unsafe{ MyTrivialBlock::new((), |_| 1) };
+    assert!(!trivial.flags().contains(crate::BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(runtime_extended_layout(trivial.as_block()), Some(c""));
+
+    let mut cell = core::mem::MaybeUninit::uninit();
+    let cell = unsafe{ core::pin::Pin::new_unchecked(&mut cell) };
+    once_noescape!(nocopy; MyNoCopyBlock (arg: u8) -> u8);
+    let noescape = unsafe{ MyNoCopyBlock::new(cell, |arg| arg) };
+    assert_eq!(runtime_extended_layout(noescape.as_block()), Some(c""));
+
+    //without a signature, the runtime can't reach the layout, so it isn't claimed
+    #[repr(C)]
+    struct Opaque(u8);
+    once_escaping!(UnsignedBlock (arg: Opaque) -> u8);
+    let unsigned = unsafe{ UnsignedBlock::new(|arg| arg.0) };
+    assert_eq!(unsigned.0.flags & (BLOCK_HAS_SIGNATURE | BLOCK_HAS_EXTENDED_LAYOUT), 0);
+    assert_eq!(runtime_extended_layout(unsigned.as_block()), None);
+
+    #[cfg(target_os = "macos")]
+    {
+        extern "C" {
+            fn _Block_extended_layout(block: *const c_void) -> *const core::ffi::c_char;
+            fn _Block_signature(block: *const c_void) -> *const core::ffi::c_char;
+        }
+        let literal = many.as_block() as *const _ as *const c_void;
+        assert_eq!(unsafe{ core::ffi::CStr::from_ptr(_Block_extended_layout(literal)) }, c"");
+        assert_eq!(unsafe{ core::ffi::CStr::from_ptr(_Block_signature(literal)) }, MyManyBlock::SIGNATURE);
+        assert!(unsafe{ _Block_extended_layout(unsigned.as_block() as *const _ as *const c_void) }.is_null());
+    }
+}
+
+#[test] fn debug_noescape() {
+    use core::pin::Pin;
+    use core::mem::MaybeUninit;
+    let mut block_value = MaybeUninit::uninit();
+    let block_value = unsafe{ Pin::new_unchecked(&mut block_value) };
+    once_noescape!(MyBlock(arg: u8) -> u8);
+    let f = unsafe{ MyBlock::new(block_value, |arg| arg) };
+    let expected = format!("MyBlock {{ flags: {:?}, size: {}, signature: {:?}, closure: inline }}", MyBlock::<()>::FLAGS, core::mem::size_of_val(&*f), c"C@?C");
+    assert_eq!(format!("{:?}", f), expected);
+}
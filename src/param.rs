@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Type-state for handing a block to objc.
+
+A once block must be passed to objc at most once.  Keeping the Rust value around after passing it invites
+passing it again, which is UB.  `into_param` consumes the block, and the resulting [BlockParam] can produce a pointer
+only once, for the duration of a single call.
+
+```
+use blocksr::once_escaping;
+once_escaping!(MyBlock (arg: u8) -> u8);
+unsafe fn objc_api(block: *const MyBlock) -> u8 {
+    (*block).as_block().invoke((2,))
+}
+let block = unsafe{ MyBlock::new(|arg| arg + 1) };
+let param = block.into_param();
+let r = param.with_ptr(|ptr| unsafe{ objc_api(ptr) });
+assert_eq!(r, 3);
+//param and block have been consumed
+```
+*/
+
+use core::marker::PhantomData;
+
+/**
+A block that is ready to be passed to objc.
+
+The lifetime is the lifetime of anything the block borrows.  For escaping blocks it is `'static`.
+*/
+#[derive(Debug)]
+#[must_use = "the block is leaked unless it is passed to objc"]
+pub struct BlockParam<'a, T> {
+    block: T,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a, T> BlockParam<'a, T> {
+    #[doc(hidden)]
+    ///# Safety
+    /// `block` must be a block literal.
+    pub unsafe fn new(block: T) -> Self {
+        BlockParam{block, _marker: PhantomData}
+    }
+    /**
+    Passes a pointer to the block into `f`, consuming the parameter.
+
+    The pointer is valid only during `f`.  Typically `f` makes the objc call, which copies the block if it needs it
+    afterwards.
+    */
+    pub fn with_ptr<R>(self, f: impl FnOnce(*const T) -> R) -> R {
+        f(&self.block)
+    }
+}
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Where blocks were created, for diagnostics.
+
+In debug builds, the constructors of escaping blocks are `#[track_caller]`, and record their caller alongside the
+block's payload.  Diagnostics name it, such as a nonreentrant block invoked re-entrantly, or a tolerant block invoked
+twice.
+
+[live_payloads] lists the payloads that haven't been freed, by where their blocks were created.  A count that keeps
+growing points at a leak: blocks objc never released, or once blocks it never called.
+
+```
+let before = blocksr::provenance::live_payloads();
+//exercise the binding...
+assert_eq!(blocksr::provenance::live_payloads(), before);
+```
+
+In release builds, nothing is recorded, and [live_payloads] is empty.  Zero-sized payloads aren't allocated, so they
+aren't recorded either.
+*/
+
+#[cfg(debug_assertions)]
+use core::ffi::c_void;
+use core::panic::Location;
+
+#[cfg(debug_assertions)]
+static CREATED: std::sync::Mutex<Option<std::collections::HashMap<usize, &'static Location<'static>>>> = std::sync::Mutex::new(None);
+
+///Records that `payload` was allocated for a block created at `location`.
+#[cfg(debug_assertions)]
+pub(crate) fn record(payload: *mut c_void, location: &'static Location<'static>) {
+    CREATED.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(Default::default).insert(payload as usize, location);
+}
+
+///Forgets `payload`, which is being freed.
+#[cfg(debug_assertions)]
+pub(crate) fn remove(payload: *mut c_void) {
+    if let Some(created) = CREATED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        created.remove(&(payload as usize));
+    }
+}
+
+///Where the block owning `payload` was created, if it was recorded.
+#[cfg(debug_assertions)]
+pub(crate) fn created_at(payload: *mut c_void) -> Option<&'static Location<'static>> {
+    CREATED.lock().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(|created| created.get(&(payload as usize)).copied())
+}
+
+/*
+A tolerant once block leaves a marker in place of its closure when it is invoked, so that a second invocation can say
+where the block was created.  The marker is the creation site's address with the low bit set.  Locations are
+pointer-aligned, so no marker is 1, which is the one odd address a closure can have: the dangling pointer of an
+align-1 zero-sized closure.
+*/
+
+///The marker to leave in place of `closure`, or null if its creation site is unknown.
+#[cfg(debug_assertions)]
+pub(crate) fn taken_marker(closure: *mut c_void) -> *mut c_void {
+    match created_at(closure) {
+        Some(location) => (location as *const Location<'static> as usize | 1) as *mut c_void,
+        None => core::ptr::null_mut(),
+    }
+}
+
+///Whether `closure` was left by [taken_marker], rather than being a closure.
+#[cfg(debug_assertions)]
+pub(crate) fn is_taken_marker(closure: *mut c_void) -> bool {
+    let address = closure as usize;
+    address & 1 == 1 && address != 1
+}
+
+///The creation site in a marker from [taken_marker].
+#[cfg(debug_assertions)]
+pub(crate) fn marker_location(marker: *mut c_void) -> Option<&'static Location<'static>> {
+    if is_taken_marker(marker) {
+        Some(unsafe{ &*((marker as usize & !1) as *const Location<'static>) })
+    }
+    else {
+        None
+    }
+}
+
+///Formats as ` (created at file:line:column)`, or nothing if the location is unknown.
+#[cfg(debug_assertions)]
+pub(crate) struct CreatedAt(pub Option<&'static Location<'static>>);
+
+#[cfg(debug_assertions)]
+impl core::fmt::Display for CreatedAt {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            Some(location) => write!(f, " (created at {})", location),
+            None => Ok(()),
+        }
+    }
+}
+
+/**
+The payloads that haven't been freed, counted by where their blocks were created, in order of location.
+
+Empty in release builds.
+*/
+pub fn live_payloads() -> Vec<(&'static Location<'static>, usize)> {
+    let mut counts: Vec<(&'static Location<'static>, usize)> = Vec::new();
+    #[cfg(debug_assertions)]
+    if let Some(created) = CREATED.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        for location in created.values() {
+            match counts.iter_mut().find(|(l, _)| *l == *location) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((location, 1)),
+            }
+        }
+    }
+    counts.sort_by_key(|(location, _)| (location.file(), location.line(), location.column()));
+    counts
+}
+
+#[cfg(debug_assertions)]
+#[test] fn records() {
+    crate::many_escaping_reentrant!(MyBlock (environment: &Vec<u8>) -> u8);
+    let line = line!() + 1;
+    let block = unsafe{ MyBlock::new(vec![1], |environment| environment[0]) };
+    let created = created_at(block.0.payload).unwrap();
+    assert_eq!((created.file(), created.line()), (file!(), line));
+    assert!(live_payloads().iter().any(|(location, count)| *location == created && *count == 1));
+    let marker = taken_marker(block.0.payload);
+    assert!(is_taken_marker(marker));
+    assert_eq!(marker_location(marker), Some(created));
+    assert!(!is_taken_marker(core::ptr::NonNull::<u8>::dangling().as_ptr() as *mut c_void));
+}
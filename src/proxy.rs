@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Proxy blocks, which wrap an existing block with hooks.
+
+A [ProxyBlock] has the same signature as the block it wraps.  When invoked, it runs a `before` hook with the arguments,
+forwards the call through the inner block's invoke pointer, and runs an `after` hook with the return value.
+This is useful for instrumenting third-party completion handlers while debugging.
+
+```
+use blocksr::{once_escaping, ProxyBlock};
+once_escaping!(MyBlock (arg: u8) -> u8);
+let inner = unsafe{ MyBlock::new(|arg| arg + 1) };
+let proxy = unsafe{ ProxyBlock::new(inner.as_block(), |args: &(u8,)| {
+    println!("called with {:?}", args);
+}, |r: &u8| {
+    println!("returned {}", r);
+})};
+let r = unsafe{ proxy.as_block().invoke((2,)) };
+assert_eq!(r, 3);
+```
+*/
+
+use core::ffi::c_void;
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use crate::{Block, BlockArguments};
+use crate::many::{BlockLiteralManyEscape, BLOCK_DESCRIPTOR_MANY};
+use crate::once::{BLOCK_HAS_COPY_DISPOSE, stret_flag};
+
+struct ProxyPayload<Args,R> {
+    inner: *const Block<Args,R>,
+    before: Box<dyn Fn(&Args) + Send>,
+    after: Box<dyn Fn(&R) + Send>,
+}
+
+/**
+Argument tuples that can be proxied.
+
+This trait is implemented for tuples of 0 to 12 elements.
+*/
+pub trait ProxyArguments<R>: BlockArguments<R> {
+    #[doc(hidden)]
+    fn proxy_thunk() -> *const c_void;
+}
+
+macro_rules! proxy_arguments {
+    ($($a:ident : $A:ident),*) => {
+        impl<$($A,)* R> ProxyArguments<R> for ($($A,)*) {
+            fn proxy_thunk() -> *const c_void {
+                //This thunk is safe to call from C
+                extern "C" fn invoke_thunk<$($A,)* R>(block: *mut BlockLiteralManyEscape $(,$a: $A)*) -> R {
+                    let payload: &ProxyPayload<($($A,)*),R> = unsafe{ &*(crate::many::live_payload(block) as *const ProxyPayload<($($A,)*),R>) };
+                    let args = ($($a,)*);
+                    (payload.before)(&args);
+                    let r = unsafe{ (*payload.inner).invoke(args) };
+                    (payload.after)(&r);
+                    r
+                }
+                invoke_thunk::<$($A,)* R> as *const c_void
+            }
+        }
+    }
+}
+proxy_arguments!();
+proxy_arguments!(a: A);
+proxy_arguments!(a: A, b: B);
+proxy_arguments!(a: A, b: B, c: C);
+proxy_arguments!(a: A, b: B, c: C, d: D);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K);
+proxy_arguments!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H, i: I, j: J, k: K, l: L);
+
+/**
+A block that wraps another block of the same signature with `before` and `after` hooks.
+
+Like the `many` blocks, the hooks are dropped when the block is dropped, with assistance from the ObjC runtime.
+*/
+#[repr(transparent)]
+#[derive(Debug)]
+pub struct ProxyBlock<Args,R>(BlockLiteralManyEscape, PhantomData<*const (Args,R)>);
+
+impl<Args,R> ProxyBlock<Args,R> where Args: ProxyArguments<R> {
+    /**
+    Creates a new proxy for `inner`.
+
+    # Safety
+    You must verify that
+    * `inner` remains valid for as long as the proxy may be invoked.  The proxy does not copy or retain it.
+    * The proxy is invoked consistently with `inner`'s kind; for example if `inner` is a once block, the proxy
+      may be invoked at most once.
+    * The proxy is not invoked in a re-entrant manner if `inner` forbids it.
+    */
+    pub unsafe fn new<B,A>(inner: *const Block<Args,R>, before: B, after: A) -> Self where B: Fn(&Args) + Send + 'static, A: Fn(&R) + Send + 'static, Args: 'static, R: 'static {
+        extern "C" fn dispose_thunk<Args,R>(block: *mut BlockLiteralManyEscape) {
+            let payload_ptr = unsafe{ crate::many::live_payload(block) } as *mut ProxyPayload<Args,R>;
+            let _boxed_payload = unsafe{ Box::from_raw(payload_ptr) };
+            //drop
+        }
+        let payload = Box::new(ProxyPayload {
+            inner,
+            before: Box::new(before),
+            after: Box::new(after),
+        });
+        let literal = BlockLiteralManyEscape {
+            isa: crate::runtime::stack_block_isa(),
+            flags: stret_flag::<R>() | BLOCK_HAS_COPY_DISPOSE,
+            reserved: 0,
+            invoke: Args::proxy_thunk(),
+            descriptor: core::ptr::addr_of_mut!(BLOCK_DESCRIPTOR_MANY) as *mut c_void,
+            //note: this leak will be cleaned up by dispose
+            payload: Box::into_raw(payload) as *mut c_void,
+            dispose: dispose_thunk::<Args,R>,
+            take_environment: None,
+            clone_payload: None,
+        };
+        ProxyBlock(literal, PhantomData)
+    }
+    ///Views this block as a generic [Block].
+    pub fn as_block(&self) -> &Block<Args,R> {
+        unsafe{ &*(self as *const Self as *const Block<Args,R>) }
+    }
+}
+
+#[test] fn proxy_hooks() {
+    use std::sync::{Arc, Mutex};
+    crate::many_escaping_reentrant!(MyBlock (environment: &u32, a: u32, b: f64) -> f64);
+    let inner = unsafe{ MyBlock::new(10, |environment, a, b| {
+        (*environment + a) as f64 * b
+    })};
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let before_log = log.clone();
+    let after_log = log.clone();
+    let proxy = unsafe{ ProxyBlock::new(inner.as_block(), move |args: &(u32,f64)| {
+        before_log.lock().unwrap().push(format!("before {:?}", args));
+    }, move |r: &f64| {
+        after_log.lock().unwrap().push(format!("after {}", r));
+    })};
+    let r = unsafe{ proxy.as_block().invoke((2, 0.5)) };
+    assert_eq!(r, 6.0);
+    let r = unsafe{ proxy.as_block().invoke((0, 2.0)) };
+    assert_eq!(r, 20.0);
+    assert_eq!(*log.lock().unwrap(), vec!["before (2, 0.5)", "after 6", "before (0, 2.0)", "after 20"]);
+}
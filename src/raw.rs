@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Raw block literals, for advanced bindings.
+
+The macros cover the common cases.  Occasionally a binding needs a block with custom flags, a custom descriptor,
+or an invoke pointer that already exists, for example to re-expose a block compiled by clang.  This module
+builds such literals directly.
+
+```
+use blocksr::raw::RawBlockBuilder;
+use core::ffi::c_void;
+extern "C" fn invoke(block: *mut blocksr::raw::RawBlock, arg: u8) -> u8 {
+    let context = unsafe{ (*block).context } as *const u8;
+    arg + unsafe{ *context }
+}
+let context = 2u8;
+let block = unsafe {
+    RawBlockBuilder::new()
+        .invoke(invoke as *const c_void)
+        .context(&context as *const u8 as *mut c_void)
+        .build()
+};
+let r = unsafe{ block.as_block::<(u8,),u8>().invoke((3,)) };
+assert_eq!(r, 5);
+```
+
+Unlike the macro-generated types, nothing here manages memory for you.
+
+# Custom invoke functions
+
+To write the invoke function by hand, for an ABI fixup or an assembly trampoline, but still have blocksr manage
+the payload, use [with_custom_invoke].  The block uses the same literal, descriptor and copy/dispose helpers as
+[many_escaping_reentrant](crate::many_escaping_reentrant), so heap copies share the payload, and it is disposed with
+the last one.
+
+```
+use blocksr::raw::{with_custom_invoke, CustomBlock};
+use core::ffi::c_void;
+extern "C" fn invoke(block: *mut CustomBlock, arg: u8) -> u8 {
+    arg + unsafe{ CustomBlock::payload::<u8>(block) }
+}
+let block = unsafe{ with_custom_invoke(invoke as *const c_void, 2u8, drop) };
+assert_eq!(unsafe{ block.as_block::<(u8,),u8>().invoke((3,)) }, 5);
+```
+*/
+
+use core::ffi::c_void;
+use core::mem::MaybeUninit;
+use core::ffi::c_int;
+use crate::Block;
+use crate::once::BlockDescriptorOnce;
+use crate::many::{BlockLiteralManyEscape, ManyLiteral, Payload};
+
+pub use crate::once::{BLOCK_HAS_COPY_DISPOSE, BLOCK_HAS_STRET, BLOCK_IS_GLOBAL, BLOCK_IS_NOESCAPE, BLOCK_HAS_SIGNATURE, BLOCK_HAS_EXTENDED_LAYOUT};
+
+pub use crate::runtime::stack_block_isa;
+
+#[cfg(feature = "std")]
+pub use crate::descriptor::{intern_descriptor, DescriptorShape};
+
+/**
+A block literal with a single pointer-sized capture.
+
+The layout matches a clang block that captures one pointer, so invoke functions compiled by clang can find it.
+*/
+#[repr(C)]
+#[derive(Debug)]
+pub struct RawBlock {
+    pub isa: *const c_void,
+    pub flags: c_int,
+    pub reserved: MaybeUninit<c_int>,
+    //first arg to this fn ptr is &block_literal_1
+    pub invoke: *const c_void,
+    pub descriptor: *const c_void,
+    pub context: *mut c_void,
+}
+
+impl RawBlock {
+    ///Views this block as a generic [Block].
+    pub fn as_block<Args,R>(&self) -> &Block<Args,R> {
+        unsafe{ &*(self as *const Self as *const Block<Args,R>) }
+    }
+}
+
+static RAW_DESCRIPTOR: BlockDescriptorOnce = BlockDescriptorOnce::new(core::mem::size_of::<RawBlock>());
+
+/**
+Builds a [RawBlock].
+
+Defaults are a stack block with no flags, a descriptor describing [RawBlock] itself, and a null context.
+*/
+#[derive(Debug)]
+pub struct RawBlockBuilder {
+    isa: *const c_void,
+    flags: c_int,
+    invoke: *const c_void,
+    descriptor: *const c_void,
+    context: *mut c_void,
+    #[cfg(feature = "std")]
+    signature: Option<&'static core::ffi::CStr>,
+}
+
+impl Default for RawBlockBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RawBlockBuilder {
+    ///Creates a builder with the defaults.
+    pub fn new() -> Self {
+        RawBlockBuilder {
+            isa: stack_block_isa(),
+            flags: 0,
+            invoke: core::ptr::null(),
+            descriptor: &RAW_DESCRIPTOR as *const BlockDescriptorOnce as *const c_void,
+            context: core::ptr::null_mut(),
+            #[cfg(feature = "std")]
+            signature: None,
+        }
+    }
+    ///Sets the isa (block class).
+    pub fn isa(mut self, isa: *const c_void) -> Self {
+        self.isa = isa;
+        self
+    }
+    ///Sets the flags, such as [BLOCK_HAS_COPY_DISPOSE].
+    pub fn flags(mut self, flags: c_int) -> Self {
+        self.flags = flags;
+        self
+    }
+    ///Sets the invoke pointer.  The first argument to this function is a pointer to the block.
+    pub fn invoke(mut self, invoke: *const c_void) -> Self {
+        self.invoke = invoke;
+        self
+    }
+    ///Sets the descriptor.  It must be consistent with the flags, e.g. contain copy/dispose helpers if
+    /// [BLOCK_HAS_COPY_DISPOSE] is set.
+    pub fn descriptor(mut self, descriptor: *const c_void) -> Self {
+        self.descriptor = descriptor;
+        self
+    }
+    /**
+    Sets the signature, in ObjC type encoding, such as `c"i12@?0i8"`.
+
+    The block gets [BLOCK_HAS_SIGNATURE] and an [interned](intern_descriptor) descriptor describing [RawBlock]
+    with this signature, which replaces any descriptor set with [descriptor](Self::descriptor).
+    */
+    #[cfg(feature = "std")]
+    pub fn signature(mut self, signature: &'static core::ffi::CStr) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+    ///Sets the captured context pointer.
+    pub fn context(mut self, context: *mut c_void) -> Self {
+        self.context = context;
+        self
+    }
+    /**
+    Builds the literal.
+
+    # Safety
+    You must verify that
+    * The isa, flags, descriptor and invoke pointer are consistent with each other
+    * The invoke pointer has the signature expected by whoever calls the block
+    * The context is valid for as long as the block may be invoked
+
+    # Panics
+    If no invoke pointer was set.
+    */
+    pub unsafe fn build(self) -> RawBlock {
+        assert!(!self.invoke.is_null(), "RawBlockBuilder requires an invoke pointer");
+        #[allow(unused_mut)]
+        let (mut flags, mut descriptor) = (self.flags, self.descriptor);
+        #[cfg(feature = "std")]
+        if let Some(signature) = self.signature {
+            flags |= BLOCK_HAS_SIGNATURE;
+            descriptor = intern_descriptor(DescriptorShape{signature: Some(signature), ..DescriptorShape::new(core::mem::size_of::<RawBlock>())});
+        }
+        RawBlock {
+            isa: self.isa,
+            flags,
+            reserved: MaybeUninit::uninit(),
+            invoke: self.invoke,
+            descriptor,
+            context: self.context,
+        }
+    }
+}
+
+/**
+A block with a hand-written invoke function, whose payload blocksr manages.  Created by [with_custom_invoke].
+*/
+#[repr(transparent)]
+#[derive(Debug)]
+pub struct CustomBlock(BlockLiteralManyEscape);
+
+unsafe impl ManyLiteral for CustomBlock {}
+
+impl CustomBlock {
+    /**
+    Returns the payload, for the invoke function.
+
+    # Safety
+    `block` must be the first argument to the invoke function of a block created by [with_custom_invoke], with a
+    payload of type `P`.  The payload may be shared by concurrent invocations, as for a reentrant block.
+    */
+    pub unsafe fn payload<'a, P: Send + Sync>(block: *mut CustomBlock) -> &'a P {
+        let payload = unsafe{ crate::many::live_payload(block as *mut BlockLiteralManyEscape) };
+        unsafe{ &(*(payload as *const Payload<fn(P), P>)).environment }
+    }
+    /**
+    Sets additional flags, such as [BLOCK_HAS_STRET] for an invoke function that returns a structure in memory.
+
+    # Safety
+    The flags must describe the invoke function and the descriptor, as for [RawBlockBuilder::flags].
+    */
+    pub unsafe fn with_flags(mut self, flags: c_int) -> Self {
+        self.0.flags |= flags;
+        self
+    }
+    ///Views this block as a generic [Block].
+    pub fn as_block<Args,R>(&self) -> &Block<Args,R> {
+        unsafe{ &*(self as *const Self as *const Block<Args,R>) }
+    }
+}
+
+/**
+Creates a stack block that calls `invoke`, and owns `payload`.
+
+`invoke` receives a `*mut CustomBlock` first, followed by the block's arguments, and reads the payload with
+[CustomBlock::payload].  Heap copies may be invoked from several threads at once, sharing the payload, so it must be
+`Sync`.  When the runtime disposes the block, `dispose` is called with the payload; pass [drop] to drop it.  As for
+the `many` macros, [HeapBlock::try_into_environment](crate::HeapBlock::try_into_environment) recovers the payload
+instead, without calling `dispose`.
+
+# Safety
+You must verify that
+* `invoke` is an `extern "C"` function, whose arguments and return type are the ones the block's callers expect
+* `invoke` only reads the payload as a `P`, and doesn't retain the reference past the invocation
+* The block is invoked only while it, or a heap copy of it, is alive, as for the macros' blocks
+*/
+#[track_caller]
+pub unsafe fn with_custom_invoke<P: Send + Sync + 'static>(invoke: *const c_void, payload: P, dispose: fn(P)) -> CustomBlock {
+    extern "C" fn dispose_thunk<P>(block: *mut BlockLiteralManyEscape) {
+        let Payload{closure: dispose, environment}: Payload<fn(P), P> = unsafe{ crate::arena::take_payload(crate::many::live_payload(block)) };
+        dispose(environment)
+    }
+    let (flags, descriptor) = crate::many::many_descriptor::<fn(P), P>(BLOCK_HAS_COPY_DISPOSE, &crate::many::ManyDescriptors::UNSIGNED);
+    CustomBlock(BlockLiteralManyEscape {
+        isa: stack_block_isa(),
+        flags,
+        reserved: 0,
+        invoke,
+        descriptor,
+        payload: crate::arena::alloc_payload(Payload{closure: dispose, environment: payload}),
+        dispose: dispose_thunk::<P>,
+        take_environment: Some(crate::many::take_environment_thunk::<fn(P), P>),
+        clone_payload: None,
+    })
+}
+
+#[test] fn build_raw() {
+    extern "C" fn invoke(block: *mut RawBlock, arg: u32) -> u32 {
+        let context = unsafe{ (*block).context } as *const u32;
+        arg * unsafe{ *context }
+    }
+    let context = 3u32;
+    let block = unsafe {
+        RawBlockBuilder::new()
+            .flags(BLOCK_IS_NOESCAPE)
+            .invoke(invoke as *const c_void)
+            .context(&context as *const u32 as *mut c_void)
+            .build()
+    };
+    assert_eq!(block.flags, BLOCK_IS_NOESCAPE);
+    assert_eq!(block.isa, stack_block_isa());
+    let r = unsafe{ block.as_block::<(u32,),u32>().invoke((5,)) };
+    assert_eq!(r, 15);
+}
+
+#[cfg(feature = "std")]
+#[test] fn raw_signature() {
+    extern "C" fn invoke(_block: *mut RawBlock) {}
+    let build = || unsafe{ RawBlockBuilder::new().invoke(invoke as *const c_void).signature(c"v8@?0").build() };
+    let (a, b) = (build(), build());
+    assert_eq!(a.descriptor, b.descriptor);
+    assert_eq!(a.as_block::<(),()>().signature(), Some(c"v8@?0"));
+}
+
+#[test] fn custom_invoke() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    static DISPOSED: AtomicU32 = AtomicU32::new(0);
+    extern "C" fn invoke(block: *mut CustomBlock, arg: u32) -> u32 {
+        arg * unsafe{ CustomBlock::payload::<u32>(block) }
+    }
+    let mut block = unsafe{ with_custom_invoke(invoke as *const c_void, 3u32, |payload| DISPOSED.store(payload, Ordering::SeqCst)) };
+    assert_eq!(block.0.flags & BLOCK_HAS_COPY_DISPOSE, BLOCK_HAS_COPY_DISPOSE);
+    assert_eq!(unsafe{ block.as_block::<(u32,),u32>().invoke((5,)) }, 15);
+    unsafe{ (crate::many::BLOCK_DESCRIPTOR_MANY.dispose_helper)(&mut block.0) };
+    assert_eq!(DISPOSED.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! The runtime layer: continuations, streams and dispatch helpers, with the `rt` or `continuation` feature.
+
+These build on the block layer in [blocks](crate::blocks) to bridge block-based APIs to async Rust.  They need `std`, and
+pull in whatever their features depend on, so minimal users can leave `rt` off and keep only the block layer.
+
+```
+use blocksr::rt::{continuation, Continuation};
+let (future, completer) = continuation::<u8>();
+completer.complete(3);
+let _: Continuation<u8> = future;
+```
+
+Each item here is also at its old path, such as `blocksr::continuation`.  `rt` enables `continuation`; `common`,
+`futures-io`, `kvo` and `test-executors` add their modules here as well.
+*/
+
+pub use crate::clock;
+pub use crate::continuation::{self, continuation, Continuation, Completer, local_continuation, channel_continuation, cancel_all, OnMain, CompletionGroup};
+#[cfg(target_vendor = "apple")]
+pub use crate::continuation::on_main;
+pub use crate::bind_async;
+pub use crate::stream::{self, stream_continuation, ContinuationStream, Yielder};
+#[cfg(feature = "common")]
+pub use crate::common;
+#[cfg(feature = "futures-io")]
+pub use crate::io;
+#[cfg(feature = "kvo")]
+pub use crate::kvo;
+#[cfg(any(test, feature = "test-executors", feature = "bench"))]
+pub use crate::executors;
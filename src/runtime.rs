@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Finding the blocks runtime.
+
+By default, the macros refer to `_NSConcreteStackBlock` directly, so a binary that can't find the blocks runtime
+fails to load.  With the `weak-runtime` feature, the symbol is instead looked up when the first block is created.
+If it is missing, that panics with an explanation, and [runtime_available] lets you check ahead of time.
+*/
+
+use core::ffi::c_void;
+
+#[cfg(not(feature = "weak-runtime"))]
+mod imp {
+    use core::ffi::c_void;
+    pub fn stack_block_isa() -> *const c_void {
+        unsafe{ &crate::once::_NSConcreteStackBlock }
+    }
+    pub fn global_block_isa() -> *const c_void {
+        unsafe{ &crate::once::_NSConcreteGlobalBlock }
+    }
+    pub fn runtime_available() -> bool {
+        //otherwise we would not have loaded
+        true
+    }
+}
+
+#[cfg(feature = "weak-runtime")]
+mod imp {
+    use core::ffi::{c_char, c_void};
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    extern "C" {
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+    #[cfg(target_vendor = "apple")]
+    const RTLD_DEFAULT: *mut c_void = -2isize as *mut c_void;
+    #[cfg(not(target_vendor = "apple"))]
+    const RTLD_DEFAULT: *mut c_void = core::ptr::null_mut();
+
+    //null until looked up.  A failed lookup is not cached, so a runtime loaded later is found.
+    static STACK_BLOCK_ISA: AtomicPtr<c_void> = AtomicPtr::new(core::ptr::null_mut());
+    static GLOBAL_BLOCK_ISA: AtomicPtr<c_void> = AtomicPtr::new(core::ptr::null_mut());
+
+    fn lookup(cache: &AtomicPtr<c_void>, symbol: &core::ffi::CStr) -> *const c_void {
+        let cached = cache.load(Ordering::Relaxed);
+        if !cached.is_null() {
+            return cached;
+        }
+        let found = unsafe{ dlsym(RTLD_DEFAULT, symbol.as_ptr()) };
+        cache.store(found, Ordering::Relaxed);
+        found
+    }
+    fn expect_isa(cache: &AtomicPtr<c_void>, symbol: &core::ffi::CStr) -> *const c_void {
+        let isa = lookup(cache, symbol);
+        assert!(!isa.is_null(), "blocksr: {} was not found, so the blocks runtime is not loaded.  On Apple platforms it is part of libSystem; elsewhere, enable the `gnustep` feature and link libobjc2.", symbol.to_str().unwrap());
+        isa
+    }
+    pub fn stack_block_isa() -> *const c_void {
+        expect_isa(&STACK_BLOCK_ISA, c"_NSConcreteStackBlock")
+    }
+    pub fn global_block_isa() -> *const c_void {
+        expect_isa(&GLOBAL_BLOCK_ISA, c"_NSConcreteGlobalBlock")
+    }
+    pub fn runtime_available() -> bool {
+        !lookup(&STACK_BLOCK_ISA, c"_NSConcreteStackBlock").is_null()
+    }
+}
+
+/**
+The isa for blocks on the stack.  This is what the macros use, unless declared with another `isa`.
+
+# Panics
+With the `weak-runtime` feature, panics if the blocks runtime is not loaded.
+*/
+pub fn stack_block_isa() -> *const c_void {
+    imp::stack_block_isa()
+}
+
+/**
+The isa for global blocks, which the runtime never copies or disposes.  Macros use it when declared with `isa = global`.
+
+# Panics
+With the `weak-runtime` feature, panics if the blocks runtime is not loaded.
+*/
+pub fn global_block_isa() -> *const c_void {
+    imp::global_block_isa()
+}
+
+/**
+Provides the isa for blocks declared without an `isa = ...` option.
+
+The macros call `Self::__isa()` with this trait in scope.  Declaring an `isa` option adds an inherent `__isa`, which
+takes precedence over the trait's, and likewise an inherent `__GLOBAL` for `isa = global`.
+*/
+pub trait DefaultIsa {
+    ///Whether the block is declared with `isa = global`.  Not for the stack block isa.
+    const __GLOBAL: bool = false;
+    ///The stack block isa.
+    fn __isa() -> *const c_void {
+        stack_block_isa()
+    }
+}
+impl<T: ?Sized> DefaultIsa for T {}
+
+/**
+Adjusts a type's flags for its isa.
+
+The runtime never copies or disposes a global block, and clang emits them with [BLOCK_IS_GLOBAL](crate::hidden::BLOCK_IS_GLOBAL)
+and no copy or dispose helpers, so these blocks are created the same way.
+*/
+#[doc(hidden)]
+pub const fn isa_flags(flags: core::ffi::c_int, global: bool) -> core::ffi::c_int {
+    if global {
+        (flags | crate::hidden::BLOCK_IS_GLOBAL) & !crate::hidden::BLOCK_HAS_COPY_DISPOSE
+    }
+    else {
+        flags
+    }
+}
+
+/**
+The isa for an `isa = ...` macro option: `stack`, `global`, or a `*const c_void` given as an identifier or in parentheses.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __isa {
+    (stack) => { blocksr::hidden::stack_block_isa() };
+    (global) => { blocksr::global_block_isa() };
+    ($isa:tt) => { $isa };
+}
+
+/**
+Whether an `isa = ...` macro option declares a global block.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __isa_global {
+    (global) => { true };
+    ($isa:tt) => { false };
+}
+
+/**
+Returns true if the blocks runtime is loaded, so blocks can be created.
+
+Without the `weak-runtime` feature, this is always true: the binary would have failed to load otherwise.
+
+```
+if !blocksr::runtime_available() {
+    eprintln!("no blocks runtime; skipping");
+}
+```
+*/
+pub fn runtime_available() -> bool {
+    imp::runtime_available()
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn available() {
+    assert!(runtime_available());
+    assert!(!stack_block_isa().is_null());
+}
@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Warnings for completion handlers that take too long, with the `slow-completion` feature.
+
+Each [Completer](crate::continuation::Completer) schedules a check on the [clock](crate::clock) when it is created.
+If it hasn't completed once the threshold passes, the check logs a warning with `log`, naming the block type that
+owns it when it is known.  A completion that arrives after the warning logs again, with how late it was.  Completing
+in time cancels the check, so the clock can discard it.
+*/
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use crate::clock::BlockClock;
+use crate::CancellationToken;
+
+static THRESHOLD_MS: AtomicU64 = AtomicU64::new(10_000);
+
+/**
+Sets how long a completer may go without completing before a warning is logged.  The default is 10 seconds.
+
+Affects completers created afterwards.
+*/
+pub fn set_slow_completion_threshold(threshold: Duration) {
+    THRESHOLD_MS.store(threshold.as_millis().try_into().unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+fn threshold() -> Duration {
+    Duration::from_millis(THRESHOLD_MS.load(Ordering::Relaxed))
+}
+
+#[derive(Debug)]
+struct WatchState {
+    created: Instant,
+    done: AtomicBool,
+    warned: AtomicBool,
+    //the result type, until a block names itself
+    name: Mutex<&'static str>,
+}
+
+/**
+A completer's check for completing late.
+*/
+pub(crate) struct Watch {
+    state: Arc<WatchState>,
+    clock: Arc<dyn BlockClock>,
+    //cancels the check
+    check: CancellationToken,
+}
+
+impl std::fmt::Debug for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Watch").field(&self.state).finish()
+    }
+}
+
+impl Watch {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Self::with_clock(name, crate::clock::clock())
+    }
+    fn with_clock(name: &'static str, clock: Arc<dyn BlockClock>) -> Self {
+        let state = Arc::new(WatchState{created: clock.now(), done: AtomicBool::new(false), warned: AtomicBool::new(false), name: Mutex::new(name)});
+        let threshold = threshold();
+        let weak: Weak<WatchState> = Arc::downgrade(&state);
+        let check = CancellationToken::new();
+        clock.call_at_cancellable(state.created + threshold, Box::new(move || {
+            //a dropped watch was finished
+            let Some(check) = weak.upgrade() else { return };
+            if !check.done.load(Ordering::Acquire) {
+                check.warned.store(true, Ordering::Release);
+                log::warn!("{} has not completed after {:?}", check.name.lock().unwrap(), threshold);
+            }
+        }), check.clone());
+        Watch{state, clock, check}
+    }
+    pub(crate) fn set_name(&self, name: &'static str) {
+        *self.state.name.lock().unwrap() = name;
+    }
+    ///Called when the completer completes, or is dropped.
+    pub(crate) fn finish(&self) {
+        self.check.cancel();
+        if !self.state.done.swap(true, Ordering::AcqRel) && self.state.warned.load(Ordering::Acquire) {
+            log::warn!("{} completed after {:?}", self.state.name.lock().unwrap(), self.clock.now().saturating_duration_since(self.state.created));
+        }
+    }
+}
+
+#[test] fn expires() {
+    let clock = Arc::new(crate::clock::ManualClock::new());
+    let (slow, fast) = (Watch::with_clock("slow", clock.clone()), Watch::with_clock("fast", clock.clone()));
+    fast.finish();
+    clock.advance(threshold());
+    assert!(slow.state.warned.load(Ordering::Acquire));
+    assert!(!fast.state.warned.load(Ordering::Acquire));
+    slow.finish();
+    assert!(slow.state.done.load(Ordering::Acquire));
+    assert!(fast.check.is_cancelled());
+}
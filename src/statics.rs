@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Blocks that live for the whole program, with the `std` feature.
+
+Code that passes the same captureless block over and over, like a no-op completion handler, creates a new literal
+each time, even with `isa = global;`.  [static_block] declares one in a `static` instead.  It is created the first time
+it is used, and every use after that gets the same `&'static` block:
+
+```
+use blocksr::{many_escaping_reentrant, static_block};
+many_escaping_reentrant!(isa = global; Completion (environment: &(), success: i8) -> ());
+static_block!(static IGNORE: Completion = unsafe{ Completion::new((), |_, _| {}) });
+let block: &'static Completion = IGNORE.get();
+assert!(core::ptr::eq(block, IGNORE.get()));
+//pass block to objc, as often as you like...
+```
+
+Where a `static` is awkward, such as in generic code, [named_block] keeps a registry of blocks by type and name.
+
+Static blocks must be global blocks, which the runtime never copies or disposes, so declare their type with
+`isa = global;`.  They are shared across threads, so they must be `many_escaping_reentrant` types.
+*/
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use crate::hidden::ReentrantLiteral;
+use crate::{Block, BlockFlags};
+
+fn assert_global<T>(block: &T) {
+    let flags = unsafe{ &*(block as *const T as *const Block<(),()>) }.flags();
+    assert!(flags.contains(BlockFlags::IS_GLOBAL), "a static block must be declared with `isa = global;`, but {} isn't", core::any::type_name::<T>());
+}
+
+/**
+A block in a `static`, created the first time it is used.  Declare one with [static_block](crate::static_block).
+*/
+pub struct StaticBlock<T> {
+    block: OnceLock<T>,
+    init: fn() -> T,
+}
+
+//Safety: the block is reentrant, and global, so any thread may invoke it, and nothing disposes it
+unsafe impl<T: ReentrantLiteral> Sync for StaticBlock<T> {}
+
+impl<T: ReentrantLiteral> StaticBlock<T> {
+    ///Creates a static block, which `init` creates when it is first used.  [static_block](crate::static_block) calls
+    ///this.
+    pub const fn new(init: fn() -> T) -> Self {
+        StaticBlock{block: OnceLock::new(), init}
+    }
+    /**
+    The block, which is created on the first call.
+
+    # Panics
+    If the block isn't a global block.
+    */
+    pub fn get(&'static self) -> &'static T {
+        self.block.get_or_init(|| {
+            let block = (self.init)();
+            assert_global(&block);
+            block
+        })
+    }
+}
+
+impl<T> std::fmt::Debug for StaticBlock<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticBlock").field("initialized", &self.block.get().is_some()).finish()
+    }
+}
+
+/**
+Declares a `static` [StaticBlock], created by an expression the first time it is used.
+
+```
+use blocksr::{many_escaping_reentrant, static_block};
+many_escaping_reentrant!(isa = global; Noop (environment: &()) -> ());
+static_block!(pub static NOOP: Noop = unsafe{ Noop::new((), |_| {}) });
+```
+
+The expression can't capture anything, since it runs later.
+*/
+#[macro_export]
+macro_rules! static_block {
+    ($(#[$meta:meta])* $vis:vis static $name:ident : $T:ty = $init:expr) => {
+        $(#[$meta])*
+        $vis static $name: blocksr::StaticBlock<$T> = blocksr::StaticBlock::new(|| $init);
+    };
+}
+
+type Registry = HashMap<(TypeId, &'static str), &'static (dyn Any + Sync)>;
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+//so blocks can be stored in the registry as Any + Sync
+struct Registered<T>(T);
+//Safety: as for StaticBlock
+unsafe impl<T: ReentrantLiteral> Sync for Registered<T> {}
+
+/**
+The static block of type `T` named `name`, which `init` creates on the first call with that name.
+
+Blocks of different types may share a name.  `init` runs with the registry locked, so it must not call this itself.
+
+```
+use blocksr::{many_escaping_reentrant, named_block};
+many_escaping_reentrant!(isa = global; Noop (environment: &()) -> ());
+fn noop() -> &'static Noop {
+    named_block("noop", || unsafe{ Noop::new((), |_| {}) })
+}
+assert!(core::ptr::eq(noop(), noop()));
+```
+
+# Panics
+If the block isn't a global block.
+*/
+pub fn named_block<T: ReentrantLiteral + 'static, I: FnOnce() -> T>(name: &'static str, init: I) -> &'static T {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let registered = *registry.get_or_insert_with(HashMap::new).entry((TypeId::of::<T>(), name)).or_insert_with(|| {
+        let block = init();
+        assert_global(&block);
+        //never disposed, like the block itself
+        Box::leak(Box::new(Registered(block)))
+    });
+    &(registered as &dyn Any).downcast_ref::<Registered<T>>().expect("registered by TypeId").0
+}
+
+#[test] fn statics() {
+    crate::many_escaping_reentrant!(isa = global; Doubler (environment: &(), arg: u8) -> u8);
+    static_block!(static DOUBLE: Doubler = unsafe{ Doubler::new((), |_, arg| arg * 2) });
+    assert_eq!(unsafe{ DOUBLE.get().as_block().invoke((3,)) }, 6);
+    assert!(core::ptr::eq(DOUBLE.get(), DOUBLE.get()));
+    let named = named_block("double", || unsafe{ Doubler::new((), |_, arg| arg * 2) });
+    assert!(core::ptr::eq(named, named_block("double", || unreachable!())));
+    assert!(!core::ptr::eq(named, named_block("triple", || unsafe{ Doubler::new((), |_, arg| arg * 3) })));
+    assert_eq!(unsafe{ named.as_block().invoke((4,)) }, 8);
+}
+
+#[test] fn static_block_environment() {
+    //an environment that needs allocating, so only `isa = global` keeps the copy and dispose helpers away
+    crate::many_escaping_reentrant!(isa = global; Adder (environment: &Vec<u8>, arg: u8) -> u8);
+    static_block!(static ADD_TWO: Adder = unsafe{ Adder::new(vec![2], |environment, arg| arg + environment[0]) });
+    let block = ADD_TWO.get();
+    assert!(block.flags().contains(BlockFlags::IS_GLOBAL));
+    assert!(!block.flags().contains(BlockFlags::HAS_COPY_DISPOSE));
+    assert_eq!(unsafe{ block.as_block().invoke((3,)) }, 5);
+}
+
+#[test] #[should_panic(expected = "isa = global")] fn static_stack_block() {
+    crate::many_escaping_reentrant!(Doubler (environment: &(), arg: u8) -> u8);
+    named_block("stack", || unsafe{ Doubler::new((), |_, arg| arg * 2) });
+}
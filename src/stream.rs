@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Continuations for handlers that are called many times, with the `continuation` feature.
+
+Some APIs call a handler block once per item, like an enumeration or a progress callback, instead of once with a
+result.  Create a pair with [stream_continuation], move the [Yielder] into a `many` block, and take the items from
+the [ContinuationStream]:
+
+```
+use blocksr::stream::stream_continuation;
+let (mut stream, yielder) = stream_continuation::<u32>();
+//ordinarily, the yielder is moved into a block that objc calls with each item
+std::thread::spawn(move || {
+    yielder.send(1);
+    yielder.send(2);
+    //dropping the yielder, or calling finish, ends the stream
+});
+# #[cfg(feature = "test-executors")]
+assert_eq!(blocksr::executors::block_on(async { (stream.next().await, stream.next().await, stream.next().await) }), (Some(1), Some(2), None));
+```
+
+Items are buffered until they are taken.  With the `futures-sink` feature, the stream is a `futures` `Stream`, and
+the yielder a `Sink`, so stream-processing code can forward items into it.
+*/
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug)]
+struct StreamState<T> {
+    items: VecDeque<T>,
+    //the yielder finished, or was dropped
+    ended: bool,
+    //the stream was dropped
+    dropped: bool,
+    waker: Option<Waker>,
+}
+
+/**
+The receiving half of a [stream_continuation], whose items a [Yielder] sends.
+
+It ends, returning `None`, once the yielder is finished or dropped and the buffered items are taken.
+*/
+#[derive(Debug)]
+pub struct ContinuationStream<T> {
+    shared: Arc<Mutex<StreamState<T>>>,
+}
+
+/**
+The sending half of a [stream_continuation], which is typically moved into a `many` block.
+
+Dropping it ends the stream, as [finish](Yielder::finish) does.
+*/
+#[derive(Debug)]
+pub struct Yielder<T> {
+    shared: Arc<Mutex<StreamState<T>>>,
+}
+
+///Creates a linked [ContinuationStream] and [Yielder].
+pub fn stream_continuation<T: Send>() -> (ContinuationStream<T>, Yielder<T>) {
+    let shared = Arc::new(Mutex::new(StreamState{items: VecDeque::new(), ended: false, dropped: false, waker: None}));
+    (ContinuationStream{shared: shared.clone()}, Yielder{shared})
+}
+
+impl<T> Yielder<T> {
+    fn update(&self, f: impl FnOnce(&mut StreamState<T>)) {
+        let waker = {
+            let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut state);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+    /**
+    Sends `item`, waking the task that takes it.
+
+    Returns `false`, and drops `item`, if the stream was dropped or finished, so nobody will take it.
+    */
+    pub fn send(&self, item: T) -> bool {
+        let mut rejected = None;
+        self.update(|state| {
+            if state.dropped || state.ended {
+                rejected = Some(item);
+            }
+            else {
+                state.items.push_back(item);
+            }
+        });
+        //dropped outside the lock
+        rejected.is_none()
+    }
+    ///Whether the [ContinuationStream] still exists, so that an item passed to [send](Yielder::send) could be taken.
+    pub fn has_consumer(&self) -> bool {
+        !self.shared.lock().unwrap_or_else(|e| e.into_inner()).dropped
+    }
+    ///Ends the stream after the items already sent.
+    pub fn finish(self) {
+        //see Drop
+    }
+    fn end(&self) {
+        self.update(|state| state.ended = true);
+    }
+}
+
+impl<T> Drop for Yielder<T> {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
+impl<T> ContinuationStream<T> {
+    /**
+    Takes the next item, or arranges for `cx` to be woken when there is one.
+
+    Returns `Ready(None)` once the stream has ended.  Only the most recent waker is woken, so poll it from one task.
+    */
+    pub fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match state.items.pop_front() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if state.ended => Poll::Ready(None),
+            None => {
+                if !state.waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+                    state.waker = Some(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+    ///Takes the next item, or `None` once the stream has ended.
+    pub async fn next(&mut self) -> Option<T> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<T> Drop for ContinuationStream<T> {
+    fn drop(&mut self) {
+        let items = {
+            let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+            state.dropped = true;
+            std::mem::take(&mut state.items)
+        };
+        //items that were never taken are dropped here, outside the lock
+        drop(items);
+    }
+}
+
+#[cfg(feature = "futures-sink")]
+impl<T> futures_core::Stream for ContinuationStream<T> {
+    type Item = T;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        ContinuationStream::poll_next(self, cx)
+    }
+}
+
+/**
+The error of a [Yielder]'s `Sink` implementation: the stream was dropped or finished, so items can't be sent.
+*/
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct StreamClosed;
+
+impl std::fmt::Display for StreamClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the stream was closed")
+    }
+}
+
+impl std::error::Error for StreamClosed {}
+
+/**
+Sends items to the stream, which buffers them, so the sink is always ready until the stream is dropped.  Closing the
+sink finishes the stream.
+*/
+#[cfg(feature = "futures-sink")]
+impl<T> futures_sink::Sink<T> for Yielder<T> {
+    type Error = StreamClosed;
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), StreamClosed>> {
+        let state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        Poll::Ready(if state.dropped || state.ended { Err(StreamClosed) } else { Ok(()) })
+    }
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), StreamClosed> {
+        if self.send(item) { Ok(()) } else { Err(StreamClosed) }
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), StreamClosed>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), StreamClosed>> {
+        self.end();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test] fn stream() {
+    use crate::executors::block_on;
+    let (mut stream, yielder) = stream_continuation::<u8>();
+    assert!(yielder.send(1));
+    let thread = std::thread::spawn(move || {
+        assert!(yielder.send(2));
+        yielder.finish();
+    });
+    assert_eq!(block_on(stream.next()), Some(1));
+    assert_eq!(block_on(stream.next()), Some(2));
+    assert_eq!(block_on(stream.next()), None);
+    thread.join().unwrap();
+    //dropping the stream drops what it didn't take, and what is sent after
+    let log = crate::testing::DropLog::new();
+    let (stream, yielder) = stream_continuation();
+    assert!(yielder.send(log.probe("buffered")));
+    drop(stream);
+    log.mark("stream dropped");
+    assert!(!yielder.has_consumer());
+    assert!(!yielder.send(log.probe("late")));
+    assert_eq!(log.events(), ["buffered", "stream dropped", "late"]);
+}
+
+#[cfg(feature = "futures-sink")]
+#[test] fn sink() {
+    use futures_core::Stream;
+    use futures_sink::Sink;
+    struct Noop;
+    impl std::task::Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = Context::from_waker(&waker);
+    let (mut stream, mut yielder) = stream_continuation::<u8>();
+    assert_eq!(Pin::new(&mut yielder).poll_ready(&mut cx), Poll::Ready(Ok(())));
+    assert_eq!(Pin::new(&mut yielder).start_send(1), Ok(()));
+    assert_eq!(Pin::new(&mut yielder).poll_flush(&mut cx), Poll::Ready(Ok(())));
+    assert_eq!(Stream::poll_next(Pin::new(&mut stream), &mut cx), Poll::Ready(Some(1)));
+    assert_eq!(Stream::poll_next(Pin::new(&mut stream), &mut cx), Poll::Pending);
+    assert_eq!(Pin::new(&mut yielder).poll_close(&mut cx), Poll::Ready(Ok(())));
+    assert_eq!(Stream::poll_next(Pin::new(&mut stream), &mut cx), Poll::Ready(None));
+    assert_eq!(Pin::new(&mut yielder).start_send(2), Err(StreamClosed));
+}
@@ -0,0 +1,329 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! A stand-in for the blocks runtime, for testing bindings.
+
+When unit-testing binding code, you often want to play the objc side: copy the block, invoke it with chosen arguments,
+and release it.  [Harness] does these through the same code paths the runtime would, the copy helper, the invoke
+pointer and the dispose helper, entirely in Rust.
+
+```
+use blocksr::many_escaping_reentrant;
+use blocksr::testing::Harness;
+many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, index: usize) -> u8);
+let block = unsafe{ MyBlock::new(vec![1,2,3], |environment, index| environment[index]) };
+let mut copy = unsafe{ Harness::copy(&block) };
+assert_eq!(unsafe{ copy.invoke::<(usize,),u8>((2,)) }, 3);
+copy.release();
+assert!(copy.is_disposed());
+```
+
+Only the helpers are simulated.  Creating a block still takes the isa from the blocks runtime.
+
+Noescape blocks are never copied, so [invoke_in_place] invokes them where they are, consuming the closure as the
+runtime would.
+
+On macOS, `soak` goes the other way: it copies, invokes and releases a block through the real runtime thousands of
+times, and checks that memory returns to where it started, to find slow leaks.
+*/
+
+use core::ffi::c_void;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::pin::Pin;
+use alloc::boxed::Box;
+use crate::{Block, BlockArguments, BlockFlags};
+
+/**
+A simulated heap copy of a block literal of type `T`.
+
+Like a block on the heap, it is refcounted.  It is disposed when the count reaches zero, or when the harness is dropped.
+*/
+#[derive(Debug)]
+pub struct Harness<T> {
+    literal: Box<ManuallyDrop<T>>,
+    refcount: usize,
+}
+
+impl<T> Harness<T> {
+    /**
+    Copies `block`, like `Block_copy` on a stack block: the literal's bytes are copied, then the copy helper runs.
+
+    # Safety
+    You must verify that `block` is a block literal, such as a type declared by the macros.
+    */
+    pub unsafe fn copy(block: &T) -> Self {
+        let literal = Box::new(ManuallyDrop::new(unsafe{ core::ptr::read(block) }));
+        let harness = Harness{literal, refcount: 1};
+        if harness.header().flags().contains(BlockFlags::HAS_COPY_DISPOSE) {
+            let copy_helper: extern "C" fn(*const T, *const T) = unsafe{ core::mem::transmute(harness.helper(0)) };
+            copy_helper(harness.as_ptr(), block);
+        }
+        harness
+    }
+    fn header(&self) -> &Block<(),()> {
+        unsafe{ &*(self.as_ptr() as *const Block<(),()>) }
+    }
+    //the descriptor's copy (0) or dispose (1) helper, which follow reserved and size
+    fn helper(&self, index: usize) -> *const c_void {
+        unsafe{ *(self.header().descriptor() as *const *const c_void).add(2 + index) }
+    }
+    ///A pointer to the copy, as objc would hold it.
+    pub fn as_ptr(&self) -> *const T {
+        &**self.literal
+    }
+    ///Adds a reference, like `Block_copy` on a heap block.
+    ///
+    /// # Panics
+    /// If the copy was disposed.
+    pub fn retain(&mut self) {
+        assert!(!self.is_disposed(), "retained a disposed block");
+        self.refcount += 1;
+    }
+    /**
+    Releases a reference, like `Block_release`.  The last release runs the dispose helper.
+
+    # Panics
+    If the copy was already disposed.
+    */
+    pub fn release(&mut self) {
+        assert!(!self.is_disposed(), "released a disposed block");
+        self.refcount -= 1;
+        if self.refcount == 0 && self.header().flags().contains(BlockFlags::HAS_COPY_DISPOSE) {
+            let dispose_helper: extern "C" fn(*const T) = unsafe{ core::mem::transmute(self.helper(1)) };
+            dispose_helper(self.as_ptr());
+        }
+    }
+    ///The number of outstanding references.
+    pub fn refcount(&self) -> usize {
+        self.refcount
+    }
+    ///Returns true if the last reference was released.
+    pub fn is_disposed(&self) -> bool {
+        self.refcount == 0
+    }
+    /**
+    Invokes the copy through its invoke pointer.
+
+    # Safety
+    See [Block::invoke].
+
+    # Panics
+    If the copy was disposed.
+    */
+    pub unsafe fn invoke<Args,R>(&self, args: Args) -> R where Args: BlockArguments<R> {
+        assert!(!self.is_disposed(), "invoked a disposed block");
+        unsafe{ (*(self.as_ptr() as *const Block<Args,R>)).invoke(args) }
+    }
+}
+
+impl<T> Drop for Harness<T> {
+    fn drop(&mut self) {
+        if !self.is_disposed() {
+            self.refcount = 1;
+            self.release();
+        }
+    }
+}
+
+/**
+A log of when values were dropped, for testing the drop order a binding relies on.
+
+Record events with [mark](DropLog::mark), and give values a [DropProbe] that records when it is dropped.
+
+```
+use blocksr::testing::DropLog;
+let log = DropLog::new();
+let value = (vec![1u8], log.probe("value"));
+log.mark("before");
+drop(value);
+log.mark("after");
+assert_eq!(log.events(), ["before", "value", "after"]);
+```
+
+With the `continuation` feature, see the drop order of completion values in `blocksr::continuation`.
+*/
+/**
+Invokes the noescape block in `storage` on the current thread, the way objc invokes a noescape block it was passed.
+
+Noescape blocks aren't copied: the runtime calls the invoke pointer on the literal in place, and the block's thunk
+moves the closure out of it with `ptr::read`.  This does the same, so a binding's noescape blocks can be tested without
+an objc consumer.  Afterwards the literal is logically moved-from, so `storage` is cleared, and invoking it again panics
+instead of running the closure a second time.
+
+```
+use blocksr::once_noescape;
+use blocksr::testing::invoke_in_place;
+use core::mem::MaybeUninit;
+once_noescape!(MyBlock (arg: u8) -> u8);
+let mut storage = core::pin::pin!(MaybeUninit::uninit());
+let captured = vec![2u8];
+unsafe{ MyBlock::new(storage.as_mut(), move |arg| captured[0] * arg) };
+assert_eq!(unsafe{ invoke_in_place::<MyBlock<_>,(u8,),u8>(storage.as_mut(), (3,)) }, 6);
+```
+
+# Safety
+You must verify that `storage` was initialized by a noescape constructor, like `new`, and that `Args` and `R` match
+the block's signature.
+
+# Panics
+If the block in `storage` was already invoked, or isn't a noescape block.
+*/
+pub unsafe fn invoke_in_place<T,Args,R>(storage: Pin<&mut MaybeUninit<T>>, args: Args) -> R where Args: BlockArguments<R> {
+    //the literal is never moved, only read and cleared in place
+    let literal = unsafe{ storage.get_unchecked_mut() }.as_mut_ptr();
+    let block = literal as *const Block<Args,R>;
+    assert!(!unsafe{ (*block).invoke_ptr() }.is_null(), "invoked a noescape block that was already invoked");
+    assert!(unsafe{ (*block).flags() }.contains(BlockFlags::IS_NOESCAPE), "invoked a block in place that isn't noescape");
+    let result = unsafe{ (*block).invoke(args) };
+    //the closure was moved out by the thunk, so the bytes left behind must not be used again
+    unsafe{ core::ptr::write_bytes(literal, 0, 1) };
+    result
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug,Clone,Default)]
+pub struct DropLog {
+    events: std::sync::Arc<std::sync::Mutex<alloc::vec::Vec<&'static str>>>,
+}
+
+#[cfg(feature = "std")]
+impl DropLog {
+    ///Creates an empty log.  Clones share it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    ///Records `event` now.
+    pub fn mark(&self, event: &'static str) {
+        self.events.lock().unwrap().push(event);
+    }
+    ///Creates a value that records `event` when it is dropped.
+    pub fn probe(&self, event: &'static str) -> DropProbe {
+        DropProbe{log: self.clone(), event}
+    }
+    ///The events so far, in order.
+    pub fn events(&self) -> alloc::vec::Vec<&'static str> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+/**
+Records an event in a [DropLog] when dropped.  Created by [DropLog::probe].
+*/
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct DropProbe {
+    log: DropLog,
+    event: &'static str,
+}
+
+#[cfg(feature = "std")]
+impl Drop for DropProbe {
+    fn drop(&mut self) {
+        self.log.mark(self.event)
+    }
+}
+
+#[cfg(all(feature = "std", target_os = "macos"))]
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)] //filled in by malloc
+struct MallocStatistics {
+    blocks_in_use: u32,
+    size_in_use: usize,
+    max_size_in_use: usize,
+    size_allocated: usize,
+}
+
+#[cfg(all(feature = "std", target_os = "macos"))]
+extern "C" {
+    fn malloc_zone_statistics(zone: *const c_void, stats: *mut MallocStatistics);
+}
+
+///Memory in use: bytes allocated in all malloc zones, and block payloads that haven't been freed.
+#[cfg(all(feature = "std", target_os = "macos"))]
+fn in_use() -> (usize, usize) {
+    let mut stats = MallocStatistics::default();
+    unsafe{ malloc_zone_statistics(core::ptr::null(), &mut stats) };
+    let payloads = crate::provenance::live_payloads().iter().map(|(_, count)| count).sum();
+    (stats.size_in_use, payloads)
+}
+
+/**
+Soak-tests `block` through the real blocks runtime, for slow leaks.
+
+Each iteration copies the block to the heap with `Block_copy`, copies that again, invokes it with `args(iteration)`,
+and releases both references, so the copy and dispose helpers run each time.  After a warm-up, memory in use is
+measured before and after `iterations` of these.
+
+```no_run
+use blocksr::many_escaping_reentrant;
+many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, index: usize) -> u8);
+let block = unsafe{ MyBlock::new(vec![1,2,3], |environment, index| environment[index]) };
+unsafe{ blocksr::testing::soak::<_,(usize,),u8>(&block, 10_000, |iteration| (iteration % 3,)) };
+```
+
+# Panics
+If block payloads weren't freed, which is counted exactly in debug builds, or if malloc's bytes in use grew by
+`iterations` or more.  A leak costs at least one allocation per iteration, while other threads allocating in the
+meantime rarely add up to that much, so run soak tests on their own, or with few other tests.
+
+# Safety
+You must verify that `block` is a block literal taking `Args` and returning `R`, such as a type declared by the
+macros, and that it may be invoked `iterations` times.
+*/
+#[cfg(all(feature = "std", target_os = "macos"))]
+pub unsafe fn soak<T,Args,R>(block: &T, iterations: usize, mut args: impl FnMut(usize) -> Args) where Args: BlockArguments<R> {
+    let mut cycle = |iteration| {
+        let heap = unsafe{ crate::HeapBlock::copy(block) };
+        let again = heap.clone();
+        drop(unsafe{ (*(again.as_ptr() as *const Block<Args,R>)).invoke(args(iteration)) });
+        drop(again);
+        drop(heap);
+    };
+    //the runtime and malloc set up their own state on first use
+    for iteration in 0..iterations.min(100) {
+        cycle(iteration);
+    }
+    let (bytes, payloads) = in_use();
+    for iteration in 0..iterations {
+        cycle(iteration);
+    }
+    let (bytes_after, payloads_after) = in_use();
+    assert_eq!(payloads_after, payloads, "{} block payloads leaked in {} iterations", payloads_after.saturating_sub(payloads), iterations);
+    let growth = bytes_after.saturating_sub(bytes);
+    assert!(growth < iterations.max(1), "memory in use grew by {} bytes in {} iterations", growth, iterations);
+}
+
+#[test] fn refcounting() {
+    use std::sync::Arc;
+    crate::many_escaping_reentrant!(MyBlock (environment: &Arc<()>, arg: u8) -> u8);
+    let environment = Arc::new(());
+    let block = unsafe{ MyBlock::new(environment.clone(), |_, arg| arg) };
+    let mut copy = unsafe{ Harness::copy(&block) };
+    copy.retain();
+    assert_eq!(unsafe{ copy.invoke::<(u8,),u8>((4,)) }, 4);
+    copy.release();
+    assert_eq!(Arc::strong_count(&environment), 2);
+    copy.release();
+    assert!(copy.is_disposed());
+    assert_eq!(Arc::strong_count(&environment), 1);
+}
+
+#[cfg(feature = "std")]
+#[test] fn in_place() {
+    crate::once_noescape!(MyBlock (arg: u8) -> u8);
+    let log = DropLog::new();
+    let probe = log.probe("closure");
+    let mut storage = core::pin::pin!(MaybeUninit::uninit());
+    unsafe{ MyBlock::new(storage.as_mut(), move |arg| { let _probe = probe; arg + 1 }) };
+    assert_eq!(unsafe{ invoke_in_place::<MyBlock<_>,(u8,),u8>(storage.as_mut(), (3,)) }, 4);
+    assert_eq!(log.events(), ["closure"]);
+    let twice = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe{ invoke_in_place::<MyBlock<_>,(u8,),u8>(storage.as_mut(), (3,)) }));
+    assert!(twice.is_err());
+    assert_eq!(log.events(), ["closure"]);
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn soaking() {
+    crate::many_escaping_reentrant!(MyBlock (environment: &Vec<u8>, index: usize) -> u8);
+    let block = unsafe{ MyBlock::new(vec![1,2,3], |environment, index| environment[index]) };
+    unsafe{ soak::<_,(usize,),u8>(&block, 10_000, |iteration| (iteration % 3,)) };
+}
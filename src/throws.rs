@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Blocks whose closures return [Result], reported the ObjC way.
+
+Many ObjC blocks follow the convention `BOOL (^)(..., NSError **error)`: return `NO` (or `nil`) on failure, and
+write an error through the out-parameter if it isn't `NULL`.  Append `; throws error: *mut *mut NSError` to a
+declaration, and the block gains that trailing argument and a `new_throwing` constructor, whose closure returns a
+[Result] instead.
+
+```
+use blocksr::{once_escaping, IntoOutError};
+//stands in for an NSError
+#[repr(C)] struct NSError { code: i32 }
+struct SaveError(i32);
+impl IntoOutError<*mut NSError> for SaveError {
+    fn into_out_error(self) -> *mut NSError {
+        //ordinarily, an autoreleased NSError
+        Box::into_raw(Box::new(NSError{code: self.0}))
+    }
+}
+once_escaping!(SaveHandler (length: usize) -> bool; throws error: *mut *mut NSError);
+let block = unsafe{ SaveHandler::new_throwing(|length| {
+    if length > 0 { Ok(()) } else { Err(SaveError(-1)) }
+})};
+let mut error: *mut NSError = std::ptr::null_mut();
+let saved = unsafe{ block.as_block().invoke((0, &mut error as *mut _)) };
+assert!(!saved);
+assert_eq!(unsafe{ (*error).code }, -1);
+# drop(unsafe{ Box::from_raw(error) });
+```
+
+The block's return type says how success and failure look, by implementing [ThrowingReturn].
+*/
+
+/**
+A block return type that signals success or failure, such as `BOOL` or a nullable pointer.
+
+`T` is the value a closure returns on success.  Implement this for your own sentinel conventions.
+*/
+pub trait ThrowingReturn<T>: Sized {
+    ///The return value for success.
+    fn success(value: T) -> Self;
+    ///The return value for failure.
+    fn failure() -> Self;
+}
+
+impl ThrowingReturn<()> for bool {
+    fn success(_value: ()) -> Self {
+        true
+    }
+    fn failure() -> Self {
+        false
+    }
+}
+
+///`BOOL` is a `signed char` on some targets.
+impl ThrowingReturn<()> for i8 {
+    fn success(_value: ()) -> Self {
+        1
+    }
+    fn failure() -> Self {
+        0
+    }
+}
+
+///`BOOL` is an `unsigned char` in GNUstep.
+impl ThrowingReturn<()> for u8 {
+    fn success(_value: ()) -> Self {
+        1
+    }
+    fn failure() -> Self {
+        0
+    }
+}
+
+impl ThrowingReturn<bool> for bool {
+    fn success(value: bool) -> Self {
+        value
+    }
+    fn failure() -> Self {
+        false
+    }
+}
+
+impl<P> ThrowingReturn<*mut P> for *mut P {
+    fn success(value: *mut P) -> Self {
+        value
+    }
+    fn failure() -> Self {
+        core::ptr::null_mut()
+    }
+}
+
+impl<P> ThrowingReturn<*const P> for *const P {
+    fn success(value: *const P) -> Self {
+        value
+    }
+    fn failure() -> Self {
+        core::ptr::null()
+    }
+}
+
+/**
+An out-parameter that an error of type `V` can be written through, such as `NSError **`.
+*/
+pub trait OutParameter<V> {
+    ///Writes `value` unless the parameter is null.
+    ///
+    /// # Safety
+    /// The parameter must be null or valid for writes.
+    unsafe fn write_out(self, value: V);
+}
+
+impl<V> OutParameter<V> for *mut V {
+    unsafe fn write_out(self, value: V) {
+        if !self.is_null() {
+            unsafe{ self.write(value) };
+        }
+    }
+}
+
+/**
+Converts a Rust error into the value written through an error out-parameter, typically an autoreleased `NSError *`.
+*/
+pub trait IntoOutError<V> {
+    ///Converts the error.
+    fn into_out_error(self) -> V;
+}
+
+/**
+Reports `result` the ObjC way.  Used by `new_throwing`.
+
+# Safety
+`out` must be null or valid for writes.
+*/
+#[doc(hidden)]
+pub unsafe fn report_result<T,X,R,O,V>(result: Result<T,X>, out: O) -> R where R: ThrowingReturn<T>, O: OutParameter<V>, X: IntoOutError<V> {
+    match result {
+        Ok(value) => R::success(value),
+        Err(error) => {
+            unsafe{ out.write_out(error.into_out_error()) };
+            R::failure()
+        }
+    }
+}
+
+#[test] fn throwing_many() {
+    static OUT_OF_RANGE: u8 = 0;
+    struct Error;
+    impl IntoOutError<*const u8> for Error {
+        fn into_out_error(self) -> *const u8 {
+            &OUT_OF_RANGE
+        }
+    }
+    crate::many_escaping_reentrant!(Lookup (environment: &Vec<u32>, index: usize) -> *const u32; throws error: *mut *const u8);
+    let block = unsafe{ Lookup::new_throwing(vec![1u32,2,3], |environment, index| {
+        environment.get(index).map(|v| v as *const u32).ok_or(Error)
+    })};
+    let mut error: *const u8 = core::ptr::null();
+    let found = unsafe{ block.as_block().invoke((1, &mut error as *mut _)) };
+    assert_eq!(unsafe{ *found }, 2);
+    assert!(error.is_null());
+    let missing = unsafe{ block.as_block().invoke((5, &mut error as *mut _)) };
+    assert!(missing.is_null());
+    assert_eq!(error, &OUT_OF_RANGE as *const u8);
+    //a null out-parameter is not written
+    let missing = unsafe{ block.as_block().invoke((5, core::ptr::null_mut())) };
+    assert!(missing.is_null());
+}
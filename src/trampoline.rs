@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Blocks and closures as a C function pointer and context.
+
+Some C APIs take `void (*fn)(void *context)` instead of a block, like `dispatch_async_f`.  Rather than maintaining a
+second callback system for them, wrap the same closure, or a block, in a [Trampoline] or [OnceTrampoline].
+
+```
+use blocksr::Trampoline;
+let trampoline = Trampoline::new(|| println!("called"));
+//pass trampoline.function() and trampoline.context() to C, and keep trampoline alive while C may call it
+(trampoline.function())(trampoline.context());
+```
+
+A block with no arguments returning `void` already has this shape: its invoke function takes the block as the
+context.  So [Trampoline::from_block] needs no thunk, only a heap copy to own.
+*/
+
+use core::ffi::c_void;
+use alloc::boxed::Box;
+use crate::{Block, HeapBlock};
+
+/**
+A function pointer and context that may be called any number of times, while this is alive.
+
+Dropping it frees the context, so keep it alive until C will no longer call the function.
+*/
+#[derive(Debug)]
+pub struct Trampoline {
+    function: extern "C" fn(*mut c_void),
+    context: *mut c_void,
+    release: unsafe fn(*mut c_void),
+}
+
+impl Trampoline {
+    ///Wraps `f`.  It may be called from any thread, perhaps from several at once.
+    pub fn new<F: Fn() + Send + Sync + 'static>(f: F) -> Self {
+        extern "C" fn call<F: Fn()>(context: *mut c_void) {
+            let f = unsafe{ &*(context as *const F) };
+            f()
+        }
+        unsafe fn release<F>(context: *mut c_void) {
+            drop(unsafe{ Box::from_raw(context as *mut F) });
+        }
+        Trampoline{function: call::<F>, context: Box::into_raw(Box::new(f)) as *mut c_void, release: release::<F>}
+    }
+    /**
+    Wraps a heap copy of `block`.
+
+    # Safety
+    You must verify that `block` may be invoked as many times, and as concurrently, as C will call the function.
+    */
+    pub unsafe fn from_block(block: &Block<(),()>) -> Self {
+        unsafe fn release(context: *mut c_void) {
+            drop(unsafe{ HeapBlock::from_raw(core::ptr::NonNull::new_unchecked(context as *mut Block<(),()>)) });
+        }
+        let heap = unsafe{ HeapBlock::copy(block) };
+        //the invoke function of a `void (^)(void)` takes the block, and nothing else
+        let function = unsafe{ core::mem::transmute::<*const c_void, extern "C" fn(*mut c_void)>((*heap.as_ptr()).invoke_ptr()) };
+        Trampoline{function, context: heap.into_raw().as_ptr() as *mut c_void, release}
+    }
+    ///The function to pass to C.
+    pub fn function(&self) -> extern "C" fn(*mut c_void) {
+        self.function
+    }
+    ///The context to pass to C alongside [function](Trampoline::function).
+    pub fn context(&self) -> *mut c_void {
+        self.context
+    }
+}
+
+impl Drop for Trampoline {
+    fn drop(&mut self) {
+        unsafe{ (self.release)(self.context) }
+    }
+}
+
+/**
+A function pointer and context that may be called once, which frees the context.
+
+Pass it to C with [into_raw](OnceTrampoline::into_raw).  If it is dropped instead, the context is freed without
+calling.
+*/
+#[derive(Debug)]
+pub struct OnceTrampoline {
+    function: extern "C" fn(*mut c_void),
+    context: *mut c_void,
+    release: unsafe fn(*mut c_void),
+}
+
+impl OnceTrampoline {
+    ///Wraps `f`.  It may be called from any thread.
+    pub fn new<F: FnOnce() + Send + 'static>(f: F) -> Self {
+        extern "C" fn call<F: FnOnce()>(context: *mut c_void) {
+            let f = unsafe{ Box::from_raw(context as *mut F) };
+            f()
+        }
+        unsafe fn release<F>(context: *mut c_void) {
+            drop(unsafe{ Box::from_raw(context as *mut F) });
+        }
+        OnceTrampoline{function: call::<F>, context: Box::into_raw(Box::new(f)) as *mut c_void, release: release::<F>}
+    }
+    /**
+    Wraps a heap copy of `block`, which is released after the call.
+
+    # Safety
+    You must verify that `block` may be invoked once, on whatever thread C calls the function.
+    */
+    pub unsafe fn from_block(block: &Block<(),()>) -> Self {
+        extern "C" fn call(context: *mut c_void) {
+            let heap = unsafe{ HeapBlock::from_raw(core::ptr::NonNull::new_unchecked(context as *mut Block<(),()>)) };
+            unsafe{ (*heap.as_ptr()).invoke(()) };
+            //released on drop
+        }
+        unsafe fn release(context: *mut c_void) {
+            drop(unsafe{ HeapBlock::from_raw(core::ptr::NonNull::new_unchecked(context as *mut Block<(),()>)) });
+        }
+        let heap = unsafe{ HeapBlock::copy(block) };
+        OnceTrampoline{function: call, context: heap.into_raw().as_ptr() as *mut c_void, release}
+    }
+    ///Gives up ownership of the context, returning the function and context to pass to C.  Calling the function frees
+    ///the context; if it is never called, the context leaks.
+    pub fn into_raw(self) -> (extern "C" fn(*mut c_void), *mut c_void) {
+        let raw = (self.function, self.context);
+        core::mem::forget(self);
+        raw
+    }
+}
+
+impl Drop for OnceTrampoline {
+    fn drop(&mut self) {
+        unsafe{ (self.release)(self.context) }
+    }
+}
+
+#[test] fn trampolines() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = count.clone();
+    let trampoline = Trampoline::new(move || { counter.fetch_add(1, Ordering::Relaxed); });
+    (trampoline.function())(trampoline.context());
+    (trampoline.function())(trampoline.context());
+    assert_eq!(count.load(Ordering::Relaxed), 2);
+    drop(trampoline);
+    assert_eq!(Arc::strong_count(&count), 1);
+
+    let counter = count.clone();
+    let (function, context) = OnceTrampoline::new(move || { counter.fetch_add(1, Ordering::Relaxed); }).into_raw();
+    function(context);
+    assert_eq!(count.load(Ordering::Relaxed), 3);
+    assert_eq!(Arc::strong_count(&count), 1);
+
+    let counter = count.clone();
+    let block = unsafe{ crate::VoidManyBlock::from_fn(move || { counter.fetch_add(1, Ordering::Relaxed); }) };
+    let trampoline = unsafe{ Trampoline::from_block(block.as_block()) };
+    (trampoline.function())(trampoline.context());
+    assert_eq!(count.load(Ordering::Relaxed), 4);
+}
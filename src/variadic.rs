@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Invoke thunks for variadic blocks.
+
+Defining a variadic function requires the nightly `c_variadic` feature.  The thunks live here, rather than
+in the macro expansions, so that only blocksr needs to enable it.
+*/
+use core::ffi::{VaList, VaArgSafe};
+use crate::hidden::{BlockLiteralOnceEscape, BlockLiteralManyEscape, Payload, take_payload, live_payload};
+
+/**
+The variable arguments passed to a variadic block.
+
+This corresponds to `va_list` in C.  It wraps the unstable [VaList] so that crates declaring variadic blocks
+don't need to enable `c_variadic` themselves.
+*/
+#[derive(Debug)]
+pub struct VaArgs<'f>(VaList<'f>);
+
+impl<'f> VaArgs<'f> {
+    ///Reads the next argument, like `va_arg` in C.
+    ///
+    /// # Safety
+    /// You must verify that
+    /// * There is a next argument
+    /// * The next argument has type `T`, after C's default argument promotions
+    ///   (e.g. `float` is passed as `double`, `char` as `int`)
+    #[allow(unused_unsafe)]
+    pub unsafe fn arg<T: VaArgSafe>(&mut self) -> T {
+        unsafe{ self.0.next_arg::<T>() }
+    }
+}
+
+macro_rules! variadic_thunks {
+    ($once:ident, $many:ident $(,$a:ident : $A:ident)*) => {
+        #[doc(hidden)]
+        pub unsafe extern "C" fn $once<G, $($A,)* R>(block: *mut BlockLiteralOnceEscape, $($a: $A,)* args: ...) -> R where G: FnOnce($($A,)* &mut VaArgs) -> R + Send {
+            let rust_fn: G = unsafe{ take_payload((*block).closure as *mut core::ffi::c_void) };
+            rust_fn($($a,)* &mut VaArgs(args))
+        }
+        #[doc(hidden)]
+        pub unsafe extern "C" fn $many<G, H, $($A,)* R>(block: *mut BlockLiteralManyEscape, $($a: $A,)* args: ...) -> R where G: Fn(&H, $($A,)* &mut VaArgs) -> R + Send {
+            //note: we are forbidden to use mutable references here, since functions overlap.
+            let payload: &Payload<G,H> = unsafe{ &*(live_payload(block) as *const Payload<G,H>) };
+            (payload.closure)(&payload.environment, $($a,)* &mut VaArgs(args))
+        }
+    }
+}
+variadic_thunks!(once_thunk_0, many_thunk_0);
+variadic_thunks!(once_thunk_1, many_thunk_1, a: A);
+variadic_thunks!(once_thunk_2, many_thunk_2, a: A, b: B);
+variadic_thunks!(once_thunk_3, many_thunk_3, a: A, b: B, c: C);
+variadic_thunks!(once_thunk_4, many_thunk_4, a: A, b: B, c: C, d: D);
+
+/**
+Selects the variadic thunk for the number of fixed arguments.
+
+Up to 4 fixed arguments are supported.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __variadic_thunk {
+    (once [$($G:ty),*] $R:ty;) => {blocksr::hidden::variadic::once_thunk_0::<$($G,)* $R>};
+    (once [$($G:ty),*] $R:ty; $A:ty) => {blocksr::hidden::variadic::once_thunk_1::<$($G,)* $A, $R>};
+    (once [$($G:ty),*] $R:ty; $A:ty, $B:ty) => {blocksr::hidden::variadic::once_thunk_2::<$($G,)* $A, $B, $R>};
+    (once [$($G:ty),*] $R:ty; $A:ty, $B:ty, $C:ty) => {blocksr::hidden::variadic::once_thunk_3::<$($G,)* $A, $B, $C, $R>};
+    (once [$($G:ty),*] $R:ty; $A:ty, $B:ty, $C:ty, $D:ty) => {blocksr::hidden::variadic::once_thunk_4::<$($G,)* $A, $B, $C, $D, $R>};
+    (many [$($G:ty),*] $R:ty;) => {blocksr::hidden::variadic::many_thunk_0::<$($G,)* $R>};
+    (many [$($G:ty),*] $R:ty; $A:ty) => {blocksr::hidden::variadic::many_thunk_1::<$($G,)* $A, $R>};
+    (many [$($G:ty),*] $R:ty; $A:ty, $B:ty) => {blocksr::hidden::variadic::many_thunk_2::<$($G,)* $A, $B, $R>};
+    (many [$($G:ty),*] $R:ty; $A:ty, $B:ty, $C:ty) => {blocksr::hidden::variadic::many_thunk_3::<$($G,)* $A, $B, $C, $R>};
+    (many [$($G:ty),*] $R:ty; $A:ty, $B:ty, $C:ty, $D:ty) => {blocksr::hidden::variadic::many_thunk_4::<$($G,)* $A, $B, $C, $D, $R>};
+}
+
+#[test] fn once_variadic() {
+    crate::once_escaping!(SumBlock (count: u32, ...) -> i32);
+    let block = unsafe{ SumBlock::new(|count, args| {
+        (0..count).map(|_| args.arg::<i32>()).sum()
+    })};
+    let invoke: unsafe extern "C" fn(*const SumBlock, u32, ...) -> i32 = unsafe{ core::mem::transmute(block.0.invoke) };
+    let r = unsafe{ invoke(&block, 3, 1i32, 2i32, 3i32) };
+    assert_eq!(r, 6);
+}
+
+#[test] fn many_variadic() {
+    crate::many_escaping_reentrant!(SumBlock (environment: &i32, count: u32, ...) -> i32);
+    let block = unsafe{ SumBlock::new(100, |environment, count, args| {
+        *environment + (0..count).map(|_| args.arg::<i32>()).sum::<i32>()
+    })};
+    let invoke: unsafe extern "C" fn(*const SumBlock, u32, ...) -> i32 = unsafe{ core::mem::transmute(block.0.invoke) };
+    let r = unsafe{ invoke(&block, 2, 1i32, 2i32) };
+    assert_eq!(r, 103);
+    let r = unsafe{ invoke(&block, 1, 5i32) };
+    assert_eq!(r, 105);
+}
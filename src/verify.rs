@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Checking that ObjC methods take the blocks bindings pass them, with the `verify` feature.
+
+A binding that declares the wrong block type for a completion handler still compiles, and objc calls the block with
+arguments it doesn't expect, which shows up as memory corruption somewhere else.  [check_method] compares the block
+signature a binding expects with the method's type encoding at runtime, so the mistake is caught in a test or at
+startup instead.
+
+```no_run
+use blocksr::{once_escaping, Signature};
+use blocksr::verify::{check_method, Verified};
+extern "C" { fn objc_getClass(name: *const core::ffi::c_char) -> *const core::ffi::c_void; }
+//(id obj, NSUInteger idx, BOOL *stop)
+once_escaping!(EnumerationBlock (object: *const core::ffi::c_void, index: usize, stop: *mut bool) -> ());
+let class = unsafe{ objc_getClass(c"NSArray".as_ptr()) };
+let verified = unsafe{ check_method(class, c"enumerateObjectsUsingBlock:", 0, EnumerationBlock::SIGNATURE) }.unwrap();
+//the compiler only recorded that the argument is a block
+assert_eq!(verified, Verified::OnlyBlock);
+```
+
+Signatures are block type encodings, the same as a block's [signature](crate::Block::signature) and
+[Signature::SIGNATURE](crate::Signature::SIGNATURE): the return type, the block itself as `@?`, then the arguments.
+Offsets, type qualifiers, class names and nested block signatures are ignored, and `c` matches `B`, since `BOOL` is a
+`signed char` on some targets and a `bool` on others.  Since Rust declarations pass objects as `*const c_void`, `^v`
+matches any object, block, class or selector.
+
+The encodings the compiler records for a class's methods only say that an argument is a block, `@?`, so that much is
+checked, and [Verified::OnlyBlock] says the signature wasn't.  The extended encodings of methods declared in
+protocols, such as delegate methods, record the block's signature too, which `check_protocol_method` compares on
+Apple platforms.
+*/
+
+use core::ffi::{c_char, c_void, CStr};
+
+#[cfg_attr(any(target_vendor = "apple", feature = "gnustep"), link(name = "objc"))]
+extern "C" {
+    fn sel_registerName(name: *const c_char) -> *const c_void;
+    fn class_getInstanceMethod(class: *const c_void, selector: *const c_void) -> *const c_void;
+    fn method_getTypeEncoding(method: *const c_void) -> *const c_char;
+    #[cfg(target_vendor = "apple")]
+    fn _protocol_getMethodTypeEncoding(protocol: *const c_void, selector: *const c_void, required: i8, instance: i8) -> *const c_char;
+}
+
+/**
+Why a method doesn't take the expected block.
+*/
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[non_exhaustive]
+pub enum Mismatch {
+    ///The class doesn't respond to the selector, or the protocol doesn't declare it.
+    NoMethod,
+    ///The method takes only `count` arguments, not counting `self` and `_cmd`.
+    NoArgument{count: usize},
+    ///The argument isn't a block.  This is its encoding.
+    NotABlock(String),
+    ///The argument is a block with a different signature.
+    Signature{method: String, expected: String},
+    ///An encoding couldn't be parsed.
+    Malformed(String),
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::NoMethod => write!(f, "no such method"),
+            Mismatch::NoArgument{count} => write!(f, "the method takes {} arguments", count),
+            Mismatch::NotABlock(encoding) => write!(f, "the argument is not a block, but {}", encoding),
+            Mismatch::Signature{method, expected} => write!(f, "the method takes a block {}, not {}", method, expected),
+            Mismatch::Malformed(encoding) => write!(f, "malformed type encoding {}", encoding),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/**
+How much of a block parameter was checked.
+*/
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[must_use = "a method's encoding may not record its block's signature, so it may not have been checked"]
+pub enum Verified {
+    ///The method's encoding records the block's signature, which matches.
+    Signature,
+    ///The method's encoding only says the argument is a block, so its signature couldn't be compared.
+    OnlyBlock,
+}
+
+/**
+Checks that argument `arg_index` of `class`'s instance method `selector` is a block that matches `signature`, such
+as a block type's [SIGNATURE](crate::Signature::SIGNATURE).
+
+`arg_index` counts from the first argument after `self` and `_cmd`.  For class methods, pass the metaclass.  Class
+methods' encodings rarely record block signatures, so this usually returns [Verified::OnlyBlock].
+
+# Safety
+You must verify that `class` is a valid ObjC class.
+*/
+pub unsafe fn check_method(class: *const c_void, selector: &CStr, arg_index: usize, signature: &CStr) -> Result<Verified, Mismatch> {
+    let method = unsafe{ class_getInstanceMethod(class, sel_registerName(selector.as_ptr())) };
+    if method.is_null() {
+        return Err(Mismatch::NoMethod);
+    }
+    let encoding = unsafe{ method_getTypeEncoding(method) };
+    if encoding.is_null() {
+        return Err(Mismatch::NoMethod);
+    }
+    check_encoding(unsafe{ CStr::from_ptr(encoding) }.to_bytes(), arg_index, signature.to_bytes())
+}
+
+/**
+Checks that argument `arg_index` of `protocol`'s method `selector` is a block with `signature`, using the protocol's
+extended type encoding.
+
+Required and optional, instance and class methods are searched, in that order.  `arg_index` counts from the first
+argument after `self` and `_cmd`.
+
+# Safety
+You must verify that `protocol` is a valid `Protocol`, such as one from `objc_getProtocol`.
+*/
+#[cfg(target_vendor = "apple")]
+pub unsafe fn check_protocol_method(protocol: *const c_void, selector: &CStr, arg_index: usize, signature: &CStr) -> Result<Verified, Mismatch> {
+    let selector = unsafe{ sel_registerName(selector.as_ptr()) };
+    let encoding = [(1, 1), (0, 1), (1, 0), (0, 0)].into_iter()
+        .map(|(required, instance)| unsafe{ _protocol_getMethodTypeEncoding(protocol, selector, required, instance) })
+        .find(|encoding| !encoding.is_null())
+        .ok_or(Mismatch::NoMethod)?;
+    check_encoding(unsafe{ CStr::from_ptr(encoding) }.to_bytes(), arg_index, signature.to_bytes())
+}
+
+fn check_encoding(method: &[u8], arg_index: usize, signature: &[u8]) -> Result<Verified, Mismatch> {
+    let malformed = |encoding: &[u8]| Mismatch::Malformed(String::from_utf8_lossy(encoding).into_owned());
+    let types = split(method).ok_or_else(|| malformed(method))?;
+    //the return type, self and _cmd
+    let argument = *types.get(arg_index + 3).ok_or(Mismatch::NoArgument{count: types.len().saturating_sub(3)})?;
+    let argument = strip_qualifiers(argument);
+    if !argument.starts_with(b"@?") {
+        return Err(Mismatch::NotABlock(String::from_utf8_lossy(argument).into_owned()));
+    }
+    let expected = split(signature).ok_or_else(|| malformed(signature))?;
+    match argument.strip_prefix(b"@?<").and_then(|inner| inner.strip_suffix(b">")) {
+        Some(inner) => {
+            let actual = split(inner).ok_or_else(|| malformed(argument))?;
+            let same = actual.len() == expected.len() && actual.iter().zip(&expected).all(|(a, e)| canonical(a) == canonical(e));
+            if same {
+                Ok(Verified::Signature)
+            }
+            else {
+                Err(Mismatch::Signature{method: String::from_utf8_lossy(inner).into_owned(), expected: String::from_utf8_lossy(signature).into_owned()})
+            }
+        }
+        //only that it is a block was recorded
+        None => Ok(Verified::OnlyBlock),
+    }
+}
+
+//Splits an encoding into its types, without their offsets.
+fn split(mut encoding: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut types = Vec::new();
+    while !encoding.is_empty() {
+        let length = type_length(encoding)?;
+        types.push(&encoding[..length]);
+        encoding = &encoding[length..];
+        let offset = encoding.iter().take_while(|b| b.is_ascii_digit() || **b == b'-').count();
+        encoding = &encoding[offset..];
+    }
+    Some(types)
+}
+
+const QUALIFIERS: &[u8] = b"rnNoORVA";
+
+fn strip_qualifiers(mut encoding: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = encoding {
+        if !QUALIFIERS.contains(first) {
+            break;
+        }
+        encoding = rest;
+    }
+    encoding
+}
+
+//The length of the first type in `encoding`, with its qualifiers.
+fn type_length(encoding: &[u8]) -> Option<usize> {
+    let qualifiers = encoding.len() - strip_qualifiers(encoding).len();
+    let encoding = &encoding[qualifiers..];
+    let length = match *encoding.first()? {
+        b'@' => match encoding.get(1) {
+            Some(b'?') => 2 + match encoding.get(2) {
+                Some(b'<') => balanced(&encoding[2..])?,
+                _ => 0,
+            },
+            Some(b'"') => 2 + encoding[2..].iter().position(|b| *b == b'"')? + 1,
+            _ => 1,
+        },
+        b'^' => 1 + type_length(&encoding[1..])?,
+        b'{' | b'(' | b'[' => balanced(encoding)?,
+        b'b' => 1 + encoding[1..].iter().take_while(|b| b.is_ascii_digit()).count(),
+        _ => 1,
+    };
+    Some(qualifiers + length)
+}
+
+//The length of a bracketed run, such as a struct, including the brackets.  Quoted names may contain anything.
+fn balanced(encoding: &[u8]) -> Option<usize> {
+    let mut depth = 0;
+    let mut quoted = false;
+    for (index, byte) in encoding.iter().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'{' | b'(' | b'[' | b'<' if !quoted => depth += 1,
+            b'}' | b')' | b']' | b'>' if !quoted => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+//A type as it is compared: without qualifiers, class names, quoted field names or nested block signatures.  Objects,
+//blocks, classes, selectors and void pointers are all `@`.
+fn canonical(encoding: &[u8]) -> Vec<u8> {
+    let encoding = strip_qualifiers(encoding);
+    match encoding {
+        [b'@', ..] | [b'#'] | [b':'] | [b'^', b'v'] => b"@".to_vec(),
+        [b'^', rest @ ..] => {
+            let mut pointer = b"^".to_vec();
+            pointer.extend(canonical(rest));
+            pointer
+        }
+        [b'B'] => b"c".to_vec(),
+        _ => {
+            let mut quoted = false;
+            encoding.iter().copied().filter(|byte| {
+                if *byte == b'"' {
+                    quoted = !quoted;
+                    return false;
+                }
+                !quoted
+            }).collect()
+        }
+    }
+}
+
+#[allow(clippy::unused_unit)]
+#[test] fn encodings() {
+    //-[NSArray enumerateObjectsUsingBlock:], as the compiler records it
+    assert_eq!(check_encoding(b"v24@0:8@?16", 0, b"v@?@Q^B"), Ok(Verified::OnlyBlock));
+    assert_eq!(check_encoding(b"@24@0:8Q16", 0, b"v@?"), Err(Mismatch::NotABlock("Q".to_string())));
+    assert_eq!(check_encoding(b"v24@0:8@?16", 1, b"v@?"), Err(Mismatch::NoArgument{count: 1}));
+    //a protocol's extended encoding, with qualifiers and class names
+    let extended = b"v48@0:8@\"NSURLSession\"16@\"NSURLSessionDataTask\"24@\"NSURLResponse\"32@?<v@?q>40";
+    assert_eq!(check_encoding(extended, 3, b"v@?q"), Ok(Verified::Signature));
+    assert_eq!(check_encoding(extended, 3, b"v@?c"), Err(Mismatch::Signature{method: "v@?q".to_string(), expected: "v@?c".to_string()}));
+    assert_eq!(check_encoding(extended, 3, b"v@?qq"), Err(Mismatch::Signature{method: "v@?q".to_string(), expected: "v@?qq".to_string()}));
+    //nested blocks, structs, BOOL and const
+    let nested = b"v16@0:8@?<v@?@?<v@?>{CGSize=\"width\"d\"height\"d}Br*>16";
+    assert_eq!(check_encoding(nested, 0, b"v@?@?{CGSize=dd}c*"), Ok(Verified::Signature));
+    //a Rust declaration's signature, with objects as void pointers
+    crate::once_escaping!(ErrorHandler (data: *const c_void, error: *mut *const c_void, code: i64) -> ());
+    let extended = b"v24@0:8@?<v@?@\"NSData\"^@q>16";
+    assert_eq!(check_encoding(extended, 0, <ErrorHandler as crate::Signature>::SIGNATURE.to_bytes()), Ok(Verified::Signature));
+    assert_eq!(check_encoding(b"v24@0:8@?<v@?i^@q>16", 0, <ErrorHandler as crate::Signature>::SIGNATURE.to_bytes()), Err(Mismatch::Signature{method: "v@?i^@q".to_string(), expected: "v@?^v^^vq".to_string()}));
+    assert_eq!(check_encoding(b"v16@0:8@?<v@?", 0, b"v@?"), Err(Mismatch::Malformed("v16@0:8@?<v@?".to_string())));
+}
+
+#[cfg(target_os = "macos")]
+#[allow(clippy::unused_unit)]
+#[test] fn runtime() {
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *const c_void;
+    }
+    #[link(name = "Foundation", kind = "framework")]
+    extern "C" {}
+    crate::once_escaping!(EnumerationBlock (object: *const c_void, index: usize, stop: *mut bool) -> ());
+    let class = unsafe{ objc_getClass(c"NSArray".as_ptr()) };
+    assert_eq!(unsafe{ check_method(class, c"enumerateObjectsUsingBlock:", 0, <EnumerationBlock as crate::Signature>::SIGNATURE) }, Ok(Verified::OnlyBlock));
+    assert_eq!(unsafe{ check_method(class, c"objectAtIndex:", 0, c"v@?") }, Err(Mismatch::NotABlock("Q".to_string())));
+    assert_eq!(unsafe{ check_method(class, c"noSuchMethod:", 0, c"v@?") }, Err(Mismatch::NoMethod));
+}
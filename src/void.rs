@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*! Pre-declared `void (^)(void)` blocks, a.k.a. `dispatch_block_t`.
+
+This is by far the most common block type, so you don't need a macro for it.
+
+```
+use blocksr::VoidBlock;
+let f = unsafe{ VoidBlock::new(|| {
+    println!("hello from dispatch_async");
+})};
+//pass f somewhere...
+```
+
+GCD runs a block at the QoS it was submitted with, or that of the queue.  A completion handler created on a
+user-interactive thread, but submitted later from a background one, runs at background QoS.  To fix its QoS when it
+is created, tag it with [VoidBlock::with_qos], which wraps it with `dispatch_block_create_with_qos_class`:
+
+```no_run
+use blocksr::{VoidBlock, QosClass};
+let f = unsafe{ VoidBlock::new(|| {
+    //update the UI
+})};
+let tagged = unsafe{ f.with_qos(QosClass::UserInitiated, 0) };
+//pass tagged.as_ptr() to dispatch_async...
+```
+*/
+
+use core::ffi::{c_int, c_ulong, c_void};
+use core::ptr::NonNull;
+use crate::{Block, HeapBlock};
+
+#[allow(clippy::unused_unit)]
+mod declarations {
+    blocksr::once_escaping!(pub VoidBlock () -> ());
+    blocksr::many_escaping_reentrant!(pub VoidManyBlock (environment: &()) -> ());
+}
+pub use declarations::{VoidBlock,VoidManyBlock};
+
+extern "C" {
+    fn dispatch_block_create_with_qos_class(flags: c_ulong, qos_class: u32, relative_priority: c_int, block: *const c_void) -> *mut c_void;
+}
+
+//DISPATCH_BLOCK_ENFORCE_QOS_CLASS: prefer the block's QoS to the queue's
+const DISPATCH_BLOCK_ENFORCE_QOS_CLASS: c_ulong = 0x20;
+//QOS_MIN_RELATIVE_PRIORITY
+const MIN_RELATIVE_PRIORITY: i32 = -15;
+
+/**
+A quality-of-service class, `qos_class_t`, for [VoidBlock::with_qos].
+*/
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+#[non_exhaustive]
+pub enum QosClass {
+    ///`QOS_CLASS_USER_INTERACTIVE`: work the user is interacting with, like updating the UI.
+    UserInteractive,
+    ///`QOS_CLASS_USER_INITIATED`: work the user started and is waiting for.
+    UserInitiated,
+    ///`QOS_CLASS_DEFAULT`.
+    Default,
+    ///`QOS_CLASS_UTILITY`: long-running work the user isn't waiting for, often with a progress indicator.
+    Utility,
+    ///`QOS_CLASS_BACKGROUND`: work the user isn't aware of, like maintenance.
+    Background,
+}
+
+impl QosClass {
+    ///The `qos_class_t` value.
+    pub const fn raw(self) -> u32 {
+        match self {
+            QosClass::UserInteractive => 0x21,
+            QosClass::UserInitiated => 0x19,
+            QosClass::Default => 0x15,
+            QosClass::Utility => 0x11,
+            QosClass::Background => 0x09,
+        }
+    }
+}
+
+//Wraps `block` in a new dispatch block with `qos`.  Safety: `block` is a `void (^)(void)` block.
+unsafe fn with_qos(block: *const c_void, qos: QosClass, relative_priority: i32) -> HeapBlock<Block<(),()>> {
+    assert!((MIN_RELATIVE_PRIORITY..=0).contains(&relative_priority), "relative priority {} is not between -15 and 0", relative_priority);
+    let wrapped = unsafe{ dispatch_block_create_with_qos_class(DISPATCH_BLOCK_ENFORCE_QOS_CLASS, qos.raw(), relative_priority, block) };
+    unsafe{ HeapBlock::from_raw(NonNull::new(wrapped as *mut Block<(),()>).expect("dispatch_block_create_with_qos_class returned null")) }
+}
+
+impl VoidBlock {
+    /**
+    Wraps this block in a heap dispatch block that runs at `qos`, even when submitted to a queue, or from a thread,
+    with a different QoS.
+
+    `relative_priority` lowers the priority within the class, from `0` down to `-15`.  The wrapper holds a copy of this
+    block, and invokes it when the wrapper is invoked, so it has the same requirements: invoke it at most once.
+
+    # Safety
+    The same requirements as `new` apply.
+
+    # Panics
+    If `relative_priority` is out of range.
+    */
+    pub unsafe fn with_qos(&self, qos: QosClass, relative_priority: i32) -> HeapBlock<Block<(),()>> {
+        unsafe{ with_qos(self as *const Self as *const c_void, qos, relative_priority) }
+    }
+}
+
+impl VoidManyBlock {
+    /**
+    Wraps this block in a heap dispatch block that runs at `qos`.  See [VoidBlock::with_qos].
+
+    # Safety
+    The same requirements as `new` apply.
+
+    # Panics
+    If `relative_priority` is out of range.
+    */
+    pub unsafe fn with_qos(&self, qos: QosClass, relative_priority: i32) -> HeapBlock<Block<(),()>> {
+        unsafe{ with_qos(self as *const Self as *const c_void, qos, relative_priority) }
+    }
+
+    /**
+    Creates a new block from a plain closure, without an environment.
+
+    # Safety
+    This has the same requirements as [VoidManyBlock::new].
+    */
+    #[track_caller]
+    pub unsafe fn from_fn<F>(f: F) -> Self where F: Fn() + Send + Sync + 'static {
+        Self::new((), move |_| f())
+    }
+}
+
+#[test] fn void_blocks() {
+    use std::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    let count = Arc::new(AtomicUsize::new(0));
+    let move_count = count.clone();
+    let once = unsafe{ VoidBlock::new(move || {
+        move_count.fetch_add(1, Ordering::Relaxed);
+    })};
+    unsafe{ once.as_block().invoke(()) };
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+
+    let move_count = count.clone();
+    let many = unsafe{ VoidManyBlock::from_fn(move || {
+        move_count.fetch_add(1, Ordering::Relaxed);
+    })};
+    unsafe{ many.as_block().invoke(()) };
+    unsafe{ many.as_block().invoke(()) };
+    assert_eq!(count.load(Ordering::Relaxed), 3);
+}
+
+#[cfg(target_os = "macos")]
+#[test] fn qos() {
+    use std::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    let count = Arc::new(AtomicUsize::new(0));
+    let move_count = count.clone();
+    let many = unsafe{ VoidManyBlock::from_fn(move || {
+        move_count.fetch_add(1, Ordering::Relaxed);
+    })};
+    let tagged = unsafe{ many.with_qos(QosClass::UserInitiated, -1) };
+    unsafe{ tagged.invoke(()) };
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+    drop(tagged);
+    assert_eq!(Arc::strong_count(&count), 1);
+}
@@ -0,0 +1,12 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Unsound uses of the macros that must not compile.
+
+These are part of the API: a change that lets one of them compile is a soundness regression.  To update the expected
+errors after a compiler upgrade, run with `TRYBUILD=overwrite` and review the diff.
+*/
+
+#[test] fn compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/compile_fail/*.rs");
+}
@@ -0,0 +1,12 @@
+//a once block's parameter produces a pointer only once, so it can't be handed to objc twice
+blocksr::once_escaping!(MyBlock (arg: u8) -> u8);
+
+unsafe fn objc_api(block: *const MyBlock) -> u8 {
+    (*block).as_block().invoke((2,))
+}
+
+fn main() {
+    let param = unsafe{ MyBlock::new(|arg| arg + 1) }.into_param();
+    let _first = param.with_ptr(|ptr| unsafe{ objc_api(ptr) });
+    let _second = param.with_ptr(|ptr| unsafe{ objc_api(ptr) });
+}
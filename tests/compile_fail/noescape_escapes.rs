@@ -0,0 +1,6 @@
+//a noescape block lives in the storage of `scoped`, so it can't be returned from it
+blocksr::once_noescape!(MyBlock (arg: u8) -> u8);
+
+fn main() {
+    let _escaped = unsafe{ MyBlock::scoped(|arg| arg, |block| block) };
+}
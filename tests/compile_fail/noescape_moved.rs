@@ -0,0 +1,10 @@
+//a noescape block points into itself, so once it is pinned it can't be moved
+use core::mem::MaybeUninit;
+use core::pin::pin;
+blocksr::once_noescape!(MyBlock (arg: u8) -> u8);
+
+fn main() {
+    let storage = pin!(MaybeUninit::uninit());
+    let block = unsafe{ MyBlock::new(storage, |arg| arg) };
+    let _moved = *block;
+}
@@ -0,0 +1,8 @@
+//once_escaping blocks may be invoked on any thread, so their closure must be Send
+use std::rc::Rc;
+blocksr::once_escaping!(MyBlock (arg: u8) -> u8);
+
+fn main() {
+    let shared = Rc::new(1u8);
+    let _block = unsafe{ MyBlock::new(move |arg| *shared + arg) };
+}
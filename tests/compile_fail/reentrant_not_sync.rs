@@ -0,0 +1,7 @@
+//reentrant blocks may be invoked concurrently, sharing their environment, so it must be Sync
+use std::cell::Cell;
+blocksr::many_escaping_reentrant!(MyBlock (environment: &Cell<u8>, arg: u8) -> u8);
+
+fn main() {
+    let _block = unsafe{ MyBlock::new(Cell::new(0), |environment, arg| environment.replace(arg)) };
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*
+ABI fixtures for blocksr, compiled when the `abi-fixtures` feature is on.
+
+Each fixture calls a block through its invoke pointer exactly as clang would, so the argument registers/stack
+layout are decided by the C compiler rather than by Rust.  Arguments cycle through the same types as the
+`arity_test!` tests in src/block.rs and the argument in position n has value n.
+*/
+#include <stdint.h>
+
+struct blocksr_fixture_literal {
+    void *isa;
+    int flags;
+    int reserved;
+    void *invoke;
+    void *descriptor;
+};
+
+typedef double (*blocksr_fixture_invoke_0)(struct blocksr_fixture_literal *);
+double blocksr_fixture_arity_0(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_0) block->invoke)(block);
+}
+typedef double (*blocksr_fixture_invoke_1)(struct blocksr_fixture_literal *, int8_t);
+double blocksr_fixture_arity_1(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_1) block->invoke)(block, 1);
+}
+typedef double (*blocksr_fixture_invoke_2)(struct blocksr_fixture_literal *, int8_t, double);
+double blocksr_fixture_arity_2(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_2) block->invoke)(block, 1, 2);
+}
+typedef double (*blocksr_fixture_invoke_3)(struct blocksr_fixture_literal *, int8_t, double, uint64_t);
+double blocksr_fixture_arity_3(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_3) block->invoke)(block, 1, 2, 3);
+}
+typedef double (*blocksr_fixture_invoke_4)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float);
+double blocksr_fixture_arity_4(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_4) block->invoke)(block, 1, 2, 3, 4);
+}
+typedef double (*blocksr_fixture_invoke_5)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t);
+double blocksr_fixture_arity_5(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_5) block->invoke)(block, 1, 2, 3, 4, 5);
+}
+typedef double (*blocksr_fixture_invoke_6)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t);
+double blocksr_fixture_arity_6(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_6) block->invoke)(block, 1, 2, 3, 4, 5, 6);
+}
+typedef double (*blocksr_fixture_invoke_7)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t, int8_t);
+double blocksr_fixture_arity_7(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_7) block->invoke)(block, 1, 2, 3, 4, 5, 6, 7);
+}
+typedef double (*blocksr_fixture_invoke_8)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t, int8_t, double);
+double blocksr_fixture_arity_8(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_8) block->invoke)(block, 1, 2, 3, 4, 5, 6, 7, 8);
+}
+typedef double (*blocksr_fixture_invoke_9)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t, int8_t, double, uint64_t);
+double blocksr_fixture_arity_9(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_9) block->invoke)(block, 1, 2, 3, 4, 5, 6, 7, 8, 9);
+}
+typedef double (*blocksr_fixture_invoke_10)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t, int8_t, double, uint64_t, float);
+double blocksr_fixture_arity_10(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_10) block->invoke)(block, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+}
+typedef double (*blocksr_fixture_invoke_11)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t, int8_t, double, uint64_t, float, int32_t);
+double blocksr_fixture_arity_11(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_11) block->invoke)(block, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11);
+}
+typedef double (*blocksr_fixture_invoke_12)(struct blocksr_fixture_literal *, int8_t, double, uint64_t, float, int32_t, uint16_t, int8_t, double, uint64_t, float, int32_t, uint16_t);
+double blocksr_fixture_arity_12(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_12) block->invoke)(block, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12);
+}
+
+/*
+Struct-by-value fixtures, shaped like CGRect/CGSize.  On x86_64 the rect is returned indirectly (stret),
+on arm64 it's a homogeneous aggregate returned in registers.
+*/
+struct blocksr_fixture_size { double width; double height; };
+struct blocksr_fixture_rect { double x; double y; double width; double height; };
+
+typedef struct blocksr_fixture_rect (*blocksr_fixture_invoke_rect)(struct blocksr_fixture_literal *, struct blocksr_fixture_rect, struct blocksr_fixture_size);
+struct blocksr_fixture_rect blocksr_fixture_rect(struct blocksr_fixture_literal *block) {
+    struct blocksr_fixture_rect rect = {1, 2, 3, 4};
+    struct blocksr_fixture_size size = {5, 6};
+    return ((blocksr_fixture_invoke_rect) block->invoke)(block, rect, size);
+}
+
+typedef struct blocksr_fixture_size (*blocksr_fixture_invoke_size)(struct blocksr_fixture_literal *, struct blocksr_fixture_size);
+struct blocksr_fixture_size blocksr_fixture_size(struct blocksr_fixture_literal *block) {
+    struct blocksr_fixture_size size = {5, 6};
+    return ((blocksr_fixture_invoke_size) block->invoke)(block, size);
+}
+
+/*
+Small returns.  The range is returned in memory on armv7 and i386 Linux, and in registers elsewhere; the 64-bit
+integer is always returned in registers.
+*/
+struct blocksr_fixture_range { uint32_t location; uint32_t length; };
+
+typedef struct blocksr_fixture_range (*blocksr_fixture_invoke_range)(struct blocksr_fixture_literal *, struct blocksr_fixture_range);
+struct blocksr_fixture_range blocksr_fixture_range(struct blocksr_fixture_literal *block) {
+    struct blocksr_fixture_range range = {5, 6};
+    return ((blocksr_fixture_invoke_range) block->invoke)(block, range);
+}
+
+typedef uint64_t (*blocksr_fixture_invoke_wide)(struct blocksr_fixture_literal *, uint32_t, uint32_t);
+uint64_t blocksr_fixture_wide(struct blocksr_fixture_literal *block) {
+    return ((blocksr_fixture_invoke_wide) block->invoke)(block, 5, 6);
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+The macros expand to paths like `core::mem`, which a module of the crate's named `core` would shadow for callers that
+glob-import it.
+*/
+
+use blocksr::*;
+
+once_escaping!(OnceBlock (arg: u8) -> u8);
+once_noescape!(NoEscapeBlock (arg: u8) -> u8);
+many_escaping_reentrant!(ReentrantBlock (environment: &(), arg: u8) -> u8);
+many_escaping_nonreentrant!(NonreentrantBlock (environment: &mut (), arg: u8) -> u8);
+
+#[test] fn glob_import() {
+    assert!(OnceBlock::literal_size() > 0);
+    assert!(NoEscapeBlock::<()>::FLAGS.contains(BlockFlags::IS_NOESCAPE));
+    assert!(ReentrantBlock::FLAGS.contains(BlockFlags::HAS_COPY_DISPOSE));
+    assert!(NonreentrantBlock::literal_size() > 0);
+}